@@ -0,0 +1,103 @@
+// A minimal implementation of standard (RFC 4648) base64, which is all that
+// jyt's transforms need.
+
+const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+pub fn encode(input: &[u8]) -> String {
+  let mut out = String::with_capacity(input.len().div_ceil(3) * 4);
+  for chunk in input.chunks(3) {
+    let b = [
+      chunk[0],
+      *chunk.get(1).unwrap_or(&0),
+      *chunk.get(2).unwrap_or(&0),
+    ];
+    let n = (b[0] as u32) << 16 | (b[1] as u32) << 8 | b[2] as u32;
+    for i in 0..4 {
+      if i <= chunk.len() {
+        out.push(ALPHABET[(n >> (18 - 6 * i) & 0x3f) as usize] as char);
+      } else {
+        out.push('=');
+      }
+    }
+  }
+  out
+}
+
+// Decodes base64 input, ignoring any whitespace (as found in line-wrapped
+// output from tools like base64(1)) and allowing padding to be omitted.
+pub fn decode(input: &str) -> Result<Vec<u8>, String> {
+  let mut out = Vec::with_capacity(input.len() / 4 * 3);
+  let mut acc = 0u32;
+  let mut bits = 0;
+  let mut padding = 0;
+  for c in input.bytes().filter(|c| !c.is_ascii_whitespace()) {
+    let sextet = match c {
+      b'A'..=b'Z' => c - b'A',
+      b'a'..=b'z' => c - b'a' + 26,
+      b'0'..=b'9' => c - b'0' + 52,
+      b'+' => 62,
+      b'/' => 63,
+      b'=' => {
+        padding += 1;
+        continue;
+      }
+      _ => return Err(format!("invalid base64 character '{}'", c as char)),
+    };
+    if padding > 0 {
+      return Err("invalid base64 padding".to_owned());
+    }
+    acc = acc << 6 | sextet as u32;
+    bits += 6;
+    if bits >= 8 {
+      bits -= 8;
+      out.push((acc >> bits) as u8);
+      acc &= (1 << bits) - 1;
+    }
+  }
+  if bits >= 6 || padding > 2 {
+    return Err("invalid base64 length".to_owned());
+  }
+  Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::{decode, encode};
+
+  // The test vectors from RFC 4648, section 10.
+  const VECTORS: [(&str, &str); 7] = [
+    ("", ""),
+    ("f", "Zg=="),
+    ("fo", "Zm8="),
+    ("foo", "Zm9v"),
+    ("foob", "Zm9vYg=="),
+    ("fooba", "Zm9vYmE="),
+    ("foobar", "Zm9vYmFy"),
+  ];
+
+  #[test]
+  fn known_answers() {
+    for (text, encoded) in VECTORS {
+      assert_eq!(encode(text.as_bytes()), encoded);
+      assert_eq!(decode(encoded).unwrap(), text.as_bytes(), "{}", encoded);
+      let unpadded = encoded.trim_end_matches('=');
+      assert_eq!(decode(unpadded).unwrap(), text.as_bytes(), "{}", unpadded);
+    }
+    assert_eq!(encode(&[0xfb, 0xff, 0xfe]), "+//+");
+    assert_eq!(decode("Zm9v\nYmFy\n").unwrap(), b"foobar");
+  }
+
+  #[test]
+  fn invalid() {
+    let cases = [
+      ("Zm9v-", "invalid base64 character '-'"),
+      ("Zg=a", "invalid base64 padding"),
+      ("Z", "invalid base64 length"),
+      ("Zm9vY", "invalid base64 length"),
+      ("Zg===", "invalid base64 length"),
+    ];
+    for (input, want) in cases {
+      assert_eq!(decode(input).unwrap_err(), want, "{}", input);
+    }
+  }
+}
@@ -0,0 +1,159 @@
+use std::error::Error;
+use std::fs;
+use std::io::{self, Write};
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+
+use structopt::StructOpt;
+
+use crate::{build_pipeline, format_from_extension, warn, write_output, Format, Opt, Size};
+
+// BenchOpt holds the options for 'jyt bench', which times jyt's own
+// conversions between formats, as a way to compare them and to catch a change
+// that makes one slower.
+#[derive(StructOpt)]
+#[structopt(name = "jyt bench", verbatim_doc_comment)]
+/// Measure how fast jyt converts between formats
+///
+/// By default, jyt bench generates a corpus of records of roughly --size bytes
+/// as JSON, converts it to YAML and TOML for input in those formats, and times
+/// the conversion from each of the three formats to each of the others. Files
+/// given on the command line are used as the corpus instead, in the format of
+/// their extension, and are timed converting to each of JSON, YAML, and TOML.
+///
+/// Each conversion runs --runs times, and the fastest run is reported, along
+/// with the input size and the throughput in MB of input per second. A
+/// conversion that fails, like input with nulls to TOML, reports its error in
+/// place of a time.
+pub struct BenchOpt {
+  #[structopt(
+    long = "size",
+    value_name = "size",
+    help = "Approximate size of the generated JSON corpus, like 4M",
+    default_value = "4M"
+  )]
+  size: Size,
+
+  #[structopt(
+    long = "runs",
+    value_name = "n",
+    help = "Times to run each conversion, reporting the fastest",
+    default_value = "3"
+  )]
+  runs: usize,
+
+  #[structopt(
+    name = "file",
+    help = "Files to time instead of a generated corpus",
+    parse(from_os_str)
+  )]
+  files: Vec<PathBuf>,
+}
+
+// The formats that a corpus is timed between.
+const FORMATS: [(&str, Format); 3] = [
+  ("json", Format::Json),
+  ("yaml", Format::Yaml),
+  ("toml", Format::Toml),
+];
+
+pub fn bench(opt: BenchOpt) -> Result<(), Box<dyn Error>> {
+  // Whatever a conversion would warn about, it warns about on every run.
+  warn::set_quiet(true);
+  let mut corpus = Vec::new();
+  if opt.files.is_empty() {
+    let json = generate(opt.size.0);
+    for (name, from) in FORMATS {
+      let (input, _) = convert(&json, Format::Json, name)?;
+      corpus.push((name.to_owned(), from, input));
+    }
+  }
+  for path in &opt.files {
+    let from = match format_from_extension(path) {
+      Some(from) => from,
+      None => Err(format!(
+        "cannot tell the format of {} from its extension",
+        path.display()
+      ))?,
+    };
+    corpus.push((path.display().to_string(), from, fs::read(path)?));
+  }
+
+  for (source, from, input) in &corpus {
+    for (to, _) in FORMATS {
+      let mut fastest = None;
+      let mut failed = None;
+      for _ in 0..opt.runs.max(1) {
+        match convert(input, *from, to) {
+          Ok((_, time)) => {
+            fastest = Some(fastest.map_or(time, |fastest: Duration| fastest.min(time)))
+          }
+          Err(err) => {
+            failed = Some(err);
+            break;
+          }
+        }
+      }
+      let result = match (failed, fastest) {
+        (Some(err), _) => format!("error: {}", err),
+        (None, Some(time)) => format!(
+          "{:>10.1?}  {:>8.1} MB/s",
+          time,
+          input.len() as f64 / 1e6 / time.as_secs_f64().max(f64::MIN_POSITIVE)
+        ),
+        (None, None) => String::new(),
+      };
+      writeln!(
+        io::stdout(),
+        "{:<12} -> {:<6} {:>10} bytes  {}",
+        source,
+        to,
+        input.len(),
+        result
+      )?;
+    }
+  }
+  Ok(())
+}
+
+// Runs a conversion to a format, the way jyt would with only -f and -t given,
+// and returns its output and how long it took.
+fn convert(input: &[u8], from: Format, to: &str) -> Result<(Vec<u8>, Duration), Box<dyn Error>> {
+  let matches = Opt::clap().get_matches_from_safe(["jyt", "-t", to])?;
+  let opt = Opt::from_clap(&matches);
+  let pipeline = build_pipeline(&opt, &matches, "<bench>".to_owned())?;
+  let mut output = Vec::new();
+  let start = Instant::now();
+  write_output(input, from, &opt, &pipeline, &mut output)?;
+  Ok((output, start.elapsed()))
+}
+
+// Returns a JSON document of about size bytes of records, with the mix of
+// strings, numbers, booleans, and nesting of typical API or configuration
+// data. There are no nulls, so that every format can hold it.
+fn generate(size: usize) -> Vec<u8> {
+  let mut records = Vec::new();
+  let mut len = 0;
+  while len < size {
+    let i = records.len();
+    let tags = &["alpha", "beta", "gamma"][..i % 4];
+    let record = serde_json::json!({
+      "id": i,
+      "name": format!("record-{}", i),
+      "active": i % 3 == 0,
+      "score": i as f64 * 0.37,
+      "tags": tags,
+      "address": {
+        "street": format!("{} Main Street", i),
+        "city": "Springfield",
+        "zip": format!("{:05}", i % 100000),
+      },
+      "notes": "A \"quoted\" note, with a tab\tand a non-ASCII café.",
+    });
+    len += record.to_string().len() + 1;
+    records.push(record);
+  }
+  serde_json::json!({ "records": records })
+    .to_string()
+    .into_bytes()
+}
@@ -0,0 +1,163 @@
+use std::io::{self, IsTerminal};
+use std::str::FromStr;
+use std::sync::atomic::{AtomicU8, Ordering};
+
+use crate::console::{self, Stream};
+
+// ColorMode is when jyt colors what it writes for people to read, like the
+// diff from --check-format and the errors on stderr. Like warnings, the mode is
+// global state, so that anything that writes can check it.
+#[derive(Copy, Clone)]
+pub enum ColorMode {
+  Auto,
+  Always,
+  Never,
+}
+
+impl FromStr for ColorMode {
+  type Err = String;
+
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    match s {
+      "auto" => Ok(Self::Auto),
+      "always" => Ok(Self::Always),
+      "never" => Ok(Self::Never),
+      _ => Err(format!("'{}' is not a valid color mode", s)),
+    }
+  }
+}
+
+static STDOUT: AtomicU8 = AtomicU8::new(ColorMode::Auto as u8);
+static STDERR: AtomicU8 = AtomicU8::new(ColorMode::Auto as u8);
+
+pub fn set_mode(stdout: ColorMode, stderr: ColorMode) {
+  STDOUT.store(stdout as u8, Ordering::Relaxed);
+  STDERR.store(stderr as u8, Ordering::Relaxed);
+}
+
+pub fn stdout() -> bool {
+  enabled(&STDOUT, || {
+    io::stdout().is_terminal() && console::escapes(Stream::Stdout)
+  })
+}
+
+pub fn stderr() -> bool {
+  enabled(&STDERR, || {
+    io::stderr().is_terminal() && console::escapes(Stream::Stderr)
+  })
+}
+
+// In auto mode, color follows the conventions of other tools: NO_COLOR turns it
+// off, CLICOLOR_FORCE turns it on, and otherwise it's used on a terminal that
+// isn't dumb.
+fn enabled(mode: &AtomicU8, terminal: impl Fn() -> bool) -> bool {
+  let var = |name| std::env::var_os(name).filter(|value| !value.is_empty());
+  match mode.load(Ordering::Relaxed) {
+    mode if mode == ColorMode::Always as u8 => true,
+    mode if mode == ColorMode::Never as u8 => false,
+    _ if var("NO_COLOR").is_some() => false,
+    _ => match var("CLICOLOR_FORCE") {
+      Some(force) if force != "0" => true,
+      _ => terminal() && var("TERM").is_none_or(|term| term != "dumb"),
+    },
+  }
+}
+
+pub const BOLD: &str = "1";
+pub const RED: &str = "31";
+pub const GREEN: &str = "32";
+pub const CYAN: &str = "36";
+pub const BOLD_RED: &str = "1;31";
+pub const BOLD_YELLOW: &str = "1;33";
+pub const BOLD_BLUE: &str = "1;34";
+pub const BOLD_CYAN: &str = "1;36";
+
+// Returns text in an SGR style, like RED, if on is true.
+pub fn paint(on: bool, style: &str, text: &str) -> String {
+  match on {
+    true => format!("\x1b[{}m{}\x1b[0m", style, text),
+    false => text.to_owned(),
+  }
+}
+
+// Returns the start of a message on stderr, like "jyt error:".
+pub fn label(kind: &str) -> String {
+  let style = match kind {
+    "warning" => BOLD_YELLOW,
+    "info" | "explain" => BOLD_CYAN,
+    _ => BOLD_RED,
+  };
+  format!("jyt {}", paint(stderr(), style, &format!("{}:", kind)))
+}
+
+// Theme is the styles that highlighted JSON output gives each kind of token,
+// for --theme. A theme is the name of a built-in one, dark or light, followed
+// by any styles that replace its own, like 'light,key=1;35,null=2'. A style is
+// a list of SGR parameters, as the constants above are.
+#[derive(Clone)]
+pub struct Theme {
+  pub key: String,
+  pub string: String,
+  pub number: String,
+  pub bool: String,
+  pub null: String,
+}
+
+impl Theme {
+  fn dark() -> Theme {
+    Theme {
+      key: BOLD_BLUE.to_owned(),
+      string: GREEN.to_owned(),
+      number: CYAN.to_owned(),
+      bool: "33".to_owned(),
+      null: "90".to_owned(),
+    }
+  }
+
+  // Bright colors wash out on a white background, so the light theme sticks
+  // to darker ones.
+  fn light() -> Theme {
+    Theme {
+      key: "34".to_owned(),
+      string: GREEN.to_owned(),
+      number: "35".to_owned(),
+      bool: RED.to_owned(),
+      null: "2".to_owned(),
+    }
+  }
+}
+
+impl FromStr for Theme {
+  type Err = String;
+
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    let (mut theme, styles) = match s.split_once(',').unwrap_or((s, "")) {
+      ("dark", styles) => (Theme::dark(), styles),
+      ("light", styles) => (Theme::light(), styles),
+      _ => (Theme::dark(), s),
+    };
+    for part in styles.split(',').filter(|part| !part.is_empty()) {
+      let (name, style) = match part.split_once('=') {
+        Some(pair) => pair,
+        None => return Err(format!("'{}' is not a valid theme", s)),
+      };
+      let valid = !style.is_empty()
+        && style
+          .split(';')
+          .all(|n| !n.is_empty() && n.bytes().all(|b| b.is_ascii_digit()));
+      if !valid {
+        return Err(format!("'{}' is not a valid style for {}", style, name));
+      }
+      let slot = match name {
+        "key" => &mut theme.key,
+        "string" => &mut theme.string,
+        "number" => &mut theme.number,
+        "bool" => &mut theme.bool,
+        "null" => &mut theme.null,
+        _ => return Err(format!("'{}' is not a part of a theme", name)),
+      };
+      *slot = style.to_owned();
+    }
+    Ok(theme)
+  }
+}
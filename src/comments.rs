@@ -0,0 +1,355 @@
+use std::collections::HashMap;
+
+use crate::path;
+use crate::value::Value;
+
+// Comments and blank lines from YAML or TOML input, which --keep-comments
+// carries over to YAML or TOML output. Neither serde_yaml nor toml can keep
+// them, so we find them in the source text, attach each one to the value that
+// starts on the line it comes before (or ends), and splice them back into the
+// output text before (or at the end of) the line where that value starts.
+// Values are matched by their path, so comments follow their values through
+// changes to the shape of the document.
+
+// Line describes one line of YAML or TOML text.
+#[derive(Default)]
+pub struct Line {
+  // The document index and path of each value that starts on the line, in
+  // order. For YAML, a map entry starts at its key and an array element at its
+  // "-"; for TOML, keys and table headers start values.
+  pub starts: Vec<(usize, Vec<String>)>,
+  // The text of a comment on the line, from its "#" to the end of the line.
+  pub comment: Option<String>,
+  // Whether the line has anything besides a comment, including text within a
+  // string or block scalar that started on an earlier line.
+  pub code: bool,
+}
+
+pub struct Comments {
+  notes: Vec<Note>,
+}
+
+struct Note {
+  doc: usize,
+  path: Vec<String>,
+  // Whether the comment follows its value on the same line, rather than coming
+  // before it on a line of its own. Blank lines have no text.
+  trailing: bool,
+  text: String,
+}
+
+impl Comments {
+  pub fn new(lines: &[Line]) -> Self {
+    let mut notes = Vec::new();
+    let mut pending = Vec::new();
+    let mut current: Option<&(usize, Vec<String>)> = None;
+    for line in lines {
+      if !line.code {
+        pending.push(line.comment.clone().unwrap_or_default());
+        continue;
+      }
+      let (first, last) = match (line.starts.first(), line.starts.last()) {
+        (Some(first), Some(last)) => (first, last),
+        _ => {
+          // A comment on a line that starts nothing, like the end of a
+          // multi-line array, goes along with the last value that started.
+          match (&line.comment, current) {
+            (Some(text), Some((doc, path))) => notes.push(Note {
+              doc: *doc,
+              path: path.clone(),
+              trailing: true,
+              text: text.clone(),
+            }),
+            (Some(text), None) => pending.push(text.clone()),
+            (None, _) => {}
+          }
+          continue;
+        }
+      };
+      current = Some(last);
+      // Blank lines at the start of a document would only separate it from its
+      // "---" marker.
+      let skip = match first.1.is_empty() {
+        true => pending.iter().take_while(|text| text.is_empty()).count(),
+        false => 0,
+      };
+      for text in pending.drain(..).skip(skip) {
+        notes.push(Note {
+          doc: first.0,
+          path: first.1.clone(),
+          trailing: false,
+          text,
+        });
+      }
+      if let Some(text) = &line.comment {
+        notes.push(Note {
+          doc: last.0,
+          path: last.1.clone(),
+          trailing: true,
+          text: text.clone(),
+        });
+      }
+    }
+    while pending.last().is_some_and(|text| text.is_empty()) {
+      pending.pop();
+    }
+    notes.extend(pending.into_iter().map(|text| Note {
+      doc: usize::MAX,
+      path: Vec::new(),
+      trailing: false,
+      text,
+    }));
+    Comments { notes }
+  }
+
+  // Returns the output text with the comments spliced in. A comment whose value
+  // doesn't start a line of the output goes before the first line within the
+  // value, or failing that, before the nearest value containing it. Comments
+  // that can't be placed at all go at the end.
+  pub fn splice(&self, output: &str, lines: &[Line]) -> String {
+    let mut exact = HashMap::new();
+    let mut within = HashMap::new();
+    for (i, line) in lines.iter().enumerate() {
+      for (doc, path) in &line.starts {
+        exact.entry((*doc, path.as_slice())).or_insert(i);
+        for len in 0..=path.len() {
+          within.entry((*doc, &path[..len])).or_insert(i);
+        }
+      }
+    }
+
+    let mut before: Vec<Vec<&str>> = vec![Vec::new(); lines.len()];
+    let mut trailing: Vec<Vec<&str>> = vec![Vec::new(); lines.len()];
+    let mut end = Vec::new();
+    for note in &self.notes {
+      if note.trailing {
+        if let Some(&i) = exact.get(&(note.doc, note.path.as_slice())) {
+          trailing[i].push(&note.text);
+          continue;
+        }
+      }
+      let found = (0..=note.path.len())
+        .rev()
+        .find_map(|len| within.get(&(note.doc, &note.path[..len])));
+      match found {
+        Some(&i) => before[i].push(&note.text),
+        None => end.push(&note.text),
+      }
+    }
+
+    let mut out = String::with_capacity(output.len());
+    for (i, text) in output.lines().enumerate() {
+      let indent = &text[..text.len() - text.trim_start().len()];
+      for comment in before.get(i).into_iter().flatten() {
+        // The output may already separate values with blank lines of its own.
+        if comment.is_empty() && (out.is_empty() || out.ends_with("\n\n")) {
+          continue;
+        }
+        if !comment.is_empty() {
+          out.push_str(indent);
+          out.push_str(comment);
+        }
+        out.push('\n');
+      }
+      out.push_str(text);
+      for comment in trailing.get(i).into_iter().flatten() {
+        out.push(' ');
+        out.push_str(comment);
+      }
+      out.push('\n');
+    }
+    for comment in end {
+      out.push_str(comment);
+      out.push('\n');
+    }
+    out
+  }
+}
+
+// Returns the position of the comment in a line, if it has one. A "#" starts a
+// comment only at the start of the line or after whitespace, and only where
+// quoted(i) is false for its character index within the line.
+pub fn find_comment<F>(line: &str, quoted: F) -> Option<usize>
+where
+  F: Fn(usize) -> bool,
+{
+  let mut prev = None;
+  for (i, (pos, c)) in line.char_indices().enumerate() {
+    if c == '#' && prev.is_none_or(char::is_whitespace) && !quoted(i) {
+      return Some(pos);
+    }
+    prev = Some(c);
+  }
+  None
+}
+
+// Describes the lines of a TOML document. TOML is line-oriented enough that
+// the only state to track between lines is whether we're within a multi-line
+// string, array, or inline table.
+pub fn toml_lines(input: &str) -> Vec<Line> {
+  let mut lines = Vec::new();
+  let mut table: Vec<String> = Vec::new();
+  let mut arrays: HashMap<Vec<String>, usize> = HashMap::new();
+  let mut state = TomlState::Normal;
+  let mut depth = 0usize;
+  for text in input.lines() {
+    let mut line = Line::default();
+    let trimmed = text.trim();
+    let statement = state == TomlState::Normal && depth == 0;
+    if statement && trimmed.starts_with('[') {
+      if let Some(path) = toml_header(trimmed, &mut table, &mut arrays) {
+        line.starts.push((0, path));
+      }
+    } else if statement && !trimmed.is_empty() && !trimmed.starts_with('#') {
+      if let Some(key) = toml_key_text(trimmed) {
+        if let Some(segments) = toml_key(key) {
+          let mut path = table.clone();
+          path.extend(segments);
+          line.starts.push((0, path));
+        }
+      }
+    }
+
+    let start_state = state;
+    let mut comment = None;
+    let mut chars = text.char_indices().peekable();
+    while let Some((pos, c)) = chars.next() {
+      let rest = &text[pos..];
+      state = match (state, c) {
+        (TomlState::Normal, '#') => {
+          comment = Some(pos);
+          break;
+        }
+        (TomlState::Normal, '[' | '{') => {
+          depth += 1;
+          TomlState::Normal
+        }
+        (TomlState::Normal, ']' | '}') => {
+          depth = depth.saturating_sub(1);
+          TomlState::Normal
+        }
+        (TomlState::Normal, '"') if rest.starts_with("\"\"\"") => {
+          chars.nth(1);
+          TomlState::MultiBasic
+        }
+        (TomlState::Normal, '"') => TomlState::Basic,
+        (TomlState::Normal, '\'') if rest.starts_with("'''") => {
+          chars.nth(1);
+          TomlState::MultiLiteral
+        }
+        (TomlState::Normal, '\'') => TomlState::Literal,
+        (TomlState::Basic | TomlState::MultiBasic, '\\') => {
+          chars.next();
+          state
+        }
+        (TomlState::Basic, '"') | (TomlState::Literal, '\'') => TomlState::Normal,
+        (TomlState::MultiBasic, '"') if rest.starts_with("\"\"\"") => {
+          chars.nth(1);
+          TomlState::Normal
+        }
+        (TomlState::MultiLiteral, '\'') if rest.starts_with("'''") => {
+          chars.nth(1);
+          TomlState::Normal
+        }
+        (state, _) => state,
+      };
+    }
+    // Single-line strings end with their line, even if they're malformed.
+    if let TomlState::Basic | TomlState::Literal = state {
+      state = TomlState::Normal;
+    }
+
+    line.comment = comment.map(|pos| text[pos..].trim_end().to_owned());
+    line.code = match start_state {
+      TomlState::Normal => !text[..comment.unwrap_or(text.len())].trim().is_empty(),
+      _ => true,
+    };
+    lines.push(line);
+  }
+  lines
+}
+
+#[derive(Copy, Clone, PartialEq)]
+enum TomlState {
+  Normal,
+  Basic,
+  Literal,
+  MultiBasic,
+  MultiLiteral,
+}
+
+// Parses a table header, updating the current table, and returns the path of
+// the table it starts. Arrays of tables get an index for each header, and the
+// headers of tables within them refer to their last element.
+fn toml_header(
+  text: &str,
+  table: &mut Vec<String>,
+  arrays: &mut HashMap<Vec<String>, usize>,
+) -> Option<Vec<String>> {
+  let (is_array, inner) = match text.strip_prefix("[[") {
+    Some(inner) => (true, inner),
+    None => (false, &text[1..]),
+  };
+  let close = match is_array {
+    true => "]]",
+    false => "]",
+  };
+  let segments = toml_key(toml_key_until(inner, close)?)?;
+  let len = segments.len();
+  let mut path = Vec::new();
+  for (i, segment) in segments.into_iter().enumerate() {
+    path.push(segment);
+    let last = i + 1 == len;
+    if is_array && last {
+      let count = arrays.entry(path.clone()).or_default();
+      let mut index = String::new();
+      path::push_index(&mut index, *count);
+      *count += 1;
+      path.push(index);
+    } else if let Some(count) = arrays.get(&path) {
+      let mut index = String::new();
+      path::push_index(&mut index, count.saturating_sub(1));
+      path.push(index);
+    }
+  }
+  *table = path.clone();
+  Some(path)
+}
+
+// Returns the key at the start of a key/value line.
+fn toml_key_text(text: &str) -> Option<&str> {
+  toml_key_until(text, "=")
+}
+
+// Returns the text before the first occurrence of a delimiter outside of
+// quotes.
+fn toml_key_until<'t>(text: &'t str, delimiter: &str) -> Option<&'t str> {
+  let mut quote = None;
+  let mut chars = text.char_indices();
+  while let Some((pos, c)) = chars.next() {
+    match (quote, c) {
+      (None, '"' | '\'') => quote = Some(c),
+      (Some('"'), '\\') => drop(chars.next()),
+      (Some(q), c) if q == c => quote = None,
+      (None, _) if text[pos..].starts_with(delimiter) => return Some(&text[..pos]),
+      _ => {}
+    }
+  }
+  None
+}
+
+// Splits a dotted key into its segments as path components, with quoted parts
+// unescaped. The toml crate does the parsing, by way of a tiny document that
+// sets the key.
+fn toml_key(key: &str) -> Option<Vec<String>> {
+  let mut v: toml::Value = toml::from_str(&format!("{} = 0", key)).ok()?;
+  let mut segments = Vec::new();
+  while let toml::Value::Table(table) = v {
+    let (k, next) = table.into_iter().next()?;
+    let mut segment = String::new();
+    path::push_key(&mut segment, &Value::String(k));
+    segments.push(segment);
+    v = next;
+  }
+  Some(segments)
+}
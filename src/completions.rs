@@ -0,0 +1,47 @@
+use std::error::Error;
+use std::io::{self, Write};
+
+use structopt::clap::Shell;
+use structopt::StructOpt;
+
+use crate::{Opt, FORMAT_NAMES};
+
+// CompletionsOpt holds the options for 'jyt completions', which prints a script
+// for a shell to complete jyt's options, including the format names for -f and
+// -t.
+#[derive(StructOpt)]
+#[structopt(name = "jyt completions", verbatim_doc_comment)]
+/// Print a shell completion script for jyt
+///
+/// The script is written to standard output, to be loaded the way the shell
+/// loads its other completions. For example:
+///
+///   bash: jyt completions bash > ~/.local/share/bash-completion/completions/jyt
+///   zsh:  jyt completions zsh > ~/.zfunc/_jyt (with ~/.zfunc in $fpath)
+///   fish: jyt completions fish > ~/.config/fish/completions/jyt.fish
+///   powershell: jyt completions powershell >> $PROFILE
+pub struct CompletionsOpt {
+  #[structopt(
+    name = "shell",
+    help = "Shell to complete for",
+    possible_values = &Shell::variants(),
+    case_insensitive = true
+  )]
+  shell: Shell,
+}
+
+pub fn completions(opt: CompletionsOpt) -> Result<(), Box<dyn Error>> {
+  // clap panics if its script can't be written, so it's written here first.
+  let mut app = Opt::clap();
+  // Format checks the names for -f and -t itself, so that a bad one gets jyt's
+  // error rather than clap's. Only the script's copy of them lists the names.
+  for arg in app.p.opts.iter_mut() {
+    if matches!(arg.b.name, "to" | "from") {
+      arg.v.possible_vals = Some(FORMAT_NAMES.to_vec());
+    }
+  }
+  let mut script = Vec::new();
+  app.gen_completions_to("jyt", opt.shell, &mut script);
+  io::stdout().write_all(&script)?;
+  Ok(())
+}
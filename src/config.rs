@@ -0,0 +1,411 @@
+use std::env;
+use std::error::Error;
+use std::ffi::{OsStr, OsString};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use structopt::clap::{App, ArgMatches};
+use structopt::StructOpt;
+
+use crate::{Format, Opt};
+
+// Defaults for jyt's options, for the options that a command line leaves out,
+// come from a pair of formats named on the command line or by the name that jyt
+// is run as, then from JYT_ environment variables, and then from the config
+// file, so that each overrides the ones after it.
+//
+// Each key of the config file is the long name of an option, and its value is
+// what the option takes: true for a flag, a string or number for an option with
+// a value, or an array for an option that can be given more than once. An
+// [extensions] table maps file extensions to the formats that they're read and
+// written as, and an [aliases] table names lists of arguments that a first
+// argument like @name stands for.
+//
+// The defaults become arguments placed ahead of the command line's, so clap
+// checks them just as it checks the rest.
+
+// The formats from the [extensions] table. This is global state so that
+// formats can be found for file names anywhere in jyt.
+static EXTENSIONS: Mutex<Vec<(String, Format)>> = Mutex::new(Vec::new());
+
+// Returns the format that the config file gives for an extension.
+pub fn extension_format(ext: &str) -> Option<Format> {
+  let extensions = EXTENSIONS.lock().unwrap_or_else(|err| err.into_inner());
+  extensions
+    .iter()
+    .find(|(known, _)| known == ext)
+    .map(|(_, format)| *format)
+}
+
+// Where each option that came from a default was given, like JYT_TO or the
+// config file's path, for --explain.
+static SOURCES: Mutex<Vec<(String, String)>> = Mutex::new(Vec::new());
+
+// Returns the layer of defaults that gave an option, or None if the command
+// line gave it (or nothing did).
+pub fn source(name: &str) -> Option<String> {
+  let sources = SOURCES.lock().unwrap_or_else(|err| err.into_inner());
+  sources
+    .iter()
+    .find(|(given, _)| given == name)
+    .map(|(_, source)| source.clone())
+}
+
+// The arguments that an alias from the config file stands for, which take the
+// place of the alias on the command line.
+static ALIAS: Mutex<Option<Vec<OsString>>> = Mutex::new(None);
+
+// Expands an alias that's the first argument of the command line, as in 'jyt
+// @k8s-clean file.yaml', into the arguments that the [aliases] table of the
+// config file gives for it.
+pub fn expand_alias() -> Result<(), String> {
+  let first = env::args_os().nth(1);
+  let name = match first.as_deref().and_then(OsStr::to_str) {
+    Some(arg) => match arg.strip_prefix('@') {
+      Some(name) => name,
+      None => return Ok(()),
+    },
+    None => return Ok(()),
+  };
+  let path = match path() {
+    Some(path) => path,
+    None => Err(format!("@{} needs a config file to define it", name))?,
+  };
+  let text = fs::read_to_string(&path).map_err(|err| match err.kind() {
+    io::ErrorKind::NotFound => format!(
+      "@{} is not defined, since {} doesn't exist",
+      name,
+      path.display()
+    ),
+    _ => format!("{}: {}", path.display(), err),
+  })?;
+  let table: toml::value::Table =
+    toml::from_str(&text).map_err(|err| format!("{}: {}", path.display(), err))?;
+  let alias = match table.get("aliases").and_then(|aliases| aliases.get(name)) {
+    Some(alias) => alias,
+    None => Err(format!(
+      "{}: no alias named @{} in [aliases]",
+      path.display(),
+      name
+    ))?,
+  };
+  let args = match alias {
+    toml::Value::Array(args) => args
+      .iter()
+      .map(|arg| arg.as_str().map(OsString::from))
+      .collect(),
+    _ => None,
+  };
+  match args {
+    Some(args) => *ALIAS.lock().unwrap_or_else(|err| err.into_inner()) = Some(args),
+    None => Err(format!(
+      "{}: @{} must be an array of strings",
+      path.display(),
+      name
+    ))?,
+  }
+  Ok(())
+}
+
+fn path() -> Option<PathBuf> {
+  match env::var_os("XDG_CONFIG_HOME") {
+    Some(dir) if !dir.is_empty() => Some(PathBuf::from(dir).join("jyt").join("config.toml")),
+    _ => env::var_os("HOME")
+      .filter(|home| !home.is_empty())
+      .map(|home| {
+        PathBuf::from(home)
+          .join(".config")
+          .join("jyt")
+          .join("config.toml")
+      }),
+  }
+}
+
+// Returns the matches for the command line with the defaults from JYT_
+// environment variables and then the config file added, or the matches as they
+// are if there aren't any.
+pub fn merge(matches: ArgMatches<'static>) -> Result<ArgMatches<'static>, Box<dyn Error>> {
+  let app = Opt::clap();
+  let settings = settings(&app);
+  let mut args = Vec::new();
+  let defaults = pair_defaults(&settings, &matches);
+  let matches = reparse(
+    &app,
+    &settings,
+    matches,
+    &mut args,
+    defaults,
+    "program name",
+  )?;
+  let defaults = env_defaults(&settings, &matches)?;
+  let matches = reparse(&app, &settings, matches, &mut args, defaults, "environment")?;
+  if Opt::from_clap(&matches).no_config {
+    return Ok(matches);
+  }
+
+  let path = match path() {
+    Some(path) => path,
+    _ => return Ok(matches),
+  };
+  let text = match fs::read_to_string(&path) {
+    Ok(text) => text,
+    Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(matches),
+    Err(err) => Err(format!("{}: {}", path.display(), err))?,
+  };
+  let defaults = file_defaults(&settings, &matches, &text)
+    .map_err(|err| format!("{}: {}", path.display(), err))?;
+  let source = path.display().to_string();
+  Ok(reparse(
+    &app, &settings, matches, &mut args, defaults, &source,
+  )?)
+}
+
+// Parses the command line again with more defaults ahead of it, where args
+// holds the defaults from before.
+fn reparse<'a>(
+  app: &App<'a, '_>,
+  settings: &[Setting],
+  matches: ArgMatches<'a>,
+  args: &mut Vec<OsString>,
+  defaults: Vec<OsString>,
+  source: &str,
+) -> Result<ArgMatches<'a>, String> {
+  if defaults.is_empty() {
+    return Ok(matches);
+  }
+  args.extend(defaults);
+  let mut argv = self::args().into_iter();
+  let argv = argv
+    .next()
+    .into_iter()
+    .chain(args.iter().cloned())
+    .chain(argv);
+  let merged = app.clone().get_matches_from_safe(argv).map_err(|err| {
+    // Only the first line of clap's message is about the error itself.
+    let message = err.message.lines().next().unwrap_or_default();
+    format!("{}: {}", source, message.trim_start_matches("error: "))
+  })?;
+  let mut sources = SOURCES.lock().unwrap_or_else(|err| err.into_inner());
+  for opt in settings {
+    if merged.occurrences_of(opt.name) > 0 && matches.occurrences_of(opt.name) == 0 {
+      let source = match source {
+        "environment" => opt.env_var(),
+        "program name" => "the format pair that jyt was run with".to_owned(),
+        source => source.to_owned(),
+      };
+      sources.push((opt.name.to_owned(), source));
+    }
+  }
+  Ok(merged)
+}
+
+// Returns the command line, with an alias expanded, and leaving out a first
+// argument that names a pair of formats, like 'jyt y2j', which pair_defaults
+// takes the formats from instead.
+pub fn args() -> Vec<OsString> {
+  let mut args: Vec<OsString> = env::args_os().collect();
+  if let Some(alias) = &*ALIAS.lock().unwrap_or_else(|err| err.into_inner()) {
+    args.splice(1..2, alias.iter().cloned());
+  } else if args
+    .get(1)
+    .and_then(|arg| arg.to_str())
+    .and_then(format_pair)
+    .is_some()
+  {
+    args.remove(1);
+  }
+  args
+}
+
+// Returns the arguments for the formats named by the first argument of the
+// command line, like 'jyt y2j', or otherwise the name that jyt is run as, like
+// yaml2json, so that jyt can stand in for single-purpose converters through
+// symlinks to it.
+fn pair_defaults(settings: &[Setting], matches: &ArgMatches) -> Vec<OsString> {
+  let mut argv = env::args_os();
+  let name = argv.next().map(PathBuf::from);
+  let name = name
+    .as_deref()
+    .and_then(Path::file_stem)
+    .and_then(OsStr::to_str);
+  let shortcut = argv.next();
+  let shortcut = shortcut.as_deref().and_then(OsStr::to_str);
+  let (from, to) = match shortcut
+    .and_then(format_pair)
+    .or_else(|| name.and_then(format_pair))
+  {
+    Some(pair) => pair,
+    None => return Vec::new(),
+  };
+  let mut args = Vec::new();
+  for (name, format) in [("from", from), ("to", to)] {
+    match settings.iter().find(|opt| opt.name == name) {
+      Some(opt) if !opt.is_given(settings, matches) => args.push(opt.with_value(format)),
+      _ => {}
+    }
+  }
+  args
+}
+
+// Returns the formats that a name like yaml2json, y2j, or yj converts between.
+fn format_pair(name: &str) -> Option<(&str, &str)> {
+  let (from, to) = match name.split_once('2') {
+    Some(pair) => pair,
+    None if name.len() == 2 && name.is_char_boundary(1) => name.split_at(1),
+    None => return None,
+  };
+  match (from.parse::<Format>(), to.parse::<Format>()) {
+    (Ok(_), Ok(_)) => Some((from, to)),
+    _ => None,
+  }
+}
+
+// Returns the arguments for the defaults in JYT_ environment variables, which
+// are named for the options they set, like JYT_TO or JYT_YAML_TAGS. A flag is
+// set by 1 or true, and an empty variable is the same as one that isn't set.
+fn env_defaults(settings: &[Setting], matches: &ArgMatches) -> Result<Vec<OsString>, String> {
+  let mut args = Vec::new();
+  for opt in settings {
+    let var = opt.env_var();
+    let value = match env::var(&var) {
+      Ok(value) if !value.is_empty() => value,
+      Ok(_) | Err(env::VarError::NotPresent) => continue,
+      Err(env::VarError::NotUnicode(_)) => Err(format!("{} is not valid UTF-8", var))?,
+    };
+    if opt.is_given(settings, matches) {
+      continue;
+    }
+    match (opt.takes_value, value.as_str()) {
+      (false, "1" | "true") => args.push(opt.switch().into()),
+      (false, "0" | "false") => {}
+      (false, _) => Err(format!("{} is a flag, which must be 1 or 0", var))?,
+      (true, value) => args.push(opt.with_value(value)),
+    }
+  }
+  Ok(args)
+}
+
+// Returns the arguments for the defaults in a config file.
+fn file_defaults(
+  settings: &[Setting],
+  matches: &ArgMatches,
+  text: &str,
+) -> Result<Vec<OsString>, Box<dyn Error>> {
+  let mut table: toml::value::Table = toml::from_str(text)?;
+  // Aliases are expanded before anything else, by expand_alias.
+  table.remove("aliases");
+  if let Some(extensions) = table.remove("extensions") {
+    let extensions = match extensions {
+      toml::Value::Table(extensions) => extensions,
+      _ => Err("extensions must be a table of formats")?,
+    };
+    let mut formats = Vec::new();
+    for (ext, format) in extensions {
+      let format = match format.as_str().map(str::parse) {
+        Some(Ok(format)) => format,
+        Some(Err(err)) => Err(format!("{} for .{} files", err, ext))?,
+        None => Err(format!("the format for .{} files must be a string", ext))?,
+      };
+      formats.push((ext, format));
+    }
+    *EXTENSIONS.lock().unwrap_or_else(|err| err.into_inner()) = formats;
+  }
+
+  let mut args = Vec::new();
+  for (key, value) in table {
+    let opt = match settings.iter().find(|opt| opt.long == Some(key.as_str())) {
+      Some(opt) => opt,
+      None => Err(format!("'{}' is not a jyt option", key))?,
+    };
+    if opt.is_given(settings, matches) {
+      continue;
+    }
+    match (opt.takes_value, value) {
+      (false, toml::Value::Boolean(set)) => {
+        if set {
+          args.push(opt.switch().into());
+        }
+      }
+      (false, _) => Err(format!("{} is a flag, which must be true or false", key))?,
+      (true, toml::Value::Array(values)) => {
+        for value in &values {
+          args.push(opt.with_value(&toml_string(&key, value)?));
+        }
+      }
+      (true, value) => args.push(opt.with_value(&toml_string(&key, &value)?)),
+    }
+  }
+  Ok(args)
+}
+
+fn toml_string(key: &str, value: &toml::Value) -> Result<String, String> {
+  match value {
+    toml::Value::String(s) => Ok(s.clone()),
+    toml::Value::Integer(n) => Ok(n.to_string()),
+    toml::Value::Float(n) => Ok(n.to_string()),
+    toml::Value::Datetime(d) => Ok(d.to_string()),
+    _ => Err(format!("{} must be a string or number", key)),
+  }
+}
+
+// Setting is what a config file needs to know about one of jyt's options.
+struct Setting<'a> {
+  name: &'a str,
+  short: Option<char>,
+  long: Option<&'a str>,
+  takes_value: bool,
+  conflicts: Vec<&'a str>,
+}
+
+impl Setting<'_> {
+  fn switch(&self) -> String {
+    match (self.long, self.short) {
+      (Some(long), _) => format!("--{}", long),
+      (None, Some(short)) => format!("-{}", short),
+      (None, None) => unreachable!(),
+    }
+  }
+
+  fn with_value(&self, value: &str) -> OsString {
+    match (self.long, self.short) {
+      (Some(long), _) => format!("--{}={}", long, value).into(),
+      (None, Some(short)) => format!("-{}{}", short, value).into(),
+      (None, None) => unreachable!(),
+    }
+  }
+
+  fn env_var(&self) -> String {
+    let name = self.long.unwrap_or(self.name);
+    format!("JYT_{}", name.to_ascii_uppercase().replace('-', "_"))
+  }
+
+  // Reports whether the command line (or a default from before) gives this
+  // option, or one that it conflicts with, so that a default mustn't.
+  fn is_given(&self, settings: &[Setting], matches: &ArgMatches) -> bool {
+    settings.iter().any(|other| {
+      matches.occurrences_of(other.name) > 0
+        && (other.name == self.name
+          || self.conflicts.contains(&other.name)
+          || other.conflicts.contains(&self.name))
+    })
+  }
+}
+
+// Returns the options of an app that take a switch, which are all but the
+// input file.
+fn settings<'a>(app: &'a App) -> Vec<Setting<'a>> {
+  let flags = app.p.flags.iter().map(|flag| (&flag.b, &flag.s, false));
+  let opts = app.p.opts.iter().map(|opt| (&opt.b, &opt.s, true));
+  flags
+    .chain(opts)
+    .map(|(base, switched, takes_value)| Setting {
+      name: base.name,
+      short: switched.short,
+      long: switched.long,
+      takes_value,
+      conflicts: base.blacklist.clone().unwrap_or_default(),
+    })
+    .collect()
+}
@@ -0,0 +1,52 @@
+// Console covers the ways that terminals differ between systems. Terminals on
+// Unix-like systems all take ANSI escape sequences, for colors and for moving
+// the cursor. The Windows console only takes them once they're turned on for
+// it, which Windows Terminal does by itself, and conhost doesn't. Writing text
+// to the console as UTF-16, which it needs for anything outside of its code
+// page, is already done by the standard library's stdout and stderr.
+
+#[derive(Copy, Clone)]
+pub enum Stream {
+  Stdout,
+  Stderr,
+}
+
+// Reports whether a stream that's a terminal takes escape sequences, turning
+// them on for it first where they have to be.
+#[cfg(not(windows))]
+pub fn escapes(_: Stream) -> bool {
+  true
+}
+
+#[cfg(windows)]
+pub fn escapes(stream: Stream) -> bool {
+  use std::ffi::c_void;
+
+  #[link(name = "kernel32")]
+  extern "system" {
+    fn GetStdHandle(std_handle: u32) -> *mut c_void;
+    fn GetConsoleMode(console: *mut c_void, mode: *mut u32) -> i32;
+    fn SetConsoleMode(console: *mut c_void, mode: u32) -> i32;
+  }
+  const STD_OUTPUT_HANDLE: u32 = -11i32 as u32;
+  const STD_ERROR_HANDLE: u32 = -12i32 as u32;
+  const ENABLE_VIRTUAL_TERMINAL_PROCESSING: u32 = 0x0004;
+
+  let handle = match stream {
+    Stream::Stdout => STD_OUTPUT_HANDLE,
+    Stream::Stderr => STD_ERROR_HANDLE,
+  };
+  // Safety: The handle is one of the process's own standard handles, and the
+  // mode is only read after GetConsoleMode fills it in.
+  unsafe {
+    let console = GetStdHandle(handle);
+    let mut mode = 0;
+    // A terminal that isn't a console, like mintty, is a pipe that the
+    // terminal reads escape sequences from itself.
+    if GetConsoleMode(console, &mut mode) == 0 {
+      return true;
+    }
+    mode & ENABLE_VIRTUAL_TERMINAL_PROCESSING != 0
+      || SetConsoleMode(console, mode | ENABLE_VIRTUAL_TERMINAL_PROCESSING) != 0
+  }
+}
@@ -0,0 +1,273 @@
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::thread;
+
+use structopt::StructOpt;
+
+use crate::{
+  build_pipeline, color, exit_status, format_from_extension, write_output, Classified, Format, Opt,
+};
+
+// ConvertOpt holds the options for 'jyt convert', which converts a batch of
+// files in place of a shell loop over them, writing each one's output next to
+// it under the extension of the new format.
+#[derive(StructOpt)]
+#[structopt(name = "jyt convert", verbatim_doc_comment)]
+/// Convert files to another format, next to the originals
+///
+/// Each file is converted with -t, and its output written beside it with the
+/// extension of the new format in place of its own, replacing any file already
+/// there, e.g. 'jyt convert -t yaml --rename '*.json' config/' writes
+/// config/app.yaml from config/app.json. A file that can't be converted is
+/// reported and left alone, with no output written for it, and the rest are
+/// still converted. jyt convert exits with the status that jyt would have for
+/// the first file that failed.
+///
+/// The files in a directory (but not its subdirectories) are converted if
+/// their names match the --rename pattern, where * matches any run of
+/// characters and ? any one character. Without --rename, they're converted if
+/// their extension names a format other than the one of -t. As in the shell,
+/// names that start with '.' are skipped, unless the pattern starts with '.'
+/// too. Files given on the command line are always converted.
+///
+/// Files are read in the format of their extension, or of -f if it's given.
+/// --remove-source deletes each file once its output is written, and
+/// --dry-run only reports the files that would be written and removed.
+///
+/// -j (--jobs) converts that many files at once, each on a thread of its own.
+/// Errors and --dry-run reports still come out in the order of the files.
+pub struct ConvertOpt {
+  #[structopt(
+    short = "t",
+    long = "to",
+    value_name = "format",
+    help = "Format to convert to: json, yaml, toml, csv, or tsv"
+  )]
+  to: Format,
+
+  #[structopt(
+    short = "f",
+    long = "from",
+    value_name = "format",
+    help = "Format to read the files as, instead of the one of their extensions"
+  )]
+  from: Option<Format>,
+
+  #[structopt(
+    long = "rename",
+    value_name = "pattern",
+    help = "Convert the files in directories whose names match a pattern, like '*.json'"
+  )]
+  rename: Option<String>,
+
+  #[structopt(
+    long = "remove-source",
+    help = "Delete each file once its output is written"
+  )]
+  remove_source: bool,
+
+  #[structopt(
+    long = "dry-run",
+    help = "Report the files that would be written and removed, without touching any"
+  )]
+  dry_run: bool,
+
+  #[structopt(
+    short = "j",
+    long = "jobs",
+    value_name = "n",
+    help = "Convert n files at once",
+    default_value = "1"
+  )]
+  jobs: usize,
+
+  #[structopt(
+    name = "path",
+    help = "Files to convert, and directories to convert files in",
+    parse(from_os_str),
+    required = true
+  )]
+  paths: Vec<PathBuf>,
+}
+
+pub fn convert(opt: ConvertOpt) -> Result<(), Box<dyn Error>> {
+  let ext = match opt.to {
+    Format::Json | Format::Yaml | Format::Toml | Format::Csv | Format::Tsv => opt.to.name(),
+    _ => Err(format!(
+      "cannot convert files to {}, which has no file extension",
+      opt.to.name()
+    ))?,
+  };
+  let mut files = Vec::new();
+  for path in &opt.paths {
+    if !path.is_dir() {
+      files.push(path.clone());
+      continue;
+    }
+    let mut entries = Vec::new();
+    for entry in fs::read_dir(path)? {
+      let entry = entry?;
+      if entry.file_type()?.is_file() && selects(&opt, &entry.path()) {
+        entries.push(entry.path());
+      }
+    }
+    entries.sort();
+    files.extend(entries);
+  }
+
+  let matches = Opt::clap().get_matches_from_safe(["jyt", "-t", ext])?;
+  let jyt_opt = Opt::from_clap(&matches);
+  let mut failed: Option<Failed> = None;
+  let mut stdout = io::stdout();
+  for round in files.chunks(opt.jobs.max(1)) {
+    let converted: Vec<Converted> = thread::scope(|scope| {
+      let workers: Vec<_> = round
+        .iter()
+        .map(|source| {
+          let (opt, jyt_opt, matches) = (&opt, &jyt_opt, &matches);
+          scope.spawn(move || {
+            let target = source.with_extension(ext);
+            convert_file(opt, jyt_opt, matches, source, &target)
+              .map_err(|err| (exit_status(err.as_ref()), err.to_string()))
+          })
+        })
+        .collect();
+      workers
+        .into_iter()
+        .map(|worker| match worker.join() {
+          Ok(result) => result,
+          Err(panic) => std::panic::resume_unwind(panic),
+        })
+        .collect()
+    });
+    for (source, result) in round.iter().zip(converted) {
+      match result {
+        Ok(report) => {
+          for line in report {
+            writeln!(stdout, "{}", line)?;
+          }
+        }
+        Err(((code, status), err)) => {
+          eprintln!("{} {}: {}", color::label("error"), source.display(), err);
+          failed
+            .get_or_insert(Failed {
+              code,
+              status,
+              failed: 0,
+              files: files.len(),
+            })
+            .failed += 1;
+        }
+      }
+    }
+  }
+  match failed {
+    Some(failed) => Err(failed)?,
+    None => Ok(()),
+  }
+}
+
+// Converted is what converting a file gives: the lines it has to report, or its
+// error along with the error's code and status.
+type Converted = Result<Vec<String>, ((&'static str, i32), String)>;
+
+// Failed reports the files that 'jyt convert' couldn't convert, with the exit
+// status for the first of them.
+#[derive(Debug)]
+pub struct Failed {
+  pub code: &'static str,
+  pub status: i32,
+  failed: usize,
+  files: usize,
+}
+
+impl fmt::Display for Failed {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    write!(
+      f,
+      "{} of {} files failed to convert",
+      self.failed, self.files
+    )
+  }
+}
+
+impl Error for Failed {}
+
+// Converts one file for 'jyt convert', writing its output to target, and
+// returns what --dry-run has to report about it.
+fn convert_file(
+  opt: &ConvertOpt,
+  jyt_opt: &Opt,
+  matches: &structopt::clap::ArgMatches,
+  source: &Path,
+  target: &Path,
+) -> Result<Vec<String>, Box<dyn Error>> {
+  let from = match opt.from.or_else(|| format_from_extension(source)) {
+    Some(from) => from,
+    None => Err("cannot tell its format from its extension; give it with -f")?,
+  };
+  let input = fs::read(source)?;
+  let pipeline = build_pipeline(jyt_opt, matches, source.display().to_string())?;
+  let mut output = Vec::new();
+  write_output(&input, from, jyt_opt, &pipeline, &mut output).map_err(Classified::output)?;
+  // Converting a file to its own extension rewrites it, and leaves nothing to
+  // remove.
+  let remove = opt.remove_source && source != target;
+
+  if opt.dry_run {
+    let action = match target.exists() {
+      true => "overwrite",
+      false => "create",
+    };
+    let mut report = vec![format!(
+      "would {} {} ({} bytes)",
+      action,
+      target.display(),
+      output.len()
+    )];
+    if remove {
+      report.push(format!("would remove {}", source.display()));
+    }
+    return Ok(report);
+  }
+  fs::write(target, output)?;
+  if remove {
+    fs::remove_file(source)?;
+  }
+  Ok(Vec::new())
+}
+
+// Returns whether a file found in a directory is one to convert.
+fn selects(opt: &ConvertOpt, path: &Path) -> bool {
+  let name = match path.file_name().and_then(|name| name.to_str()) {
+    Some(name) => name,
+    None => return false,
+  };
+  // As in the shell, a name that starts with '.' is hidden from a pattern that
+  // doesn't.
+  let pattern = opt.rename.as_deref();
+  if name.starts_with('.') && !pattern.is_some_and(|pattern| pattern.starts_with('.')) {
+    return false;
+  }
+  match pattern {
+    Some(pattern) => matches_pattern(
+      &pattern.chars().collect::<Vec<_>>(),
+      &name.chars().collect::<Vec<_>>(),
+    ),
+    None => format_from_extension(path).is_some_and(|from| from.name() != opt.to.name()),
+  }
+}
+
+// Returns whether a name matches a pattern, where * matches any run of
+// characters and ? any one character.
+fn matches_pattern(pattern: &[char], name: &[char]) -> bool {
+  match pattern.split_first() {
+    None => name.is_empty(),
+    Some(('*', rest)) => (0..=name.len()).any(|i| matches_pattern(rest, &name[i..])),
+    Some(('?', rest)) => !name.is_empty() && matches_pattern(rest, &name[1..]),
+    Some((c, rest)) => name.first() == Some(c) && matches_pattern(rest, &name[1..]),
+  }
+}
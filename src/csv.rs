@@ -0,0 +1,265 @@
+use std::io::{self, Write};
+use std::str::FromStr;
+
+use crate::value::{Map, Value};
+
+// A minimal implementation of CSV (RFC 4180), along with TSV as the same format
+// with a tab delimiter, which is all that jyt's tabular input and output need.
+
+// Writes one record, quoting any field that contains the delimiter, a quote,
+// or a line break.
+pub fn write_record<W, S>(w: &mut W, fields: &[S], delimiter: char) -> io::Result<()>
+where
+  W: Write,
+  S: AsRef<str>,
+{
+  for (i, field) in fields.iter().enumerate() {
+    if i > 0 {
+      write!(w, "{}", delimiter)?;
+    }
+    let field = field.as_ref();
+    if field.contains([delimiter, '"', '\n', '\r']) {
+      write!(w, "\"{}\"", field.replace('"', "\"\""))?;
+    } else {
+      w.write_all(field.as_bytes())?;
+    }
+  }
+  writeln!(w)
+}
+
+// Returns the text of a value as a field, with null as an empty field and
+// arrays and objects as compact JSON.
+pub fn field(v: &Value) -> String {
+  match v {
+    Value::Null => String::new(),
+    Value::Seq(_) | Value::Map(_) => serde_json::to_string(v).unwrap_or_default(),
+    v => v.key_str().unwrap_or_default(),
+  }
+}
+
+// Missing is what to do when a record lacks one of the requested columns.
+#[derive(Copy, Clone)]
+pub enum Missing {
+  Blank,
+  Error,
+}
+
+impl FromStr for Missing {
+  type Err = String;
+
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    match s {
+      "blank" => Ok(Missing::Blank),
+      "error" => Ok(Missing::Error),
+      _ => Err(format!("'{}' is not a valid missing column policy", s)),
+    }
+  }
+}
+
+// Parses the records of an input, accepting either LF or CRLF line endings and
+// ignoring a final line ending. A byte order mark, which spreadsheets like to
+// write, isn't part of the first column's name.
+pub fn read_records(input: &str, delimiter: char) -> Result<Vec<Vec<String>>, String> {
+  let input = input.strip_prefix('\u{feff}').unwrap_or(input);
+  let mut records = Vec::new();
+  let mut record = Vec::new();
+  let mut field = String::new();
+  let mut chars = input.chars().peekable();
+  let mut line = 1;
+  let mut at_start = true;
+  while let Some(c) = chars.next() {
+    match c {
+      '"' if field.is_empty() => {
+        let start = line;
+        loop {
+          match chars.next() {
+            Some('"') if chars.peek() == Some(&'"') => {
+              chars.next();
+              field.push('"');
+            }
+            Some('"') => break,
+            Some(c) => {
+              if c == '\n' {
+                line += 1;
+              }
+              field.push(c);
+            }
+            None => return Err(format!("line {}: unterminated quoted field", start)),
+          }
+        }
+        match chars.peek() {
+          None | Some('\n' | '\r') => {}
+          Some(&c) if c == delimiter => {}
+          Some(_) => return Err(format!("line {}: unexpected text after quoted field", line)),
+        }
+      }
+      c if c == delimiter => record.push(std::mem::take(&mut field)),
+      '\r' if chars.peek() == Some(&'\n') => {}
+      '\n' => {
+        record.push(std::mem::take(&mut field));
+        records.push(std::mem::take(&mut record));
+        line += 1;
+        at_start = true;
+        continue;
+      }
+      c => field.push(c),
+    }
+    at_start = false;
+  }
+  if !at_start {
+    record.push(field);
+    records.push(record);
+  }
+  Ok(records)
+}
+
+// Converts the records of an input into documents, one for each record after
+// the header, with the header's column names as keys.
+pub fn to_documents(records: Vec<Vec<String>>, hints: &[ColumnHint]) -> Result<Vec<Value>, String> {
+  let mut records = records.into_iter();
+  let header = match records.next() {
+    Some(header) => header,
+    None => return Ok(vec![]),
+  };
+  for (i, name) in header.iter().enumerate() {
+    if header[..i].contains(name) {
+      return Err(format!(
+        "the header has more than one column named '{}'",
+        name
+      ));
+    }
+  }
+  for hint in hints {
+    if !header.contains(&hint.name) {
+      return Err(format!(
+        "no column named '{}' to apply a type to",
+        hint.name
+      ));
+    }
+  }
+  let types: Vec<ColumnType> = header
+    .iter()
+    .map(|name| {
+      hints
+        .iter()
+        .find(|hint| hint.name == *name)
+        .map_or(ColumnType::String, |hint| hint.kind)
+    })
+    .collect();
+
+  let mut docs = Vec::new();
+  for (i, record) in records.enumerate() {
+    if record.len() > header.len() {
+      return Err(format!(
+        "row {} has {} fields, but the header has only {}",
+        i + 1,
+        record.len(),
+        header.len()
+      ));
+    }
+    let mut doc = Map::new();
+    for ((name, kind), field) in header.iter().zip(types.iter()).zip(record) {
+      let v = kind
+        .parse(field)
+        .map_err(|err| format!("row {}, column '{}': {}", i + 1, name, err))?;
+      doc.push(Value::from(name.as_str()), v);
+    }
+    docs.push(Value::Map(doc));
+  }
+  Ok(docs)
+}
+
+// ColumnHint gives the type of the values in a column of tabular input, as
+// given on the command line in the form "name:type".
+#[derive(Clone)]
+pub struct ColumnHint {
+  name: String,
+  kind: ColumnType,
+}
+
+#[derive(Copy, Clone)]
+enum ColumnType {
+  String,
+  Int,
+  Float,
+  Bool,
+  Json,
+  Auto,
+}
+
+impl FromStr for ColumnHint {
+  type Err = String;
+
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    let (name, kind) = match s.rsplit_once(':') {
+      Some(split) => split,
+      None => return Err(format!("'{}' is not of the form NAME:TYPE", s)),
+    };
+    let kind = match kind {
+      "string" => ColumnType::String,
+      "int" => ColumnType::Int,
+      "float" => ColumnType::Float,
+      "bool" => ColumnType::Bool,
+      "json" => ColumnType::Json,
+      "auto" => ColumnType::Auto,
+      _ => {
+        return Err(format!(
+          "'{}' is not a column type (must be string, int, float, bool, json, or auto)",
+          kind
+        ))
+      }
+    };
+    Ok(ColumnHint {
+      name: name.to_owned(),
+      kind,
+    })
+  }
+}
+
+impl ColumnType {
+  // Parses a field as this type. Empty fields are null for every type but
+  // string, since there's no other way to tell a missing value apart.
+  fn parse(self, field: String) -> Result<Value, String> {
+    let invalid = |what: &str| format!("'{}' is not {}", field, what);
+    if field.is_empty() {
+      return Ok(match self {
+        ColumnType::String => Value::String(field),
+        _ => Value::Null,
+      });
+    }
+    match self {
+      ColumnType::String => Ok(Value::String(field)),
+      ColumnType::Int => match field.trim().parse::<i64>() {
+        Ok(n) => Ok(Value::I64(n)),
+        Err(_) => field
+          .trim()
+          .parse::<u64>()
+          .map(Value::U64)
+          .map_err(|_| invalid("an integer")),
+      },
+      ColumnType::Float => field
+        .trim()
+        .parse::<f64>()
+        .map(Value::F64)
+        .map_err(|_| invalid("a number")),
+      ColumnType::Bool => match field.trim().to_ascii_lowercase().as_str() {
+        "true" | "yes" | "y" | "1" => Ok(Value::Bool(true)),
+        "false" | "no" | "n" | "0" => Ok(Value::Bool(false)),
+        _ => Err(invalid("a boolean")),
+      },
+      ColumnType::Json => serde_json::from_str(&field).map_err(|_| invalid("valid JSON")),
+      ColumnType::Auto => Ok(match serde_json::from_str(&field) {
+        Ok(
+          v @ (Value::Null
+          | Value::Bool(_)
+          | Value::I64(_)
+          | Value::U64(_)
+          | Value::I128(_)
+          | Value::U128(_)
+          | Value::F64(_)),
+        ) => v,
+        _ => Value::String(field),
+      }),
+    }
+  }
+}
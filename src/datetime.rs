@@ -0,0 +1,261 @@
+use std::str::FromStr;
+
+use crate::value::Value;
+
+// The toml crate represents datetimes to serde as objects with a single private
+// key, whose value is the datetime's text. Other formats see those objects as
+// they are, and TOML output turns them back into datetimes.
+pub const TOML_FIELD: &str = "$__toml_private_datetime";
+
+// TomlDatetime is how TOML datetimes appear in other formats. Objects keep the
+// toml crate's representation, so that they convert back into datetimes.
+// Strings are the datetime's text, and TOML output turns any string that is a
+// valid datetime back into one. Epoch times are seconds since the Unix epoch,
+// and only cover datetimes with an offset; others become strings.
+#[derive(Copy, Clone, Default, PartialEq)]
+pub enum TomlDatetime {
+  #[default]
+  Object,
+  String,
+  Epoch,
+}
+
+impl FromStr for TomlDatetime {
+  type Err = String;
+
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    match s {
+      "object" => Ok(TomlDatetime::Object),
+      "string" => Ok(TomlDatetime::String),
+      "epoch" => Ok(TomlDatetime::Epoch),
+      _ => Err(format!("'{}' is not a valid TOML datetime mode", s)),
+    }
+  }
+}
+
+// Replaces the datetime objects in a document from TOML input.
+pub fn from_toml(v: &mut Value, mode: TomlDatetime) {
+  if let Some(text) = toml_text(v) {
+    let text = text.to_owned();
+    *v = match mode {
+      TomlDatetime::Object => return,
+      TomlDatetime::Epoch => match epoch_seconds(&text) {
+        Some(n) => epoch_value(n),
+        None => Value::String(text),
+      },
+      TomlDatetime::String => Value::String(text),
+    };
+    return;
+  }
+  match v {
+    Value::Seq(items) => items.iter_mut().for_each(|v| from_toml(v, mode)),
+    Value::Map(map) => map.values_mut().for_each(|v| from_toml(v, mode)),
+    _ => {}
+  }
+}
+
+// Reports whether a document from TOML input has any datetimes.
+pub fn has_toml_datetimes(v: &Value) -> bool {
+  match v {
+    v if toml_text(v).is_some() => true,
+    Value::Seq(items) => items.iter().any(has_toml_datetimes),
+    Value::Map(map) => map.iter().any(|(_, v)| has_toml_datetimes(v)),
+    _ => false,
+  }
+}
+
+fn toml_text(v: &Value) -> Option<&str> {
+  match v {
+    Value::Map(map) if map.len() == 1 => match map.iter().next() {
+      Some((Value::String(k), Value::String(text))) if k == TOML_FIELD => Some(text),
+      _ => None,
+    },
+    _ => None,
+  }
+}
+
+// YamlTimestamps is how plain YAML scalars in the format of the YAML 1.1
+// timestamp type (like 2001-12-14 or 2001-12-14 21:59:43.10 -5) appear in
+// other formats. Strings are the scalar's text as it was written, which is all
+// serde_yaml gives us. RFC 3339 strings are the same times written the one
+// standard way, and epoch times are seconds since the Unix epoch. Timestamps
+// without a time zone are in UTC, as the YAML type says, and dates alone stay
+// dates in RFC 3339 or are the start of their day in epoch times.
+#[derive(Copy, Clone, Default, PartialEq)]
+pub enum YamlTimestamps {
+  #[default]
+  String,
+  Rfc3339,
+  Epoch,
+}
+
+impl FromStr for YamlTimestamps {
+  type Err = String;
+
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    match s {
+      "string" => Ok(YamlTimestamps::String),
+      "rfc3339" => Ok(YamlTimestamps::Rfc3339),
+      "epoch" => Ok(YamlTimestamps::Epoch),
+      _ => Err(format!("'{}' is not a valid YAML timestamp mode", s)),
+    }
+  }
+}
+
+// Returns the value for the text of a plain YAML scalar that is a timestamp, or
+// None if it isn't one or the mode keeps it as it is.
+pub fn from_yaml(text: &str, mode: YamlTimestamps) -> Option<Value> {
+  if mode == YamlTimestamps::String {
+    return None;
+  }
+  let normalized = yaml_rfc3339(text)?;
+  Some(match mode {
+    YamlTimestamps::Epoch if normalized.len() == 10 => {
+      epoch_value(epoch_seconds(&format!("{}T00:00:00Z", normalized))?)
+    }
+    YamlTimestamps::Epoch => epoch_value(epoch_seconds(&normalized)?),
+    _ => Value::String(normalized),
+  })
+}
+
+// Returns a YAML timestamp in RFC 3339 form, or None if the text isn't one.
+fn yaml_rfc3339(text: &str) -> Option<String> {
+  // Returns the number at the start of s, if it has between min and max digits,
+  // and the rest of s after it.
+  fn num(s: &str, min: usize, max: usize) -> Option<(u32, &str)> {
+    let len = s.bytes().take_while(u8::is_ascii_digit).count();
+    match (min..=max).contains(&len) {
+      true => Some((s[..len].parse().ok()?, &s[len..])),
+      false => None,
+    }
+  }
+  let (year, rest) = num(text, 4, 4)?;
+  let (month, rest) = num(rest.strip_prefix('-')?, 1, 2)?;
+  let (day, rest) = num(rest.strip_prefix('-')?, 1, 2)?;
+  if !(1..=12).contains(&month) || !(1..=31).contains(&day) {
+    return None;
+  }
+  let date = format!("{:04}-{:02}-{:02}", year, month, day);
+  // A date alone has to have two digits each for the month and day.
+  if rest.is_empty() {
+    return match text.len() {
+      10 => Some(date),
+      _ => None,
+    };
+  }
+
+  let rest = match rest.strip_prefix(['T', 't']) {
+    Some(rest) => rest,
+    None => match rest.trim_start_matches([' ', '\t']) {
+      trimmed if trimmed.len() < rest.len() => trimmed,
+      _ => return None,
+    },
+  };
+  let (hour, rest) = num(rest, 1, 2)?;
+  let (minute, rest) = num(rest.strip_prefix(':')?, 2, 2)?;
+  let (second, rest) = num(rest.strip_prefix(':')?, 2, 2)?;
+  if hour > 23 || minute > 59 || second > 60 {
+    return None;
+  }
+  let (fraction, rest) = match rest.strip_prefix('.') {
+    Some(rest) => rest.split_at(rest.bytes().take_while(u8::is_ascii_digit).count()),
+    None => ("", rest),
+  };
+  let fraction = match fraction {
+    "" => String::new(),
+    digits => format!(".{}", digits),
+  };
+
+  let zone = match rest.trim_start_matches([' ', '\t']) {
+    "" | "Z" => "Z".to_owned(),
+    zone => {
+      let sign = zone.get(..1).filter(|sign| *sign == "+" || *sign == "-")?;
+      let (hours, rest) = num(&zone[1..], 1, 2)?;
+      let minutes = match rest {
+        "" => 0,
+        rest => match num(rest.strip_prefix(':')?, 2, 2)? {
+          (minutes, "") => minutes,
+          _ => return None,
+        },
+      };
+      format!("{}{:02}:{:02}", sign, hours, minutes)
+    }
+  };
+  Some(format!(
+    "{}T{:02}:{:02}:{:02}{}{}",
+    date, hour, minute, second, fraction, zone
+  ))
+}
+
+// Returns an epoch time as an integer where it's a whole number of seconds.
+fn epoch_value(n: f64) -> Value {
+  match n.fract() == 0.0 && n.abs() < 1e15 {
+    true => Value::I64(n as i64),
+    false => Value::F64(n),
+  }
+}
+
+// Replaces the strings in a document for TOML output that are valid datetimes.
+pub fn to_toml(v: &mut toml::Value) {
+  match v {
+    toml::Value::String(s) => {
+      if let Ok(dt) = s.parse() {
+        *v = toml::Value::Datetime(dt);
+      }
+    }
+    toml::Value::Array(items) => items.iter_mut().for_each(to_toml),
+    toml::Value::Table(table) => table.iter_mut().for_each(|(_, v)| to_toml(v)),
+    _ => {}
+  }
+}
+
+// Returns the seconds since the Unix epoch of an RFC 3339 datetime with an
+// offset, as the toml crate writes them (e.g. "1979-05-27T07:32:00.5-07:00").
+fn epoch_seconds(text: &str) -> Option<f64> {
+  let num = |s: &str| -> Option<i64> {
+    match s.bytes().all(|b| b.is_ascii_digit()) && !s.is_empty() {
+      true => s.parse().ok(),
+      false => None,
+    }
+  };
+  let (date, rest) = (text.get(..10)?, text.get(11..)?);
+  if !matches!(text.as_bytes().get(10), Some(b'T' | b't' | b' ')) {
+    return None;
+  }
+  let (year, month, day) = (num(&date[..4])?, num(&date[5..7])?, num(&date[8..10])?);
+
+  let offset_at = rest.find(['Z', 'z', '+', '-'])?;
+  let (time, offset) = rest.split_at(offset_at);
+  let (hms, fraction) = match time.split_once('.') {
+    Some((hms, fraction)) => (hms, format!("0.{}", fraction).parse::<f64>().ok()?),
+    None => (time, 0.0),
+  };
+  let (hour, minute, second) = (
+    num(hms.get(..2)?)?,
+    num(hms.get(3..5)?)?,
+    num(hms.get(6..)?)?,
+  );
+  let offset_seconds = match offset {
+    "Z" | "z" => 0,
+    _ => {
+      let sign = if offset.starts_with('-') { -1 } else { 1 };
+      sign * (num(offset.get(1..3)?)? * 3600 + num(offset.get(4..6)?)? * 60)
+    }
+  };
+
+  let seconds =
+    days_from_civil(year, month, day) * 86400 + hour * 3600 + minute * 60 + second - offset_seconds;
+  Some(seconds as f64 + fraction)
+}
+
+// Returns the number of days between the Unix epoch and a date in the
+// proleptic Gregorian calendar, following Howard Hinnant's days_from_civil.
+fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+  let year = if month <= 2 { year - 1 } else { year };
+  let era = year.div_euclid(400);
+  let year_of_era = year - era * 400;
+  let month_from_march = (month + 9) % 12;
+  let day_of_year = (153 * month_from_march + 2) / 5 + day - 1;
+  let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+  era * 146097 + day_of_era - 719468
+}
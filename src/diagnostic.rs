@@ -0,0 +1,219 @@
+use std::error::Error;
+use std::fmt;
+use std::str::FromStr;
+
+use crate::color::{self, BOLD_BLUE, BOLD_RED};
+use crate::value::{Map, Value};
+
+// Diagnostic is an error in the input at a known position, which is shown
+// along with the line of input that it points to, in the style of rustc:
+//
+//   expected `:`
+//    --> config.json:2:7
+//     |
+//   2 |   "b" 2}
+//     |       ^
+#[derive(Debug)]
+pub struct Diagnostic {
+  message: String,
+  file: String,
+  line: usize,
+  column: usize,
+  text: String,
+}
+
+// Adds the position of an error to it, if it has one. serde_json, serde_yaml,
+// and toml all end their messages with the position in the same form, and it
+// survives being passed through serde_transcode (which only keeps messages),
+// so we find it there. Errors without a position come back as they are.
+pub fn locate(err: Box<dyn Error>, input: &[u8], file: &str) -> Box<dyn Error> {
+  let message = err.to_string();
+  let (message, line, column) = match split_position(&message) {
+    Some(found) => found,
+    None => return err,
+  };
+  // A position at the very end of input that ends with a line break is on a
+  // line of its own that lines() doesn't see, which is empty.
+  let input = String::from_utf8_lossy(input);
+  let text = match input.lines().nth(line.saturating_sub(1)) {
+    Some(text) => text.trim_end_matches('\r').to_owned(),
+    None if line == input.lines().count() + 1 => String::new(),
+    None => return err,
+  };
+  Box::new(Diagnostic {
+    message: message.to_owned(),
+    file: file.to_owned(),
+    line,
+    column,
+    text,
+  })
+}
+
+// Splits the position off the end of a message. An error that passed through
+// more than one parser (like a serializer error that serde_yaml wrapped) may
+// have more than one, and the last is the outermost.
+fn split_position(message: &str) -> Option<(&str, usize, usize)> {
+  let split = |message: &str| {
+    let (rest, position) = message.rsplit_once(" at line ")?;
+    let (line, column) = position.split_once(" column ")?;
+    Some((rest.len(), line.parse().ok()?, column.parse().ok()?))
+  };
+  let (mut len, line, column) = split(message)?;
+  while let Some((inner, _, _)) = split(&message[..len]) {
+    len = inner;
+  }
+  Some((&message[..len], line, column))
+}
+
+// Reports whether a message ends with a position.
+pub fn has_position(message: &str) -> bool {
+  split_position(message).is_some()
+}
+
+// Returns an error message with the line of its position moved down, for an
+// error from parsing part of the input.
+pub fn with_line_offset(message: &str, offset: usize) -> String {
+  with_offset(message, offset, 0)
+}
+
+// Returns an error message with its position moved to where a part of the
+// input starts, as a number of lines and then a number of columns into the
+// line. Only positions on the first line of the part move across.
+pub fn with_offset(message: &str, lines: usize, columns: usize) -> String {
+  match split_position(message) {
+    Some((message, line, column)) => format!(
+      "{} at line {} column {}",
+      message,
+      line + lines,
+      if line == 1 { column + columns } else { column }
+    ),
+    None => message.to_owned(),
+  }
+}
+
+// Moves an error's position to where a part of the input starts, as
+// with_offset does, for an error from converting that part. Errors without a
+// position come back as they are, with their types.
+pub fn offset_position(err: Box<dyn Error>, lines: usize, columns: usize) -> Box<dyn Error> {
+  let message = err.to_string();
+  match split_position(&message) {
+    Some(_) => with_offset(&message, lines, columns).into(),
+    None => err,
+  }
+}
+
+impl fmt::Display for Diagnostic {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    let number = self.line.to_string();
+    let gutter = " ".repeat(number.len());
+    // Tabs in the line stay as they are in the padding before the caret, so
+    // that the caret lines up however wide they appear.
+    let pad: String = self
+      .text
+      .chars()
+      .take(self.column.saturating_sub(1))
+      .map(|c| if c == '\t' { '\t' } else { ' ' })
+      .collect();
+    // Diagnostics go to stderr, and are colored the way it is.
+    let on = color::stderr();
+    let blue = |text: &str| color::paint(on, BOLD_BLUE, text);
+    writeln!(f, "{}", self.message)?;
+    writeln!(
+      f,
+      "{}{} {}:{}:{}",
+      gutter,
+      blue("-->"),
+      self.file,
+      self.line,
+      self.column
+    )?;
+    writeln!(f, "{}", blue(&format!("{} |", gutter)))?;
+    writeln!(f, "{} {}", blue(&format!("{} |", number)), self.text)?;
+    write!(
+      f,
+      "{} {}{}",
+      blue(&format!("{} |", gutter)),
+      pad,
+      color::paint(on, BOLD_RED, "^")
+    )
+  }
+}
+
+impl Error for Diagnostic {}
+
+// ErrorFormat is how errors appear on stderr. JSON errors are objects on lines
+// of their own, for editors and other tools to read.
+#[derive(Copy, Clone, Default)]
+pub enum ErrorFormat {
+  #[default]
+  Text,
+  Json,
+}
+
+impl FromStr for ErrorFormat {
+  type Err = String;
+
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    match s {
+      "text" => Ok(ErrorFormat::Text),
+      "json" => Ok(ErrorFormat::Json),
+      _ => Err(format!("'{}' is not a valid error format", s)),
+    }
+  }
+}
+
+// Report is an error as its own JSON object. The code is a short name for the
+// kind of error, which stays the same while messages change. The path is the
+// path to the value in the document that the error is about, where there is
+// one, and the file, line, and column point to where the error is in the input.
+pub struct Report<'e> {
+  pub code: &'e str,
+  pub message: String,
+  pub file: Option<&'e str>,
+  pub document: Option<usize>,
+  pub path: Option<&'e str>,
+  pub line: Option<usize>,
+  pub column: Option<usize>,
+}
+
+impl<'e> Report<'e> {
+  // Returns the report for an error, with the position of a Diagnostic (or of
+  // a message that ends with one) split out.
+  pub fn new(code: &'e str, err: &'e (dyn Error + 'static)) -> Self {
+    let mut report = Report {
+      code,
+      message: err.to_string(),
+      file: None,
+      document: None,
+      path: None,
+      line: None,
+      column: None,
+    };
+    if let Some(diagnostic) = err.downcast_ref::<Diagnostic>() {
+      report.message = diagnostic.message.clone();
+      report.file = Some(&diagnostic.file);
+      report.line = Some(diagnostic.line);
+      report.column = Some(diagnostic.column);
+    } else if let Some((message, line, column)) = split_position(&report.message) {
+      report.message = message.to_owned();
+      report.line = Some(line);
+      report.column = Some(column);
+    }
+    report
+  }
+
+  pub fn eprint(&self) {
+    let text = |s: Option<&str>| s.map_or(Value::Null, Value::from);
+    let number = |n: Option<usize>| n.map_or(Value::Null, |n| Value::U64(n as u64));
+    let mut report = Map::new();
+    report.push("code".into(), self.code.into());
+    report.push("message".into(), self.message.as_str().into());
+    report.push("file".into(), text(self.file));
+    report.push("document".into(), number(self.document));
+    report.push("path".into(), text(self.path));
+    report.push("line".into(), number(self.line));
+    report.push("column".into(), number(self.column));
+    let report = serde_json::to_string(&Value::Map(report)).unwrap_or_default();
+    eprintln!("{}", report);
+  }
+}
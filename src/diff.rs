@@ -0,0 +1,200 @@
+use std::fmt::Write;
+
+use crate::color::{self, BOLD, CYAN, GREEN, RED};
+
+// The lines of context around each change in a diff.
+const CONTEXT: usize = 3;
+
+// Past this many differing lines, the shortest diff costs more to find than
+// it's worth, and the part of the text between the lines that match at its
+// start and end is shown as replaced in full.
+const MAX_EDITS: usize = 4096;
+
+#[derive(Copy, Clone, PartialEq)]
+enum Edit {
+  Keep,
+  Delete,
+  Insert,
+}
+
+// Returns a unified diff of the lines of two texts, like 'diff -u', or an empty
+// string if they're the same.
+pub fn unified(old: &str, new: &str, old_name: &str, new_name: &str) -> String {
+  let a: Vec<&str> = old.split_inclusive('\n').collect();
+  let b: Vec<&str> = new.split_inclusive('\n').collect();
+  let edits = edits(&a, &b);
+  let mut diff = String::new();
+  if edits.iter().all(|&edit| edit == Edit::Keep) {
+    return diff;
+  }
+
+  // The lines of each text that come before each edit.
+  let mut at = vec![(0, 0)];
+  for &edit in &edits {
+    let (i, j) = at[at.len() - 1];
+    at.push(match edit {
+      Edit::Keep => (i + 1, j + 1),
+      Edit::Delete => (i + 1, j),
+      Edit::Insert => (i, j + 1),
+    });
+  }
+  let changed = |i: &usize| edits[*i] != Edit::Keep;
+
+  let _ = writeln!(diff, "--- {}\n+++ {}", old_name, new_name);
+  let mut next = (0..edits.len()).find(changed);
+  while let Some(first) = next {
+    // A hunk runs on through any change that's close enough for their context
+    // to meet.
+    let mut last = first;
+    next = (last + 1..edits.len()).find(changed);
+    while let Some(i) = next.filter(|&i| i - last <= 2 * CONTEXT + 1) {
+      last = i;
+      next = (last + 1..edits.len()).find(changed);
+    }
+    let start = first.saturating_sub(CONTEXT);
+    let end = (last + 1 + CONTEXT).min(edits.len());
+    let _ = writeln!(
+      diff,
+      "@@ -{} +{} @@",
+      range(at[start].0, at[end].0),
+      range(at[start].1, at[end].1)
+    );
+    for i in start..end {
+      let (sign, line) = match edits[i] {
+        Edit::Keep => (' ', a[at[i].0]),
+        Edit::Delete => ('-', a[at[i].0]),
+        Edit::Insert => ('+', b[at[i].1]),
+      };
+      diff.push(sign);
+      diff.push_str(line);
+      if !line.ends_with('\n') {
+        diff.push_str("\n\\ No newline at end of file\n");
+      }
+    }
+  }
+  diff
+}
+
+// Returns a unified diff with colors for a terminal: headers in bold, hunk
+// ranges in cyan, and removed and added lines in red and green.
+pub fn paint(diff: &str) -> String {
+  let mut painted = String::new();
+  for (i, line) in diff.split_inclusive('\n').enumerate() {
+    let text = line.strip_suffix('\n').unwrap_or(line);
+    let style = match text.as_bytes().first() {
+      _ if i < 2 => Some(BOLD),
+      Some(b'@') => Some(CYAN),
+      Some(b'-') => Some(RED),
+      Some(b'+') => Some(GREEN),
+      _ => None,
+    };
+    match style {
+      Some(style) => painted.push_str(&color::paint(true, style, text)),
+      None => painted.push_str(text),
+    }
+    painted.push_str(&line[text.len()..]);
+  }
+  painted
+}
+
+// Returns the range of lines from start to end in a hunk header, where a range
+// of no lines is given by the line before it.
+fn range(start: usize, end: usize) -> String {
+  match end - start {
+    0 => format!("{},0", start),
+    1 => format!("{}", start + 1),
+    len => format!("{},{}", start + 1, len),
+  }
+}
+
+// Returns the edits that turn the lines of a into the lines of b.
+fn edits(a: &[&str], b: &[&str]) -> Vec<Edit> {
+  let prefix = a.iter().zip(b).take_while(|(x, y)| x == y).count();
+  let suffix = (a[prefix..].iter().rev())
+    .zip(b[prefix..].iter().rev())
+    .take_while(|(x, y)| x == y)
+    .count();
+  let a = &a[prefix..a.len() - suffix];
+  let b = &b[prefix..b.len() - suffix];
+  let mut edits = vec![Edit::Keep; prefix];
+  match shortest_edits(a, b) {
+    Some(middle) => edits.extend(middle),
+    None => {
+      edits.extend(a.iter().map(|_| Edit::Delete));
+      edits.extend(b.iter().map(|_| Edit::Insert));
+    }
+  }
+  edits.extend((0..suffix).map(|_| Edit::Keep));
+  edits
+}
+
+// Returns the fewest edits that turn a into b, by Myers' algorithm, or None if
+// that takes more than MAX_EDITS. The furthest point reached along each
+// diagonal k (where x - y = k) after d edits is kept for every d, so that the
+// path can be traced back from the end.
+fn shortest_edits(a: &[&str], b: &[&str]) -> Option<Vec<Edit>> {
+  let (n, m) = (a.len() as isize, b.len() as isize);
+  let mut trace: Vec<Vec<isize>> = Vec::new();
+  for d in 0..=(n + m).min(MAX_EDITS as isize) {
+    let mut reached = vec![-1; 2 * d as usize + 1];
+    for k in (-d..=d).step_by(2) {
+      let mut x = match d {
+        0 => 0,
+        _ => match step(&trace[d as usize - 1], d, k, n, m) {
+          Some((_, x)) => x,
+          None => continue,
+        },
+      };
+      while x < n && x - k < m && a[x as usize] == b[(x - k) as usize] {
+        x += 1;
+      }
+      reached[(k + d) as usize] = x;
+      if x == n && x - k == m {
+        trace.push(reached);
+        return Some(trace_back(&trace, n, m));
+      }
+    }
+    trace.push(reached);
+  }
+  None
+}
+
+// Returns the diagonal that the furthest path to diagonal k after d edits comes
+// from, and the point it reaches with its last edit, given the points reached
+// after d - 1 edits. Paths only move within the bounds of a and b.
+fn step(prev: &[isize], d: isize, k: isize, n: isize, m: isize) -> Option<(isize, isize)> {
+  let reached = |k: isize| match k.abs() < d {
+    true => Some(prev[(k + d - 1) as usize]).filter(|&x| x >= 0),
+    false => None,
+  };
+  let insert = reached(k + 1).filter(|&x| x - k <= m);
+  let delete = reached(k - 1).map(|x| x + 1).filter(|&x| x <= n);
+  match (insert, delete) {
+    (Some(inserted), Some(deleted)) if inserted >= deleted => Some((k + 1, inserted)),
+    (_, Some(deleted)) => Some((k - 1, deleted)),
+    (Some(inserted), None) => Some((k + 1, inserted)),
+    (None, None) => None,
+  }
+}
+
+fn trace_back(trace: &[Vec<isize>], n: isize, m: isize) -> Vec<Edit> {
+  let mut edits = Vec::new();
+  let (mut x, mut k) = (n, n - m);
+  for d in (1..trace.len() as isize).rev() {
+    let prev = &trace[d as usize - 1];
+    let (from, moved) = match step(prev, d, k, n, m) {
+      Some(step) => step,
+      None => unreachable!(),
+    };
+    edits.extend((moved..x).map(|_| Edit::Keep));
+    edits.push(match from == k + 1 {
+      true => Edit::Insert,
+      false => Edit::Delete,
+    });
+    x = prev[(from + d - 1) as usize];
+    k = from;
+  }
+  edits.extend((0..x).map(|_| Edit::Keep));
+  edits.reverse();
+  edits
+}
@@ -0,0 +1,403 @@
+// Minimal implementations of the SHA-1 and SHA-2 (FIPS 180-4) hash functions,
+// which is all that jyt needs for content digests of documents.
+
+use std::str::FromStr;
+
+#[derive(Copy, Clone)]
+pub enum Algorithm {
+  Sha1,
+  Sha256,
+  Sha512,
+}
+
+impl FromStr for Algorithm {
+  type Err = String;
+
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    match s {
+      "sha1" => Ok(Algorithm::Sha1),
+      "sha256" => Ok(Algorithm::Sha256),
+      "sha512" => Ok(Algorithm::Sha512),
+      _ => Err(format!("'{}' is not a supported hash algorithm", s)),
+    }
+  }
+}
+
+impl Algorithm {
+  // Returns the lowercase hex digest of the input.
+  pub fn hex_digest(self, input: &[u8]) -> String {
+    let digest = match self {
+      Algorithm::Sha1 => sha1(input),
+      Algorithm::Sha256 => sha256(input),
+      Algorithm::Sha512 => sha512(input),
+    };
+    digest.iter().map(|b| format!("{:02x}", b)).collect()
+  }
+}
+
+// Pads a message as SHA-1 and SHA-256 expect, to a multiple of 64 bytes with a
+// trailing 64-bit length. SHA-512 uses 128-byte blocks and a 128-bit length.
+fn pad(input: &[u8], block: usize) -> Vec<u8> {
+  let len_bytes = block / 8;
+  let mut msg = input.to_vec();
+  msg.push(0x80);
+  while msg.len() % block != block - len_bytes {
+    msg.push(0);
+  }
+  let bits = (input.len() as u128) * 8;
+  msg.extend_from_slice(&bits.to_be_bytes()[16 - len_bytes..]);
+  msg
+}
+
+fn sha1(input: &[u8]) -> Vec<u8> {
+  let mut h: [u32; 5] = [0x67452301, 0xefcdab89, 0x98badcfe, 0x10325476, 0xc3d2e1f0];
+  for chunk in pad(input, 64).chunks(64) {
+    let mut w = [0u32; 80];
+    for (i, word) in chunk.chunks(4).enumerate() {
+      w[i] = u32::from_be_bytes([word[0], word[1], word[2], word[3]]);
+    }
+    for i in 16..80 {
+      w[i] = (w[i - 3] ^ w[i - 8] ^ w[i - 14] ^ w[i - 16]).rotate_left(1);
+    }
+    let [mut a, mut b, mut c, mut d, mut e] = h;
+    for (i, &wi) in w.iter().enumerate() {
+      let (f, k) = match i {
+        0..=19 => ((b & c) | (!b & d), 0x5a827999),
+        20..=39 => (b ^ c ^ d, 0x6ed9eba1),
+        40..=59 => ((b & c) | (b & d) | (c & d), 0x8f1bbcdc),
+        _ => (b ^ c ^ d, 0xca62c1d6),
+      };
+      let t = a
+        .rotate_left(5)
+        .wrapping_add(f)
+        .wrapping_add(e)
+        .wrapping_add(k)
+        .wrapping_add(wi);
+      e = d;
+      d = c;
+      c = b.rotate_left(30);
+      b = a;
+      a = t;
+    }
+    for (h, v) in h.iter_mut().zip([a, b, c, d, e].iter()) {
+      *h = h.wrapping_add(*v);
+    }
+  }
+  h.iter().flat_map(|h| h.to_be_bytes()).collect()
+}
+
+const SHA256_K: [u32; 64] = [
+  0x428a2f98, 0x71374491, 0xb5c0fbcf, 0xe9b5dba5, 0x3956c25b, 0x59f111f1, 0x923f82a4, 0xab1c5ed5,
+  0xd807aa98, 0x12835b01, 0x243185be, 0x550c7dc3, 0x72be5d74, 0x80deb1fe, 0x9bdc06a7, 0xc19bf174,
+  0xe49b69c1, 0xefbe4786, 0x0fc19dc6, 0x240ca1cc, 0x2de92c6f, 0x4a7484aa, 0x5cb0a9dc, 0x76f988da,
+  0x983e5152, 0xa831c66d, 0xb00327c8, 0xbf597fc7, 0xc6e00bf3, 0xd5a79147, 0x06ca6351, 0x14292967,
+  0x27b70a85, 0x2e1b2138, 0x4d2c6dfc, 0x53380d13, 0x650a7354, 0x766a0abb, 0x81c2c92e, 0x92722c85,
+  0xa2bfe8a1, 0xa81a664b, 0xc24b8b70, 0xc76c51a3, 0xd192e819, 0xd6990624, 0xf40e3585, 0x106aa070,
+  0x19a4c116, 0x1e376c08, 0x2748774c, 0x34b0bcb5, 0x391c0cb3, 0x4ed8aa4a, 0x5b9cca4f, 0x682e6ff3,
+  0x748f82ee, 0x78a5636f, 0x84c87814, 0x8cc70208, 0x90befffa, 0xa4506ceb, 0xbef9a3f7, 0xc67178f2,
+];
+
+fn sha256(input: &[u8]) -> Vec<u8> {
+  let mut h: [u32; 8] = [
+    0x6a09e667, 0xbb67ae85, 0x3c6ef372, 0xa54ff53a, 0x510e527f, 0x9b05688c, 0x1f83d9ab, 0x5be0cd19,
+  ];
+  for chunk in pad(input, 64).chunks(64) {
+    let mut w = [0u32; 64];
+    for (i, word) in chunk.chunks(4).enumerate() {
+      w[i] = u32::from_be_bytes([word[0], word[1], word[2], word[3]]);
+    }
+    for i in 16..64 {
+      let s0 = w[i - 15].rotate_right(7) ^ w[i - 15].rotate_right(18) ^ (w[i - 15] >> 3);
+      let s1 = w[i - 2].rotate_right(17) ^ w[i - 2].rotate_right(19) ^ (w[i - 2] >> 10);
+      w[i] = w[i - 16]
+        .wrapping_add(s0)
+        .wrapping_add(w[i - 7])
+        .wrapping_add(s1);
+    }
+    let mut v = h;
+    for (&k, &wi) in SHA256_K.iter().zip(w.iter()) {
+      let [a, b, c, d, e, f, g, hh] = v;
+      let s1 = e.rotate_right(6) ^ e.rotate_right(11) ^ e.rotate_right(25);
+      let ch = (e & f) ^ (!e & g);
+      let t1 = hh
+        .wrapping_add(s1)
+        .wrapping_add(ch)
+        .wrapping_add(k)
+        .wrapping_add(wi);
+      let s0 = a.rotate_right(2) ^ a.rotate_right(13) ^ a.rotate_right(22);
+      let maj = (a & b) ^ (a & c) ^ (b & c);
+      let t2 = s0.wrapping_add(maj);
+      v = [t1.wrapping_add(t2), a, b, c, d.wrapping_add(t1), e, f, g];
+    }
+    for (h, v) in h.iter_mut().zip(v.iter()) {
+      *h = h.wrapping_add(*v);
+    }
+  }
+  h.iter().flat_map(|h| h.to_be_bytes()).collect()
+}
+
+const SHA512_K: [u64; 80] = [
+  0x428a2f98d728ae22,
+  0x7137449123ef65cd,
+  0xb5c0fbcfec4d3b2f,
+  0xe9b5dba58189dbbc,
+  0x3956c25bf348b538,
+  0x59f111f1b605d019,
+  0x923f82a4af194f9b,
+  0xab1c5ed5da6d8118,
+  0xd807aa98a3030242,
+  0x12835b0145706fbe,
+  0x243185be4ee4b28c,
+  0x550c7dc3d5ffb4e2,
+  0x72be5d74f27b896f,
+  0x80deb1fe3b1696b1,
+  0x9bdc06a725c71235,
+  0xc19bf174cf692694,
+  0xe49b69c19ef14ad2,
+  0xefbe4786384f25e3,
+  0x0fc19dc68b8cd5b5,
+  0x240ca1cc77ac9c65,
+  0x2de92c6f592b0275,
+  0x4a7484aa6ea6e483,
+  0x5cb0a9dcbd41fbd4,
+  0x76f988da831153b5,
+  0x983e5152ee66dfab,
+  0xa831c66d2db43210,
+  0xb00327c898fb213f,
+  0xbf597fc7beef0ee4,
+  0xc6e00bf33da88fc2,
+  0xd5a79147930aa725,
+  0x06ca6351e003826f,
+  0x142929670a0e6e70,
+  0x27b70a8546d22ffc,
+  0x2e1b21385c26c926,
+  0x4d2c6dfc5ac42aed,
+  0x53380d139d95b3df,
+  0x650a73548baf63de,
+  0x766a0abb3c77b2a8,
+  0x81c2c92e47edaee6,
+  0x92722c851482353b,
+  0xa2bfe8a14cf10364,
+  0xa81a664bbc423001,
+  0xc24b8b70d0f89791,
+  0xc76c51a30654be30,
+  0xd192e819d6ef5218,
+  0xd69906245565a910,
+  0xf40e35855771202a,
+  0x106aa07032bbd1b8,
+  0x19a4c116b8d2d0c8,
+  0x1e376c085141ab53,
+  0x2748774cdf8eeb99,
+  0x34b0bcb5e19b48a8,
+  0x391c0cb3c5c95a63,
+  0x4ed8aa4ae3418acb,
+  0x5b9cca4f7763e373,
+  0x682e6ff3d6b2b8a3,
+  0x748f82ee5defb2fc,
+  0x78a5636f43172f60,
+  0x84c87814a1f0ab72,
+  0x8cc702081a6439ec,
+  0x90befffa23631e28,
+  0xa4506cebde82bde9,
+  0xbef9a3f7b2c67915,
+  0xc67178f2e372532b,
+  0xca273eceea26619c,
+  0xd186b8c721c0c207,
+  0xeada7dd6cde0eb1e,
+  0xf57d4f7fee6ed178,
+  0x06f067aa72176fba,
+  0x0a637dc5a2c898a6,
+  0x113f9804bef90dae,
+  0x1b710b35131c471b,
+  0x28db77f523047d84,
+  0x32caab7b40c72493,
+  0x3c9ebe0a15c9bebc,
+  0x431d67c49c100d4c,
+  0x4cc5d4becb3e42b6,
+  0x597f299cfc657e2a,
+  0x5fcb6fab3ad6faec,
+  0x6c44198c4a475817,
+];
+
+fn sha512(input: &[u8]) -> Vec<u8> {
+  let mut h: [u64; 8] = [
+    0x6a09e667f3bcc908,
+    0xbb67ae8584caa73b,
+    0x3c6ef372fe94f82b,
+    0xa54ff53a5f1d36f1,
+    0x510e527fade682d1,
+    0x9b05688c2b3e6c1f,
+    0x1f83d9abfb41bd6b,
+    0x5be0cd19137e2179,
+  ];
+  for chunk in pad(input, 128).chunks(128) {
+    let mut w = [0u64; 80];
+    for (i, word) in chunk.chunks(8).enumerate() {
+      let mut bytes = [0u8; 8];
+      bytes.copy_from_slice(word);
+      w[i] = u64::from_be_bytes(bytes);
+    }
+    for i in 16..80 {
+      let s0 = w[i - 15].rotate_right(1) ^ w[i - 15].rotate_right(8) ^ (w[i - 15] >> 7);
+      let s1 = w[i - 2].rotate_right(19) ^ w[i - 2].rotate_right(61) ^ (w[i - 2] >> 6);
+      w[i] = w[i - 16]
+        .wrapping_add(s0)
+        .wrapping_add(w[i - 7])
+        .wrapping_add(s1);
+    }
+    let mut v = h;
+    for (&k, &wi) in SHA512_K.iter().zip(w.iter()) {
+      let [a, b, c, d, e, f, g, hh] = v;
+      let s1 = e.rotate_right(14) ^ e.rotate_right(18) ^ e.rotate_right(41);
+      let ch = (e & f) ^ (!e & g);
+      let t1 = hh
+        .wrapping_add(s1)
+        .wrapping_add(ch)
+        .wrapping_add(k)
+        .wrapping_add(wi);
+      let s0 = a.rotate_right(28) ^ a.rotate_right(34) ^ a.rotate_right(39);
+      let maj = (a & b) ^ (a & c) ^ (b & c);
+      let t2 = s0.wrapping_add(maj);
+      v = [t1.wrapping_add(t2), a, b, c, d.wrapping_add(t1), e, f, g];
+    }
+    for (h, v) in h.iter_mut().zip(v.iter()) {
+      *h = h.wrapping_add(*v);
+    }
+  }
+  h.iter().flat_map(|h| h.to_be_bytes()).collect()
+}
+
+#[cfg(test)]
+mod tests {
+  use super::Algorithm;
+
+  // The examples from FIPS 180, plus messages whose padding lands on either
+  // side of a block boundary.
+  #[test]
+  fn known_answers() {
+    let million = "a".repeat(1_000_000);
+    let cases = [
+      (
+        Algorithm::Sha1,
+        "",
+        "da39a3ee5e6b4b0d3255bfef95601890afd80709",
+      ),
+      (
+        Algorithm::Sha1,
+        "abc",
+        "a9993e364706816aba3e25717850c26c9cd0d89d",
+      ),
+      (
+        Algorithm::Sha1,
+        "abcdbcdecdefdefgefghfghighijhijkijkljklmklmnlmnomnopnopq",
+        "84983e441c3bd26ebaae4aa1f95129e5e54670f1",
+      ),
+      (
+        Algorithm::Sha1,
+        &million,
+        "34aa973cd4c4daa4f61eeb2bdbad27316534016f",
+      ),
+      (
+        Algorithm::Sha1,
+        &million[..55],
+        "c1c8bbdc22796e28c0e15163d20899b65621d65a",
+      ),
+      (
+        Algorithm::Sha1,
+        &million[..56],
+        "c2db330f6083854c99d4b5bfb6e8f29f201be699",
+      ),
+      (
+        Algorithm::Sha1,
+        &million[..64],
+        "0098ba824b5c16427bd7a1122a5a442a25ec644d",
+      ),
+      (
+        Algorithm::Sha256,
+        "",
+        "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855",
+      ),
+      (
+        Algorithm::Sha256,
+        "abc",
+        "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad",
+      ),
+      (
+        Algorithm::Sha256,
+        "abcdbcdecdefdefgefghfghighijhijkijkljklmklmnlmnomnopnopq",
+        "248d6a61d20638b8e5c026930c3e6039a33ce45964ff2167f6ecedd419db06c1",
+      ),
+      (
+        Algorithm::Sha256,
+        &million,
+        "cdc76e5c9914fb9281a1c7e284d73e67f1809a48a497200e046d39ccc7112cd0",
+      ),
+      (
+        Algorithm::Sha256,
+        &million[..55],
+        "9f4390f8d30c2dd92ec9f095b65e2b9ae9b0a925a5258e241c9f1e910f734318",
+      ),
+      (
+        Algorithm::Sha256,
+        &million[..56],
+        "b35439a4ac6f0948b6d6f9e3c6af0f5f590ce20f1bde7090ef7970686ec6738a",
+      ),
+      (
+        Algorithm::Sha256,
+        &million[..64],
+        "ffe054fe7ae0cb6dc65c3af9b61d5209f439851db43d0ba5997337df154668eb",
+      ),
+      (
+        Algorithm::Sha512,
+        "",
+        "cf83e1357eefb8bdf1542850d66d8007d620e4050b5715dc83f4a921d36ce9ce\
+         47d0d13c5d85f2b0ff8318d2877eec2f63b931bd47417a81a538327af927da3e",
+      ),
+      (
+        Algorithm::Sha512,
+        "abc",
+        "ddaf35a193617abacc417349ae20413112e6fa4e89a97ea20a9eeee64b55d39a\
+         2192992a274fc1a836ba3c23a3feebbd454d4423643ce80e2a9ac94fa54ca49f",
+      ),
+      (
+        Algorithm::Sha512,
+        "abcdefghbcdefghicdefghijdefghijkefghijklfghijklmghijklmn\
+         hijklmnoijklmnopjklmnopqklmnopqrlmnopqrsmnopqrstnopqrstu",
+        "8e959b75dae313da8cf4f72814fc143f8f7779c6eb9f7fa17299aeadb6889018\
+         501d289e4900f7e4331b99dec4b5433ac7d329eeb6dd26545e96e55b874be909",
+      ),
+      (
+        Algorithm::Sha512,
+        &million,
+        "e718483d0ce769644e2e42c7bc15b4638e1f98b13b2044285632a803afa973eb\
+         de0ff244877ea60a4cb0432ce577c31beb009c5c2c49aa2e4eadb217ad8cc09b",
+      ),
+      (
+        Algorithm::Sha512,
+        &million[..111],
+        "fa9121c7b32b9e01733d034cfc78cbf67f926c7ed83e82200ef86818196921760\
+         b4beff48404df811b953828274461673c68d04e297b0eb7b2b4d60fc6b566a2",
+      ),
+      (
+        Algorithm::Sha512,
+        &million[..112],
+        "c01d080efd492776a1c43bd23dd99d0a2e626d481e16782e75d54c2503b5dc32\
+         bd05f0f1ba33e568b88fd2d970929b719ecbb152f58f130a407c8830604b70ca",
+      ),
+      (
+        Algorithm::Sha512,
+        &million[..128],
+        "b73d1929aa615934e61a871596b3f3b33359f42b8175602e89f7e06e5f658a24\
+         3667807ed300314b95cacdd579f3e33abdfbe351909519a846d465c59582f321",
+      ),
+    ];
+    for (algorithm, input, want) in cases {
+      assert_eq!(
+        algorithm.hex_digest(input.as_bytes()),
+        want,
+        "{} bytes",
+        input.len()
+      );
+    }
+  }
+}
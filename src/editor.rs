@@ -0,0 +1,211 @@
+use std::io::{self, BufRead, IsTerminal, Read, Write};
+
+use unicode_width::UnicodeWidthStr;
+
+// Reads a line typed at a prompt, for 'jyt repl'. On a terminal, this is a
+// small line editor in the style of readline: the arrow keys move along the
+// line and through the history of earlier lines, and ctrl-A, ctrl-E, ctrl-K,
+// and ctrl-U work as they do in a shell. Anywhere else, like input from a pipe,
+// lines are read as they come, with no prompt. Returns None at the end of the
+// input (or ctrl-D on an empty line).
+pub fn read_line(prompt: &str, history: &[String]) -> io::Result<Option<String>> {
+  let terminal = io::stdin().is_terminal() && io::stdout().is_terminal();
+  if let Some(raw) = terminal.then(RawMode::enter).flatten() {
+    let line = edit(prompt, history);
+    drop(raw);
+    return line;
+  }
+  if terminal {
+    print!("{}", prompt);
+    io::stdout().flush()?;
+  }
+  let mut line = String::new();
+  match io::stdin().lock().read_line(&mut line)? {
+    0 => Ok(None),
+    _ => Ok(Some(line.trim_end_matches(&['\n', '\r'][..]).to_owned())),
+  }
+}
+
+fn edit(prompt: &str, history: &[String]) -> io::Result<Option<String>> {
+  let mut stdout = io::stdout();
+  let mut stdin = io::stdin().lock();
+  let mut next = || -> io::Result<Option<u8>> {
+    let mut byte = [0];
+    match stdin.read(&mut byte)? {
+      0 => Ok(None),
+      _ => Ok(Some(byte[0])),
+    }
+  };
+  let mut line: Vec<char> = Vec::new();
+  let mut cursor = 0;
+  // The line of history on show, where the end of the history is the line
+  // being typed, which is kept aside while an earlier one is.
+  let mut recalled = history.len();
+  let mut typed = Vec::new();
+  loop {
+    redraw(&mut stdout, prompt, &line, cursor)?;
+    let byte = match next()? {
+      Some(byte) => byte,
+      None => return Ok(None),
+    };
+    match byte {
+      b'\r' | b'\n' => {
+        writeln!(stdout)?;
+        return Ok(Some(line.into_iter().collect()));
+      }
+      // ctrl-C gives up on the line, as in a shell.
+      3 => {
+        writeln!(stdout, "^C")?;
+        line.clear();
+        cursor = 0;
+        recalled = history.len();
+      }
+      4 if line.is_empty() => {
+        writeln!(stdout)?;
+        return Ok(None);
+      }
+      4 if cursor < line.len() => {
+        line.remove(cursor);
+      }
+      1 => cursor = 0,
+      5 => cursor = line.len(),
+      2 => cursor = cursor.saturating_sub(1),
+      6 => cursor = (cursor + 1).min(line.len()),
+      11 => line.truncate(cursor),
+      21 => {
+        line.drain(..cursor);
+        cursor = 0;
+      }
+      8 | 127 if cursor > 0 => {
+        cursor -= 1;
+        line.remove(cursor);
+      }
+      8 | 127 => {}
+      27 => {
+        let key = match next()? {
+          Some(b'[' | b'O') => next()?,
+          _ => None,
+        };
+        match key {
+          Some(b'A') if recalled > 0 => {
+            if recalled == history.len() {
+              typed = line.clone();
+            }
+            recalled -= 1;
+            line = history[recalled].chars().collect();
+            cursor = line.len();
+          }
+          Some(b'B') if recalled < history.len() => {
+            recalled += 1;
+            line = match history.get(recalled) {
+              Some(later) => later.chars().collect(),
+              None => typed.clone(),
+            };
+            cursor = line.len();
+          }
+          Some(b'C') => cursor = (cursor + 1).min(line.len()),
+          Some(b'D') => cursor = cursor.saturating_sub(1),
+          Some(b'H') => cursor = 0,
+          Some(b'F') => cursor = line.len(),
+          // Keys like Delete end with a number and a "~".
+          Some(b'0'..=b'9') => {
+            let mut number = vec![key.unwrap_or_default()];
+            while let Some(byte @ b'0'..=b'9') = next()? {
+              number.push(byte);
+            }
+            match &number[..] {
+              b"3" if cursor < line.len() => {
+                line.remove(cursor);
+              }
+              b"1" | b"7" => cursor = 0,
+              b"4" | b"8" => cursor = line.len(),
+              _ => {}
+            }
+          }
+          _ => {}
+        }
+      }
+      // The rest of the bytes of a character come after its first.
+      0x20.. => {
+        let len = match byte {
+          0xf0.. => 4,
+          0xe0.. => 3,
+          0xc0.. => 2,
+          _ => 1,
+        };
+        let mut bytes = vec![byte];
+        for _ in 1..len {
+          bytes.extend(next()?);
+        }
+        for c in String::from_utf8_lossy(&bytes).chars() {
+          line.insert(cursor, c);
+          cursor += 1;
+        }
+      }
+      _ => {}
+    }
+  }
+}
+
+// Draws the prompt and line over the last drawing of them, with the terminal's
+// cursor at the line's.
+fn redraw(stdout: &mut io::Stdout, prompt: &str, line: &[char], cursor: usize) -> io::Result<()> {
+  let text: String = line.iter().collect();
+  let after: String = line[cursor..].iter().collect();
+  write!(stdout, "\r{}{}\x1b[K", prompt, text)?;
+  match after.width() {
+    0 => {}
+    width => write!(stdout, "\x1b[{}D", width)?,
+  }
+  stdout.flush()
+}
+
+// RawMode is the terminal with its own line editing and echo turned off, so
+// that the editor gets each key as it's typed. The terminal is put back as it
+// was when this is dropped.
+#[cfg(unix)]
+struct RawMode(libc::termios);
+
+#[cfg(unix)]
+impl RawMode {
+  fn enter() -> Option<RawMode> {
+    // Safety: termios is plain data, which tcgetattr fills in before it's
+    // read, and the descriptor is stdin, which stays open.
+    unsafe {
+      let mut saved: libc::termios = std::mem::zeroed();
+      if libc::tcgetattr(libc::STDIN_FILENO, &mut saved) != 0 {
+        return None;
+      }
+      let mut raw = saved;
+      raw.c_lflag &= !(libc::ICANON | libc::ECHO | libc::ISIG | libc::IEXTEN);
+      raw.c_iflag &= !(libc::IXON | libc::ICRNL);
+      raw.c_cc[libc::VMIN] = 1;
+      raw.c_cc[libc::VTIME] = 0;
+      if libc::tcsetattr(libc::STDIN_FILENO, libc::TCSADRAIN, &raw) != 0 {
+        return None;
+      }
+      Some(RawMode(saved))
+    }
+  }
+}
+
+#[cfg(unix)]
+impl Drop for RawMode {
+  fn drop(&mut self) {
+    // Safety: The settings are the ones that tcgetattr gave for stdin.
+    unsafe {
+      libc::tcsetattr(libc::STDIN_FILENO, libc::TCSADRAIN, &self.0);
+    }
+  }
+}
+
+// Where there's no raw mode, lines are read as they would be from a pipe.
+#[cfg(not(unix))]
+struct RawMode;
+
+#[cfg(not(unix))]
+impl RawMode {
+  fn enter() -> Option<RawMode> {
+    None
+  }
+}
@@ -0,0 +1,326 @@
+use std::cell::RefCell;
+use std::collections::{HashMap, VecDeque};
+use std::io::{self, BufRead};
+use std::rc::Rc;
+
+use serde::de::value::Error;
+use serde::de::{self, DeserializeSeed, Deserializer, IgnoredAny, MapAccess, SeqAccess, Visitor};
+use serde::Deserialize;
+use yaml_rust::parser::{Event, Parser};
+use yaml_rust::scanner::{Marker, TScalarStyle, TokenType};
+
+// Events is event-driven YAML input, for --stream. serde_yaml collects all of
+// the events of a document before anything reads it, but Events hands each one
+// on as soon as the parser produces it, and the parser reads the input as it
+// goes. Only the events of anchored values are kept, to replay for aliases.
+//
+// Each document is read through a Deserializer for &mut Events, after
+// next_document reports that there is one.
+pub struct Events<R> {
+  parser: Parser<Chars<R>>,
+  error: Rc<RefCell<Option<io::Error>>>,
+  peeked: Option<(Event, Marker)>,
+  replay: VecDeque<(Event, Marker)>,
+  recording: Vec<Recording>,
+  anchors: HashMap<usize, Vec<(Event, Marker)>>,
+  depth: usize,
+}
+
+// Recording is the anchor, starting depth, and events so far of an anchored
+// value that is being read.
+type Recording = (usize, usize, Vec<(Event, Marker)>);
+
+impl<R: BufRead> Events<R> {
+  pub fn new(reader: R) -> Self {
+    let error = Rc::new(RefCell::new(None));
+    let chars = Chars {
+      reader,
+      line: String::new(),
+      pos: 0,
+      error: error.clone(),
+    };
+    Events {
+      parser: Parser::new(chars),
+      error,
+      peeked: None,
+      replay: VecDeque::new(),
+      recording: Vec::new(),
+      anchors: HashMap::new(),
+      depth: 0,
+    }
+  }
+
+  // Reads up to the start of the next document, and reports whether there is
+  // one. Anchors don't carry over from one document to the next.
+  pub fn next_document(&mut self) -> Result<bool, Box<dyn std::error::Error>> {
+    self.anchors.clear();
+    loop {
+      match self.next() {
+        Ok((Event::DocumentStart, _)) => return Ok(true),
+        Ok((Event::StreamEnd, _)) => return Ok(false),
+        Ok(_) => {}
+        Err(err) => return Err(self.io_error(err.into())),
+      }
+    }
+  }
+
+  // Returns the error that stopped the input from being read, if there was
+  // one, in place of the error that the parser saw because of it.
+  pub fn io_error(&self, err: Box<dyn std::error::Error>) -> Box<dyn std::error::Error> {
+    match self.error.borrow_mut().take() {
+      Some(ioerr) => ioerr.into(),
+      None => err,
+    }
+  }
+
+  // Returns the next event from the input, with aliases replaced by the events
+  // of their anchored values.
+  fn pull(&mut self) -> Result<(Event, Marker), Error> {
+    loop {
+      let (event, mark) = match self.replay.pop_front() {
+        Some(next) => next,
+        None => self.parser.next().map_err(de::Error::custom)?,
+      };
+      if let Some(ioerr) = &*self.error.borrow() {
+        return Err(de::Error::custom(ioerr));
+      }
+      match event {
+        Event::Alias(id) => match self.anchors.get(&id).cloned() {
+          Some(events) => events
+            .into_iter()
+            .rev()
+            .for_each(|next| self.replay.push_front(next)),
+          None => Err(de::Error::custom(format!(
+            "unknown anchor at line {} column {}",
+            mark.line(),
+            mark.col() + 1
+          )))?,
+        },
+        event => return Ok((event, mark)),
+      }
+    }
+  }
+
+  fn peek(&mut self) -> Result<&Event, Error> {
+    if self.peeked.is_none() {
+      self.peeked = Some(self.pull()?);
+    }
+    Ok(
+      self
+        .peeked
+        .as_ref()
+        .map(|(event, _)| event)
+        .unwrap_or(&Event::Nothing),
+    )
+  }
+
+  fn next(&mut self) -> Result<(Event, Marker), Error> {
+    let (event, mark) = match self.peeked.take() {
+      Some(next) => next,
+      None => self.pull()?,
+    };
+    for (_, _, events) in &mut self.recording {
+      events.push((event.clone(), mark));
+    }
+    let anchor = match &event {
+      Event::Scalar(_, _, anchor, _) => *anchor,
+      Event::SequenceStart(anchor) | Event::MappingStart(anchor) => *anchor,
+      _ => 0,
+    };
+    if anchor > 0 {
+      self
+        .recording
+        .push((anchor, self.depth, vec![(event.clone(), mark)]));
+    }
+    match event {
+      Event::SequenceStart(_) | Event::MappingStart(_) => self.depth += 1,
+      Event::SequenceEnd | Event::MappingEnd => self.depth -= 1,
+      _ => {}
+    }
+    while matches!(self.recording.last(), Some((_, depth, _)) if *depth == self.depth) {
+      if let Some((anchor, _, events)) = self.recording.pop() {
+        self.anchors.insert(anchor, events);
+      }
+    }
+    Ok((event, mark))
+  }
+
+  // Reads the rest of the entries of a sequence or mapping, after a visitor
+  // that didn't read them all.
+  fn skip_to_end(&mut self) -> Result<(), Error> {
+    loop {
+      match self.peek()? {
+        Event::SequenceEnd | Event::MappingEnd => return self.next().map(drop),
+        _ => IgnoredAny::deserialize(&mut *self).map(drop)?,
+      }
+    }
+  }
+}
+
+impl<'de, R: BufRead> Deserializer<'de> for &mut Events<R> {
+  type Error = Error;
+
+  fn deserialize_any<V>(self, visitor: V) -> Result<V::Value, Error>
+  where
+    V: Visitor<'de>,
+  {
+    let (event, mark) = self.next()?;
+    match event {
+      Event::Scalar(v, TScalarStyle::Plain, _, None) => visit_plain(v, visitor),
+      Event::Scalar(v, TScalarStyle::Plain, _, Some(TokenType::Tag(handle, suffix)))
+        if !(handle == "!" || (handle == "!!" && suffix == "str")) =>
+      {
+        visit_plain(v, visitor)
+      }
+      Event::Scalar(v, _, _, _) => visitor.visit_string(v),
+      Event::SequenceStart(_) => {
+        let result = visitor.visit_seq(&mut *self)?;
+        self.skip_to_end()?;
+        Ok(result)
+      }
+      Event::MappingStart(_) => {
+        let result = visitor.visit_map(&mut *self)?;
+        self.skip_to_end()?;
+        Ok(result)
+      }
+      _ => Err(de::Error::custom(format!(
+        "unexpected YAML event at line {} column {}",
+        mark.line(),
+        mark.col() + 1
+      ))),
+    }
+  }
+
+  serde::forward_to_deserialize_any! {
+    bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+    bytes byte_buf option unit unit_struct newtype_struct seq tuple
+    tuple_struct map struct enum identifier ignored_any
+  }
+}
+
+impl<'de, R: BufRead> SeqAccess<'de> for &mut Events<R> {
+  type Error = Error;
+
+  fn next_element_seed<T>(&mut self, seed: T) -> Result<Option<T::Value>, Error>
+  where
+    T: DeserializeSeed<'de>,
+  {
+    match self.peek()? {
+      Event::SequenceEnd => Ok(None),
+      _ => seed.deserialize(&mut **self).map(Some),
+    }
+  }
+}
+
+impl<'de, R: BufRead> MapAccess<'de> for &mut Events<R> {
+  type Error = Error;
+
+  fn next_key_seed<K>(&mut self, seed: K) -> Result<Option<K::Value>, Error>
+  where
+    K: DeserializeSeed<'de>,
+  {
+    match self.peek()? {
+      Event::MappingEnd => Ok(None),
+      _ => seed.deserialize(&mut **self).map(Some),
+    }
+  }
+
+  fn next_value_seed<V>(&mut self, seed: V) -> Result<V::Value, Error>
+  where
+    V: DeserializeSeed<'de>,
+  {
+    seed.deserialize(&mut **self)
+  }
+}
+
+// Visits a plain scalar as the type that serde_yaml would resolve it to.
+fn visit_plain<'de, V>(v: String, visitor: V) -> Result<V::Value, Error>
+where
+  V: Visitor<'de>,
+{
+  match v.as_str() {
+    "~" | "null" => return visitor.visit_unit(),
+    "true" => return visitor.visit_bool(true),
+    "false" => return visitor.visit_bool(false),
+    _ => {}
+  }
+  let (sign, unsigned) = match v.strip_prefix(['-', '+']) {
+    Some(unsigned) => (&v[..1], unsigned),
+    None => ("", v.as_str()),
+  };
+  for (prefix, radix) in [("0x", 16), ("0o", 8), ("0b", 2)] {
+    if let Some(digits) = unsigned.strip_prefix(prefix) {
+      match sign {
+        "-" => {
+          if let Ok(n) = i64::from_str_radix(&format!("-{}", digits), radix) {
+            return visitor.visit_i64(n);
+          }
+        }
+        _ => {
+          if let Ok(n) = u64::from_str_radix(digits, radix) {
+            return visitor.visit_u64(n);
+          }
+        }
+      }
+    }
+  }
+  // Digits with a leading zero are a string in YAML 1.2.
+  if v.len() > 1 && v.starts_with('0') && v.bytes().all(|b| b.is_ascii_digit()) {
+    return visitor.visit_string(v);
+  }
+  if let Ok(n) = v.parse() {
+    return visitor.visit_u64(n);
+  }
+  if let Ok(n) = v.parse() {
+    return visitor.visit_u128(n);
+  }
+  if let Ok(n) = v.parse() {
+    return visitor.visit_i64(n);
+  }
+  if let Ok(n) = v.parse() {
+    return visitor.visit_i128(n);
+  }
+  if let ".inf" | ".Inf" | ".INF" = v.trim_start_matches('+') {
+    return visitor.visit_f64(f64::INFINITY);
+  }
+  match v.as_str() {
+    "-.inf" | "-.Inf" | "-.INF" => return visitor.visit_f64(f64::NEG_INFINITY),
+    ".nan" | ".NaN" | ".NAN" => return visitor.visit_f64(f64::NAN),
+    _ => {}
+  }
+  match v.parse::<f64>() {
+    Ok(n) if n.is_finite() => visitor.visit_f64(n),
+    _ => visitor.visit_string(v),
+  }
+}
+
+// Chars reads the characters of UTF-8 input a line at a time for the parser,
+// which can only take an iterator of them. An error ends the input, and is
+// kept for Events to report.
+struct Chars<R> {
+  reader: R,
+  line: String,
+  pos: usize,
+  error: Rc<RefCell<Option<io::Error>>>,
+}
+
+impl<R: BufRead> Iterator for Chars<R> {
+  type Item = char;
+
+  fn next(&mut self) -> Option<char> {
+    if self.pos == self.line.len() {
+      self.line.clear();
+      self.pos = 0;
+      match self.reader.read_line(&mut self.line) {
+        Ok(_) => {}
+        Err(err) => {
+          *self.error.borrow_mut() = Some(err);
+          self.line.clear();
+        }
+      }
+    }
+    let c = self.line[self.pos..].chars().next()?;
+    self.pos += c.len_utf8();
+    Some(c)
+  }
+}
@@ -0,0 +1,112 @@
+use std::str::FromStr;
+
+use crate::path;
+use crate::regex::Regex;
+use crate::value::{Map, Value};
+
+// Grep searches the keys and scalar values of documents for a regex, to help
+// find where something lives in a large document. Each match is reported as a
+// document of its own so that it can be output in any format.
+pub struct Grep {
+  regex: Regex,
+  scope: Scope,
+  documents: usize,
+}
+
+#[derive(Copy, Clone)]
+pub enum Scope {
+  Keys,
+  Values,
+  Both,
+}
+
+impl FromStr for Scope {
+  type Err = String;
+
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    match s {
+      "keys" => Ok(Scope::Keys),
+      "values" => Ok(Scope::Values),
+      "both" => Ok(Scope::Both),
+      _ => Err(format!("'{}' is not a valid grep scope", s)),
+    }
+  }
+}
+
+impl Grep {
+  pub fn new(regex: Regex, scope: Scope) -> Self {
+    Grep {
+      regex,
+      scope,
+      documents: 0,
+    }
+  }
+
+  // Returns a report of each match in a document, in document order.
+  pub fn search(&mut self, doc: &Value) -> Vec<Value> {
+    let mut matches = Vec::new();
+    let mut path = String::new();
+    self.visit(doc, &mut path, &mut matches);
+    self.documents += 1;
+    matches
+  }
+
+  fn visit(&self, v: &Value, path: &mut String, matches: &mut Vec<Value>) {
+    match v {
+      Value::Seq(items) => {
+        for (i, item) in items.iter().enumerate() {
+          let len = path.len();
+          path::push_index(path, i);
+          self.visit(item, path, matches);
+          path.truncate(len);
+        }
+      }
+      Value::Map(map) => {
+        for (k, v) in map.iter() {
+          let len = path.len();
+          path::push_key(path, k);
+          let key_matched =
+            self.search_keys() && k.key_str().is_some_and(|k| self.regex.is_match(&k));
+          if key_matched {
+            matches.push(self.report(path, v));
+          }
+          // A value under a matching key is already part of the report, so we
+          // don't report it again on its own.
+          if !key_matched || !is_scalar(v) {
+            self.visit(v, path, matches);
+          }
+          path.truncate(len);
+        }
+      }
+      v => {
+        let matched = self.search_values() && v.key_str().is_some_and(|s| self.regex.is_match(&s));
+        if matched {
+          matches.push(self.report(path, v));
+        }
+      }
+    }
+  }
+
+  fn search_keys(&self) -> bool {
+    matches!(self.scope, Scope::Keys | Scope::Both)
+  }
+
+  fn search_values(&self) -> bool {
+    matches!(self.scope, Scope::Values | Scope::Both)
+  }
+
+  fn report(&self, path: &str, v: &Value) -> Value {
+    let mut report = Map::new();
+    report.push(Value::from("document"), Value::U64(self.documents as u64));
+    report.push(
+      Value::from("path"),
+      Value::from(if path.is_empty() { "." } else { path }),
+    );
+    report.push(Value::from("value"), v.clone());
+    Value::Map(report)
+  }
+}
+
+fn is_scalar(v: &Value) -> bool {
+  !matches!(v, Value::Seq(_) | Value::Map(_))
+}
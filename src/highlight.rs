@@ -0,0 +1,104 @@
+use std::io::{self, Write};
+
+use crate::color::Theme;
+
+// Highlight colors JSON as it's written, by the kind of each token, for JSON
+// output to a terminal. It reads the JSON a byte at a time as it goes by, so
+// that a token can be split across writes, and it leaves the bytes of the JSON
+// itself alone.
+pub struct Highlight<W> {
+  w: W,
+  theme: Theme,
+  token: Token,
+  // Whether each array or object that the JSON is inside of is an object.
+  objects: Vec<bool>,
+  // Whether the next string in an object is a key.
+  key_next: bool,
+  buf: Vec<u8>,
+}
+
+enum Token {
+  None,
+  String { escaped: bool },
+  // A number, or a literal like true or null, which runs to the next byte that
+  // can't be part of one.
+  Bare,
+}
+
+impl<W> Highlight<W> {
+  pub fn new(theme: Theme, w: W) -> Self {
+    Highlight {
+      w,
+      theme,
+      token: Token::None,
+      objects: Vec::new(),
+      key_next: false,
+      buf: Vec::new(),
+    }
+  }
+
+  fn start(&mut self, style: fn(&Theme) -> &str, token: Token) {
+    self.buf.extend_from_slice(b"\x1b[");
+    self.buf.extend_from_slice(style(&self.theme).as_bytes());
+    self.buf.push(b'm');
+    self.token = token;
+  }
+
+  fn end(&mut self) {
+    self.buf.extend_from_slice(b"\x1b[0m");
+    self.token = Token::None;
+  }
+
+  fn push(&mut self, byte: u8) {
+    match self.token {
+      Token::String { escaped: true } => self.token = Token::String { escaped: false },
+      Token::String { .. } if byte == b'\\' => self.token = Token::String { escaped: true },
+      Token::String { .. } if byte == b'"' => {
+        self.buf.push(byte);
+        return self.end();
+      }
+      Token::String { .. } => {}
+      Token::Bare if byte.is_ascii_alphanumeric() || matches!(byte, b'+' | b'-' | b'.') => {}
+      Token::Bare => {
+        self.end();
+        return self.push(byte);
+      }
+      Token::None => match byte {
+        b'"' if self.key_next => self.start(|theme| &theme.key, Token::String { escaped: false }),
+        b'"' => self.start(|theme| &theme.string, Token::String { escaped: false }),
+        b'{' => {
+          self.objects.push(true);
+          self.key_next = true;
+        }
+        b'[' => self.objects.push(false),
+        b'}' | b']' => {
+          self.objects.pop();
+          self.key_next = false;
+        }
+        b',' => self.key_next = self.objects.last() == Some(&true),
+        b':' => self.key_next = false,
+        b't' | b'f' => self.start(|theme| &theme.bool, Token::Bare),
+        b'n' => self.start(|theme| &theme.null, Token::Bare),
+        // Numbers include the NaN and Infinity of '--non-finite literal'.
+        b'-' | b'0'..=b'9' | b'N' | b'I' => self.start(|theme| &theme.number, Token::Bare),
+        _ => {}
+      },
+    }
+    self.buf.push(byte);
+  }
+}
+
+impl<W: Write> Write for Highlight<W> {
+  fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+    for &byte in buf {
+      self.push(byte);
+    }
+    self.w.write_all(&self.buf)?;
+    self.buf.clear();
+    Ok(buf.len())
+  }
+
+  fn flush(&mut self) -> io::Result<()> {
+    self.w.flush()
+  }
+}
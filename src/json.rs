@@ -0,0 +1,832 @@
+use std::error::Error;
+use std::fmt;
+use std::str::FromStr;
+use std::thread;
+
+use serde::de::{
+  DeserializeSeed, Deserializer, Error as _, IntoDeserializer, MapAccess, SeqAccess, Visitor,
+};
+use serde::ser::{Serialize, SerializeMap, SerializeSeq, SerializeStruct, Serializer};
+use serde::Deserialize;
+
+use crate::diagnostic;
+use crate::path;
+use crate::schema::Positions;
+use crate::value::Value;
+use crate::warn;
+
+// This module handles details of JSON that serde_json doesn't cover on its own
+// terms, chiefly the precision of numbers and floats that JSON can't represent.
+//
+// serde_json is built with arbitrary_precision so that it keeps the source text
+// of each number instead of converting it to a float when it doesn't fit in 64
+// bits. It hands that text to visitors as a single-entry map under a private
+// key, which Numbers turns back into the narrowest primitive that holds the
+// number exactly: a 64-bit integer where possible, then a 128-bit integer,
+// then a float.
+pub const NUMBER_TOKEN: &str = "$serde_json::private::Number";
+
+#[derive(Copy, Clone, Debug)]
+pub enum Number {
+  U64(u64),
+  I64(i64),
+  U128(u128),
+  I128(i128),
+  F64(f64),
+}
+
+impl Number {
+  pub fn parse(text: &str) -> Result<Number, String> {
+    let out_of_range = || format!("number out of range: {}", text);
+    if !text.contains(['.', 'e', 'E']) {
+      if let Ok(n) = text.parse() {
+        return Ok(Number::U64(n));
+      }
+      if let Ok(n) = text.parse() {
+        return Ok(Number::I64(n));
+      }
+      if let Ok(n) = text.parse() {
+        return Ok(Number::U128(n));
+      }
+      if let Ok(n) = text.parse() {
+        return Ok(Number::I128(n));
+      }
+      warn::warn("integers too large for 128 bits were converted to floats, losing precision");
+    }
+    match text.parse::<f64>() {
+      Ok(n) if n.is_finite() => Ok(Number::F64(n)),
+      _ => Err(out_of_range()),
+    }
+  }
+
+  pub fn visit<'de, V, E>(self, visitor: V) -> Result<V::Value, E>
+  where
+    V: Visitor<'de>,
+    E: serde::de::Error,
+  {
+    match self {
+      Number::U64(n) => visitor.visit_u64(n),
+      Number::I64(n) => visitor.visit_i64(n),
+      Number::U128(n) => visitor.visit_u128(n),
+      Number::I128(n) => visitor.visit_i128(n),
+      Number::F64(n) => visitor.visit_f64(n),
+    }
+  }
+}
+
+// Numbers wraps a serde_json deserializer so that numbers come out of it as
+// primitives, as they would without arbitrary_precision, but without losing
+// precision for integers of up to 128 bits.
+pub struct Numbers<D>(pub D);
+
+impl<'de, D> Deserializer<'de> for Numbers<D>
+where
+  D: Deserializer<'de>,
+{
+  type Error = D::Error;
+
+  fn deserialize_any<V>(self, visitor: V) -> Result<V::Value, D::Error>
+  where
+    V: Visitor<'de>,
+  {
+    self.0.deserialize_any(NumbersVisitor(visitor))
+  }
+
+  serde::forward_to_deserialize_any! {
+    bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+    bytes byte_buf option unit unit_struct newtype_struct seq tuple
+    tuple_struct map struct enum identifier ignored_any
+  }
+}
+
+// NumbersVisitor forwards everything that serde_json's deserializer produces to
+// another visitor, wrapping nested values so that they get the same treatment.
+struct NumbersVisitor<V>(V);
+
+impl<'de, V> Visitor<'de> for NumbersVisitor<V>
+where
+  V: Visitor<'de>,
+{
+  type Value = V::Value;
+
+  fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    self.0.expecting(f)
+  }
+
+  fn visit_bool<E>(self, v: bool) -> Result<V::Value, E>
+  where
+    E: serde::de::Error,
+  {
+    self.0.visit_bool(v)
+  }
+
+  fn visit_i64<E>(self, v: i64) -> Result<V::Value, E>
+  where
+    E: serde::de::Error,
+  {
+    self.0.visit_i64(v)
+  }
+
+  fn visit_u64<E>(self, v: u64) -> Result<V::Value, E>
+  where
+    E: serde::de::Error,
+  {
+    self.0.visit_u64(v)
+  }
+
+  fn visit_f64<E>(self, v: f64) -> Result<V::Value, E>
+  where
+    E: serde::de::Error,
+  {
+    self.0.visit_f64(v)
+  }
+
+  fn visit_str<E>(self, v: &str) -> Result<V::Value, E>
+  where
+    E: serde::de::Error,
+  {
+    self.0.visit_str(v)
+  }
+
+  fn visit_borrowed_str<E>(self, v: &'de str) -> Result<V::Value, E>
+  where
+    E: serde::de::Error,
+  {
+    self.0.visit_borrowed_str(v)
+  }
+
+  fn visit_string<E>(self, v: String) -> Result<V::Value, E>
+  where
+    E: serde::de::Error,
+  {
+    self.0.visit_string(v)
+  }
+
+  fn visit_unit<E>(self) -> Result<V::Value, E>
+  where
+    E: serde::de::Error,
+  {
+    self.0.visit_unit()
+  }
+
+  fn visit_seq<A>(self, seq: A) -> Result<V::Value, A::Error>
+  where
+    A: SeqAccess<'de>,
+  {
+    self.0.visit_seq(NumbersSeq(seq))
+  }
+
+  fn visit_map<A>(self, mut map: A) -> Result<V::Value, A::Error>
+  where
+    A: MapAccess<'de>,
+  {
+    let first: Option<String> = map.next_key()?;
+    if first.as_deref() == Some(NUMBER_TOKEN) {
+      let text: String = map.next_value()?;
+      let n = Number::parse(&text).map_err(A::Error::custom)?;
+      return n.visit(self.0);
+    }
+    self.0.visit_map(NumbersMap {
+      map,
+      first: Some(first),
+    })
+  }
+}
+
+struct NumbersSeed<S>(S);
+
+impl<'de, S> DeserializeSeed<'de> for NumbersSeed<S>
+where
+  S: DeserializeSeed<'de>,
+{
+  type Value = S::Value;
+
+  fn deserialize<D>(self, deserializer: D) -> Result<S::Value, D::Error>
+  where
+    D: Deserializer<'de>,
+  {
+    self.0.deserialize(Numbers(deserializer))
+  }
+}
+
+struct NumbersSeq<A>(A);
+
+impl<'de, A> SeqAccess<'de> for NumbersSeq<A>
+where
+  A: SeqAccess<'de>,
+{
+  type Error = A::Error;
+
+  fn next_element_seed<T>(&mut self, seed: T) -> Result<Option<T::Value>, A::Error>
+  where
+    T: DeserializeSeed<'de>,
+  {
+    self.0.next_element_seed(NumbersSeed(seed))
+  }
+
+  fn size_hint(&self) -> Option<usize> {
+    self.0.size_hint()
+  }
+}
+
+// NumbersMap replays the first key of a map, which NumbersVisitor had to read
+// to tell whether the map was really a number.
+struct NumbersMap<A> {
+  map: A,
+  first: Option<Option<String>>,
+}
+
+impl<'de, A> MapAccess<'de> for NumbersMap<A>
+where
+  A: MapAccess<'de>,
+{
+  type Error = A::Error;
+
+  fn next_key_seed<K>(&mut self, seed: K) -> Result<Option<K::Value>, A::Error>
+  where
+    K: DeserializeSeed<'de>,
+  {
+    match self.first.take() {
+      Some(None) => Ok(None),
+      Some(Some(key)) => seed.deserialize(key.into_deserializer()).map(Some),
+      None => self.map.next_key_seed(seed),
+    }
+  }
+
+  fn next_value_seed<T>(&mut self, seed: T) -> Result<T::Value, A::Error>
+  where
+    T: DeserializeSeed<'de>,
+  {
+    self.map.next_value_seed(NumbersSeed(seed))
+  }
+
+  fn size_hint(&self) -> Option<usize> {
+    self.map.size_hint()
+  }
+}
+
+// Returns a copy of JSON input with comments and trailing commas blanked out
+// with spaces, for --lenient. Keeping everything else in place (including the
+// line breaks within block comments) keeps the positions in error messages
+// accurate.
+pub fn strip_extensions(input: &[u8]) -> Vec<u8> {
+  let mut out = input.to_vec();
+  let mut i = 0;
+  let mut in_string = false;
+  let mut last_comma = None;
+  while i < out.len() {
+    match (in_string, out[i]) {
+      (true, b'\\') => i += 1,
+      (true, b'"') => in_string = false,
+      (true, _) => {}
+      (false, b'"') => {
+        in_string = true;
+        last_comma = None;
+      }
+      (false, b'/') if out.get(i + 1) == Some(&b'/') => {
+        while i < out.len() && out[i] != b'\n' {
+          out[i] = b' ';
+          i += 1;
+        }
+        continue;
+      }
+      (false, b'/') if out.get(i + 1) == Some(&b'*') => {
+        let end = out[i + 2..]
+          .windows(2)
+          .position(|w| w == b"*/")
+          .map_or(out.len(), |pos| i + 2 + pos + 2);
+        for b in &mut out[i..end] {
+          if *b != b'\n' {
+            *b = b' ';
+          }
+        }
+        i = end;
+        continue;
+      }
+      (false, b',') => last_comma = Some(i),
+      (false, b']' | b'}') => {
+        if let Some(comma) = last_comma.take() {
+          out[comma] = b' ';
+        }
+      }
+      (false, b) if b.is_ascii_whitespace() => {}
+      (false, _) => last_comma = None,
+    }
+    i += 1;
+  }
+  out
+}
+
+// Surrogates is what to do with \u escapes in JSON strings that don't stand for
+// a character: lone halves of UTF-16 surrogate pairs, and escapes without four
+// hex digits. serde_json rejects them, and strings can't hold lone surrogates,
+// so the other policies replace them in the input text before parsing.
+#[derive(Copy, Clone)]
+pub enum Surrogates {
+  Error,
+  Replace,
+  Escape,
+}
+
+impl FromStr for Surrogates {
+  type Err = String;
+
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    match s {
+      "error" => Ok(Surrogates::Error),
+      "replace" => Ok(Surrogates::Replace),
+      "escape" => Ok(Surrogates::Escape),
+      _ => Err(format!("'{}' is not a valid surrogate policy", s)),
+    }
+  }
+}
+
+// Returns a copy of JSON input with the bad \u escapes in its strings fixed by
+// a policy, or None if there are none (or the policy is to reject them).
+// Replacing an escape with \ufffd keeps the length of the input the same, so
+// positions in error messages stay accurate.
+pub fn fix_surrogates(input: &[u8], policy: Surrogates) -> Option<Vec<u8>> {
+  if let Surrogates::Error = policy {
+    return None;
+  }
+  let hex = |at: usize| -> Option<u16> {
+    let digits = input.get(at..at + 4)?;
+    u16::from_str_radix(std::str::from_utf8(digits).ok()?, 16).ok()
+  };
+  let mut out = Vec::with_capacity(input.len());
+  let mut changed = false;
+  let mut in_string = false;
+  let mut i = 0;
+  while i < input.len() {
+    let b = input[i];
+    match (in_string, b) {
+      (false, b'"') => in_string = true,
+      (false, _) => {}
+      (true, b'"') => in_string = false,
+      (true, b'\\') if input.get(i + 1) == Some(&b'u') => {
+        let (len, valid) = match hex(i + 2) {
+          Some(0xd800..=0xdbff) => match (input.get(i + 6..i + 8), hex(i + 8)) {
+            (Some(b"\\u"), Some(0xdc00..=0xdfff)) => (12, true),
+            _ => (6, false),
+          },
+          Some(0xdc00..=0xdfff) => (6, false),
+          Some(_) => (6, true),
+          // An escape without four hex digits ends where the digits do.
+          None => {
+            let digits = input[i + 2..]
+              .iter()
+              .take(4)
+              .take_while(|b| b.is_ascii_hexdigit())
+              .count();
+            (2 + digits, false)
+          }
+        };
+        match (valid, policy) {
+          (true, _) => out.extend_from_slice(&input[i..i + len]),
+          (false, Surrogates::Escape) => {
+            out.push(b'\\');
+            out.extend_from_slice(&input[i..i + len]);
+          }
+          (false, _) => out.extend_from_slice(b"\\ufffd"),
+        }
+        changed |= !valid;
+        i += len;
+        continue;
+      }
+      (true, b'\\') => {
+        out.extend_from_slice(&input[i..(i + 2).min(input.len())]);
+        i += 2;
+        continue;
+      }
+      (true, _) => {}
+    }
+    out.push(b);
+    i += 1;
+  }
+  match changed {
+    true => Some(out),
+    false => None,
+  }
+}
+
+// NonFinite is what to do with NaN and infinite floats in JSON output, which
+// has no way to represent them.
+#[derive(Copy, Clone)]
+pub enum NonFinite {
+  Error,
+  Null,
+  String,
+  Literal,
+}
+
+impl FromStr for NonFinite {
+  type Err = String;
+
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    match s {
+      "error" => Ok(NonFinite::Error),
+      "null" => Ok(NonFinite::Null),
+      "string" => Ok(NonFinite::String),
+      "literal" => Ok(NonFinite::Literal),
+      _ => Err(format!("'{}' is not a valid non-finite number policy", s)),
+    }
+  }
+}
+
+// Returns the path to the first non-finite float in a value, if it has one.
+pub fn find_non_finite(v: &Value) -> Option<(String, f64)> {
+  let mut path = String::new();
+  find_non_finite_at(v, &mut path).map(|n| (path, n))
+}
+
+fn find_non_finite_at(v: &Value, path: &mut String) -> Option<f64> {
+  match v {
+    Value::F64(n) if !n.is_finite() => Some(*n),
+    Value::Seq(items) => items.iter().enumerate().find_map(|(i, item)| {
+      let len = path.len();
+      path::push_index(path, i);
+      let found = find_non_finite_at(item, path);
+      if found.is_none() {
+        path.truncate(len);
+      }
+      found
+    }),
+    Value::Map(map) => map.iter().find_map(|(k, item)| {
+      let len = path.len();
+      path::push_key(path, k);
+      let found = find_non_finite_at(item, path);
+      if found.is_none() {
+        path.truncate(len);
+      }
+      found
+    }),
+    _ => None,
+  }
+}
+
+// Returns the JavaScript spelling of a non-finite float, which is what most
+// JSON extensions that allow them use.
+pub fn non_finite_name(n: f64) -> &'static str {
+  if n.is_nan() {
+    "NaN"
+  } else if n > 0.0 {
+    "Infinity"
+  } else {
+    "-Infinity"
+  }
+}
+
+// WithNonFinite serializes a value with its non-finite floats written as
+// strings or as bare literals. Literals rely on serde_json's handling of its
+// private number token, which writes the number's text as-is.
+pub struct WithNonFinite<'v>(pub &'v Value, pub NonFinite);
+
+impl<'v> Serialize for WithNonFinite<'v> {
+  fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+  where
+    S: Serializer,
+  {
+    match (self.0, self.1) {
+      (Value::F64(n), NonFinite::String) if !n.is_finite() => {
+        serializer.serialize_str(non_finite_name(*n))
+      }
+      (Value::F64(n), NonFinite::Literal) if !n.is_finite() => {
+        let mut s = serializer.serialize_struct(NUMBER_TOKEN, 1)?;
+        s.serialize_field(NUMBER_TOKEN, non_finite_name(*n))?;
+        s.end()
+      }
+      (Value::Seq(items), policy) => {
+        let mut seq = serializer.serialize_seq(Some(items.len()))?;
+        for item in items {
+          seq.serialize_element(&WithNonFinite(item, policy))?;
+        }
+        seq.end()
+      }
+      (Value::Map(entries), policy) => {
+        let mut map = serializer.serialize_map(Some(entries.len()))?;
+        for (k, v) in entries.iter() {
+          map.serialize_entry(k, &WithNonFinite(v, policy))?;
+        }
+        map.end()
+      }
+      (v, _) => v.serialize(serializer),
+    }
+  }
+}
+
+// Cursor is a place in JSON input with several documents, from which to read
+// the next one. serde_json works out the position of an error, including the
+// one that end() returns when there's more input, by counting lines from the
+// start of what it was given. With one deserializer for the whole input, that
+// takes longer for each document than the last, so each document gets its own
+// deserializer for the rest of the input from the cursor instead.
+pub struct Cursor<'a> {
+  input: &'a [u8],
+  offset: usize,
+  // The lines before the cursor, and the bytes before it on its own line.
+  lines: usize,
+  columns: usize,
+}
+
+impl<'a> Cursor<'a> {
+  pub fn new(input: &'a [u8]) -> Self {
+    Cursor {
+      input,
+      offset: 0,
+      lines: 0,
+      columns: 0,
+    }
+  }
+
+  pub fn rest(&self) -> &'a [u8] {
+    &self.input[self.offset..]
+  }
+
+  // Moves the cursor up to a position in the rest of the input, as serde_json
+  // gives it: a line counting from 1, and a column that counts the byte at the
+  // position itself.
+  pub fn advance(&mut self, line: usize, column: usize) {
+    let rest = self.rest();
+    let mut start = 0;
+    for _ in 1..line {
+      match rest[start..].iter().position(|b| *b == b'\n') {
+        Some(i) => start += i + 1,
+        None => start = rest.len(),
+      }
+    }
+    let end = (start + column.saturating_sub(1)).min(rest.len());
+    let lines = rest[..start].iter().filter(|b| **b == b'\n').count();
+    match lines {
+      0 => self.columns += end,
+      _ => self.columns = end - start,
+    }
+    self.lines += lines;
+    self.offset += end;
+  }
+
+  // Moves the position of an error from reading the rest of the input to its
+  // place in the whole input.
+  pub fn offset_position(&self, err: Box<dyn Error>) -> Box<dyn Error> {
+    diagnostic::offset_position(err, self.lines, self.columns)
+  }
+}
+
+// Returns where each value of a JSON input starts, for reporting violations of
+// a schema, or None if the input isn't plain JSON. Columns count bytes, as in
+// serde_json's errors, and a map entry starts at its key. The input has already
+// been parsed, so this only follows its structure, without checking it, and
+// without recursing, so that deep nesting can't overflow the stack.
+pub fn positions(input: &[u8]) -> Option<Positions> {
+  enum Frame {
+    Map(usize),
+    Seq(usize, usize),
+  }
+  let mut scan = Scan {
+    input,
+    i: 0,
+    line: 1,
+    column: 1,
+  };
+  let mut positions = Positions::new();
+  let mut doc = 0;
+  loop {
+    scan.skip_space();
+    if scan.i >= input.len() {
+      return Some(positions);
+    }
+    let mut path = String::new();
+    let mut stack = Vec::new();
+    positions
+      .entry((doc, ".".to_owned()))
+      .or_default()
+      .push(scan.position());
+    // Each turn of the loop reads a value, and then everything up to the start
+    // of the next value in the document, recording where that one starts.
+    'document: loop {
+      match scan.peek()? {
+        b'{' | b'[' => {
+          let map = scan.next()? == b'{';
+          scan.skip_space();
+          match (map, scan.peek()?) {
+            (true, b'}') | (false, b']') => {
+              scan.next();
+            }
+            (true, _) => {
+              stack.push(Frame::Map(path.len()));
+              scan.key(doc, &mut path, &mut positions)?;
+              continue;
+            }
+            (false, _) => {
+              stack.push(Frame::Seq(path.len(), 0));
+              path::push_index(&mut path, 0);
+              positions
+                .entry((doc, path.clone()))
+                .or_default()
+                .push(scan.position());
+              continue;
+            }
+          }
+        }
+        b'"' => scan.string()?,
+        _ => scan.bare(),
+      }
+      loop {
+        scan.skip_space();
+        match (stack.last_mut(), scan.peek()) {
+          (None, _) => break 'document,
+          (Some(Frame::Map(len)), Some(b',')) => {
+            path.truncate(*len);
+            scan.next();
+            scan.skip_space();
+            scan.key(doc, &mut path, &mut positions)?;
+            break;
+          }
+          (Some(Frame::Seq(len, i)), Some(b',')) => {
+            path.truncate(*len);
+            *i += 1;
+            path::push_index(&mut path, *i);
+            scan.next();
+            scan.skip_space();
+            positions
+              .entry((doc, path.clone()))
+              .or_default()
+              .push(scan.position());
+            break;
+          }
+          (Some(Frame::Map(_)), Some(b'}')) | (Some(Frame::Seq(..)), Some(b']')) => {
+            scan.next();
+            stack.pop();
+          }
+          _ => return None,
+        }
+      }
+    }
+    doc += 1;
+  }
+}
+
+// Scan is a position in JSON input for positions, as a byte index and as the
+// line and column that it's at.
+struct Scan<'a> {
+  input: &'a [u8],
+  i: usize,
+  line: usize,
+  column: usize,
+}
+
+impl<'a> Scan<'a> {
+  fn position(&self) -> (usize, usize) {
+    (self.line, self.column)
+  }
+
+  fn peek(&self) -> Option<u8> {
+    self.input.get(self.i).copied()
+  }
+
+  fn next(&mut self) -> Option<u8> {
+    let b = self.peek()?;
+    self.i += 1;
+    match b {
+      b'\n' => {
+        self.line += 1;
+        self.column = 1;
+      }
+      _ => self.column += 1,
+    }
+    Some(b)
+  }
+
+  fn skip_space(&mut self) {
+    // JSON text sequences put a record separator before each document.
+    while matches!(self.peek(), Some(b' ' | b'\t' | b'\r' | b'\n' | b'\x1e')) {
+      self.next();
+    }
+  }
+
+  // Reads past a string.
+  fn string(&mut self) -> Option<()> {
+    self.next();
+    loop {
+      match self.next()? {
+        b'\\' => {
+          self.next();
+        }
+        b'"' => return Some(()),
+        _ => {}
+      }
+    }
+  }
+
+  // Reads past a number or a literal like true.
+  fn bare(&mut self) {
+    while let Some(b) = self.peek() {
+      if b.is_ascii_whitespace() || matches!(b, b',' | b']' | b'}' | b'\x1e') {
+        break;
+      }
+      self.next();
+    }
+  }
+
+  // Reads a key of a map and the colon after it, adding the key to a path and
+  // recording where the entry starts.
+  fn key(&mut self, doc: usize, path: &mut String, positions: &mut Positions) -> Option<()> {
+    let (start, position) = (self.i, self.position());
+    if self.peek()? != b'"' {
+      return None;
+    }
+    self.string()?;
+    let key: String = serde_json::from_slice(&self.input[start..self.i]).ok()?;
+    path::push_key(path, &Value::from(key.as_str()));
+    positions
+      .entry((doc, path.clone()))
+      .or_default()
+      .push(position);
+    self.skip_space();
+    match self.next()? {
+      b':' => {
+        self.skip_space();
+        Some(())
+      }
+      _ => None,
+    }
+  }
+}
+
+// The size of the chunks of input that parse_parallel hands to each thread.
+const CHUNK_SIZE: usize = 1 << 20;
+
+// Parses JSON input with a document on each line on several threads, handing
+// the documents to f in input order until it returns false. The input is split
+// into chunks at line breaks, and each round of chunks (one for each thread)
+// is parsed at once, so only that many chunks of documents are held in memory.
+// A document that spans lines may be split between chunks, which is an error.
+pub fn parse_parallel<F>(input: &[u8], jobs: usize, mut f: F) -> Result<(), Box<dyn Error>>
+where
+  F: FnMut(Value) -> Result<bool, Box<dyn Error>>,
+{
+  let mut chunks = Vec::new();
+  let mut start = 0;
+  while start < input.len() {
+    let end = match input[(start + CHUNK_SIZE).min(input.len())..]
+      .iter()
+      .position(|b| *b == b'\n')
+    {
+      Some(i) => start + CHUNK_SIZE + i + 1,
+      None => input.len(),
+    };
+    chunks.push(&input[start..end]);
+    start = end;
+  }
+
+  let mut line = 0;
+  for round in chunks.chunks(jobs) {
+    let parsed: Vec<(Vec<Value>, Option<String>)> = thread::scope(|scope| {
+      let workers: Vec<_> = round
+        .iter()
+        .map(|chunk| scope.spawn(move || parse_chunk(chunk)))
+        .collect();
+      workers
+        .into_iter()
+        .map(|worker| worker.join().unwrap_or_default())
+        .collect()
+    });
+    for (chunk, (docs, err)) in round.iter().zip(parsed) {
+      for doc in docs {
+        if !f(doc)? {
+          return Ok(());
+        }
+      }
+      if let Some(err) = err {
+        Err(diagnostic::with_line_offset(&err, line))?;
+      }
+      line += chunk.iter().filter(|b| **b == b'\n').count();
+    }
+  }
+  Ok(())
+}
+
+// Returns the documents of a chunk of JSON input, and the error that stopped
+// parsing it, if any.
+fn parse_chunk(chunk: &[u8]) -> (Vec<Value>, Option<String>) {
+  let mut docs = Vec::new();
+  for doc in serde_json::Deserializer::from_slice(chunk).into_iter::<Doc>() {
+    match doc {
+      Ok(Doc(doc)) => docs.push(doc),
+      Err(err) => return (docs, Some(err.to_string())),
+    }
+  }
+  (docs, None)
+}
+
+// Doc is a document of JSON input read through Numbers, for serde_json's
+// StreamDeserializer.
+struct Doc(Value);
+
+impl<'de> Deserialize<'de> for Doc {
+  fn deserialize<D>(de: D) -> Result<Self, D::Error>
+  where
+    D: Deserializer<'de>,
+  {
+    Value::deserialize(Numbers(de)).map(Doc)
+  }
+}
@@ -0,0 +1,221 @@
+use std::str::FromStr;
+
+use crate::value::Value;
+
+// The order in which resources of each kind can be safely applied to a
+// Kubernetes cluster, with each kind coming after the kinds it may depend on.
+// This follows the install order that Helm uses. Kinds not in the list (such as
+// custom resources) come after all of the known kinds.
+const APPLY_ORDER: &[&str] = &[
+  "Namespace",
+  "NetworkPolicy",
+  "ResourceQuota",
+  "LimitRange",
+  "PodSecurityPolicy",
+  "PodDisruptionBudget",
+  "ServiceAccount",
+  "Secret",
+  "SecretList",
+  "ConfigMap",
+  "StorageClass",
+  "PersistentVolume",
+  "PersistentVolumeClaim",
+  "CustomResourceDefinition",
+  "ClusterRole",
+  "ClusterRoleList",
+  "ClusterRoleBinding",
+  "ClusterRoleBindingList",
+  "Role",
+  "RoleList",
+  "RoleBinding",
+  "RoleBindingList",
+  "Service",
+  "DaemonSet",
+  "Pod",
+  "ReplicationController",
+  "ReplicaSet",
+  "Deployment",
+  "HorizontalPodAutoscaler",
+  "StatefulSet",
+  "Job",
+  "CronJob",
+  "IngressClass",
+  "Ingress",
+  "APIService",
+];
+
+// Sorts resources into an order that is safe to apply. The sort is stable, so
+// resources of the same kind keep their input order.
+pub fn sort(docs: &mut [Value]) {
+  docs.sort_by_key(|doc| {
+    let kind = field(doc, &["kind"]);
+    APPLY_ORDER
+      .iter()
+      .position(|k| Some(*k) == kind)
+      .unwrap_or(APPLY_ORDER.len())
+  });
+}
+
+// Selector matches resources by kind, name, and namespace, as given on the
+// command line in the form "kind=Deployment,namespace=prod". A resource matches
+// when it matches every condition. Kinds match case-insensitively, so that
+// "kind=deployment" works as it does with kubectl.
+#[derive(Clone)]
+pub struct Selector(Vec<(Field, String)>);
+
+#[derive(Copy, Clone)]
+enum Field {
+  Kind,
+  Name,
+  Namespace,
+}
+
+impl Selector {
+  pub fn matches(&self, doc: &Value) -> bool {
+    self.0.iter().all(|(f, want)| match f {
+      Field::Kind => field(doc, &["kind"]).is_some_and(|kind| kind.eq_ignore_ascii_case(want)),
+      Field::Name => field(doc, &["metadata", "name"]) == Some(want.as_str()),
+      Field::Namespace => field(doc, &["metadata", "namespace"]).unwrap_or("") == want,
+    })
+  }
+}
+
+impl FromStr for Selector {
+  type Err = String;
+
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    let mut conditions = Vec::new();
+    for condition in s.split(',') {
+      let (f, value) = match condition.split_once('=') {
+        Some(split) => split,
+        None => return Err(format!("'{}' is not of the form FIELD=VALUE", condition)),
+      };
+      let f = match f.trim() {
+        "kind" => Field::Kind,
+        "name" => Field::Name,
+        "namespace" => Field::Namespace,
+        f => {
+          return Err(format!(
+            "'{}' is not a selectable field (must be kind, name, or namespace)",
+            f
+          ))
+        }
+      };
+      conditions.push((f, value.trim().to_owned()));
+    }
+    Ok(Selector(conditions))
+  }
+}
+
+fn field<'v>(doc: &'v Value, keys: &[&str]) -> Option<&'v str> {
+  let mut v = doc;
+  for key in keys {
+    v = match v {
+      Value::Map(map) => map.get(key)?,
+      _ => return None,
+    };
+  }
+  match v {
+    Value::String(s) => Some(s),
+    _ => None,
+  }
+}
+
+// Applies a strategic merge patch to a resource, as kubectl does, if the patch
+// targets it. A patch that gives a kind and name targets only the resource with
+// that kind and name, so that one patch can be applied to a whole manifest.
+pub fn patch(doc: &mut Value, patch: &Value) {
+  let targets = ["kind", "metadata.name"].iter().all(|f| {
+    let keys: Vec<&str> = f.split('.').collect();
+    match field(patch, &keys) {
+      Some(want) => field(doc, &keys) == Some(want),
+      None => true,
+    }
+  });
+  if targets {
+    merge(doc, patch);
+  }
+}
+
+// Merges a patch into a value. Objects merge key by key, with null removing a
+// key. Arrays of objects that all have a "name" merge element by element with
+// the patch elements of the same name, and other arrays are replaced. A
+// "$patch: replace" directive replaces an object or array instead of merging
+// it, and "$patch: delete" removes it.
+fn merge(v: &mut Value, patch: &Value) {
+  let patch_map = match patch {
+    Value::Map(patch_map) => patch_map,
+    Value::Seq(patch_items) => return merge_seq(v, patch_items),
+    patch => return *v = patch.clone(),
+  };
+  match directive(patch) {
+    Some("replace") => return *v = without_directive(patch),
+    Some("delete") => return *v = Value::Null,
+    _ => {}
+  }
+  let map = match v {
+    Value::Map(map) => map,
+    v => return *v = without_directive(patch),
+  };
+  for (k, pv) in patch_map.iter() {
+    let key = match k.key_str() {
+      Some(key) => key,
+      None => continue,
+    };
+    match pv {
+      Value::Null => drop(map.remove(&key)),
+      pv if directive(pv) == Some("delete") => drop(map.remove(&key)),
+      pv => match map.get_mut(&key) {
+        Some(existing) => merge(existing, pv),
+        None => map.push(k.clone(), without_directive(pv)),
+      },
+    }
+  }
+}
+
+fn merge_seq(v: &mut Value, patch_items: &[Value]) {
+  // As an element of a list, the replace directive stands on its own, and
+  // replaces the list with the rest of the elements.
+  if patch_items
+    .iter()
+    .any(|pv| directive(pv) == Some("replace"))
+  {
+    let rest = patch_items.iter().filter(|pv| directive(pv).is_none());
+    return *v = Value::Seq(rest.cloned().collect());
+  }
+  let items = match v {
+    Value::Seq(items) if is_named_list(items) && is_named_list(patch_items) => items,
+    v => {
+      let replaced = patch_items
+        .iter()
+        .filter(|pv| directive(pv) != Some("delete"));
+      return *v = Value::Seq(replaced.map(without_directive).collect());
+    }
+  };
+  for pv in patch_items {
+    let name = field(pv, &["name"]);
+    let idx = items.iter().position(|item| field(item, &["name"]) == name);
+    match (idx, directive(pv)) {
+      (Some(idx), Some("delete")) => drop(items.remove(idx)),
+      (None, Some("delete")) => {}
+      (Some(idx), _) => merge(&mut items[idx], pv),
+      (None, _) => items.push(without_directive(pv)),
+    }
+  }
+}
+
+fn is_named_list(items: &[Value]) -> bool {
+  !items.is_empty() && items.iter().all(|item| field(item, &["name"]).is_some())
+}
+
+fn directive(v: &Value) -> Option<&str> {
+  field(v, &["$patch"])
+}
+
+fn without_directive(v: &Value) -> Value {
+  let mut v = v.clone();
+  if let Value::Map(map) = &mut v {
+    map.remove("$patch");
+  }
+  v
+}
@@ -1,321 +1,2738 @@
 use std::error::Error;
+use std::fmt;
 use std::fs::File;
-use std::io::{self, BufWriter, Read, Write};
+use std::io::{self, BufRead, BufReader, BufWriter, IsTerminal, Read, Write};
 use std::ops::Deref;
 use std::path::PathBuf;
 use std::process;
 use std::str::{self, FromStr};
+use std::sync::mpsc;
+use std::thread;
+use std::time::Instant;
 
 use clap::ErrorKind::{HelpDisplayed, VersionDisplayed};
+use clap::{ArgGroup, ArgMatches};
 use memmap2::MmapOptions;
 use serde::Deserialize;
 use structopt::StructOpt;
 
+mod base64;
+mod bench;
+mod color;
+mod comments;
+mod completions;
+mod config;
+mod console;
+mod convert;
+mod csv;
+mod datetime;
+mod diagnostic;
+mod diff;
+mod digest;
+mod editor;
+mod events;
+mod grep;
+mod highlight;
+mod json;
+mod k8s;
+mod man;
+mod memory;
+mod mmap;
+mod nesting;
+mod outfile;
+mod output;
+mod path;
+mod progress;
+mod regex;
+mod repl;
+mod schema;
+mod stages;
+mod stats;
+mod table;
+mod template;
+mod textconv;
+mod timing;
+mod transform;
+mod tree;
+#[cfg(feature = "self-update")]
+mod update;
+mod utf8;
+mod value;
+mod vars;
+mod version;
+mod warn;
+mod writers;
+mod yaml;
+
+use comments::Comments;
+use datetime::{TomlDatetime, YamlTimestamps};
+use diagnostic::{Diagnostic, ErrorFormat, Report};
+use grep::Grep;
+use highlight::Highlight;
+use json::NonFinite;
+use mmap::MapOptions;
+use output::{CollectOutput, DiscardOutput, Output};
+use schema::{Inference, ValidationError, Validator};
+use stages::{
+  AggregateOutput, CastOutput, ControlCharsOutput, DuplicateKey, DuplicateKeysOutput, GrepOutput,
+  HashOutput, Limit, LimitOutput, MaxDepthOutput, NthOutput, PointerOutput, ProgressOutput,
+  SummaryOutput, TimingOutput, TransformOutput, ValidateOutput,
+};
+use stats::{Histogram, Stats};
+use template::Template;
+use transform::{Aggregate, Transform};
+use utf8::InvalidUtf8;
+use value::{ControlChars, DuplicateKeys, KeyStyle, Value};
+use writers::{
+  CsvOutput, JsonOutput, TableOutput, TemplateOutput, TomlOutput, TreeOutput, YamlOutput,
+};
+
+#[global_allocator]
+static MEMORY: memory::Memory = memory::Memory;
+
 fn main() {
-  let opt = match Opt::from_args_safe() {
-    Ok(opt) => opt,
+  let command = std::env::args_os().nth(1);
+  if command.as_ref().is_some_and(|arg| arg == "bench") {
+    run_command(bench::bench(command_opt("jyt bench")));
+  }
+  if command.as_ref().is_some_and(|arg| arg == "completions") {
+    run_command(completions::completions(command_opt("jyt completions")));
+  }
+  if command.as_ref().is_some_and(|arg| arg == "convert") {
+    run_command(convert::convert(command_opt("jyt convert")));
+  }
+  if command.as_ref().is_some_and(|arg| arg == "git-textconv") {
+    run_command(textconv::textconv(command_opt("jyt git-textconv")));
+  }
+  if command.as_ref().is_some_and(|arg| arg == "repl") {
+    run_command(repl::repl(command_opt("jyt repl")));
+  }
+  if command.as_ref().is_some_and(|arg| arg == "self-update") {
+    #[cfg(feature = "self-update")]
+    run_command(update::self_update(command_opt("jyt self-update")));
+    #[cfg(not(feature = "self-update"))]
+    run_command(Err(
+      "this jyt was built without self-update, so update it the way it was installed".into(),
+    ));
+  }
+  if command.as_ref().is_some_and(|arg| arg == "version") {
+    run_command(version::version(command_opt("jyt version")));
+  }
+  if command.as_ref().is_some_and(|arg| arg == "man") {
+    run_command(man::man(command_opt("jyt man")));
+  }
+
+  if let Err(err) = config::expand_alias() {
+    eprintln!("{} {}", color::label("error"), err);
+    process::exit(1);
+  }
+  let matches = match Opt::clap().get_matches_from_safe(config::args()) {
+    Ok(matches) => matches,
     Err(err) => match err.kind {
-      HelpDisplayed | VersionDisplayed => err.exit(),
+      HelpDisplayed | VersionDisplayed => print_help(err),
       _ => {
         // As of this writing, clap's error messages (other than those above)
         // include an "error:" prefix, so this gives consistent formatting for
         // both argument and translation errors. It is a bit fragile, since it's
         // unlikely that clap's error message format is guaranteed to be stable.
-        eprint!("jyt {}\n", err.message);
+        eprintln!("jyt {}", err.message);
         process::exit(1);
       }
     },
   };
 
-  match jyt(opt) {
-    Ok(_) => {}
-    Err(err) if is_broken_pipe(err.as_ref()) => {}
+  let matches = match config::merge(matches) {
+    Ok(matches) => matches,
     Err(err) => {
-      eprint!("jyt error: {}\n", err);
+      eprintln!("{} {}", color::label("error"), err);
       process::exit(1);
     }
+  };
+
+  let opt = Opt::from_clap(&matches);
+  let errors = opt.error_format;
+  warn::set_verbose(opt.verbose);
+  // JSON errors are for tools to read, and stay plain.
+  match errors {
+    ErrorFormat::Text => color::set_mode(opt.color, opt.color),
+    ErrorFormat::Json => color::set_mode(opt.color, color::ColorMode::Never),
+  }
+
+  let pipe_error = opt.pipe_error;
+  match jyt(opt, &matches) {
+    Ok(_) => {}
+    Err(err) if is_broken_pipe(err.as_ref()) => {
+      if pipe_error {
+        process::exit(PIPE_ERROR_STATUS);
+      }
+    }
+    Err(err) => {
+      let (code, status) = exit_status(err.as_ref());
+      match (errors, err.downcast_ref::<ValidationError>()) {
+        (ErrorFormat::Text, Some(_)) => eprintln!("{} {}", color::label("invalid"), err),
+        (ErrorFormat::Text, None) => eprintln!("{} {}", color::label("error"), err),
+        // Each violation of a schema gets a report of its own.
+        (ErrorFormat::Json, Some(invalid)) => {
+          for (document, violation) in invalid.violations() {
+            let mut report = Report::new(code, err.as_ref());
+            report.message = violation.message.clone();
+            report.document = Some(*document);
+            report.path = Some(&violation.path);
+            if let Some((line, column)) = violation.position {
+              report.line = Some(line);
+              report.column = Some(column);
+            }
+            report.eprint();
+          }
+        }
+        (ErrorFormat::Json, None) => {
+          let mut report = Report::new(code, err.as_ref());
+          if let Some(duplicate) = err.downcast_ref::<DuplicateKey>() {
+            report.message = "duplicate key".to_owned();
+            report.document = Some(duplicate.document);
+            report.path = Some(&duplicate.path);
+            if let Some((line, column)) = duplicate.position {
+              report.line = Some(line);
+              report.column = Some(column);
+            }
+          }
+          report.eprint();
+        }
+      }
+      process::exit(status);
+    }
+  }
+}
+
+// Parses the arguments of a command like 'jyt bench', which come after its name.
+fn command_opt<T: StructOpt>(bin_name: &str) -> T {
+  let app = T::clap().bin_name(bin_name);
+  match app.get_matches_from_safe(std::env::args_os().skip(1)) {
+    Ok(matches) => T::from_clap(&matches),
+    Err(err) => match err.kind {
+      HelpDisplayed | VersionDisplayed => print_help(err),
+      _ => {
+        eprintln!("jyt {}", err.message);
+        process::exit(1);
+      }
+    },
+  }
+}
+
+// Exits with the result of a command like 'jyt bench', with the same statuses
+// as jyt itself.
+fn run_command(result: Result<(), Box<dyn Error>>) -> ! {
+  match result {
+    Err(err) if !is_broken_pipe(err.as_ref()) => {
+      eprintln!("{} {}", color::label("error"), err);
+      process::exit(exit_status(err.as_ref()).1);
+    }
+    _ => process::exit(0),
+  }
+}
+
+// Returns the code that --error-format json gives an error, and the status that
+// jyt exits with for it.
+fn exit_status(err: &(dyn Error + 'static)) -> (&'static str, i32) {
+  if let Some(failed) = err.downcast_ref::<convert::Failed>() {
+    return (failed.code, failed.status);
+  }
+  match err {
+    err if err.is::<Diagnostic>() => ("syntax", 2),
+    err if err.is::<DuplicateKey>() => ("input", 2),
+    err if err.is::<io::Error>() => ("io", 4),
+    err if err.is::<ValidationError>() => ("invalid", 5),
+    err if err.is::<Skipped>() => ("skipped", 6),
+    err if err.is::<Unformatted>() => ("unformatted", 8),
+    err => match err.downcast_ref::<Classified>() {
+      Some(Classified(Class::Input, _)) => ("input", 2),
+      Some(Classified(Class::Output, _)) => ("output", 3),
+      Some(Classified(Class::Usage, _)) | None => ("usage", 1),
+    },
   }
 }
 
+// The status for output cut off by a closed pipe, with --pipe-error, which is
+// the one that a shell gives a command killed by SIGPIPE.
+const PIPE_ERROR_STATUS: i32 = 141;
+
+// Prints the help or version text that clap returns as an error. clap's own
+// Error::exit panics if stdout is a pipe that's closed, like 'jyt --help | head
+// -1', where jyt should end as quietly as it does for its output.
+fn print_help(err: clap::Error) -> ! {
+  let _ = writeln!(io::stdout(), "{}", err.message);
+  process::exit(0)
+}
+
 fn is_broken_pipe(err: &(dyn Error + 'static)) -> bool {
-  return matches!(
+  matches!(
     err.downcast_ref::<io::Error>(),
     Some(ioerr) if ioerr.kind() == io::ErrorKind::BrokenPipe
-  );
+  )
 }
 
-fn jyt(opt: Opt) -> Result<(), Box<dyn Error>> {
+fn jyt(mut opt: Opt, matches: &ArgMatches) -> Result<(), Box<dyn Error>> {
   // serde_json and serde_yaml support deserializing from readers rather than
   // slices, however there's no real benefit to doing this. serde_json is much
   // slower with readers, and memory use isn't much different between buffering
   // stdin and streaming it to the parser (presumably it borrows from the input
   // instead of allocating a bunch of stuff?). serde_yaml buffers the contents
   // of the reader into a slice under the hood, so it's no different at all.
-  let input = get_input_slice(opt.input_source())?;
-  let from = match opt.detect_from() {
+  let start = Instant::now();
+  if opt.timing {
+    timing::enable();
+  }
+  if let Some(limit) = opt.max_memory {
+    memory::set_limit(limit.0, matches!(opt.error_format, ErrorFormat::Json));
+  }
+  let variables = opt.arg.iter().chain(&opt.argjson).cloned().collect();
+  vars::bind(variables).map_err(Classified::usage)?;
+  if let Some(path) = &opt.output_filename {
+    if opt.to_from_output_filename(matches) {
+      opt.to = match format_from_extension(path) {
+        Some(format) => format,
+        None => Err(Classified::usage(format!(
+          "can't tell the format to write {} in from its extension (see -t)",
+          path.display()
+        )))?,
+      };
+    }
+    opt.output = Some(path.clone());
+  }
+  if opt.stream {
+    return stream(&opt, matches);
+  }
+  let mut input = get_input_slice(opt.input_source(), opt.map_options())?;
+  let file = opt.input_name();
+  match utf8::fix(&input, opt.invalid_utf8) {
+    Ok(None) => {}
+    Ok(Some(fixed)) => input = Box::new(fixed),
+    Err(err) => Err(diagnostic::locate(err.into(), &input, &file))?,
+  }
+  // Fixes for JSON input apply only if the input turns out to be JSON.
+  let mut fixed_json = match opt.lenient {
+    true => Some(json::strip_extensions(&input)),
+    false => None,
+  };
+  if let Some(fixed) = json::fix_surrogates(fixed_json.as_deref().unwrap_or(&input), opt.surrogates)
+  {
+    fixed_json = Some(fixed);
+  }
+  let mut rejected = Vec::new();
+  let mut from = match opt.detect_from() {
     Some(format) => format,
-    None => match detect_format(&input) {
-      Some(format) => format,
-      None => Err("cannot parse input as any known format")?,
+    None => match (&fixed_json, sniff_format(&input, &mut rejected)) {
+      (Some(stripped), _) if matches!(detect_format(stripped), Some(Format::Json)) => Format::Json,
+      (_, Some(format)) => format,
+      (_, None) => Err(Classified::input("cannot parse input as any known format"))?,
     },
   };
+  let how = match (opt.from, &opt.input_filename) {
+    (Some(_), _) => "as given by -f",
+    (None, Some(path)) if format_from_extension(path).is_some() => "from its extension",
+    (None, _) => "detected from its contents",
+  };
+  warn::info(&format!("reading {} as {}, {}", file, from.name(), how));
+  let mut explained = explain_input(&opt, matches, from, &rejected);
+  if let (Format::Json, true) = (
+    from,
+    rejected
+      .iter()
+      .any(|(tried, _)| matches!(tried, Format::Json)),
+  ) {
+    explained.push("  json once --lenient took out its comments and trailing commas".to_owned());
+  }
+  if opt.format || opt.check_format {
+    opt.to = from;
+    opt.keep_comments = matches!(from, Format::Yaml | Format::Toml);
+  }
+  if let (Format::Json, Some(stripped)) = (from, fixed_json) {
+    input = Box::new(stripped);
+  }
+  // Empty input stands in for input in a format that it's valid for, so that
+  // the rest of the pipeline (like --stats) works as it would for any other.
+  if let (Some(empty), true) = (opt.empty, input.iter().all(u8::is_ascii_whitespace)) {
+    input = match empty {
+      EmptyInput::Error => Err(Classified::input("input is empty (see --empty)"))?,
+      EmptyInput::Null => Box::new(b"null".to_vec()),
+      EmptyInput::Skip => Box::new(Vec::new()),
+    };
+    from = Format::Json;
+    explained.push(match empty {
+      EmptyInput::Null => "  the input is empty, so it's read as null, for --empty".to_owned(),
+      _ => "  the input is empty, so it has no documents, for --empty".to_owned(),
+    });
+  }
+  if opt.explain {
+    explained.extend(explain_output(&opt, matches));
+    explain(&explained);
+  }
+  let locate = |err| diagnostic::locate(err, &input, &file);
+  if let (Format::Yaml, true) = (from, opt.no_expand_aliases) {
+    yaml::forbid_aliases(str::from_utf8(&input)?).map_err(Classified::input)?;
+  }
+  if let (Format::Yaml, true) = (from, opt.strict) {
+    yaml::forbid_tab_indentation(str::from_utf8(&input)?).map_err(|err| locate(err.into()))?;
+  }
+  // Nothing is lost in a conversion that has no output.
+  warn::set_quiet(opt.quiet || opt.check);
+  warn_lossy(&input, from, &opt)?;
+
+  let comments = match (opt.keep_comments, opt.to) {
+    (false, _) => None,
+    (true, Format::Yaml | Format::Toml) => {
+      let lines = comment_lines(str::from_utf8(&input)?, from).map_err(locate)?;
+      Some(Comments::new(&lines))
+    }
+    (true, _) => Err(KEEP_COMMENTS_FORMATS)?,
+  };
+
+  let pipeline = build_pipeline(&opt, matches, file.clone())?;
+  timing::record(|timing| {
+    timing.read = start.elapsed();
+    timing.bytes_in = Some(input.len());
+  });
+  let progress = opt.progress.then(|| progress::start(&input));
+
+  if opt.check {
+    transcode_with_pipeline(&input, from, &pipeline, DiscardOutput)
+      .map_err(locate)
+      .map_err(Classified::output)?;
+    if let Some(progress) = progress {
+      progress.finish();
+    }
+    timing::report(start.elapsed(), 0);
+    return Ok(());
+  }
 
   // Note that BufWriter attempts to flush when dropped, but ignores flush
   // errors. This is fine, we only drop before flushing if a transcode error
   // forces us to abort early, in which case the real error happened during
   // transcoding.
-  let mut w = BufWriter::new(io::stdout());
+  let output = timing::Counted::new(open_output(&opt)?);
+  let mut w = BufWriter::with_capacity(opt.buffer_size.0, Crlf::new(opt.crlf, output));
 
-  match opt.to {
-    Format::Json => {
-      let output = JsonOutput(&mut w);
-      transcode_all_input(&input, from, output)?;
-    }
-    Format::Yaml => {
-      let output = YamlOutput(&mut w);
-      transcode_all_input(&input, from, output)?;
-    }
-    Format::Toml => {
-      let output = TomlOutput {
-        w: &mut w,
-        used: false,
-      };
-      transcode_all_input(&input, from, output)?;
+  // A formatter's output replaces the document, so none of it can be written
+  // until all of it is good.
+  let mut unformatted = false;
+  match (comments, opt.format || opt.check_format) {
+    (None, false) => write_output(&input, from, &opt, &pipeline, &mut w)
+      .map_err(locate)
+      .map_err(Classified::output)?,
+    (comments, _) => {
+      let mut buf = Vec::new();
+      write_output(&input, from, &opt, &pipeline, &mut buf)
+        .map_err(locate)
+        .map_err(Classified::output)?;
+      if let Some(comments) = comments {
+        let output = str::from_utf8(&buf)?;
+        buf = comments
+          .splice(output, &comment_lines(output, opt.to)?)
+          .into_bytes();
+      }
+      match opt.check_format {
+        false => w.write_all(&buf)?,
+        true => {
+          // Lines that --crlf would end with CRLF are only formatted if
+          // they already end that way.
+          if opt.crlf {
+            let mut crlf = Crlf::new(true, Vec::new());
+            crlf.write_all(&buf)?;
+            buf = crlf.w;
+          }
+          let formatted = format!("{} (formatted)", file);
+          let diff = diff::unified(
+            str::from_utf8(&input)?,
+            str::from_utf8(&buf)?,
+            &file,
+            &formatted,
+          );
+          unformatted = !diff.is_empty();
+          match color::stdout() {
+            true => w.write_all(diff::paint(&diff).as_bytes())?,
+            false => w.write_all(diff.as_bytes())?,
+          }
+        }
+      }
     }
   }
 
   w.flush()?;
+  if let Some(progress) = progress {
+    progress.finish();
+  }
+  timing::report(start.elapsed(), w.get_ref().w.count);
+  report_dry_run(&opt, w.get_ref().w.count)?;
+  if unformatted {
+    Err(Unformatted)?;
+  }
   Ok(())
 }
 
-fn get_input_slice(source: InputSource) -> io::Result<Box<dyn Deref<Target = [u8]>>> {
-  let mut input: Box<dyn Read> = match source {
-    InputSource::Stdin => Box::new(io::stdin()),
-    InputSource::File(path) => {
-      let file = File::open(path)?;
-      // Safety: Modification of the mapped file outside the process triggers
-      // undefined behavior. Our dirty "solution" is to document this in the
-      // help output.
-      match unsafe { MmapOptions::new().populate().map(&file) } {
-        // Per memmap2 docs, it's safe to drop the file once mmap succeeds.
-        Ok(map) => return Ok(Box::new(map)),
-        // If mmap fails, we can still try regular buffering.
-        Err(_) => Box::new(file),
-      }
-    }
+// Converts the input a document at a time as it's read, for --stream. The
+// options that need all of the input at once, like format detection and
+// --keep-comments, don't apply.
+fn stream(opt: &Opt, matches: &ArgMatches) -> Result<(), Box<dyn Error>> {
+  let from = match opt.detect_from() {
+    Some(from @ (Format::Json | Format::Yaml)) => from,
+    Some(_) => Err(Classified::usage(
+      "--stream only works with JSON and YAML input",
+    ))?,
+    None => Err(Classified::usage(
+      "--stream needs an input format, from -f or the input file's extension",
+    ))?,
   };
-
-  let mut buf = Vec::new();
-  input.read_to_end(&mut buf)?;
-  Ok(Box::new(buf))
+  warn::set_quiet(opt.quiet || opt.check);
+  if opt.progress {
+    warn::info("--progress doesn't apply to --stream");
+  }
+  if opt.explain {
+    let mut explained = explain_input(opt, matches, from, &[]);
+    explained.extend(explain_output(opt, matches));
+    explain(&explained);
+  }
+  let start = Instant::now();
+  let pipeline = build_pipeline(opt, matches, opt.input_name())?;
+  if opt.check {
+    transcode_with_pipeline(&[], from, &pipeline, DiscardOutput).map_err(Classified::output)?;
+    timing::report(start.elapsed(), 0);
+    return Ok(());
+  }
+  let output = timing::Counted::new(open_output(opt)?);
+  let mut w = BufWriter::with_capacity(opt.buffer_size.0, Crlf::new(opt.crlf, output));
+  write_output(&[], from, opt, &pipeline, &mut w).map_err(Classified::output)?;
+  w.flush()?;
+  timing::report(start.elapsed(), w.get_ref().w.count);
+  report_dry_run(opt, w.get_ref().w.count)?;
+  Ok(())
 }
 
-fn detect_format(input: &[u8]) -> Option<Format> {
-  // Formats are organized, in rough terms, from least to most "permissive."
-  // It's important that YAML be last, since it seems like just about any input
-  // that doesn't contain ":" can be parsed as a YAML string. This also matches
-  // the behavior of older versions of jyt that always used YAML as the fallback
-  // for unknown input types.
-  for from in [Format::Json, Format::Toml, Format::Yaml] {
-    if let Ok(_) = transcode_all_input(input, from, DiscardOutput) {
-      return Some(from);
-    }
-  }
-  None
+// Returns the pipeline of stages that the documents of the input go through.
+fn build_pipeline(
+  opt: &Opt,
+  matches: &ArgMatches,
+  file: String,
+) -> Result<Pipeline, Box<dyn Error>> {
+  Ok(Pipeline {
+    read: ReadOptions {
+      column_types: opt.column_types.clone(),
+      yaml_tags: opt.yaml_tags,
+      binary: opt.binary,
+      yaml_version: opt.yaml_version,
+      yaml_merge: opt.yaml_merge,
+      int_literals: opt.int_literals,
+      yaml_timestamps: opt.yaml_timestamps,
+      toml_datetime: opt.toml_datetime,
+      single_json: opt.strict,
+      keep_going: opt.keep_going,
+      jobs: opt.jobs,
+      parse_thread: opt.parse_thread,
+      errors: opt.error_format,
+      stream: opt.stream,
+      path: match opt.input_source() {
+        InputSource::Stdin => None,
+        InputSource::File(path) => Some(path.clone()),
+      },
+      file,
+    },
+    nth: opt.nth,
+    pointer: opt.pointer.clone(),
+    duplicate_keys: match (opt.duplicate_keys, opt.strict) {
+      (None, true) => Some(DuplicateKeys::Error),
+      (policy, _) => policy,
+    },
+    caster: match &opt.cast_schema {
+      Some(path) => Some(Validator::new(read_document(path)?)?),
+      None => None,
+    },
+    validator: match &opt.validate_schema {
+      Some(path) => Some(Validator::new(read_document(path)?)?),
+      None => None,
+    },
+    limit: opt.limit(),
+    transforms: opt.transforms(matches)?,
+    aggregates: opt.aggregates()?,
+    max_depth: opt.max_depth,
+    control_chars: match (opt.control_chars, opt.to) {
+      (ControlChars::Raw, _) => None,
+      (ControlChars::Escape, Format::Json | Format::Yaml | Format::Toml) => None,
+      (policy, _) => Some(policy),
+    },
+  })
 }
 
-fn transcode_all_input<O>(input: &[u8], from: Format, mut output: O) -> Result<(), Box<dyn Error>>
+// Writes the output of the pipeline in the output format.
+fn write_output<W>(
+  input: &[u8],
+  from: Format,
+  opt: &Opt,
+  pipeline: &Pipeline,
+  mut w: W,
+) -> Result<(), Box<dyn Error>>
 where
-  O: Output,
+  W: Write,
 {
-  match from {
+  match opt.to {
     Format::Json => {
-      let mut de = serde_json::Deserializer::from_slice(input);
-      while let Err(_) = de.end() {
-        output.transcode_from(&mut de)?;
-      }
+      let output = JsonOutput::new(
+        &mut w,
+        opt.non_finite,
+        opt.key_style,
+        !matches!(from, Format::Yaml),
+      );
+      transcode_with_options(input, from, opt, pipeline, output)?;
     }
     Format::Yaml => {
-      for de in serde_yaml::Deserializer::from_slice(input) {
-        output.transcode_from(de)?;
-      }
+      let output = YamlOutput::new(
+        &mut w,
+        opt.yaml_tags,
+        opt.yaml_version,
+        opt.int_literals,
+        opt.yaml_anchors,
+      );
+      transcode_with_options(input, from, opt, pipeline, output)?;
     }
     Format::Toml => {
-      let input_str = str::from_utf8(input)?;
-      let mut de = toml::Deserializer::new(input_str);
-      output.transcode_from(&mut de)?;
+      let output = TomlOutput::new(&mut w, opt.key_style, opt.toml_datetime);
+      transcode_with_options(input, from, opt, pipeline, output)?;
+    }
+    Format::Csv | Format::Tsv => {
+      let delimiter = if let Format::Tsv = opt.to { '\t' } else { ',' };
+      let output = CsvOutput::new(&mut w, delimiter, &opt.columns, opt.on_missing_column);
+      transcode_with_options(input, from, opt, pipeline, output)?;
+    }
+    Format::Table => {
+      let output = TableOutput::new(&mut w, &opt.columns, opt.max_column_width);
+      transcode_with_options(input, from, opt, pipeline, output)?;
+    }
+    Format::Tree => {
+      let output = TreeOutput(&mut w);
+      transcode_with_options(input, from, opt, pipeline, output)?;
+    }
+    Format::Template => {
+      let template = match &opt.template {
+        Some(path) => {
+          Template::parse(&std::fs::read_to_string(path)?).map_err(Classified::usage)?
+        }
+        None => Err(Classified::usage("template output requires --template"))?,
+      };
+      let output = TemplateOutput {
+        w: &mut w,
+        template: &template,
+      };
+      transcode_with_options(input, from, opt, pipeline, output)?;
     }
   }
+
   Ok(())
 }
 
-trait Output {
-  fn transcode_from<'de, D, E>(&mut self, de: D) -> Result<(), Box<dyn Error + 'static>>
-  where
-    D: serde::de::Deserializer<'de, Error = E>,
-    E: serde::de::Error + 'static;
+// Warns about details of the input that won't make it to the output, before
+// any conversion happens.
+fn warn_lossy(input: &[u8], from: Format, opt: &Opt) -> Result<(), Box<dyn Error>> {
+  if opt.quiet || opt.check || !matches!(from, Format::Yaml | Format::Toml) {
+    return Ok(());
+  }
+  let text = str::from_utf8(input)?;
+  if let Format::Yaml = from {
+    let features = yaml::find_features(text);
+    if features.aliases && !(matches!(opt.to, Format::Yaml) && opt.yaml_anchors) {
+      warn::warn(
+        "YAML aliases were expanded into copies of their anchored values (see --yaml-anchors)",
+      );
+    }
+    if features.tags && opt.yaml_tags == yaml::Tags::Drop {
+      warn::warn("custom YAML tags were dropped (see --yaml-tags)");
+    }
+    if features.binary {
+      warn::warn("YAML !!binary tags were dropped, leaving their values untagged (see --binary)");
+    }
+  }
+  let has_comments = |lines: Vec<comments::Line>| lines.iter().any(|line| line.comment.is_some());
+  let comments = match from {
+    Format::Yaml => text.contains('#') && yaml::comment_lines(text).is_ok_and(has_comments),
+    _ => has_comments(comments::toml_lines(text)),
+  };
+  if comments && !opt.keep_comments {
+    warn::warn("comments were discarded (see --keep-comments)");
+  }
+  let datetimes_kept = match opt.toml_datetime {
+    TomlDatetime::Object => true,
+    TomlDatetime::String => matches!(opt.to, Format::Toml),
+    TomlDatetime::Epoch => false,
+  };
+  if let (Format::Toml, false) = (from, datetimes_kept) {
+    nesting::check_toml(text)?;
+    let doc = toml::from_str(text).unwrap_or(Value::Null);
+    if datetime::has_toml_datetimes(&doc) {
+      warn::warn("TOML datetimes were converted to plain values (see --toml-datetime)");
+    }
+  }
+  Ok(())
 }
 
-struct DiscardOutput;
-
-impl Output for DiscardOutput {
-  fn transcode_from<'de, D, E>(&mut self, de: D) -> Result<(), Box<dyn Error>>
-  where
-    D: serde::de::Deserializer<'de, Error = E>,
-    E: serde::de::Error + 'static,
-  {
-    match serde::de::IgnoredAny::deserialize(de) {
-      Ok(_) => Ok(()),
-      Err(err) => Err(err)?,
-    }
+// Describes the lines of YAML or TOML text, for --keep-comments.
+fn comment_lines(text: &str, format: Format) -> Result<Vec<comments::Line>, Box<dyn Error>> {
+  match format {
+    Format::Yaml => Ok(yaml::comment_lines(text)?),
+    Format::Toml => Ok(comments::toml_lines(text)),
+    _ => Err(KEEP_COMMENTS_FORMATS)?,
   }
 }
 
-struct JsonOutput<W>(W);
+const KEEP_COMMENTS_FORMATS: &str =
+  "--keep-comments only works with YAML and TOML input and output";
 
-impl<W> Output for JsonOutput<W>
-where
-  W: Write,
-{
-  fn transcode_from<'de, D, E>(&mut self, de: D) -> Result<(), Box<dyn Error>>
-  where
-    D: serde::de::Deserializer<'de, Error = E>,
-    E: serde::de::Error + 'static,
-  {
-    let mut ser = serde_json::Serializer::new(&mut self.0);
-    serde_transcode::transcode(de, &mut ser)?;
-    writeln!(&mut self.0, "")?;
-    Ok(())
-  }
+// EmptyInput is what empty input (or input of only whitespace) produces, for
+// scripts that need the same result whatever the input format. Without a
+// policy, it's whatever the input format makes of it: no documents for JSON and
+// YAML, and an empty table for TOML.
+#[derive(Copy, Clone)]
+enum EmptyInput {
+  Error,
+  Null,
+  Skip,
 }
 
-struct YamlOutput<W>(W);
+impl FromStr for EmptyInput {
+  type Err = String;
 
-impl<W> Output for YamlOutput<W>
-where
-  W: Write,
-{
-  fn transcode_from<'de, D, E>(&mut self, de: D) -> Result<(), Box<dyn Error>>
-  where
-    D: serde::de::Deserializer<'de, Error = E>,
-    E: serde::de::Error + 'static,
-  {
-    let mut ser = serde_yaml::Serializer::new(&mut self.0);
-    serde_transcode::transcode(de, &mut ser)?;
-    Ok(())
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    match s {
+      "error" => Ok(Self::Error),
+      "null" => Ok(Self::Null),
+      "skip" => Ok(Self::Skip),
+      _ => Err(format!("'{}' is not a valid empty input policy", s)),
+    }
   }
 }
 
-struct TomlOutput<W> {
-  w: W,
-  used: bool,
-}
+// Size is a number of bytes, with an optional K, M, or G suffix for KiB, MiB,
+// or GiB.
+#[derive(Copy, Clone)]
+struct Size(usize);
 
-impl<W> Output for TomlOutput<W>
-where
-  W: Write,
-{
-  fn transcode_from<'de, D, E>(&mut self, de: D) -> Result<(), Box<dyn Error>>
-  where
-    D: serde::de::Deserializer<'de, Error = E>,
-    E: serde::de::Error + 'static,
-  {
-    self.used = match self.used {
-      false => true,
-      true => Err("TOML does not support multi-document output")?,
-    };
+impl FromStr for Size {
+  type Err = String;
 
-    // TOML requires that all non-table values appear before any tables at a
-    // given "level." Since we can't enforce this for all input types, we buffer
-    // the inputs into a toml::Value, which will serialize them back out in the
-    // necessary order.
-    let value = toml::Value::deserialize(de)?;
-
-    // From the spec: "TOML is designed to map unambiguously to a hash table."
-    // Without this check, the other input types could produce something like a
-    // boolean or array that we would attempt to dump the TOML representation of
-    // without a second thought. The toml crate can even produce invalid TOML
-    // for some of these representations, such as dumping each element of an
-    // array of tables with an empty name, i.e. with a "[[]]" header.
-    if !value.is_table() {
-      Err("root of TOML output must be a table")?;
-    }
-
-    // As of this writing, the toml crate can't output directly to a writer.
-    let output_buf = toml::to_string_pretty(&value)?;
-    self.w.write_all(output_buf.as_bytes())?;
-    Ok(())
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    let (digits, unit) = match s.char_indices().last() {
+      Some((i, 'K' | 'k')) => (&s[..i], 1 << 10),
+      Some((i, 'M' | 'm')) => (&s[..i], 1 << 20),
+      Some((i, 'G' | 'g')) => (&s[..i], 1 << 30),
+      _ => (s, 1),
+    };
+    digits
+      .parse::<usize>()
+      .ok()
+      .and_then(|n| n.checked_mul(unit))
+      .map(Size)
+      .ok_or_else(|| format!("'{}' is not a valid size", s))
   }
 }
 
-#[derive(StructOpt)]
-#[structopt(verbatim_doc_comment)]
-/// Translate between serialized data formats
-///
-/// This version of jyt supports the following formats, each of which may be
-/// specified by full name or first character (e.g. '-ty' == '-t yaml'):
-///
-///   json: Multi-document with self-delineating values (object, array, string)
-///         and / or whitespace between values. Default format for .json files.
-///
-///   yaml: Multi-document with "---" syntax. Default format for .yaml and .yml
-///         files.
-///
-///   toml: Single documents only. Does not support null values. Default format
-///         for .toml files.
-///
-/// When the input format is not specified with -f or detected from a file
-/// extension, jyt will attempt to auto-detect it by parsing the input as
-/// different formats in an unspecified order until one works. jyt's behavior is
-/// undefined if an input file is modified while jyt is running.
-struct Opt {
-  #[structopt(short = "t", help = "Format to convert to", default_value = "json")]
-  to: Format,
-
-  #[structopt(short = "f", help = "Format to convert from")]
-  from: Option<Format>,
-
-  #[structopt(
-    name = "file",
-    help = "File to read input from [default: stdin]",
-    parse(from_os_str)
-  )]
-  input_filename: Option<PathBuf>,
+#[derive(Copy, Clone)]
+enum PagerMode {
+  Auto,
+  Always,
+  Never,
 }
 
-impl Opt {
-  fn detect_from(&self) -> Option<Format> {
-    if self.from.is_some() {
-      return self.from;
-    }
+impl FromStr for PagerMode {
+  type Err = String;
 
-    match &self.input_filename {
-      None => None,
-      Some(path) => match path.extension().map(|ext| ext.to_str()).flatten() {
-        Some("json") => Some(Format::Json),
-        Some("yaml" | "yml") => Some(Format::Yaml),
-        Some("toml") => Some(Format::Toml),
-        _ => None,
-      },
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    match s {
+      "auto" => Ok(Self::Auto),
+      "always" => Ok(Self::Always),
+      "never" => Ok(Self::Never),
+      _ => Err(format!("'{}' is not a valid pager mode", s)),
     }
   }
+}
 
-  fn input_source(&self) -> InputSource {
-    match &self.input_filename {
-      None => InputSource::Stdin,
-      Some(path) if path.to_str() == Some("-") => InputSource::Stdin,
-      Some(path) => InputSource::File(path),
+// Returns the writer for jyt's output, which is the file given to -o, or else
+// stdout or (depending on the mode) the input of a pager process. As with git,
+// the pager comes from $PAGER (defaulting to less), and less is configured
+// through the LESS variable to exit right away if the output fits on one
+// screen.
+fn open_output(opt: &Opt) -> Result<Box<dyn Write>, Box<dyn Error>> {
+  let path = match &opt.output {
+    Some(path) => path,
+    None => {
+      let stdout = start_pager(opt.pager)?;
+      // A formatter's output is the text of a file, and a diff isn't JSON.
+      return Ok(
+        match (opt.to, opt.format || opt.check_format, color::stdout()) {
+          (Format::Json, false, true) => Box::new(Highlight::new(opt.theme.clone(), stdout)),
+          _ => stdout,
+        },
+      );
+    }
+  };
+  // Replacing the input file would cut it off while it's still being read.
+  if let InputSource::File(input) = opt.input_source() {
+    if let (Ok(input), Ok(output)) = (input.canonicalize(), path.canonicalize()) {
+      if input == output {
+        Err(Classified::usage(
+          "the output file cannot be the input file",
+        ))?;
+      }
     }
   }
+  if opt.dry_run {
+    return Ok(Box::new(io::sink()));
+  }
+  let size = opt.preallocate.map(|size| size.0);
+  Ok(Box::new(outfile::OutputFile::create(path, size)?))
 }
 
-#[derive(Copy, Clone)]
-enum Format {
-  Json,
-  Yaml,
-  Toml,
+// Reports the file that -o would have written, for --dry-run, with the size of
+// the output that went to it instead.
+fn report_dry_run(opt: &Opt, written: usize) -> io::Result<()> {
+  let path = match (&opt.output, opt.dry_run) {
+    (Some(path), true) => path,
+    _ => return Ok(()),
+  };
+  let action = match path.exists() {
+    true => "overwrite",
+    false => "create",
+  };
+  writeln!(
+    io::stdout(),
+    "would {} {} ({} bytes)",
+    action,
+    path.display(),
+    written
+  )
 }
 
+fn start_pager(mode: PagerMode) -> io::Result<Box<dyn Write>> {
+  let use_pager = match mode {
+    PagerMode::Always => true,
+    PagerMode::Auto => io::stdout().is_terminal(),
+    PagerMode::Never => false,
+  };
+  let pager = std::env::var("PAGER").unwrap_or_else(|_| "less".to_owned());
+  if !use_pager || pager.is_empty() || pager == "cat" {
+    // Holding the lock saves taking it for every write.
+    return Ok(Box::new(io::stdout().lock()));
+  }
+
+  let mut cmd = match cfg!(windows) {
+    true => process::Command::new("cmd"),
+    false => process::Command::new("sh"),
+  };
+  let flag = if cfg!(windows) { "/C" } else { "-c" };
+  cmd.arg(flag).arg(&pager).stdin(process::Stdio::piped());
+  if std::env::var_os("LESS").is_none() {
+    cmd.env("LESS", "FRX");
+  }
+  let mut child = cmd.spawn()?;
+  Ok(Box::new(Pager {
+    stdin: child.stdin.take(),
+    child,
+  }))
+}
+
+// Pager is the input of a pager process. Dropping it waits for the pager to
+// exit, so that jyt doesn't exit (and give the terminal back to the shell)
+// while the pager is still showing output.
+struct Pager {
+  stdin: Option<process::ChildStdin>,
+  child: process::Child,
+}
+
+impl Write for Pager {
+  fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+    match &mut self.stdin {
+      Some(stdin) => stdin.write(buf),
+      None => Ok(buf.len()),
+    }
+  }
+
+  fn flush(&mut self) -> io::Result<()> {
+    match &mut self.stdin {
+      Some(stdin) => stdin.flush(),
+      None => Ok(()),
+    }
+  }
+}
+
+impl Drop for Pager {
+  fn drop(&mut self) {
+    drop(self.stdin.take());
+    let _ = self.child.wait();
+  }
+}
+
+// Crlf ends each line written through it with CRLF rather than LF, for --crlf,
+// where it doesn't already. Without --crlf, it passes everything through.
+struct Crlf<W> {
+  w: W,
+  enabled: bool,
+  // Whether the last byte written was a CR, which a LF at the start of the
+  // next write already goes with.
+  after_cr: bool,
+  buf: Vec<u8>,
+}
+
+impl<W> Crlf<W> {
+  fn new(enabled: bool, w: W) -> Self {
+    Crlf {
+      w,
+      enabled,
+      after_cr: false,
+      buf: Vec::new(),
+    }
+  }
+}
+
+impl<W: Write> Write for Crlf<W> {
+  fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+    if !self.enabled {
+      return self.w.write(buf);
+    }
+    // The output goes on in one write, rather than one for each line, since a
+    // line-buffered stdout would make a system call for each.
+    self.buf.clear();
+    let mut prev = match self.after_cr {
+      true => b'\r',
+      false => 0,
+    };
+    for &byte in buf {
+      if byte == b'\n' && prev != b'\r' {
+        self.buf.push(b'\r');
+      }
+      self.buf.push(byte);
+      prev = byte;
+    }
+    self.w.write_all(&self.buf)?;
+    self.after_cr = prev == b'\r';
+    Ok(buf.len())
+  }
+
+  fn flush(&mut self) -> io::Result<()> {
+    self.w.flush()
+  }
+}
+
+// Reads a single document from a file, such as a schema or other input to an
+// option, with the same format detection as for the main input.
+fn read_document(path: &PathBuf) -> Result<Value, Box<dyn Error>> {
+  let mut docs = read_documents(path)?;
+  match docs.len() {
+    1 => Ok(docs.remove(0)),
+    _ => Err(format!(
+      "{} must contain exactly one document",
+      path.display()
+    ))?,
+  }
+}
+
+fn read_documents(path: &PathBuf) -> Result<Vec<Value>, Box<dyn Error>> {
+  let input = get_input_slice(InputSource::File(path), MapOptions::default())?;
+  let from = match format_from_extension(path).or_else(|| detect_format(&input)) {
+    Some(format) => format,
+    None => Err(Classified::input(format!(
+      "cannot parse {} as any known format",
+      path.display()
+    )))?,
+  };
+  let mut docs = CollectOutput(Vec::new());
+  transcode_all_input(&input, from, &ReadOptions::default(), &mut docs).map_err(|err| {
+    match err.is::<io::Error>() || err.is::<Diagnostic>() || err.is::<Classified>() {
+      true => err,
+      false => Classified::input(err),
+    }
+  })?;
+  Ok(docs.0)
+}
+
+fn get_input_slice(
+  source: InputSource,
+  map: MapOptions,
+) -> io::Result<Box<dyn Deref<Target = [u8]>>> {
+  let mut input: Box<dyn Read> = match source {
+    InputSource::Stdin => Box::new(io::stdin()),
+    InputSource::File(path) => {
+      let file = File::open(path)?;
+      let small = match file.metadata() {
+        Ok(meta) => meta.len() < map.threshold as u64,
+        Err(_) => false,
+      };
+      let mut options = MmapOptions::new();
+      if map.populate {
+        options.populate();
+      }
+      // Safety: Modification of the mapped file outside the process triggers
+      // undefined behavior. Our dirty "solution" is to document this in the
+      // help output.
+      match small {
+        true => Box::new(file),
+        false => match unsafe { options.map(&file) } {
+          // Per memmap2 docs, it's safe to drop the file once mmap succeeds.
+          Ok(mapped) => {
+            mmap::advise(&mapped, map.advice);
+            return Ok(Box::new(mapped));
+          }
+          // If mmap fails, we can still try regular buffering.
+          Err(_) => Box::new(file),
+        },
+      }
+    }
+  };
+
+  let mut buf = Vec::new();
+  input.read_to_end(&mut buf)?;
+  Ok(Box::new(buf))
+}
+
+fn detect_format(input: &[u8]) -> Option<Format> {
+  sniff_format(input, &mut Vec::new())
+}
+
+// Returns the first format that the input parses as, adding the formats tried
+// before it to rejected, with the errors that ruled them out.
+fn sniff_format(input: &[u8], rejected: &mut Vec<(Format, String)>) -> Option<Format> {
+  // Formats are organized, in rough terms, from least to most "permissive."
+  // It's important that YAML be last, since it seems like just about any input
+  // that doesn't contain ":" can be parsed as a YAML string. This also matches
+  // the behavior of older versions of jyt that always used YAML as the fallback
+  // for unknown input types.
+  [Format::Json, Format::Toml, Format::Yaml]
+    .iter()
+    .copied()
+    .find(
+      |&from| match transcode_all_input(input, from, &ReadOptions::default(), DiscardOutput) {
+        Ok(()) => true,
+        Err(err) => {
+          warn::info(&format!("input is not {}: {}", from.name(), err));
+          rejected.push((from, err.to_string()));
+          false
+        }
+      },
+    )
+}
+
+// Writes an explanation of a conversion to stderr, for --explain.
+fn explain(lines: &[String]) {
+  for line in lines {
+    eprintln!("{} {}", color::label("explain"), line);
+  }
+}
+
+// Returns the lines of --explain about how jyt chose the input format, given
+// the formats that detection ruled out.
+fn explain_input(
+  opt: &Opt,
+  matches: &ArgMatches,
+  from: Format,
+  rejected: &[(Format, String)],
+) -> Vec<String> {
+  let ext = opt
+    .input_filename
+    .as_deref()
+    .and_then(|path| path.extension());
+  let ext = ext.and_then(|ext| ext.to_str()).unwrap_or_default();
+  let how = match (opt.from, opt.detect_from()) {
+    (Some(_), _) => format!("given by -f {}", given_by(matches, "from")),
+    (None, Some(_)) if config::extension_format(ext).is_some() => format!(
+      "from its extension .{}, as the config file's [extensions] gives it",
+      ext
+    ),
+    (None, Some(_)) => format!("from its extension .{}", ext),
+    (None, None) => "detected from its contents, trying json, toml, and yaml in turn".to_owned(),
+  };
+  let mut lines = vec![
+    format!("input: {}", opt.input_name()),
+    format!("input format: {}, {}", from.name(), how),
+  ];
+  for (tried, err) in rejected {
+    lines.push(format!("  not {}: {}", tried.name(), err));
+  }
+  if let (Format::Yaml, false) = (from, rejected.is_empty()) {
+    lines.push("  yaml is the fallback, since nearly any text is a YAML string".to_owned());
+  }
+  lines
+}
+
+// Returns where the value of an option came from, for --explain.
+fn given_by(matches: &ArgMatches, name: &str) -> String {
+  match (matches.occurrences_of(name), config::source(name)) {
+    (0, _) => "by default".to_owned(),
+    (_, Some(source)) => format!("from {}", source),
+    (_, None) => "on the command line".to_owned(),
+  }
+}
+
+// Returns the lines of --explain about the output format and the settings that
+// apply to it, with where each of them came from.
+fn explain_output(opt: &Opt, matches: &ArgMatches) -> Vec<String> {
+  let how = match (opt.format || opt.check_format, matches.occurrences_of("to")) {
+    (true, _) => "the same as the input's, for --format or --check-format".to_owned(),
+    _ if opt.to_from_output_filename(matches) => "from the output file's extension".to_owned(),
+    (false, 0) => "by default".to_owned(),
+    (false, _) => format!("given by -t {}", given_by(matches, "to")),
+  };
+  let (style, options): (&str, &[&str]) = match opt.to {
+    Format::Json => (
+      "compact, with each document on a line of its own",
+      &["key-style", "non-finite"],
+    ),
+    Format::Yaml => (
+      "block style, indented by 2 spaces, with --- before each document",
+      &[
+        "yaml-tags",
+        "yaml-version",
+        "int-literals",
+        "yaml-anchors",
+        "keep-comments",
+      ],
+    ),
+    Format::Toml => (
+      "tables as [sections], with arrays broken up one element to a line",
+      &["key-style", "toml-datetime", "keep-comments"],
+    ),
+    Format::Csv | Format::Tsv => (
+      "a header of the rows' keys, then a row for each object",
+      &["columns", "on-missing-column"],
+    ),
+    Format::Table => (
+      "the rows that csv would have, aligned into columns",
+      &["columns", "max-column-width"],
+    ),
+    Format::Tree => ("the structure of each document, like tree(1)", &[]),
+    Format::Template => ("each document rendered through a template", &["template"]),
+  };
+  let mut lines = vec![
+    format!("output format: {}, {}", opt.to.name(), how),
+    format!("output style: {}", style),
+  ];
+  let flags = ["yaml-anchors", "keep-comments", "crlf"];
+  for &name in options.iter().chain(&["crlf"]) {
+    let value = match matches.values_of(name) {
+      Some(values) => values.collect::<Vec<_>>().join(","),
+      None if matches.is_present(name) => "on".to_owned(),
+      None if flags.contains(&name) => "off".to_owned(),
+      None => "unset".to_owned(),
+    };
+    let how = match name {
+      "keep-comments" if opt.format || opt.check_format => {
+        "for --format or --check-format".to_owned()
+      }
+      _ => given_by(matches, name),
+    };
+    let value = match name {
+      "keep-comments" if opt.keep_comments => "on".to_owned(),
+      _ => value,
+    };
+    lines.push(format!("  --{} {}, {}", name, value, how));
+  }
+  let output = match (&opt.output, opt.dry_run) {
+    _ if opt.check => "nothing, for --check".to_owned(),
+    _ if opt.check_format => "a diff against the input, on stdout".to_owned(),
+    (Some(path), true) => format!("nothing, as --dry-run only reports on {}", path.display()),
+    (Some(path), false) => path.display().to_string(),
+    (None, _) => "<stdout>".to_owned(),
+  };
+  lines.push(format!("output: {}", output));
+  lines
+}
+
+// Pipeline holds everything that happens to the input documents on their way to
+// the output, in the order that it happens.
+struct Pipeline {
+  read: ReadOptions,
+  nth: Option<usize>,
+  pointer: Option<path::Path>,
+  duplicate_keys: Option<DuplicateKeys>,
+  caster: Option<Validator>,
+  validator: Option<Validator>,
+  limit: Option<Limit>,
+  transforms: Vec<Transform>,
+  aggregates: Vec<Aggregate>,
+  max_depth: Option<usize>,
+  control_chars: Option<ControlChars>,
+}
+
+fn transcode_with_options<O>(
+  input: &[u8],
+  from: Format,
+  opt: &Opt,
+  pipeline: &Pipeline,
+  output: O,
+) -> Result<(), Box<dyn Error>>
+where
+  O: Output,
+{
+  if opt.stats {
+    let output = SummaryOutput {
+      summary: Stats::default(),
+      output,
+    };
+    transcode_with_pipeline(input, from, pipeline, output)
+  } else if opt.infer_schema {
+    let output = SummaryOutput {
+      summary: Inference::default(),
+      output,
+    };
+    transcode_with_pipeline(input, from, pipeline, output)
+  } else if let Some(path) = &opt.histogram {
+    let output = SummaryOutput {
+      summary: Histogram::new(path.clone()),
+      output,
+    };
+    transcode_with_pipeline(input, from, pipeline, output)
+  } else if opt.hash {
+    let output = HashOutput {
+      algorithm: opt.hash_algorithm,
+      output,
+    };
+    transcode_with_pipeline(input, from, pipeline, output)
+  } else if let Some(regex) = &opt.grep {
+    let output = GrepOutput {
+      grep: Grep::new(regex.clone(), opt.grep_in),
+      output,
+    };
+    transcode_with_pipeline(input, from, pipeline, output)
+  } else {
+    transcode_with_pipeline(input, from, pipeline, output)
+  }
+}
+
+// Most pipeline stages require the full document to be buffered in memory, so
+// we only pay that cost when one of them is actually requested.
+fn transcode_with_pipeline<O>(
+  input: &[u8],
+  from: Format,
+  pipeline: &Pipeline,
+  output: O,
+) -> Result<(), Box<dyn Error>>
+where
+  O: Output,
+{
+  // Each stage passes documents straight through to the next when it has
+  // nothing to do, so the pipeline is always built in its full form.
+  let output = ControlCharsOutput {
+    policy: pipeline.control_chars,
+    output,
+  };
+  let output = MaxDepthOutput {
+    depth: pipeline.max_depth,
+    output,
+  };
+  // The limit is on the documents that come out of sorting and grouping, not on
+  // the ones that go in.
+  let output = LimitOutput::new(pipeline.limit, output);
+  let output = AggregateOutput::new(&pipeline.aggregates, output);
+  let output = TransformOutput {
+    transforms: &pipeline.transforms,
+    output,
+  };
+  let source = match (&pipeline.pointer, pipeline.nth) {
+    (None, None) => Some((input, from)),
+    _ => None,
+  };
+  let output = ValidateOutput::new(pipeline.validator.as_ref(), source, output);
+  let output = CastOutput {
+    caster: pipeline.caster.as_ref(),
+    output,
+  };
+  let output = DuplicateKeysOutput::new(pipeline.duplicate_keys, source, output);
+  let output = PointerOutput::new(pipeline.pointer.as_ref(), output);
+  let output = NthOutput::new(pipeline.nth, output);
+  let output = TimingOutput {
+    enabled: timing::is_enabled(),
+    output,
+  };
+  let output = ProgressOutput {
+    enabled: progress::is_enabled(),
+    output,
+  };
+  transcode_all_input(input, from, &pipeline.read, output)
+}
+
+// ReadOptions holds the options that affect how input is parsed into documents.
+#[derive(Default)]
+struct ReadOptions {
+  column_types: Vec<csv::ColumnHint>,
+  yaml_tags: yaml::Tags,
+  binary: yaml::Binary,
+  yaml_version: Option<yaml::Version>,
+  yaml_merge: yaml::MergeKeys,
+  int_literals: yaml::IntLiterals,
+  yaml_timestamps: YamlTimestamps,
+  toml_datetime: TomlDatetime,
+  single_json: bool,
+  keep_going: bool,
+  jobs: usize,
+  parse_thread: bool,
+  stream: bool,
+  path: Option<PathBuf>,
+  errors: ErrorFormat,
+  file: String,
+}
+
+impl ReadOptions {
+  // Reports whether YAML input needs jyt's own loader rather than serde_yaml.
+  fn uses_yaml_loader(&self) -> bool {
+    self.yaml_tags == yaml::Tags::Wrap
+      || self.binary != yaml::Binary::Text
+      || self.yaml_version.is_some()
+      || self.int_literals != yaml::IntLiterals::Convert
+      || self.yaml_timestamps != YamlTimestamps::String
+  }
+
+  // Opens the input for reading as a stream, for --stream.
+  fn open(&self) -> io::Result<Box<dyn BufRead>> {
+    Ok(match &self.path {
+      None => Box::new(BufReader::new(io::stdin())),
+      Some(path) => Box::new(BufReader::new(File::open(path)?)),
+    })
+  }
+}
+
+fn transcode_all_input<O>(
+  input: &[u8],
+  from: Format,
+  read: &ReadOptions,
+  output: O,
+) -> Result<(), Box<dyn Error>>
+where
+  O: Output,
+{
+  match from {
+    Format::Json | Format::Yaml if read.parse_thread && !read.stream && !read.keep_going => {
+      transcode_on_parse_thread(input, from, read, output)
+    }
+    _ => {
+      if read.parse_thread {
+        warn::info(
+          "--parse-thread only applies to JSON and YAML input without --stream or --keep-going",
+        );
+      }
+      transcode_input(input, from, read, output)
+    }
+  }
+}
+
+// --parse-thread hands documents over in batches of this many, since handing
+// them over one at a time costs more than converting small documents, and lets
+// the parsing thread get this many batches ahead of the output.
+const PARSED_BATCH: usize = 256;
+const PARSED_BATCHES: usize = 4;
+
+// Parses the input on a thread of its own, for --parse-thread, so that the next
+// documents are parsed while the output converts and writes the last ones.
+// Errors from parsing come back as their messages, since they can't be sent
+// between threads.
+fn transcode_on_parse_thread<O>(
+  input: &[u8],
+  from: Format,
+  read: &ReadOptions,
+  mut output: O,
+) -> Result<(), Box<dyn Error>>
+where
+  O: Output,
+{
+  thread::scope(|scope| {
+    let (tx, rx) = mpsc::sync_channel(PARSED_BATCHES);
+    let parser = scope.spawn(move || {
+      let mut output = ChannelOutput {
+        tx,
+        batch: Vec::with_capacity(PARSED_BATCH),
+        closed: false,
+      };
+      let result = transcode_input(input, from, read, &mut output);
+      // The documents before an error are still output.
+      output.send();
+      result.map_err(|err| err.to_string())
+    });
+    'batches: for batch in rx {
+      for doc in batch {
+        output.transcode_from(doc)?;
+        if output.is_done() {
+          break 'batches;
+        }
+      }
+    }
+    match parser.join() {
+      Ok(result) => result?,
+      Err(panic) => std::panic::resume_unwind(panic),
+    }
+    output.finish()
+  })
+}
+
+fn transcode_input<O>(
+  input: &[u8],
+  from: Format,
+  read: &ReadOptions,
+  mut output: O,
+) -> Result<(), Box<dyn Error>>
+where
+  O: Output,
+{
+  let mut skipped = Skipped::default();
+  match from {
+    // Streamed input is read as it goes, so none of it is in the input slice.
+    Format::Json if read.stream => {
+      let mut de = serde_json::Deserializer::from_reader(read.open()?);
+      while !output.is_done() && de.end().is_err() {
+        output.transcode_from(json::Numbers(&mut de))?;
+        if read.single_json {
+          de.end().map_err(trailing_content)?;
+        }
+      }
+    }
+    // Merge keys need a look at the whole document, so documents only go
+    // straight from the parser to the output when they're kept as they are.
+    Format::Yaml if read.stream && !read.uses_yaml_loader() => {
+      let mut events = events::Events::new(read.open()?);
+      while !output.is_done() && events.next_document()? {
+        match read.yaml_merge {
+          yaml::MergeKeys::Keep => output.transcode_from(&mut events),
+          _ => Value::deserialize(&mut events)
+            .map_err(Into::into)
+            .and_then(|mut doc| {
+              yaml::merge_keys(&mut doc, read.yaml_merge).map_err(Classified::input)?;
+              output.transcode_from(doc)
+            }),
+        }
+        .map_err(|err| events.io_error(err))?;
+      }
+    }
+    Format::Yaml if read.stream => {
+      for doc in yaml::Documents::new(read.open()?) {
+        let (line, text) = doc?;
+        if output.is_done() {
+          break;
+        }
+        transcode_yaml(text.as_bytes(), read, &mut output)
+          .map_err(|err| diagnostic::offset_position(err, line, 0))?;
+      }
+    }
+    Format::Json if read.keep_going => {
+      // After an error, there's no telling where the broken document ends, so
+      // this picks up at the next line, which suits input with a document on
+      // each line.
+      let mut cursor = json::Cursor::new(input);
+      while !output.is_done() {
+        let mut de = serde_json::Deserializer::from_slice(cursor.rest());
+        if de.end().is_ok() {
+          break;
+        }
+        match Value::deserialize(json::Numbers(&mut de)) {
+          Ok(doc) => {
+            skipped.total += 1;
+            output.transcode_from(doc)?;
+            match de.end() {
+              Ok(()) => break,
+              Err(err) => cursor.advance(err.line(), err.column()),
+            }
+          }
+          Err(err) => {
+            let line = err.line();
+            skipped.report(cursor.offset_position(err.into()).to_string(), read);
+            cursor.advance(line + 1, 1);
+          }
+        }
+      }
+    }
+    Format::Json if read.jobs > 1 && !read.single_json => {
+      json::parse_parallel(input, read.jobs, |doc| {
+        output.transcode_from(doc)?;
+        Ok(!output.is_done())
+      })?;
+    }
+    Format::Yaml if read.keep_going => {
+      for (line, text) in yaml::split_documents(str::from_utf8(input)?) {
+        if output.is_done() {
+          break;
+        }
+        // Padding each document to its place in the input keeps the positions
+        // in error messages right.
+        let padded = "\n".repeat(line) + text;
+        let docs: Result<Vec<Value>, Box<dyn Error>> = match read.uses_yaml_loader() {
+          true => yaml::load(
+            &padded,
+            read.yaml_tags,
+            read.binary,
+            read.yaml_version,
+            read.int_literals,
+            read.yaml_timestamps,
+          )
+          .map_err(Into::into),
+          false => serde_yaml::Deserializer::from_str(&padded)
+            .map(|de| Value::deserialize(de).map_err(Into::into))
+            .collect(),
+        };
+        match docs {
+          Ok(docs) => {
+            for mut doc in docs {
+              skipped.total += 1;
+              yaml::merge_keys(&mut doc, read.yaml_merge).map_err(Classified::input)?;
+              output.transcode_from(doc)?;
+            }
+          }
+          Err(err) => skipped.report(err.to_string(), read),
+        }
+      }
+    }
+    Format::Json => {
+      let mut cursor = json::Cursor::new(input);
+      while !output.is_done() {
+        let mut de = serde_json::Deserializer::from_slice(cursor.rest());
+        if de.end().is_ok() {
+          break;
+        }
+        output
+          .transcode_from(json::Numbers(&mut de))
+          .map_err(|err| cursor.offset_position(err))?;
+        match de.end() {
+          Ok(()) => break,
+          Err(err) if read.single_json => {
+            Err(cursor.offset_position(trailing_content(err).into()))?
+          }
+          Err(err) => cursor.advance(err.line(), err.column()),
+        }
+      }
+    }
+    Format::Yaml => transcode_yaml(input, read, &mut output)?,
+    Format::Toml => {
+      let input_str = str::from_utf8(input)?;
+      nesting::check_toml(input_str)?;
+      let mut de = toml::Deserializer::new(input_str);
+      match read.toml_datetime {
+        TomlDatetime::Object => output.transcode_from(&mut de)?,
+        mode => {
+          let mut doc = Value::deserialize(&mut de)?;
+          datetime::from_toml(&mut doc, mode);
+          output.transcode_from(doc)?;
+        }
+      }
+    }
+    Format::Csv | Format::Tsv => {
+      let delimiter = if let Format::Tsv = from { '\t' } else { ',' };
+      let records =
+        csv::read_records(str::from_utf8(input)?, delimiter).map_err(Classified::input)?;
+      let docs = csv::to_documents(records, &read.column_types).map_err(Classified::input)?;
+      for doc in docs {
+        if output.is_done() {
+          break;
+        }
+        output.transcode_from(doc)?;
+      }
+    }
+    Format::Table => Err("table is not a supported input format")?,
+    Format::Tree => Err("tree is not a supported input format")?,
+    Format::Template => Err("template is not a supported input format")?,
+  }
+  output.finish()?;
+  match skipped.count {
+    0 => Ok(()),
+    _ => Err(skipped)?,
+  }
+}
+
+// Transcodes the documents of YAML input, through jyt's own loader or
+// serde_yaml.
+fn transcode_yaml<O>(input: &[u8], read: &ReadOptions, output: &mut O) -> Result<(), Box<dyn Error>>
+where
+  O: Output,
+{
+  match read.uses_yaml_loader() {
+    true => {
+      for doc in yaml::load(
+        str::from_utf8(input)?,
+        read.yaml_tags,
+        read.binary,
+        read.yaml_version,
+        read.int_literals,
+        read.yaml_timestamps,
+      )? {
+        if output.is_done() {
+          break;
+        }
+        let mut doc = doc;
+        yaml::merge_keys(&mut doc, read.yaml_merge).map_err(Classified::input)?;
+        output.transcode_from(doc)?;
+      }
+    }
+    // Merge keys need a look at the whole document, so documents only go
+    // straight through from serde_yaml when there can't be any.
+    false if read.yaml_merge != yaml::MergeKeys::Keep && input.windows(2).any(|w| w == b"<<") => {
+      for de in serde_yaml::Deserializer::from_slice(input) {
+        if output.is_done() {
+          break;
+        }
+        let mut doc = Value::deserialize(de)?;
+        yaml::merge_keys(&mut doc, read.yaml_merge).map_err(Classified::input)?;
+        output.transcode_from(doc)?;
+      }
+    }
+    false => {
+      for de in serde_yaml::Deserializer::from_slice(input) {
+        if output.is_done() {
+          break;
+        }
+        output.transcode_from(de)?;
+      }
+    }
+  }
+  Ok(())
+}
+
+// Returns the error for JSON input with more after its value, for --strict.
+// Anything after the first value, whether it's another document or not, is
+// most likely a mistake in whatever produced the input, like two outputs
+// written to the same file. The error from end() points right to it.
+fn trailing_content(err: serde_json::Error) -> String {
+  format!(
+    "input has more after its JSON value, and --strict was given at line {} column {}",
+    err.line(),
+    err.column()
+  )
+}
+
+// Skipped counts the documents that --keep-going skipped over, and is the error
+// for an input where any were skipped, which exits with a status of its own.
+#[derive(Debug, Default)]
+struct Skipped {
+  count: usize,
+  total: usize,
+}
+
+impl Skipped {
+  fn report(&mut self, message: String, read: &ReadOptions) {
+    match read.errors {
+      ErrorFormat::Text => eprintln!(
+        "{} document {}, {} (skipped)",
+        color::label("error"),
+        self.total,
+        message
+      ),
+      ErrorFormat::Json => {
+        let err: Box<dyn Error> = message.into();
+        let mut report = Report::new("syntax", err.as_ref());
+        report.file = Some(&read.file);
+        report.document = Some(self.total);
+        report.eprint();
+      }
+    }
+    self.count += 1;
+    self.total += 1;
+  }
+}
+
+impl fmt::Display for Skipped {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    write!(
+      f,
+      "skipped {} of {} documents that could not be read",
+      self.count, self.total
+    )
+  }
+}
+
+impl Error for Skipped {}
+
+// Unformatted is the error for --check-format input that jyt would format
+// differently, which exits with a status of its own.
+#[derive(Debug)]
+struct Unformatted;
+
+impl fmt::Display for Unformatted {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    write!(f, "input is not formatted the way jyt would write it")
+  }
+}
+
+impl Error for Unformatted {}
+
+// Class is the kind of problem behind an error, which decides the status that
+// jyt exits with. Errors whose types already tell (like a Diagnostic for input
+// that can't be parsed, or an io::Error) aren't classified any further, and
+// any other unclassified error is a usage error.
+#[derive(Copy, Clone, Debug)]
+enum Class {
+  Usage,
+  Input,
+  Output,
+}
+
+#[derive(Debug)]
+struct Classified(Class, Box<dyn Error>);
+
+impl Classified {
+  fn usage(err: impl Into<Box<dyn Error>>) -> Box<dyn Error> {
+    Box::new(Classified(Class::Usage, err.into()))
+  }
+
+  fn input(err: impl Into<Box<dyn Error>>) -> Box<dyn Error> {
+    Box::new(Classified(Class::Input, err.into()))
+  }
+
+  // Classifies an error from converting the input as an output error, unless
+  // it's already known to be something else. Input that couldn't be parsed
+  // has a position, which is on a Diagnostic where jyt has the input to show.
+  fn output(err: Box<dyn Error>) -> Box<dyn Error> {
+    let known = err.is::<Classified>()
+      || err.is::<Diagnostic>()
+      || err.is::<DuplicateKey>()
+      || err.is::<io::Error>()
+      || err.is::<ValidationError>()
+      || err.is::<Skipped>();
+    match known {
+      true => err,
+      false if diagnostic::has_position(&err.to_string()) => {
+        Box::new(Classified(Class::Input, err))
+      }
+      false => Box::new(Classified(Class::Output, err)),
+    }
+  }
+}
+
+impl fmt::Display for Classified {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    self.1.fmt(f)
+  }
+}
+
+impl Error for Classified {}
+
+// ChannelOutput sends batches of documents to another thread, for
+// --parse-thread. It's done once the other thread stops taking them.
+struct ChannelOutput {
+  tx: mpsc::SyncSender<Vec<Value>>,
+  batch: Vec<Value>,
+  closed: bool,
+}
+
+impl ChannelOutput {
+  fn send(&mut self) {
+    if !self.batch.is_empty() {
+      let batch = std::mem::replace(&mut self.batch, Vec::with_capacity(PARSED_BATCH));
+      self.closed = self.tx.send(batch).is_err();
+    }
+  }
+}
+
+impl Output for ChannelOutput {
+  fn transcode_from<'de, D, E>(&mut self, de: D) -> Result<(), Box<dyn Error>>
+  where
+    D: serde::de::Deserializer<'de, Error = E>,
+    E: serde::de::Error + 'static,
+  {
+    self.batch.push(Value::deserialize(de)?);
+    if self.batch.len() == PARSED_BATCH {
+      self.send();
+    }
+    Ok(())
+  }
+
+  fn is_done(&self) -> bool {
+    self.closed
+  }
+}
+
+#[derive(StructOpt)]
+#[structopt(
+  verbatim_doc_comment,
+  group = ArgGroup::with_name("destination")
+)]
+/// Translate between serialized data formats
+///
+/// This version of jyt supports the following formats, each of which may be
+/// specified by full name or first character (e.g. '-ty' == '-t yaml'):
+///
+///   json: Multi-document with self-delineating values (object, array, string)
+///         and / or whitespace between values. Default format for .json files.
+///
+///   yaml: Multi-document with "---" syntax. Default format for .yaml and .yml
+///         files.
+///
+///   toml: Single documents only. Does not support null values. Default format
+///         for .toml files.
+///
+///   csv, tsv: Reads each row after the header as a document, with every value
+///         a string unless --column-types says otherwise (e.g. 'id:int,
+///         active:bool', with types string, int, float, bool, json, and auto).
+///         Writes each object, or each object in an array, as a row, with the
+///         union of their keys (or the --columns) as the header. Default
+///         format for .csv and .tsv files. No single-character shorthand.
+///
+///   table: Output only. Writes the same rows as csv as an aligned table for
+///         reading on a terminal, truncating cells to --max-column-width. No
+///         single-character shorthand.
+///
+///   tree: Output only. Draws the structure of each document like tree(1),
+///         with keys and indices as branches and scalar values as leaves. No
+///         single-character shorthand.
+///
+///   template: Output only. Renders each document through the template file
+///         given with --template, using {{ .path }}, {{#each .path}},
+///         {{#if .path}}, {{else}}, {{@key}}, and {{@index}} tags. As in
+///         Handlebars, {{ .path }} escapes the value for HTML, and
+///         {{{ .path }}} inserts it as it is. No single-character shorthand.
+///
+/// When the input format is not specified with -f or detected from a file
+/// extension, jyt will attempt to auto-detect it by parsing the input as
+/// different formats in an unspecified order until one works. jyt's behavior is
+/// undefined if an input file is modified while jyt is running.
+///
+/// Options that take a path accept either a jq-like path (e.g. '.data.*',
+/// '.items[0].name', or '.items[10:20]') or a JSON Pointer extended with '*'
+/// wildcards (e.g. '/data/*'). Slices select the array elements between two
+/// indices, either of which may be omitted or negative to count from the end.
+/// Transforms run in the order they are given on the command line.
+///
+/// jyt also has the commands convert, bench, repl, git-textconv, version,
+/// completions, and man (and self-update, in builds with that feature), each with
+/// its own --help. To convert a file with one of these names, give it with a path
+/// like './bench'. 'jyt man' prints a man page that goes into the details of each
+/// option.
+///
+/// jyt exits with one of these statuses, so that scripts can tell what went
+/// wrong:
+///
+///   0  success
+///   1  usage error, like a bad option or an invalid schema or template
+///   2  the input can't be parsed, or isn't what the options require
+///   3  the input can't be written in the output format, or a transform failed
+///   4  I/O error, like a missing input file
+///   5  --validate-schema found an invalid document
+///   6  --keep-going skipped some documents
+///   7  memory use went over --max-memory
+///   8  --check-format found input that isn't formatted
+///   141 the output went to a pipe that closed early, with --pipe-error
+struct Opt {
+  #[structopt(
+    short = "t",
+    long = "to",
+    value_name = "format",
+    help = "Format to convert to",
+    default_value = "json"
+  )]
+  to: Format,
+
+  #[structopt(
+    short = "f",
+    long = "from",
+    value_name = "format",
+    help = "Format to convert from"
+  )]
+  from: Option<Format>,
+
+  #[structopt(
+    name = "file",
+    help = "File to read input from [default: stdin]",
+    parse(from_os_str)
+  )]
+  input_filename: Option<PathBuf>,
+
+  #[structopt(
+    name = "outfile",
+    help = "File to write output to, in the format of its extension [default: stdout]",
+    group = "destination",
+    parse(from_os_str)
+  )]
+  output_filename: Option<PathBuf>,
+
+  #[structopt(
+    long = "expand-aliases",
+    help = "Expand YAML aliases into copies of their anchored values (default)",
+    overrides_with = "no-expand-aliases"
+  )]
+  _expand_aliases: bool,
+
+  #[structopt(
+    long = "no-expand-aliases",
+    help = "Fail on YAML input that uses aliases instead of expanding them",
+    overrides_with = "expand-aliases"
+  )]
+  no_expand_aliases: bool,
+
+  #[structopt(
+    long = "column-types",
+    value_name = "name:type",
+    help = "Types of the columns of CSV or TSV input",
+    use_delimiter = true,
+    require_delimiter = true
+  )]
+  column_types: Vec<csv::ColumnHint>,
+
+  #[structopt(
+    long = "columns",
+    value_name = "names",
+    help = "Columns to output in CSV, TSV, or table output, in order",
+    use_delimiter = true,
+    require_delimiter = true
+  )]
+  columns: Vec<String>,
+
+  #[structopt(
+    long = "max-column-width",
+    value_name = "N",
+    help = "Width to truncate the cells of table output to",
+    default_value = "40"
+  )]
+  max_column_width: usize,
+
+  #[structopt(
+    long = "on-missing-column",
+    value_name = "policy",
+    help = "What to do when a row lacks one of --columns: blank or error",
+    default_value = "blank"
+  )]
+  on_missing_column: csv::Missing,
+
+  #[structopt(
+    short = "o",
+    long = "output",
+    value_name = "file",
+    help = "Write the output to a file instead of stdout",
+    group = "destination",
+    parse(from_os_str)
+  )]
+  output: Option<PathBuf>,
+
+  #[structopt(
+    long = "preallocate",
+    value_name = "size",
+    help = "Reserve size bytes for the -o file before writing to it, like 512M or 2G",
+    requires = "destination"
+  )]
+  preallocate: Option<Size>,
+
+  #[structopt(
+    long = "dry-run",
+    help = "Convert as usual, but only report the -o file that would be written",
+    requires = "destination"
+  )]
+  dry_run: bool,
+
+  #[structopt(
+    long = "pager",
+    value_name = "mode",
+    help = "When to page output through $PAGER: auto (on a terminal), always, or never",
+    default_value = "never"
+  )]
+  pager: PagerMode,
+
+  #[structopt(
+    long = "color",
+    value_name = "when",
+    help = "When to color output, diffs, and errors: auto (on a terminal), always, or never",
+    default_value = "auto"
+  )]
+  color: color::ColorMode,
+
+  #[structopt(
+    long = "theme",
+    value_name = "theme",
+    help = "Colors for JSON output: dark or light, then any of key, string, number, bool, null=style",
+    default_value = "dark"
+  )]
+  theme: color::Theme,
+
+  #[structopt(
+    long = "crlf",
+    help = "End the lines of the output with CRLF, as on Windows"
+  )]
+  crlf: bool,
+
+  #[structopt(
+    long = "pipe-error",
+    help = "Exit with status 141 if the output is cut off by a closed pipe"
+  )]
+  pipe_error: bool,
+
+  #[structopt(
+    long = "buffer-size",
+    value_name = "size",
+    help = "Size of the output buffer, like 64K or 1M",
+    default_value = "64K"
+  )]
+  buffer_size: Size,
+
+  #[structopt(
+    long = "mmap-populate",
+    help = "Read all of a memory-mapped input file in before parsing it (default)",
+    overrides_with = "no-mmap-populate"
+  )]
+  _mmap_populate: bool,
+
+  #[structopt(
+    long = "no-mmap-populate",
+    help = "Read a memory-mapped input file in as it's parsed",
+    overrides_with = "mmap-populate"
+  )]
+  no_mmap_populate: bool,
+
+  #[structopt(
+    long = "mmap-advice",
+    value_name = "advice",
+    help = "How a memory-mapped input file will be read: normal, sequential, or willneed",
+    default_value = "normal"
+  )]
+  mmap_advice: mmap::Advice,
+
+  #[structopt(
+    long = "mmap-threshold",
+    value_name = "size",
+    help = "Read input files smaller than size without memory mapping them",
+    default_value = "0"
+  )]
+  mmap_threshold: Size,
+
+  #[structopt(
+    long = "max-memory",
+    value_name = "size",
+    help = "Stop with an error if jyt's memory use goes over size, like 512M or 2G"
+  )]
+  max_memory: Option<Size>,
+
+  #[structopt(
+    long = "template",
+    value_name = "file",
+    help = "Template file for template output",
+    parse(from_os_str)
+  )]
+  template: Option<PathBuf>,
+
+  #[structopt(
+    long = "b64-decode",
+    value_name = "path",
+    help = "Base64 decode the strings at a path, which must decode to UTF-8 text",
+    number_of_values = 1
+  )]
+  b64_decode: Vec<path::Path>,
+
+  #[structopt(
+    long = "b64-encode",
+    value_name = "path",
+    help = "Base64 encode the strings at a path",
+    number_of_values = 1
+  )]
+  b64_encode: Vec<path::Path>,
+
+  #[structopt(
+    long = "set",
+    value_name = "path=value",
+    help = "Set the value at a path",
+    number_of_values = 1
+  )]
+  set: Vec<transform::Assignment>,
+
+  #[structopt(
+    long = "arg",
+    value_name = "name=value",
+    help = "Bind $name to a string, for --set, --interpolate, and templates",
+    number_of_values = 1,
+    parse(try_from_str = vars::parse_arg)
+  )]
+  arg: Vec<vars::Variable>,
+
+  #[structopt(
+    long = "argjson",
+    value_name = "name=json",
+    help = "Bind $name to a JSON value, for --set, --interpolate, and templates",
+    number_of_values = 1,
+    parse(try_from_str = vars::parse_argjson)
+  )]
+  argjson: Vec<vars::Variable>,
+
+  #[structopt(
+    long = "delete",
+    value_name = "path",
+    help = "Delete the values at a path",
+    number_of_values = 1
+  )]
+  delete: Vec<path::Path>,
+
+  #[structopt(
+    long = "rename-map",
+    value_name = "file",
+    help = "Rename paths according to a map of old paths to new paths",
+    number_of_values = 1,
+    parse(from_os_str)
+  )]
+  rename_map: Vec<PathBuf>,
+
+  #[structopt(
+    long = "only",
+    value_name = "paths",
+    help = "Keep only the values at a comma-separated list of paths",
+    number_of_values = 1
+  )]
+  only: Vec<path::PathList>,
+
+  #[structopt(
+    long = "except",
+    value_name = "paths",
+    help = "Delete the values at a comma-separated list of paths",
+    number_of_values = 1
+  )]
+  except: Vec<path::PathList>,
+
+  #[structopt(
+    long = "strategic-merge",
+    value_name = "file",
+    help = "Apply a Kubernetes strategic merge patch from a file",
+    number_of_values = 1,
+    parse(from_os_str)
+  )]
+  strategic_merge: Vec<PathBuf>,
+
+  #[structopt(
+    long = "defaults",
+    value_name = "file",
+    help = "Fill in keys missing from the input with those from another file",
+    number_of_values = 1,
+    parse(from_os_str)
+  )]
+  defaults: Vec<PathBuf>,
+
+  #[structopt(
+    long = "transpose",
+    value_name = "path",
+    help = "Convert between an array of objects and an object of arrays at a path",
+    number_of_values = 1
+  )]
+  transpose: Vec<path::Path>,
+
+  #[structopt(
+    long = "join",
+    value_name = "file",
+    help = "Join input records with the records of another file",
+    requires = "on",
+    parse(from_os_str)
+  )]
+  join: Option<PathBuf>,
+
+  #[structopt(
+    long = "on",
+    value_name = "path",
+    help = "Path to the key that --join matches records on"
+  )]
+  on: Option<path::Path>,
+
+  #[structopt(
+    long = "join-type",
+    value_name = "type",
+    help = "Kind of join to perform: inner or left",
+    default_value = "inner"
+  )]
+  join_type: transform::JoinType,
+
+  #[structopt(
+    long = "group-by",
+    value_name = "path",
+    help = "Group documents (or array elements) by the value at a path"
+  )]
+  group_by: Option<path::Path>,
+
+  #[structopt(
+    long = "sort-docs-by",
+    value_name = "path",
+    help = "Sort documents by the value at a path"
+  )]
+  sort_docs_by: Option<path::Path>,
+
+  #[structopt(
+    long = "aggregate",
+    value_name = "function(path)",
+    help = "Output the sum, min, max, avg, or count of the values at a path",
+    number_of_values = 1
+  )]
+  aggregate: Vec<transform::Reduction>,
+
+  #[structopt(
+    long = "k8s",
+    help = "Sort Kubernetes resources into a safe order to apply them in"
+  )]
+  k8s: bool,
+
+  #[structopt(
+    long = "k8s-select",
+    value_name = "selector",
+    help = "Output only Kubernetes resources matching a selector",
+    number_of_values = 1
+  )]
+  k8s_select: Vec<k8s::Selector>,
+
+  #[structopt(
+    long = "interpolate",
+    help = "Replace ${path} references in strings with values from the same document"
+  )]
+  interpolate: bool,
+
+  #[structopt(
+    long = "max-depth",
+    value_name = "N",
+    help = "Summarize values nested more than N levels deep"
+  )]
+  max_depth: Option<usize>,
+
+  #[structopt(
+    long = "nth",
+    value_name = "N",
+    help = "Output only the Nth document, counting from 0",
+    conflicts_with_all = &["head", "tail"]
+  )]
+  nth: Option<usize>,
+
+  #[structopt(
+    long = "pointer",
+    value_name = "path",
+    help = "Output only the values at a path in the first document",
+    conflicts_with = "strict"
+  )]
+  pointer: Option<path::Path>,
+
+  #[structopt(
+    long = "head",
+    value_name = "N",
+    help = "Output only the first N documents (or array elements)",
+    conflicts_with = "tail"
+  )]
+  head: Option<usize>,
+
+  #[structopt(
+    long = "tail",
+    value_name = "N",
+    help = "Output only the last N documents (or array elements)"
+  )]
+  tail: Option<usize>,
+
+  #[structopt(
+    long = "stats",
+    help = "Output a summary of the input documents instead of the documents"
+  )]
+  stats: bool,
+
+  #[structopt(
+    long = "infer-schema",
+    help = "Output a JSON Schema inferred from the input documents",
+    conflicts_with = "stats"
+  )]
+  infer_schema: bool,
+
+  #[structopt(
+    long = "histogram",
+    value_name = "path",
+    help = "Output how often each distinct value appears at a path",
+    conflicts_with_all = &["stats", "infer-schema"]
+  )]
+  histogram: Option<path::Path>,
+
+  #[structopt(
+    long = "yaml-tags",
+    value_name = "mode",
+    help = "What to do with custom YAML tags: drop or wrap",
+    default_value = "drop"
+  )]
+  yaml_tags: yaml::Tags,
+
+  #[structopt(
+    long = "int-literals",
+    value_name = "mode",
+    help = "How to read YAML integers like 0o755, 0x1F, and 0755: convert, octal, or keep",
+    default_value = "convert"
+  )]
+  int_literals: yaml::IntLiterals,
+
+  #[structopt(
+    long = "yaml-timestamps",
+    value_name = "mode",
+    help = "How to read YAML timestamps like 2001-12-14 21:59:43 -5: string, rfc3339, or epoch",
+    default_value = "string"
+  )]
+  yaml_timestamps: YamlTimestamps,
+
+  #[structopt(
+    long = "yaml-merge",
+    value_name = "mode",
+    help = "What to do with YAML merge keys (<<): merge, keep, or error",
+    default_value = "merge"
+  )]
+  yaml_merge: yaml::MergeKeys,
+
+  #[structopt(
+    long = "yaml-version",
+    value_name = "version",
+    help = "Resolve plain YAML scalars by the rules of YAML 1.1 or 1.2"
+  )]
+  yaml_version: Option<yaml::Version>,
+
+  #[structopt(
+    short = "n",
+    long = "check",
+    help = "Read all of the input without writing any output, to check it for errors"
+  )]
+  check: bool,
+
+  #[structopt(
+    long = "format",
+    help = "Reformat stdin to stdout in its own format, for use as an editor's formatter",
+    conflicts_with_all = &["to", "file", "output", "outfile", "check", "stream"]
+  )]
+  format: bool,
+
+  #[structopt(
+    long = "check-format",
+    help = "Show a diff of what --format would change, and fail if it would change anything",
+    conflicts_with_all = &["to", "output", "outfile", "check", "stream", "format"]
+  )]
+  check_format: bool,
+
+  #[structopt(
+    long = "no-config",
+    help = "Ignore the defaults in the config file (see 'jyt man')"
+  )]
+  no_config: bool,
+
+  #[structopt(
+    short = "v",
+    long = "verbose",
+    help = "Note what jyt is doing, like how it chose the input format",
+    conflicts_with = "quiet"
+  )]
+  verbose: bool,
+
+  #[structopt(
+    long = "explain",
+    help = "Explain on stderr how jyt chose the formats and output settings"
+  )]
+  explain: bool,
+
+  #[structopt(
+    short = "q",
+    long = "quiet",
+    help = "Don't warn about information lost in conversion"
+  )]
+  quiet: bool,
+
+  #[structopt(
+    long = "error-format",
+    value_name = "format",
+    help = "How to write errors to stderr: text or json",
+    default_value = "text"
+  )]
+  error_format: ErrorFormat,
+
+  #[structopt(
+    long = "keep-going",
+    help = "Skip JSON or YAML documents that can't be read, instead of stopping"
+  )]
+  keep_going: bool,
+
+  #[structopt(
+    short = "j",
+    long = "jobs",
+    value_name = "n",
+    help = "Parse JSON input with a document on each line on n threads",
+    default_value = "1"
+  )]
+  jobs: usize,
+
+  #[structopt(
+    long = "parse-thread",
+    help = "Parse JSON or YAML input on a thread of its own, while jyt writes the output"
+  )]
+  parse_thread: bool,
+
+  #[structopt(
+    long = "timing",
+    help = "Report how long reading, parsing, and writing took on stderr"
+  )]
+  timing: bool,
+
+  #[structopt(
+    long = "progress",
+    help = "Show how far through the input jyt is on stderr"
+  )]
+  progress: bool,
+
+  #[structopt(
+    long = "stream",
+    help = "Read and convert JSON or YAML input a document at a time, without holding it all in memory",
+    conflicts_with_all = &["lenient", "keep-comments", "keep-going", "empty"]
+  )]
+  stream: bool,
+
+  #[structopt(
+    long = "strict",
+    help = "Reject input that is questionable, even if it can be parsed",
+    conflicts_with = "lenient"
+  )]
+  strict: bool,
+
+  #[structopt(
+    long = "surrogates",
+    value_name = "policy",
+    help = "What to do with lone surrogates and invalid \\u escapes in JSON: error, replace, or escape",
+    default_value = "error"
+  )]
+  surrogates: json::Surrogates,
+
+  #[structopt(
+    long = "empty",
+    value_name = "policy",
+    help = "What empty input produces: error, null (one null document), or skip (no documents)"
+  )]
+  empty: Option<EmptyInput>,
+
+  #[structopt(
+    long = "control-chars",
+    value_name = "policy",
+    help = "What to do with control characters in strings: raw, escape, or error",
+    default_value = "raw"
+  )]
+  control_chars: ControlChars,
+
+  #[structopt(
+    long = "invalid-utf8",
+    value_name = "policy",
+    help = "What to do with input that isn't valid UTF-8: error, replace, or escape",
+    default_value = "error"
+  )]
+  invalid_utf8: InvalidUtf8,
+
+  #[structopt(
+    long = "lenient",
+    help = "Accept comments and trailing commas in JSON input"
+  )]
+  lenient: bool,
+
+  #[structopt(
+    long = "keep-comments",
+    help = "Keep the comments and blank lines of YAML and TOML input in the output"
+  )]
+  keep_comments: bool,
+
+  #[structopt(
+    long = "yaml-anchors",
+    help = "Write repeated objects and arrays in YAML output as anchors and aliases"
+  )]
+  yaml_anchors: bool,
+
+  #[structopt(
+    long = "binary",
+    value_name = "mode",
+    help = "How YAML !!binary values are converted: text, base64, or array",
+    default_value = "text"
+  )]
+  binary: yaml::Binary,
+
+  #[structopt(
+    long = "key-style",
+    value_name = "style",
+    help = "How JSON and TOML output write keys that aren't strings: stringify, error, or tagged",
+    default_value = "stringify"
+  )]
+  key_style: KeyStyle,
+
+  #[structopt(
+    long = "toml-datetime",
+    value_name = "mode",
+    help = "How TOML datetimes appear in other formats: object, string, or epoch",
+    default_value = "object"
+  )]
+  toml_datetime: TomlDatetime,
+
+  #[structopt(
+    long = "non-finite",
+    value_name = "policy",
+    help = "How JSON output writes NaN and infinity: error, null, string, or literal",
+    default_value = "null"
+  )]
+  non_finite: NonFinite,
+
+  #[structopt(
+    long = "duplicate-keys",
+    value_name = "policy",
+    help = "What to do with repeated object keys: error, first, or last"
+  )]
+  duplicate_keys: Option<DuplicateKeys>,
+
+  #[structopt(
+    long = "cast-schema",
+    value_name = "file",
+    help = "Convert values to the types declared by a JSON Schema",
+    parse(from_os_str)
+  )]
+  cast_schema: Option<PathBuf>,
+
+  #[structopt(
+    long = "validate-schema",
+    value_name = "file",
+    help = "Validate input documents against a JSON Schema",
+    parse(from_os_str)
+  )]
+  validate_schema: Option<PathBuf>,
+
+  #[structopt(
+    long = "hash",
+    help = "Output a digest of each input document instead of the document",
+    conflicts_with_all = &["stats", "infer-schema", "histogram"]
+  )]
+  hash: bool,
+
+  #[structopt(
+    long = "hash-algorithm",
+    value_name = "name",
+    help = "Hash algorithm for --hash: sha1, sha256, or sha512",
+    default_value = "sha256"
+  )]
+  hash_algorithm: digest::Algorithm,
+
+  #[structopt(
+    long = "grep",
+    value_name = "regex",
+    help = "Output the paths and values of keys and values matching a regex",
+    conflicts_with_all = &["stats", "infer-schema", "histogram", "hash"]
+  )]
+  grep: Option<regex::Regex>,
+
+  #[structopt(
+    long = "grep-in",
+    value_name = "scope",
+    help = "What --grep searches: keys, values, or both",
+    default_value = "both"
+  )]
+  grep_in: grep::Scope,
+}
+
+impl Opt {
+  fn detect_from(&self) -> Option<Format> {
+    if self.from.is_some() {
+      return self.from;
+    }
+
+    match &self.input_filename {
+      None => None,
+      Some(path) => format_from_extension(path),
+    }
+  }
+
+  // Returns the transforms requested by the user, in the order that they were
+  // given on the command line.
+  fn transforms(&self, matches: &ArgMatches) -> Result<Vec<Transform>, Box<dyn Error>> {
+    let mut rename_maps = Vec::with_capacity(self.rename_map.len());
+    for path in &self.rename_map {
+      rename_maps.push(transform::parse_rename_map(read_document(path)?)?);
+    }
+
+    let mut defaults = Vec::with_capacity(self.defaults.len());
+    for path in &self.defaults {
+      defaults.push(read_document(path)?);
+    }
+
+    let mut patches = Vec::with_capacity(self.strategic_merge.len());
+    for path in &self.strategic_merge {
+      patches.push(read_documents(path)?);
+    }
+
+    let mut transforms = Vec::new();
+    let (b64_decode, b64_encode) = (&self.b64_decode, &self.b64_encode);
+    transforms.extend(positioned(
+      matches,
+      "b64-decode",
+      b64_decode,
+      Transform::Base64Decode,
+    ));
+    transforms.extend(positioned(
+      matches,
+      "b64-encode",
+      b64_encode,
+      Transform::Base64Encode,
+    ));
+    let mut set = self.set.clone();
+    for assignment in &mut set {
+      assignment.bind()?;
+    }
+    transforms.extend(positioned(matches, "set", &set, Transform::Set));
+    transforms.extend(positioned(
+      matches,
+      "delete",
+      &self.delete,
+      Transform::Delete,
+    ));
+    transforms.extend(positioned(matches, "only", &self.only, |list| {
+      Transform::Only(list.0)
+    }));
+    transforms.extend(positioned(matches, "except", &self.except, |list| {
+      Transform::Except(list.0)
+    }));
+    transforms.extend(positioned(
+      matches,
+      "strategic-merge",
+      &patches,
+      Transform::StrategicMerge,
+    ));
+    transforms.extend(positioned(
+      matches,
+      "defaults",
+      &defaults,
+      Transform::Defaults,
+    ));
+    transforms.extend(positioned(
+      matches,
+      "transpose",
+      &self.transpose,
+      Transform::Transpose,
+    ));
+    transforms.extend(positioned(
+      matches,
+      "rename-map",
+      &rename_maps,
+      Transform::Rename,
+    ));
+
+    transforms.sort_by_key(|(idx, _)| *idx);
+    let mut transforms: Vec<_> = transforms.into_iter().map(|(_, t)| t).collect();
+
+    if self.interpolate {
+      transforms.push(Transform::Interpolate);
+    }
+    Ok(transforms)
+  }
+
+  // Returns the aggregates requested by the user, in the order that they apply.
+  fn aggregates(&self) -> Result<Vec<Aggregate>, Box<dyn Error>> {
+    let mut aggregates = Vec::new();
+    if !self.k8s_select.is_empty() {
+      aggregates.push(Aggregate::K8sSelect(self.k8s_select.clone()));
+    }
+    if let (Some(path), Some(on)) = (&self.join, &self.on) {
+      aggregates.push(Aggregate::Join {
+        right: read_documents(path)?,
+        on: on.clone(),
+        join_type: self.join_type,
+      });
+    }
+    if let Some(path) = &self.group_by {
+      aggregates.push(Aggregate::GroupBy(path.clone()));
+    }
+    if let Some(path) = &self.sort_docs_by {
+      aggregates.push(Aggregate::SortDocsBy(path.clone()));
+    }
+    if self.k8s {
+      aggregates.push(Aggregate::K8sSort);
+    }
+    if !self.aggregate.is_empty() {
+      aggregates.push(Aggregate::Reduce(self.aggregate.clone()));
+    }
+    Ok(aggregates)
+  }
+
+  fn limit(&self) -> Option<Limit> {
+    match (self.head, self.tail) {
+      (Some(n), _) => Some(Limit::Head(n)),
+      (_, Some(n)) => Some(Limit::Tail(n)),
+      _ => None,
+    }
+  }
+
+  fn map_options(&self) -> MapOptions {
+    MapOptions {
+      populate: !self.no_mmap_populate,
+      advice: self.mmap_advice,
+      threshold: self.mmap_threshold.0,
+    }
+  }
+
+  // Returns the name of the input for error messages.
+  fn input_name(&self) -> String {
+    match self.input_source() {
+      InputSource::Stdin => "<stdin>".to_owned(),
+      InputSource::File(path) => path.display().to_string(),
+    }
+  }
+
+  // Reports whether the output format comes from the extension of a second
+  // file on the command line, which it does unless the command line itself
+  // gives -t.
+  fn to_from_output_filename(&self, matches: &ArgMatches) -> bool {
+    self.output_filename.is_some()
+      && (matches.occurrences_of("to") == 0 || config::source("to").is_some())
+  }
+
+  fn input_source(&self) -> InputSource<'_> {
+    match &self.input_filename {
+      None => InputSource::Stdin,
+      Some(path) if path.to_str() == Some("-") => InputSource::Stdin,
+      Some(path) => InputSource::File(path),
+    }
+  }
+}
+
+// Pairs the values of a multi-valued option with their positions on the command
+// line, so that the values of different options can be ordered relative to
+// each other.
+fn positioned<'a, T>(
+  matches: &'a ArgMatches,
+  name: &str,
+  values: &'a [T],
+  make: fn(T) -> Transform,
+) -> impl Iterator<Item = (usize, Transform)> + 'a
+where
+  T: Clone,
+{
+  let indices = matches.indices_of(name).into_iter().flatten();
+  indices.zip(values.iter().cloned().map(make))
+}
+
+fn format_from_extension(path: &std::path::Path) -> Option<Format> {
+  let ext = path.extension().and_then(|ext| ext.to_str());
+  if let Some(format) = ext.and_then(config::extension_format) {
+    return Some(format);
+  }
+  match ext {
+    Some("json") => Some(Format::Json),
+    Some("yaml" | "yml") => Some(Format::Yaml),
+    Some("toml") => Some(Format::Toml),
+    Some("csv") => Some(Format::Csv),
+    Some("tsv") => Some(Format::Tsv),
+    _ => None,
+  }
+}
+
+#[derive(Copy, Clone)]
+enum Format {
+  Json,
+  Yaml,
+  Toml,
+  Csv,
+  Tsv,
+  Table,
+  Tree,
+  Template,
+}
+
+impl Format {
+  fn name(self) -> &'static str {
+    match self {
+      Format::Json => "json",
+      Format::Yaml => "yaml",
+      Format::Toml => "toml",
+      Format::Csv => "csv",
+      Format::Tsv => "tsv",
+      Format::Table => "table",
+      Format::Tree => "tree",
+      Format::Template => "template",
+    }
+  }
+}
+
+// The names that Format parses, which completion scripts offer for -f and -t.
+const FORMAT_NAMES: &[&str] = &[
+  "json", "j", "yaml", "y", "toml", "t", "csv", "tsv", "table", "tree", "template",
+];
+
 impl FromStr for Format {
   type Err = String;
 
@@ -324,6 +2741,11 @@ impl FromStr for Format {
       "j" | "json" => Ok(Self::Json),
       "y" | "yaml" => Ok(Self::Yaml),
       "t" | "toml" => Ok(Self::Toml),
+      "csv" => Ok(Self::Csv),
+      "tsv" => Ok(Self::Tsv),
+      "table" => Ok(Self::Table),
+      "tree" => Ok(Self::Tree),
+      "template" => Ok(Self::Template),
       _ => Err(format!("'{}' is not a valid format", s)),
     }
   }
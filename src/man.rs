@@ -0,0 +1,187 @@
+use std::error::Error;
+use std::fmt::{self, Write as _};
+use std::io::{self, Write};
+
+use structopt::clap::{App, ArgSettings};
+use structopt::StructOpt;
+
+use crate::bench::BenchOpt;
+use crate::completions::CompletionsOpt;
+use crate::convert::ConvertOpt;
+use crate::repl::ReplOpt;
+use crate::textconv::TextconvOpt;
+use crate::version::VersionOpt;
+use crate::Opt;
+
+// ManOpt holds the options for 'jyt man', which writes jyt's help as a man page
+// for packaging.
+#[derive(StructOpt)]
+#[structopt(name = "jyt man", verbatim_doc_comment)]
+/// Print a man page for jyt
+///
+/// The page is written as roff to standard output, from the same text as jyt's
+/// --help and the help of its commands, e.g. 'jyt man > jyt.1' to install it or
+/// 'jyt man | man -l -' to read it.
+pub struct ManOpt {}
+
+// The details of jyt's options, which are too long for --help, but belong in
+// the man page with the rest of its description.
+const DETAILS: &str = include_str!("man.txt");
+
+pub fn man(_: ManOpt) -> Result<(), Box<dyn Error>> {
+  let mut app = Opt::clap();
+  let mut commands = vec![
+    ConvertOpt::clap(),
+    BenchOpt::clap(),
+    ReplOpt::clap(),
+    TextconvOpt::clap(),
+    VersionOpt::clap(),
+    CompletionsOpt::clap(),
+    ManOpt::clap(),
+  ];
+  #[cfg(feature = "self-update")]
+  commands.push(crate::update::UpdateOpt::clap());
+  let mut page = String::new();
+  let about = app.p.meta.about.unwrap_or_default();
+  writeln!(page, ".TH JYT 1 \"\" \"jyt {}\"", env!("CARGO_PKG_VERSION"))?;
+  writeln!(page, ".SH NAME\njyt \\- {}", escape(about))?;
+  page.push_str(".SH SYNOPSIS\n");
+  for (i, app) in std::iter::once(&app).chain(&commands).enumerate() {
+    if i > 0 {
+      page.push_str(".br\n");
+    }
+    write_synopsis(&mut page, app)?;
+  }
+  // clap only adds --help and --version when it parses arguments, but they
+  // belong with the rest of the options.
+  for app in std::iter::once(&mut app).chain(&mut commands) {
+    app.p.create_help_and_version();
+  }
+
+  // The exit statuses come at the end of jyt's help, and get their own section.
+  let text = app.p.meta.long_about.unwrap_or_default();
+  let text = text.strip_prefix(about).unwrap_or(text);
+  let (description, statuses) = match text.find("\n\njyt exits with") {
+    Some(i) => (&text[..i], &text[i..]),
+    None => (text, ""),
+  };
+  page.push_str(".SH DESCRIPTION\n");
+  write_text(&mut page, description)?;
+  write_text(&mut page, DETAILS)?;
+  page.push_str(".SH OPTIONS\n");
+  write_options(&mut page, &app)?;
+  page.push_str(".SH COMMANDS\n");
+  for app in &commands {
+    writeln!(page, ".SS \"{}\"", app.p.meta.name)?;
+    let about = app.p.meta.about.unwrap_or_default();
+    let text = app.p.meta.long_about.unwrap_or(about);
+    write_text(&mut page, text)?;
+    write_options(&mut page, app)?;
+  }
+  if !statuses.is_empty() {
+    page.push_str(".SH \"EXIT STATUS\"\n");
+    write_text(&mut page, statuses)?;
+  }
+
+  io::stdout().write_all(page.as_bytes())?;
+  Ok(())
+}
+
+// Writes a line of usage for a command, like "jyt [options] [file]".
+fn write_synopsis(page: &mut String, app: &App) -> fmt::Result {
+  write!(page, "\\fB{}\\fR", app.p.meta.name)?;
+  if !app.p.flags.is_empty() || !app.p.opts.is_empty() {
+    page.push_str(" [\\fIoptions\\fR]");
+  }
+  for arg in app.p.positionals.values() {
+    let repeat = if arg.b.is_set(ArgSettings::Multiple) {
+      "..."
+    } else {
+      ""
+    };
+    match arg.b.is_set(ArgSettings::Required) {
+      true => write!(page, " \\fI{}\\fR{}", arg.b.name, repeat)?,
+      false => write!(page, " [\\fI{}\\fR{}]", arg.b.name, repeat)?,
+    }
+  }
+  page.push('\n');
+  Ok(())
+}
+
+// Writes the options and arguments of a command as a list, in the same order as
+// its --help: options by name, then positional arguments.
+fn write_options(page: &mut String, app: &App) -> fmt::Result {
+  let mut options = Vec::new();
+  for flag in &app.p.flags {
+    options.push((&flag.b, switches(flag.s.short, flag.s.long), None));
+  }
+  for opt in &app.p.opts {
+    let mut names = switches(opt.s.short, opt.s.long);
+    match &opt.v.val_names {
+      Some(values) => values
+        .values()
+        .for_each(|v| names.push_str(&format!(" \\fI{}\\fR", v))),
+      None => names.push_str(&format!(" \\fI{}\\fR", opt.b.name)),
+    }
+    options.push((&opt.b, names, opt.v.default_val));
+  }
+  options.sort_by_key(|(arg, _, _)| arg.name.to_lowercase());
+  for arg in app.p.positionals.values() {
+    options.push((&arg.b, format!("\\fI{}\\fR", arg.b.name), arg.v.default_val));
+  }
+
+  for (arg, names, default) in options {
+    if arg.is_set(ArgSettings::Hidden) {
+      continue;
+    }
+    writeln!(page, ".TP\n{}", names)?;
+    let help = arg.long_help.or(arg.help).unwrap_or_default();
+    for line in help.lines() {
+      writeln!(page, "{}", escape(line))?;
+    }
+    if let Some(default) = default {
+      writeln!(page, "[default: {}]", escape(&default.to_string_lossy()))?;
+    }
+  }
+  Ok(())
+}
+
+fn switches(short: Option<char>, long: Option<&str>) -> String {
+  match (short, long) {
+    (Some(short), Some(long)) => format!("\\fB\\-{}\\fR, \\fB\\-\\-{}\\fR", short, long),
+    (Some(short), None) => format!("\\fB\\-{}\\fR", short),
+    (None, Some(long)) => format!("\\fB\\-\\-{}\\fR", long),
+    (None, None) => String::new(),
+  }
+}
+
+// Writes help text as roff paragraphs. Indented paragraphs, like the list of
+// formats, keep their lines as they are.
+fn write_text(page: &mut String, text: &str) -> fmt::Result {
+  for paragraph in text.split("\n\n").filter(|p| !p.trim().is_empty()) {
+    let paragraph = paragraph.trim_start_matches('\n');
+    page.push_str(".PP\n");
+    if paragraph.starts_with("  ") {
+      page.push_str(".RS 2\n.nf\n");
+      for line in paragraph.lines() {
+        writeln!(page, "{}", escape(line.strip_prefix("  ").unwrap_or(line)))?;
+      }
+      page.push_str(".fi\n.RE\n");
+    } else {
+      for line in paragraph.lines() {
+        writeln!(page, "{}", escape(line))?;
+      }
+    }
+  }
+  Ok(())
+}
+
+// Escapes a line of text for roff, where a backslash starts an escape and a
+// line that starts with a period or apostrophe is a request.
+fn escape(line: &str) -> String {
+  let line = line.replace('\\', "\\e");
+  match line.starts_with(['.', '\'']) {
+    true => format!("\\&{}", line),
+    false => line,
+  }
+}
@@ -0,0 +1,75 @@
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::io::{self, Write};
+use std::process;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+
+// Memory is the system allocator, counting the bytes that jyt has allocated
+// and not yet freed, so that --max-memory can stop jyt with an error before the
+// system runs out of memory and kills it. Memory that jyt doesn't allocate
+// itself, like the memory map of an input file, doesn't count.
+pub struct Memory;
+
+// The exit status for going over the limit.
+pub const EXIT_STATUS: i32 = 7;
+
+static USED: AtomicUsize = AtomicUsize::new(0);
+static LIMIT: AtomicUsize = AtomicUsize::new(usize::MAX);
+static JSON_ERRORS: AtomicBool = AtomicBool::new(false);
+
+pub fn set_limit(limit: usize, json_errors: bool) {
+  JSON_ERRORS.store(json_errors, Ordering::Relaxed);
+  LIMIT.store(limit, Ordering::Relaxed);
+}
+
+fn reserve(size: usize) {
+  let used = USED.fetch_add(size, Ordering::Relaxed) + size;
+  if used > LIMIT.load(Ordering::Relaxed) {
+    exceeded();
+  }
+}
+
+// Exits right away, since there's no way to return an error from here. This
+// can't rely on anything that allocates, so the error is a fixed message.
+fn exceeded() -> ! {
+  LIMIT.store(usize::MAX, Ordering::Relaxed);
+  let message: &[u8] = match JSON_ERRORS.load(Ordering::Relaxed) {
+    true => concat!(
+      r#"{"code":"memory","message":"memory use went over --max-memory","#,
+      r#""file":null,"document":null,"path":null,"line":null,"column":null}"#,
+      "\n"
+    )
+    .as_bytes(),
+    false => b"jyt error: memory use went over --max-memory\n",
+  };
+  let _ = io::stderr().write_all(message);
+  process::exit(EXIT_STATUS);
+}
+
+// Safety: Each method hands the request straight to the system allocator, and
+// only does its counting around it.
+unsafe impl GlobalAlloc for Memory {
+  unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+    reserve(layout.size());
+    System.alloc(layout)
+  }
+
+  unsafe fn alloc_zeroed(&self, layout: Layout) -> *mut u8 {
+    reserve(layout.size());
+    System.alloc_zeroed(layout)
+  }
+
+  unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+    System.dealloc(ptr, layout);
+    USED.fetch_sub(layout.size(), Ordering::Relaxed);
+  }
+
+  unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+    match new_size > layout.size() {
+      true => reserve(new_size - layout.size()),
+      false => {
+        USED.fetch_sub(layout.size() - new_size, Ordering::Relaxed);
+      }
+    }
+    System.realloc(ptr, layout, new_size)
+  }
+}
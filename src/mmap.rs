@@ -0,0 +1,68 @@
+use std::str::FromStr;
+
+use memmap2::Mmap;
+
+// MapOptions is how input files are memory mapped. Populating a map reads the
+// whole file in up front, which makes parsing it faster but delays the start
+// of output, and files under the threshold are read normally instead, since
+// mapping them costs more than it saves.
+#[derive(Copy, Clone)]
+pub struct MapOptions {
+  pub populate: bool,
+  pub advice: Advice,
+  pub threshold: usize,
+}
+
+impl Default for MapOptions {
+  fn default() -> Self {
+    MapOptions {
+      populate: true,
+      advice: Advice::Normal,
+      threshold: 0,
+    }
+  }
+}
+
+// Advice is the hint about how a memory map will be read that goes to the
+// kernel (with madvise), where there's a way to give one.
+#[derive(Copy, Clone)]
+pub enum Advice {
+  Normal,
+  Sequential,
+  WillNeed,
+}
+
+impl FromStr for Advice {
+  type Err = String;
+
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    match s {
+      "normal" => Ok(Advice::Normal),
+      "sequential" => Ok(Advice::Sequential),
+      "willneed" => Ok(Advice::WillNeed),
+      _ => Err(format!("'{}' is not a valid mmap advice", s)),
+    }
+  }
+}
+
+// Gives the kernel the advice for a map. The advice is only a hint, so it's
+// fine for it to fail.
+#[cfg(unix)]
+pub fn advise(map: &Mmap, advice: Advice) {
+  let advice = match advice {
+    Advice::Normal => return,
+    Advice::Sequential => libc::MADV_SEQUENTIAL,
+    Advice::WillNeed => libc::MADV_WILLNEED,
+  };
+  if map.is_empty() {
+    return;
+  }
+  // Safety: The range is exactly the map, which stays mapped for the call, and
+  // advice doesn't change its contents.
+  unsafe {
+    libc::madvise(map.as_ptr() as *mut libc::c_void, map.len(), advice);
+  }
+}
+
+#[cfg(not(unix))]
+pub fn advise(_: &Mmap, _: Advice) {}
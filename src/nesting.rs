@@ -0,0 +1,112 @@
+// Deeply nested input can overflow the stack of any part of jyt that walks a
+// document recursively, which is most of them. serde_json and yaml-rust refuse
+// input nested more than 128 levels deep with a "recursion limit exceeded"
+// error, but the toml crate has no such limit, so TOML input gets checked for
+// the same limit before anything parses it.
+pub const MAX_DEPTH: usize = 128;
+
+// Returns an error for TOML input nested more than MAX_DEPTH levels deep, in
+// the same form as serde_json's. Nesting comes from arrays and inline tables,
+// and from each segment of a dotted key or table header.
+pub fn check_toml(input: &str) -> Result<(), String> {
+  let check = |depth: usize, line: usize, column: usize| match depth > MAX_DEPTH {
+    true => Err(format!(
+      "recursion limit exceeded at line {} column {}",
+      line, column
+    )),
+    false => Ok(()),
+  };
+  let mut header = 0;
+  // Each level of open brackets, with whether it's an inline table and the
+  // number of dots in the current key at that level. The statement itself is
+  // the first level.
+  let mut levels: Vec<(bool, usize)> = Vec::new();
+  let mut quote: Option<&str> = None;
+  for (i, line) in input.lines().enumerate() {
+    let mut in_key = false;
+    if quote.is_none() && levels.len() <= 1 {
+      let trimmed = line.trim_start();
+      if trimmed.starts_with('[') {
+        header = key_segments(trimmed) + usize::from(trimmed.starts_with("[["));
+        levels.clear();
+        check(header, i + 1, 1)?;
+        continue;
+      }
+      levels = vec![(false, 0)];
+      in_key = true;
+    }
+    let mut chars = line.char_indices();
+    while let Some((pos, c)) = chars.next() {
+      let rest = &line[pos..];
+      if let Some(q) = quote {
+        if c == '\\' && q.starts_with('"') {
+          chars.next();
+        } else if rest.starts_with(q) {
+          chars.by_ref().take(q.len() - 1).for_each(drop);
+          quote = None;
+        }
+        continue;
+      }
+      match c {
+        '#' => break,
+        '"' | '\'' => {
+          let q = match (c, rest.starts_with("\"\"\""), rest.starts_with("'''")) {
+            (_, true, _) => "\"\"\"",
+            (_, _, true) => "'''",
+            ('"', _, _) => "\"",
+            _ => "'",
+          };
+          chars.by_ref().take(q.len() - 1).for_each(drop);
+          quote = Some(q);
+          continue;
+        }
+        '=' => in_key = false,
+        ',' if levels.last().is_some_and(|(table, _)| *table) => {
+          if let Some((_, dots)) = levels.last_mut() {
+            *dots = 0;
+          }
+          in_key = true;
+          continue;
+        }
+        '.' if in_key => {
+          if let Some((_, dots)) = levels.last_mut() {
+            *dots += 1;
+          }
+        }
+        '[' | '{' => {
+          levels.push((c == '{', 0));
+          in_key = c == '{';
+        }
+        ']' | '}' if levels.len() > 1 => {
+          levels.pop();
+          continue;
+        }
+        _ => continue,
+      }
+      let dots: usize = levels.iter().map(|(_, dots)| dots).sum();
+      let column = line[..pos].chars().count() + 1;
+      check(header + levels.len() + dots, i + 1, column)?;
+    }
+    // Only multi-line strings go on past the end of the line.
+    if matches!(quote, Some("\"" | "'")) {
+      quote = None;
+    }
+  }
+  Ok(())
+}
+
+// Returns the number of segments in the dotted key of a table header.
+fn key_segments(header: &str) -> usize {
+  let mut quote = None;
+  let mut segments = 1;
+  for c in header.chars() {
+    match (quote, c) {
+      (None, '"' | '\'') => quote = Some(c),
+      (Some(q), c) if q == c => quote = None,
+      (None, '.') => segments += 1,
+      (None, ']') => break,
+      _ => {}
+    }
+  }
+  segments
+}
@@ -0,0 +1,71 @@
+use std::fs::File;
+use std::io::{self, Write};
+use std::path::Path;
+
+// OutputFile is a file given to -o. With --preallocate, the file's space is
+// reserved before anything is written, so that the filesystem doesn't have to
+// find more as a big output grows, and the file is cut back to the length of
+// what was written once the output is flushed (or dropped after an error).
+pub struct OutputFile {
+  file: File,
+  written: u64,
+  preallocated: bool,
+}
+
+impl OutputFile {
+  pub fn create(path: &Path, preallocate: Option<usize>) -> io::Result<Self> {
+    let file = File::create(path)?;
+    let preallocated = match preallocate {
+      Some(size) if size > 0 => {
+        reserve(&file, size)?;
+        true
+      }
+      _ => false,
+    };
+    Ok(OutputFile {
+      file,
+      written: 0,
+      preallocated,
+    })
+  }
+}
+
+impl Write for OutputFile {
+  fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+    let n = self.file.write(buf)?;
+    self.written += n as u64;
+    Ok(n)
+  }
+
+  fn flush(&mut self) -> io::Result<()> {
+    if self.preallocated {
+      self.file.set_len(self.written)?;
+    }
+    self.file.flush()
+  }
+}
+
+impl Drop for OutputFile {
+  fn drop(&mut self) {
+    if self.preallocated {
+      let _ = self.file.set_len(self.written);
+    }
+  }
+}
+
+// Reserves space for a file. Where there's no way to do that, the file is
+// written as it would be without --preallocate.
+#[cfg(target_os = "linux")]
+fn reserve(file: &File, size: usize) -> io::Result<()> {
+  use std::os::unix::io::AsRawFd;
+  // Safety: The descriptor belongs to the file, which stays open for the call.
+  match unsafe { libc::posix_fallocate(file.as_raw_fd(), 0, size as libc::off_t) } {
+    0 => Ok(()),
+    err => Err(io::Error::from_raw_os_error(err)),
+  }
+}
+
+#[cfg(not(target_os = "linux"))]
+fn reserve(_: &File, _: usize) -> io::Result<()> {
+  Ok(())
+}
@@ -0,0 +1,76 @@
+use std::error::Error;
+
+use serde::Deserialize;
+
+use crate::value::Value;
+
+// Output is where jyt sends each document it reads, as a deserializer that can
+// be transcoded straight to a serializer without building the document first.
+// Outputs that need the whole document deserialize it into a Value instead.
+pub trait Output {
+  fn transcode_from<'de, D, E>(&mut self, de: D) -> Result<(), Box<dyn Error + 'static>>
+  where
+    D: serde::de::Deserializer<'de, Error = E>,
+    E: serde::de::Error + 'static;
+
+  // Called after the last input document has been transcoded, for outputs that
+  // hold documents back until they have seen the whole input.
+  fn finish(&mut self) -> Result<(), Box<dyn Error>> {
+    Ok(())
+  }
+
+  // Reports that the output will not use any more documents, so that we can
+  // stop reading the input early.
+  fn is_done(&self) -> bool {
+    false
+  }
+}
+
+impl<T> Output for &mut T
+where
+  T: Output,
+{
+  fn transcode_from<'de, D, E>(&mut self, de: D) -> Result<(), Box<dyn Error>>
+  where
+    D: serde::de::Deserializer<'de, Error = E>,
+    E: serde::de::Error + 'static,
+  {
+    (**self).transcode_from(de)
+  }
+
+  fn finish(&mut self) -> Result<(), Box<dyn Error>> {
+    (**self).finish()
+  }
+
+  fn is_done(&self) -> bool {
+    (**self).is_done()
+  }
+}
+
+pub struct DiscardOutput;
+
+impl Output for DiscardOutput {
+  fn transcode_from<'de, D, E>(&mut self, de: D) -> Result<(), Box<dyn Error>>
+  where
+    D: serde::de::Deserializer<'de, Error = E>,
+    E: serde::de::Error + 'static,
+  {
+    match serde::de::IgnoredAny::deserialize(de) {
+      Ok(_) => Ok(()),
+      Err(err) => Err(err)?,
+    }
+  }
+}
+
+pub struct CollectOutput(pub Vec<Value>);
+
+impl Output for CollectOutput {
+  fn transcode_from<'de, D, E>(&mut self, de: D) -> Result<(), Box<dyn Error>>
+  where
+    D: serde::de::Deserializer<'de, Error = E>,
+    E: serde::de::Error + 'static,
+  {
+    self.0.push(Value::deserialize(de)?);
+    Ok(())
+  }
+}
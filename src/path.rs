@@ -0,0 +1,906 @@
+use std::error::Error;
+use std::fmt;
+use std::ops::Range;
+use std::str::FromStr;
+
+use serde::de::{self, DeserializeSeed, Deserializer, IgnoredAny, MapAccess, SeqAccess, Visitor};
+use serde::Deserialize;
+
+use crate::value::{Map, Value};
+
+// Path is a location (or set of locations) within a document, as given to
+// path-based options on the command line. Two syntaxes are accepted:
+//
+//   .spec.containers[0].image   ."quoted key"   .data.*   .items[]   .items[10:20]
+//   /spec/containers/0/image    /data/*   /spec/**/image
+//
+// The first is a jq-like syntax, and the second is a JSON Pointer (RFC 6901)
+// extended with "*" and "**" wildcards. Both parse into the same list of
+// segments.
+#[derive(Clone, Debug)]
+pub struct Path {
+  source: String,
+  segments: Vec<Segment>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Segment {
+  // A map key or, if it looks like one, an array index. Matching arrays makes
+  // JSON Pointers like "/items/0" work without a separate syntax.
+  Key(String),
+  // An array index, counted from the end of the array when negative.
+  Index(i64),
+  // Every value in an array or map.
+  Wildcard,
+  // The array elements from a start index up to (but not including) an end
+  // index, where missing bounds extend to the ends of the array and negative
+  // bounds count from the end, as in Python.
+  Slice(Option<i64>, Option<i64>),
+  // A value and every value nested within it, at any depth.
+  Recursive,
+}
+
+impl Path {
+  // Returns every value in the document matched by the path, in document order.
+  pub fn select<'v>(&self, v: &'v Value) -> Vec<&'v Value> {
+    let mut matches = Vec::new();
+    select(&self.segments, v, &mut matches);
+    matches
+  }
+
+  // Reports whether the path can only ever match a single value, with no
+  // wildcards, slices, or indexes counted from the end of an array.
+  pub fn is_single(&self) -> bool {
+    self.segments.iter().all(|segment| match segment {
+      Segment::Key(_) => true,
+      Segment::Index(idx) => *idx >= 0,
+      _ => false,
+    })
+  }
+
+  // Returns the value at a single path from a document as it's read, skipping
+  // everything before it without building it. Once it has the value, it stops
+  // the deserializer with an error rather than reading the rest of the
+  // document, so the deserializer can't be read from again.
+  pub fn extract<'de, D>(&self, de: D) -> Result<Option<Value>, D::Error>
+  where
+    D: Deserializer<'de>,
+  {
+    let mut found = None;
+    let extract = Extract {
+      segments: &self.segments,
+      found: &mut found,
+    };
+    match extract.deserialize(de) {
+      Err(_) if found.is_some() => Ok(found),
+      Err(err) => Err(err),
+      Ok(()) => Ok(None),
+    }
+  }
+
+  // Calls f with each value in the document matched by the path, stopping at
+  // the first error.
+  pub fn visit_mut<F>(&self, v: &mut Value, f: &mut F) -> Result<(), Box<dyn Error>>
+  where
+    F: FnMut(&mut Value) -> Result<(), Box<dyn Error>>,
+  {
+    visit_mut(&self.segments, v, f)
+  }
+
+  // Calls f with the concrete path to each value matched by the path, like
+  // ".data.password" for ".data.*", and with the value itself.
+  pub fn visit_paths_mut<F>(&self, v: &mut Value, f: &mut F) -> Result<(), Box<dyn Error>>
+  where
+    F: FnMut(&str, &mut Value) -> Result<(), Box<dyn Error>>,
+  {
+    visit_paths_mut(&self.segments, v, &mut String::new(), &mut |path, v| {
+      f(if path.is_empty() { "." } else { path }, v)
+    })
+  }
+
+  // Sets the value at the path, creating any missing objects along the way. A
+  // wildcard sets the value within every existing match.
+  pub fn set(&self, doc: &mut Value, value: &Value) -> Result<(), String> {
+    set(&self.segments, doc, value).map_err(|err| format!("cannot set {}: {}", self, err))
+  }
+
+  // Moves every value matched by this path to the corresponding location given
+  // by another path. Each wildcard in the destination stands for whatever the
+  // wildcard in the same position in the source matched, so that renames like
+  // '.items[].old' to '.items[].new' apply within every array element.
+  pub fn rename(&self, to: &Path, doc: &mut Value) -> Result<(), String> {
+    let mut matches = Vec::new();
+    concrete(&self.segments, doc, &mut vec![], &mut vec![], &mut matches);
+
+    let mut moves = Vec::with_capacity(matches.len());
+    for (from, captures) in matches {
+      let mut captures = captures.into_iter();
+      let mut dest = Vec::with_capacity(to.segments.len());
+      for segment in &to.segments {
+        match segment {
+          Segment::Wildcard => match captures.next() {
+            Some(capture) => dest.push(capture),
+            None => {
+              return Err(format!(
+                "cannot rename {} to {}: too many wildcards",
+                self, to
+              ))
+            }
+          },
+          segment => dest.push(segment.clone()),
+        }
+      }
+      moves.push((self.concrete(from), to.concrete(dest)));
+    }
+
+    // Renaming a key within the same object should keep its position, which a
+    // general delete-and-set would lose.
+    let mut moves: Vec<(Path, Path)> = moves
+      .into_iter()
+      .filter(|(from, dest)| !rename_in_place(doc, &from.segments, &dest.segments))
+      .collect();
+
+    // Removing values in reverse order keeps the array indices of the values
+    // we haven't removed yet stable.
+    let mut values = Vec::with_capacity(moves.len());
+    for (from, _) in moves.iter().rev() {
+      values.push(from.select(doc).first().map(|v| (*v).clone()));
+      from.delete(doc)?;
+    }
+    for ((_, dest), value) in moves.drain(..).zip(values.into_iter().rev()) {
+      if let Some(value) = value {
+        dest.set(doc, &value)?;
+      }
+    }
+    Ok(())
+  }
+
+  fn concrete(&self, segments: Vec<Segment>) -> Path {
+    Path {
+      source: self.source.clone(),
+      segments,
+    }
+  }
+
+  // Removes every value matched by the path from its containing array or
+  // object. Deleting something that isn't there is not an error.
+  pub fn delete(&self, doc: &mut Value) -> Result<(), String> {
+    let (last, parents) = match self.segments.split_last() {
+      Some(split) => split,
+      None => {
+        return Err(format!(
+          "cannot delete {}: cannot delete the whole document",
+          self
+        ))
+      }
+    };
+    let mut each_parent = |parent: &mut Value| {
+      match (last, parent) {
+        (Segment::Wildcard | Segment::Recursive, Value::Seq(items)) => items.clear(),
+        (Segment::Wildcard | Segment::Recursive, Value::Map(map)) => *map = Map::new(),
+        (Segment::Key(key), Value::Map(map)) => drop(map.remove(key)),
+        (Segment::Key(key), Value::Seq(items)) => {
+          if let Ok(idx) = key.parse::<usize>() {
+            if idx < items.len() {
+              items.remove(idx);
+            }
+          }
+        }
+        (Segment::Index(idx), Value::Seq(items)) => {
+          if let Some(idx) = resolve_index(*idx, items.len()) {
+            items.remove(idx);
+          }
+        }
+        (Segment::Slice(start, end), Value::Seq(items)) => {
+          items.drain(resolve_slice(*start, *end, items.len()));
+        }
+        _ => {}
+      }
+      Ok(())
+    };
+    visit_mut(parents, doc, &mut each_parent).map_err(|err| err.to_string())
+  }
+}
+
+// Returns a copy of a document with only the values matched by any of the
+// paths, along with the arrays and objects containing them. Array elements that
+// are kept move down to fill the gaps left by any that aren't.
+pub fn keep_only(paths: &[Path], doc: &Value) -> Value {
+  let mut tree = Keep::default();
+  for path in paths {
+    let mut matches = Vec::new();
+    concrete(&path.segments, doc, &mut vec![], &mut vec![], &mut matches);
+    for (segments, _) in matches {
+      let mut node = &mut tree;
+      for segment in segments {
+        let idx = match node.children.iter().position(|(s, _)| *s == segment) {
+          Some(idx) => idx,
+          None => {
+            node.children.push((segment, Keep::default()));
+            node.children.len() - 1
+          }
+        };
+        node = &mut node.children[idx].1;
+      }
+      node.all = true;
+    }
+  }
+  tree.prune(doc)
+}
+
+// Keep is a tree of the concrete paths to keep in a document.
+#[derive(Default)]
+struct Keep {
+  all: bool,
+  children: Vec<(Segment, Keep)>,
+}
+
+impl Keep {
+  fn child(&self, segment: &Segment) -> Option<&Keep> {
+    self
+      .children
+      .iter()
+      .find(|(s, _)| s == segment)
+      .map(|(_, keep)| keep)
+  }
+
+  fn prune(&self, v: &Value) -> Value {
+    if self.all {
+      return v.clone();
+    }
+    match v {
+      Value::Seq(items) => Value::Seq(
+        items
+          .iter()
+          .enumerate()
+          .filter_map(|(i, v)| Some(self.child(&Segment::Index(i as i64))?.prune(v)))
+          .collect(),
+      ),
+      Value::Map(map) => {
+        let mut pruned = Map::new();
+        for (k, v) in map.iter() {
+          let keep = k.key_str().and_then(|k| self.child(&Segment::Key(k)));
+          if let Some(keep) = keep {
+            pruned.push(k.clone(), keep.prune(v));
+          }
+        }
+        Value::Map(pruned)
+      }
+      _ => Value::Null,
+    }
+  }
+}
+
+fn set(segments: &[Segment], v: &mut Value, value: &Value) -> Result<(), String> {
+  let (first, rest) = match segments.split_first() {
+    None => {
+      *v = value.clone();
+      return Ok(());
+    }
+    Some(split) => split,
+  };
+  if let (Segment::Key(_), Value::Null) = (first, &v) {
+    *v = Value::Map(Map::new());
+  }
+  // Setting a key within every nested value would create new values at every
+  // level, which is never what anyone wants.
+  if let Segment::Recursive = first {
+    return Err("'**' cannot be used to set values".to_owned());
+  }
+  match (first, v) {
+    (Segment::Key(key), Value::Map(map)) => set(rest, map.entry(key), value),
+    (Segment::Wildcard | Segment::Slice(..), v) => {
+      for child in children_mut(first, v) {
+        set(rest, child, value)?;
+      }
+      Ok(())
+    }
+    (segment, v) => match children_mut(segment, v).pop() {
+      Some(child) => set(rest, child, value),
+      None => match v {
+        Value::Seq(_) => Err("array index out of range".to_owned()),
+        v => Err(format!("cannot index into {}", v.type_name())),
+      },
+    },
+  }
+}
+
+// Renames a key in place if both concrete paths point into the same object and
+// the destination key isn't already taken, and reports whether it did.
+fn rename_in_place(doc: &mut Value, from: &[Segment], to: &[Segment]) -> bool {
+  let (from_parent, to_parent) = (
+    &from[..from.len().saturating_sub(1)],
+    &to[..to.len().saturating_sub(1)],
+  );
+  let (from_key, to_key) = match (from.last(), to.last()) {
+    (Some(Segment::Key(from_key)), Some(Segment::Key(to_key))) => (from_key, to_key),
+    _ => return false,
+  };
+  if from_parent != to_parent {
+    return false;
+  }
+  let mut renamed = false;
+  let _ = visit_mut(from_parent, doc, &mut |parent| {
+    if let Value::Map(map) = parent {
+      renamed = map.get(to_key).is_none() && map.rename(from_key, to_key);
+    }
+    Ok(())
+  });
+  renamed
+}
+
+// Finds the concrete location of every value matched by a path, along with the
+// segments that the path's wildcards matched along the way.
+fn concrete(
+  segments: &[Segment],
+  v: &Value,
+  prefix: &mut Vec<Segment>,
+  captures: &mut Vec<Segment>,
+  out: &mut Vec<(Vec<Segment>, Vec<Segment>)>,
+) {
+  let (first, rest) = match segments.split_first() {
+    None => return out.push((prefix.clone(), captures.clone())),
+    Some(split) => split,
+  };
+  if let Segment::Recursive = first {
+    concrete(rest, v, prefix, captures, out);
+  }
+  let children: Vec<(Segment, &Value)> = match (first, v) {
+    (Segment::Wildcard | Segment::Recursive, Value::Seq(items)) => items
+      .iter()
+      .enumerate()
+      .map(|(i, v)| (Segment::Index(i as i64), v))
+      .collect(),
+    (Segment::Wildcard | Segment::Recursive, Value::Map(map)) => map
+      .iter()
+      .filter_map(|(k, v)| Some((Segment::Key(k.key_str()?), v)))
+      .collect(),
+    (Segment::Key(key), Value::Map(map)) => match map.get(key) {
+      Some(v) => vec![(first.clone(), v)],
+      None => vec![],
+    },
+    (Segment::Key(key), Value::Seq(items)) => match key.parse::<usize>() {
+      Ok(idx) if idx < items.len() => vec![(Segment::Index(idx as i64), &items[idx])],
+      _ => vec![],
+    },
+    (Segment::Index(idx), Value::Seq(items)) => match resolve_index(*idx, items.len()) {
+      Some(idx) => vec![(Segment::Index(idx as i64), &items[idx])],
+      None => vec![],
+    },
+    (Segment::Slice(start, end), Value::Seq(items)) => resolve_slice(*start, *end, items.len())
+      .map(|i| (Segment::Index(i as i64), &items[i]))
+      .collect(),
+    _ => vec![],
+  };
+  for (segment, child) in children {
+    let wildcard = matches!(first, Segment::Wildcard | Segment::Slice(..));
+    if wildcard {
+      captures.push(segment.clone());
+    }
+    prefix.push(segment);
+    match first {
+      Segment::Recursive => concrete(segments, child, prefix, captures, out),
+      _ => concrete(rest, child, prefix, captures, out),
+    }
+    prefix.pop();
+    if wildcard {
+      captures.pop();
+    }
+  }
+}
+
+fn select<'v>(segments: &[Segment], v: &'v Value, matches: &mut Vec<&'v Value>) {
+  let (first, rest) = match segments.split_first() {
+    None => return matches.push(v),
+    Some(split) => split,
+  };
+  if let Segment::Recursive = first {
+    select(rest, v, matches);
+    for child in children(&Segment::Wildcard, v) {
+      select(segments, child, matches);
+    }
+    return;
+  }
+  for child in children(first, v) {
+    select(rest, child, matches);
+  }
+}
+
+fn children<'v>(segment: &Segment, v: &'v Value) -> Vec<&'v Value> {
+  match (segment, v) {
+    (Segment::Wildcard, Value::Seq(items)) => items.iter().collect(),
+    (Segment::Wildcard, Value::Map(map)) => map.iter().map(|(_, v)| v).collect(),
+    (Segment::Key(key), Value::Map(map)) => map.get(key).into_iter().collect(),
+    (Segment::Key(key), Value::Seq(items)) => match key.parse::<usize>() {
+      Ok(idx) => items.get(idx).into_iter().collect(),
+      Err(_) => vec![],
+    },
+    (Segment::Index(idx), Value::Seq(items)) => match resolve_index(*idx, items.len()) {
+      Some(idx) => vec![&items[idx]],
+      None => vec![],
+    },
+    (Segment::Slice(start, end), Value::Seq(items)) => {
+      let range = resolve_slice(*start, *end, items.len());
+      items[range].iter().collect()
+    }
+    _ => vec![],
+  }
+}
+
+// Extract looks for the value at a single path while a document is read, for
+// Path::extract.
+struct Extract<'a> {
+  segments: &'a [Segment],
+  found: &'a mut Option<Value>,
+}
+
+impl<'de, 'a> DeserializeSeed<'de> for Extract<'a> {
+  type Value = ();
+
+  fn deserialize<D>(self, de: D) -> Result<(), D::Error>
+  where
+    D: Deserializer<'de>,
+  {
+    if self.segments.is_empty() {
+      *self.found = Some(Value::deserialize(de)?);
+      return Err(de::Error::custom("stopped after the value at the path"));
+    }
+    de.deserialize_any(self)
+  }
+}
+
+impl<'de, 'a> Visitor<'de> for Extract<'a> {
+  type Value = ();
+
+  fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    f.write_str("any value")
+  }
+
+  fn visit_bool<E>(self, _: bool) -> Result<(), E>
+  where
+    E: de::Error,
+  {
+    Ok(())
+  }
+
+  fn visit_i64<E>(self, _: i64) -> Result<(), E>
+  where
+    E: de::Error,
+  {
+    Ok(())
+  }
+
+  fn visit_u64<E>(self, _: u64) -> Result<(), E>
+  where
+    E: de::Error,
+  {
+    Ok(())
+  }
+
+  fn visit_i128<E>(self, _: i128) -> Result<(), E>
+  where
+    E: de::Error,
+  {
+    Ok(())
+  }
+
+  fn visit_u128<E>(self, _: u128) -> Result<(), E>
+  where
+    E: de::Error,
+  {
+    Ok(())
+  }
+
+  fn visit_f64<E>(self, _: f64) -> Result<(), E>
+  where
+    E: de::Error,
+  {
+    Ok(())
+  }
+
+  fn visit_str<E>(self, _: &str) -> Result<(), E>
+  where
+    E: de::Error,
+  {
+    Ok(())
+  }
+
+  fn visit_bytes<E>(self, _: &[u8]) -> Result<(), E>
+  where
+    E: de::Error,
+  {
+    Ok(())
+  }
+
+  fn visit_unit<E>(self) -> Result<(), E>
+  where
+    E: de::Error,
+  {
+    Ok(())
+  }
+
+  fn visit_none<E>(self) -> Result<(), E>
+  where
+    E: de::Error,
+  {
+    Ok(())
+  }
+
+  fn visit_some<D>(self, de: D) -> Result<(), D::Error>
+  where
+    D: Deserializer<'de>,
+  {
+    self.deserialize(de)
+  }
+
+  fn visit_seq<A>(self, mut seq: A) -> Result<(), A::Error>
+  where
+    A: SeqAccess<'de>,
+  {
+    let idx = match &self.segments[0] {
+      Segment::Key(key) => key.parse::<usize>().ok(),
+      Segment::Index(idx) if *idx >= 0 => Some(*idx as usize),
+      _ => None,
+    };
+    let idx = match idx {
+      Some(idx) => idx,
+      None => return skip_seq(seq),
+    };
+    for _ in 0..idx {
+      if seq.next_element::<IgnoredAny>()?.is_none() {
+        return Ok(());
+      }
+    }
+    let rest = Extract {
+      segments: &self.segments[1..],
+      found: self.found,
+    };
+    if seq.next_element_seed(rest)?.is_some() {
+      skip_seq(seq)?;
+    }
+    Ok(())
+  }
+
+  fn visit_map<A>(self, mut map: A) -> Result<(), A::Error>
+  where
+    A: MapAccess<'de>,
+  {
+    let key = match &self.segments[0] {
+      Segment::Key(key) => key,
+      _ => {
+        while map.next_entry::<IgnoredAny, IgnoredAny>()?.is_some() {}
+        return Ok(());
+      }
+    };
+    while let Some(k) = map.next_key::<Value>()? {
+      if k.key_str().as_deref() != Some(key.as_str()) {
+        map.next_value::<IgnoredAny>()?;
+        continue;
+      }
+      map.next_value_seed(Extract {
+        segments: &self.segments[1..],
+        found: self.found,
+      })?;
+      while map.next_entry::<IgnoredAny, IgnoredAny>()?.is_some() {}
+      return Ok(());
+    }
+    Ok(())
+  }
+}
+
+fn skip_seq<'de, A>(mut seq: A) -> Result<(), A::Error>
+where
+  A: SeqAccess<'de>,
+{
+  while seq.next_element::<IgnoredAny>()?.is_some() {}
+  Ok(())
+}
+
+fn visit_mut<F>(segments: &[Segment], v: &mut Value, f: &mut F) -> Result<(), Box<dyn Error>>
+where
+  F: FnMut(&mut Value) -> Result<(), Box<dyn Error>>,
+{
+  let (first, rest) = match segments.split_first() {
+    None => return f(v),
+    Some(split) => split,
+  };
+  if let Segment::Recursive = first {
+    visit_mut(rest, v, f)?;
+    for child in children_mut(&Segment::Wildcard, v) {
+      visit_mut(segments, child, f)?;
+    }
+    return Ok(());
+  }
+  for child in children_mut(first, v) {
+    visit_mut(rest, child, f)?;
+  }
+  Ok(())
+}
+
+// Does what visit_mut does, while building the concrete path to each value.
+fn visit_paths_mut<F>(
+  segments: &[Segment],
+  v: &mut Value,
+  path: &mut String,
+  f: &mut F,
+) -> Result<(), Box<dyn Error>>
+where
+  F: FnMut(&str, &mut Value) -> Result<(), Box<dyn Error>>,
+{
+  let (first, rest) = match segments.split_first() {
+    None => return f(path, v),
+    Some(split) => split,
+  };
+  let (segment, rest) = match first {
+    Segment::Recursive => {
+      visit_paths_mut(rest, v, path, f)?;
+      (&Segment::Wildcard, segments)
+    }
+    segment => (segment, rest),
+  };
+  for (step, child) in steps_mut(segment, v) {
+    let len = path.len();
+    step.push_to(path);
+    visit_paths_mut(rest, child, path, f)?;
+    path.truncate(len);
+  }
+  Ok(())
+}
+
+// Step is how a value was reached from its parent, for building the concrete
+// paths of the values that a path matches.
+enum Step<'v> {
+  Key(&'v Value),
+  Name(&'v str),
+  Index(usize),
+}
+
+impl Step<'_> {
+  fn push_to(&self, path: &mut String) {
+    match self {
+      Step::Key(key) => push_key(path, key),
+      Step::Name(name) => push_key(path, &Value::from(*name)),
+      Step::Index(idx) => push_index(path, *idx),
+    }
+  }
+}
+
+fn children_mut<'v>(segment: &'v Segment, v: &'v mut Value) -> Vec<&'v mut Value> {
+  steps_mut(segment, v)
+    .into_iter()
+    .map(|(_, child)| child)
+    .collect()
+}
+
+fn steps_mut<'v>(segment: &'v Segment, v: &'v mut Value) -> Vec<(Step<'v>, &'v mut Value)> {
+  match (segment, v) {
+    (Segment::Wildcard, Value::Seq(items)) => items
+      .iter_mut()
+      .enumerate()
+      .map(|(i, item)| (Step::Index(i), item))
+      .collect(),
+    (Segment::Wildcard, Value::Map(map)) => {
+      map.iter_mut().map(|(k, v)| (Step::Key(k), v)).collect()
+    }
+    (Segment::Key(key), Value::Map(map)) => map
+      .get_mut(key)
+      .map(|v| (Step::Name(key), v))
+      .into_iter()
+      .collect(),
+    (Segment::Key(key), Value::Seq(items)) => match key.parse::<usize>() {
+      Ok(idx) => items
+        .get_mut(idx)
+        .map(|item| (Step::Index(idx), item))
+        .into_iter()
+        .collect(),
+      Err(_) => vec![],
+    },
+    (Segment::Index(idx), Value::Seq(items)) => match resolve_index(*idx, items.len()) {
+      Some(idx) => vec![(Step::Index(idx), &mut items[idx])],
+      None => vec![],
+    },
+    (Segment::Slice(start, end), Value::Seq(items)) => {
+      let range = resolve_slice(*start, *end, items.len());
+      let offset = range.start;
+      items[range]
+        .iter_mut()
+        .enumerate()
+        .map(|(i, item)| (Step::Index(offset + i), item))
+        .collect()
+    }
+    _ => vec![],
+  }
+}
+
+fn resolve_index(idx: i64, len: usize) -> Option<usize> {
+  let resolved = if idx < 0 { len as i64 + idx } else { idx };
+  if resolved >= 0 && (resolved as usize) < len {
+    Some(resolved as usize)
+  } else {
+    None
+  }
+}
+
+fn resolve_slice(start: Option<i64>, end: Option<i64>, len: usize) -> Range<usize> {
+  let clamp = |idx: i64| {
+    let resolved = if idx < 0 { len as i64 + idx } else { idx };
+    resolved.clamp(0, len as i64) as usize
+  };
+  let start = start.map_or(0, clamp);
+  let end = end.map_or(len, clamp);
+  start..end.max(start)
+}
+
+// Appends a map key to a concrete path string in the jq-like syntax, quoting
+// it if it wouldn't otherwise parse back as the same key.
+pub fn push_key(path: &mut String, key: &Value) {
+  let key = match key.key_str() {
+    Some(key) => key,
+    None => serde_json::to_string(key).unwrap_or_default(),
+  };
+  let plain = !key.is_empty()
+    && key != "*"
+    && key
+      .chars()
+      .all(|c| c.is_alphanumeric() || c == '_' || c == '-');
+  if plain {
+    path.push('.');
+    path.push_str(&key);
+  } else {
+    path.push('.');
+    path.push_str(&serde_json::to_string(&key).unwrap_or_default());
+  }
+}
+
+// Appends an array index to a concrete path string in the jq-like syntax.
+pub fn push_index(path: &mut String, idx: usize) {
+  path.push_str(&format!("[{}]", idx));
+}
+
+impl fmt::Display for Path {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    f.write_str(&self.source)
+  }
+}
+
+impl FromStr for Path {
+  type Err = String;
+
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    let segments = match s.chars().next() {
+      Some('.') => parse_dotted(s)?,
+      Some('/') => parse_pointer(s),
+      _ => {
+        return Err(format!(
+          "'{}' is not a valid path (must start with '.' or '/')",
+          s
+        ))
+      }
+    };
+    Ok(Path {
+      source: s.to_owned(),
+      segments,
+    })
+  }
+}
+
+// PathList is a comma-separated list of paths, as given to options that take
+// several paths at once.
+#[derive(Clone)]
+pub struct PathList(pub Vec<Path>);
+
+impl FromStr for PathList {
+  type Err = String;
+
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    // A quoted key may contain its own "," character.
+    let mut paths = Vec::new();
+    let (mut start, mut quoted, mut escaped) = (0, false, false);
+    for (i, c) in s.char_indices() {
+      match c {
+        _ if escaped => escaped = false,
+        '\\' if quoted => escaped = true,
+        '"' => quoted = !quoted,
+        ',' if !quoted => {
+          paths.push(s[start..i].trim().parse()?);
+          start = i + 1;
+        }
+        _ => {}
+      }
+    }
+    paths.push(s[start..].trim().parse()?);
+    Ok(PathList(paths))
+  }
+}
+
+fn parse_pointer(s: &str) -> Vec<Segment> {
+  s[1..]
+    .split('/')
+    .map(|token| match token {
+      "*" => Segment::Wildcard,
+      "**" => Segment::Recursive,
+      _ => Segment::Key(token.replace("~1", "/").replace("~0", "~")),
+    })
+    .collect()
+}
+
+fn parse_dotted(s: &str) -> Result<Vec<Segment>, String> {
+  let invalid = |why: &str| format!("'{}' is not a valid path ({})", s, why);
+
+  let mut segments = Vec::new();
+  let mut rest = s;
+  while let Some(c) = rest.chars().next() {
+    match c {
+      '.' => {
+        rest = &rest[1..];
+        if rest.starts_with('"') {
+          let (key, after) = parse_quoted(rest).ok_or_else(|| invalid("unterminated quote"))?;
+          segments.push(Segment::Key(key));
+          rest = after;
+          continue;
+        }
+        let end = rest.find(['.', '[']).unwrap_or(rest.len());
+        match &rest[..end] {
+          "" if rest.is_empty() && segments.is_empty() => {}
+          "" if rest.starts_with('[') => {}
+          "" => return Err(invalid("empty key")),
+          "*" => segments.push(Segment::Wildcard),
+          "**" => segments.push(Segment::Recursive),
+          key => segments.push(Segment::Key(key.to_owned())),
+        }
+        rest = &rest[end..];
+      }
+      '[' => {
+        if rest[1..].starts_with('"') {
+          let (key, after) =
+            parse_quoted(&rest[1..]).ok_or_else(|| invalid("unterminated quote"))?;
+          rest = after
+            .strip_prefix(']')
+            .ok_or_else(|| invalid("expected ']'"))?;
+          segments.push(Segment::Key(key));
+          continue;
+        }
+        let end = rest.find(']').ok_or_else(|| invalid("expected ']'"))?;
+        match rest[1..end].trim() {
+          "" | "*" => segments.push(Segment::Wildcard),
+          slice if slice.matches(':').count() > 1 => {
+            return Err(invalid("slices can't have a step"))
+          }
+          slice if slice.contains(':') => {
+            let (start, end) = slice.split_once(':').unwrap_or_default();
+            let bound = |b: &str| match b.trim() {
+              "" => Ok(None),
+              b => b.parse().map(Some),
+            };
+            match (bound(start), bound(end)) {
+              (Ok(start), Ok(end)) => segments.push(Segment::Slice(start, end)),
+              _ => return Err(invalid("slice bounds must be integers")),
+            }
+          }
+          idx => match idx.parse() {
+            Ok(idx) => segments.push(Segment::Index(idx)),
+            Err(_) => return Err(invalid("array index must be an integer")),
+          },
+        }
+        rest = &rest[end + 1..];
+      }
+      _ => return Err(invalid("expected '.' or '['")),
+    }
+  }
+  Ok(segments)
+}
+
+// Parses a double-quoted key at the start of s, returning the unescaped key and
+// the remainder of s following the closing quote.
+fn parse_quoted(s: &str) -> Option<(String, &str)> {
+  let mut key = String::new();
+  let mut chars = s.char_indices().skip(1);
+  while let Some((i, c)) = chars.next() {
+    match c {
+      '"' => return Some((key, &s[i + 1..])),
+      '\\' => key.push(chars.next()?.1),
+      c => key.push(c),
+    }
+  }
+  None
+}
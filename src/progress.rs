@@ -0,0 +1,366 @@
+use std::fmt;
+use std::io::{self, IsTerminal, Write};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use serde::de::{DeserializeSeed, Deserializer, EnumAccess, MapAccess, SeqAccess, Visitor};
+
+use crate::console::{self, Stream};
+
+// Progress is how far jyt has read through its input, for --progress. Parsers
+// don't say where they are, so the position comes from the strings that they
+// borrow straight from the input, which is the only way to get it from inside a
+// single large document. Input that no strings are borrowed from, like YAML,
+// only reports its documents until it's done. Like timing, it's global state,
+// so that each part can add to it without every layer of jyt passing it around.
+
+static ENABLED: AtomicBool = AtomicBool::new(false);
+static START: AtomicUsize = AtomicUsize::new(0);
+static LEN: AtomicUsize = AtomicUsize::new(0);
+static POSITION: AtomicUsize = AtomicUsize::new(0);
+static DOCUMENTS: AtomicUsize = AtomicUsize::new(0);
+
+// How often the report is redrawn on a terminal, or written out as a new line
+// anywhere else.
+const REDRAW: Duration = Duration::from_millis(200);
+const RELINE: Duration = Duration::from_secs(5);
+
+pub fn is_enabled() -> bool {
+  ENABLED.load(Ordering::Relaxed)
+}
+
+pub fn document() {
+  DOCUMENTS.fetch_add(1, Ordering::Relaxed);
+}
+
+// Reporter writes the progress to stderr from a thread of its own until it's
+// finished, or until it's dropped after an error, which clears the report
+// instead of leaving it half done.
+pub struct Reporter {
+  stop: Option<mpsc::Sender<bool>>,
+  thread: Option<thread::JoinHandle<()>>,
+}
+
+pub fn start(input: &[u8]) -> Reporter {
+  START.store(input.as_ptr() as usize, Ordering::Relaxed);
+  LEN.store(input.len(), Ordering::Relaxed);
+  POSITION.store(0, Ordering::Relaxed);
+  DOCUMENTS.store(0, Ordering::Relaxed);
+  ENABLED.store(true, Ordering::Relaxed);
+
+  let (stop, stopped) = mpsc::channel();
+  let thread = thread::spawn(move || {
+    let start = Instant::now();
+    // A terminal that can't move the cursor gets a line at a time, as a file
+    // would.
+    let terminal = io::stderr().is_terminal() && console::escapes(Stream::Stderr);
+    let interval = if terminal { REDRAW } else { RELINE };
+    let finished = loop {
+      match stopped.recv_timeout(interval) {
+        Err(RecvTimeoutError::Timeout) => draw(start.elapsed(), terminal, false),
+        Ok(finished) => break finished,
+        Err(RecvTimeoutError::Disconnected) => break false,
+      }
+    };
+    match (finished, terminal) {
+      (true, _) => {
+        POSITION.store(LEN.load(Ordering::Relaxed), Ordering::Relaxed);
+        draw(start.elapsed(), terminal, true);
+      }
+      (false, true) => eprint!("\r\x1b[K"),
+      (false, false) => {}
+    }
+  });
+  Reporter {
+    stop: Some(stop),
+    thread: Some(thread),
+  }
+}
+
+impl Reporter {
+  pub fn finish(mut self) {
+    self.stop(true);
+  }
+
+  fn stop(&mut self, finished: bool) {
+    ENABLED.store(false, Ordering::Relaxed);
+    if let Some(stop) = self.stop.take() {
+      let _ = stop.send(finished);
+    }
+    if let Some(thread) = self.thread.take() {
+      let _ = thread.join();
+    }
+  }
+}
+
+impl Drop for Reporter {
+  fn drop(&mut self) {
+    self.stop(false);
+  }
+}
+
+fn draw(elapsed: Duration, terminal: bool, last: bool) {
+  let len = LEN.load(Ordering::Relaxed);
+  let position = POSITION.load(Ordering::Relaxed).min(len);
+  let documents = DOCUMENTS.load(Ordering::Relaxed);
+  let mut report = format!(
+    "jyt progress: {} of {}, {} document{}",
+    Megabytes(position),
+    Megabytes(len),
+    documents,
+    if documents == 1 { "" } else { "s" }
+  );
+  if position > 0 && !last {
+    let left = elapsed.mul_f64((len - position) as f64 / position as f64);
+    report.push_str(&format!(
+      ", {}%, ETA {}",
+      position * 100 / len.max(1),
+      Seconds(left)
+    ));
+  }
+  if last {
+    report.push_str(&format!(" in {}", Seconds(elapsed)));
+  }
+  let mut stderr = io::stderr().lock();
+  let _ = match (terminal, last) {
+    (true, false) => write!(stderr, "\r\x1b[K{}", report),
+    _ if terminal => writeln!(stderr, "\r\x1b[K{}", report),
+    _ => writeln!(stderr, "{}", report),
+  };
+}
+
+struct Megabytes(usize);
+
+impl fmt::Display for Megabytes {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    write!(f, "{:.1} MB", self.0 as f64 / 1e6)
+  }
+}
+
+struct Seconds(Duration);
+
+impl fmt::Display for Seconds {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    match self.0.as_secs() {
+      s if s >= 60 => write!(f, "{}m{:02}s", s / 60, s % 60),
+      s => write!(f, "{}s", s),
+    }
+  }
+}
+
+// Notes how far into the input a string borrowed from it ends.
+fn seen(s: &[u8]) {
+  let start = START.load(Ordering::Relaxed);
+  let end = (s.as_ptr() as usize)
+    .wrapping_sub(start)
+    .wrapping_add(s.len());
+  if end <= LEN.load(Ordering::Relaxed) {
+    POSITION.fetch_max(end, Ordering::Relaxed);
+  }
+}
+
+// Tracked wraps a deserializer to note the position of each string that it
+// borrows from the input, passing everything else straight through.
+pub struct Tracked<D>(pub D);
+
+impl<'de, D> Deserializer<'de> for Tracked<D>
+where
+  D: Deserializer<'de>,
+{
+  type Error = D::Error;
+
+  fn deserialize_any<V>(self, visitor: V) -> Result<V::Value, D::Error>
+  where
+    V: Visitor<'de>,
+  {
+    self.0.deserialize_any(TrackedVisitor(visitor))
+  }
+
+  fn is_human_readable(&self) -> bool {
+    self.0.is_human_readable()
+  }
+
+  serde::forward_to_deserialize_any! {
+    bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+    bytes byte_buf option unit unit_struct newtype_struct seq tuple
+    tuple_struct map struct enum identifier ignored_any
+  }
+}
+
+struct TrackedVisitor<V>(V);
+
+// Forwards visits of values that can't hold strings from the input.
+macro_rules! forward_visits {
+  ($($method:ident($ty:ty),)*) => {
+    $(
+      fn $method<E>(self, v: $ty) -> Result<V::Value, E>
+      where
+        E: serde::de::Error,
+      {
+        self.0.$method(v)
+      }
+    )*
+  };
+}
+
+impl<'de, V> Visitor<'de> for TrackedVisitor<V>
+where
+  V: Visitor<'de>,
+{
+  type Value = V::Value;
+
+  fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    self.0.expecting(f)
+  }
+
+  forward_visits! {
+    visit_bool(bool),
+    visit_i8(i8),
+    visit_i16(i16),
+    visit_i32(i32),
+    visit_i64(i64),
+    visit_i128(i128),
+    visit_u8(u8),
+    visit_u16(u16),
+    visit_u32(u32),
+    visit_u64(u64),
+    visit_u128(u128),
+    visit_f32(f32),
+    visit_f64(f64),
+    visit_char(char),
+    visit_str(&str),
+    visit_string(String),
+    visit_bytes(&[u8]),
+    visit_byte_buf(Vec<u8>),
+  }
+
+  fn visit_borrowed_str<E>(self, v: &'de str) -> Result<V::Value, E>
+  where
+    E: serde::de::Error,
+  {
+    seen(v.as_bytes());
+    self.0.visit_borrowed_str(v)
+  }
+
+  fn visit_borrowed_bytes<E>(self, v: &'de [u8]) -> Result<V::Value, E>
+  where
+    E: serde::de::Error,
+  {
+    seen(v);
+    self.0.visit_borrowed_bytes(v)
+  }
+
+  fn visit_none<E>(self) -> Result<V::Value, E>
+  where
+    E: serde::de::Error,
+  {
+    self.0.visit_none()
+  }
+
+  fn visit_unit<E>(self) -> Result<V::Value, E>
+  where
+    E: serde::de::Error,
+  {
+    self.0.visit_unit()
+  }
+
+  fn visit_some<D>(self, de: D) -> Result<V::Value, D::Error>
+  where
+    D: Deserializer<'de>,
+  {
+    self.0.visit_some(Tracked(de))
+  }
+
+  fn visit_newtype_struct<D>(self, de: D) -> Result<V::Value, D::Error>
+  where
+    D: Deserializer<'de>,
+  {
+    self.0.visit_newtype_struct(Tracked(de))
+  }
+
+  fn visit_seq<A>(self, seq: A) -> Result<V::Value, A::Error>
+  where
+    A: SeqAccess<'de>,
+  {
+    self.0.visit_seq(TrackedSeq(seq))
+  }
+
+  fn visit_map<A>(self, map: A) -> Result<V::Value, A::Error>
+  where
+    A: MapAccess<'de>,
+  {
+    self.0.visit_map(TrackedMap(map))
+  }
+
+  fn visit_enum<A>(self, data: A) -> Result<V::Value, A::Error>
+  where
+    A: EnumAccess<'de>,
+  {
+    self.0.visit_enum(data)
+  }
+}
+
+struct TrackedSeed<S>(S);
+
+impl<'de, S> DeserializeSeed<'de> for TrackedSeed<S>
+where
+  S: DeserializeSeed<'de>,
+{
+  type Value = S::Value;
+
+  fn deserialize<D>(self, de: D) -> Result<S::Value, D::Error>
+  where
+    D: Deserializer<'de>,
+  {
+    self.0.deserialize(Tracked(de))
+  }
+}
+
+struct TrackedSeq<A>(A);
+
+impl<'de, A> SeqAccess<'de> for TrackedSeq<A>
+where
+  A: SeqAccess<'de>,
+{
+  type Error = A::Error;
+
+  fn next_element_seed<T>(&mut self, seed: T) -> Result<Option<T::Value>, A::Error>
+  where
+    T: DeserializeSeed<'de>,
+  {
+    self.0.next_element_seed(TrackedSeed(seed))
+  }
+
+  fn size_hint(&self) -> Option<usize> {
+    self.0.size_hint()
+  }
+}
+
+struct TrackedMap<A>(A);
+
+impl<'de, A> MapAccess<'de> for TrackedMap<A>
+where
+  A: MapAccess<'de>,
+{
+  type Error = A::Error;
+
+  fn next_key_seed<K>(&mut self, seed: K) -> Result<Option<K::Value>, A::Error>
+  where
+    K: DeserializeSeed<'de>,
+  {
+    self.0.next_key_seed(TrackedSeed(seed))
+  }
+
+  fn next_value_seed<T>(&mut self, seed: T) -> Result<T::Value, A::Error>
+  where
+    T: DeserializeSeed<'de>,
+  {
+    self.0.next_value_seed(TrackedSeed(seed))
+  }
+
+  fn size_hint(&self) -> Option<usize> {
+    self.0.size_hint()
+  }
+}
@@ -0,0 +1,612 @@
+use std::fmt;
+use std::str::FromStr;
+
+// Regex is a small regular expression engine, supporting the common subset of
+// Perl-style syntax that people reach for on the command line:
+//
+//   literals and escapes   a  \.  \t  \n
+//   any character          .
+//   classes                [abc]  [^a-z]  \d  \w  \s  \D  \W  \S
+//   anchors                ^  $  \b
+//   groups                 (a|b)  (?:a|b)
+//   repetition             *  +  ?  {n}  {n,}  {n,m}, each with a lazy "?" form
+//   flags                  (?i) at the start for case-insensitive matching
+//
+// A pattern is compiled to a program for a Pike VM, which runs every way that
+// the pattern could match at once, a character at a time. Unlike backtracking,
+// that takes time in proportion to the length of the text times the size of the
+// program, whatever the pattern, and it doesn't recurse.
+#[derive(Clone)]
+pub struct Regex {
+  source: String,
+  program: Vec<Inst>,
+  case_insensitive: bool,
+}
+
+// Patterns that compile to more instructions than this are rejected, since
+// counted repetition like "(a{1000}){1000}" can make a program of any size.
+const MAX_PROGRAM: usize = 1 << 16;
+
+#[derive(Clone, Debug)]
+enum Node {
+  Char(char),
+  Any,
+  Class(Class),
+  Start,
+  End,
+  WordBoundary,
+  Concat(Vec<Node>),
+  Alt(Vec<Node>),
+  Repeat {
+    node: Box<Node>,
+    min: usize,
+    max: Option<usize>,
+    greedy: bool,
+  },
+}
+
+// Inst is an instruction of a compiled pattern. Each thread of the VM is at an
+// instruction, and moves to the next one when it matches the character at its
+// position, or right away for the instructions that don't match a character.
+#[derive(Clone, Debug)]
+enum Inst {
+  Char(char),
+  Any,
+  Class(Class),
+  Start,
+  End,
+  WordBoundary,
+  // Continues at both of two instructions, preferring the first.
+  Split(usize, usize),
+  Jump(usize),
+  Match,
+}
+
+#[derive(Clone, Debug)]
+struct Class {
+  negated: bool,
+  items: Vec<ClassItem>,
+}
+
+#[derive(Clone, Debug)]
+enum ClassItem {
+  Range(char, char),
+  Digit(bool),
+  Word(bool),
+  Space(bool),
+}
+
+impl Regex {
+  pub fn is_match(&self, text: &str) -> bool {
+    let chars: Vec<char> = match self.case_insensitive {
+      true => text.chars().flat_map(char::to_lowercase).collect(),
+      false => text.chars().collect(),
+    };
+    let mut vm = Vm {
+      program: &self.program,
+      chars: &chars,
+      stack: Vec::new(),
+    };
+    let mut current = Threads::new(self.program.len());
+    let mut next = Threads::new(self.program.len());
+    for pos in 0..=chars.len() {
+      // The search isn't anchored, so a match can start at any position.
+      if vm.add(&mut current, 0, pos) {
+        return true;
+      }
+      next.clear();
+      for &pc in &current.pcs {
+        let matched = match (&self.program[pc], chars.get(pos)) {
+          (Inst::Char(want), Some(c)) => want == c,
+          (Inst::Any, Some(&c)) => c != '\n',
+          (Inst::Class(class), Some(&c)) => class.matches(c),
+          _ => false,
+        };
+        if matched && vm.add(&mut next, pc + 1, pos + 1) {
+          return true;
+        }
+      }
+      std::mem::swap(&mut current, &mut next);
+    }
+    false
+  }
+}
+
+impl fmt::Display for Regex {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    f.write_str(&self.source)
+  }
+}
+
+impl FromStr for Regex {
+  type Err = String;
+
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    let (pattern, case_insensitive) = match s.strip_prefix("(?i)") {
+      Some(rest) => (rest, true),
+      None => (s, false),
+    };
+    let mut parser = Parser {
+      chars: pattern.chars().collect(),
+      pos: 0,
+      case_insensitive,
+    };
+    let mut program = Vec::new();
+    parser
+      .alternation()
+      .and_then(|node| match parser.peek() {
+        None => Ok(node),
+        Some(_) => Err("unmatched ')'".to_owned()),
+      })
+      .and_then(|node| compile(&node, &mut program))
+      .map_err(|err| format!("'{}' is not a valid regex ({})", s, err))?;
+    program.push(Inst::Match);
+    Ok(Regex {
+      source: s.to_owned(),
+      program,
+      case_insensitive,
+    })
+  }
+}
+
+struct Parser {
+  chars: Vec<char>,
+  pos: usize,
+  case_insensitive: bool,
+}
+
+impl Parser {
+  fn peek(&self) -> Option<char> {
+    self.chars.get(self.pos).copied()
+  }
+
+  fn next(&mut self) -> Option<char> {
+    let c = self.peek();
+    self.pos += 1;
+    c
+  }
+
+  fn eat(&mut self, c: char) -> bool {
+    if self.peek() == Some(c) {
+      self.pos += 1;
+      true
+    } else {
+      false
+    }
+  }
+
+  fn alternation(&mut self) -> Result<Node, String> {
+    let mut options = vec![self.concatenation()?];
+    while self.eat('|') {
+      options.push(self.concatenation()?);
+    }
+    match options.len() {
+      1 => Ok(options.remove(0)),
+      _ => Ok(Node::Alt(options)),
+    }
+  }
+
+  fn concatenation(&mut self) -> Result<Node, String> {
+    let mut nodes = Vec::new();
+    while let Some(c) = self.peek() {
+      if c == '|' || c == ')' {
+        break;
+      }
+      let atom = self.atom()?;
+      nodes.push(self.repetition(atom)?);
+    }
+    Ok(Node::Concat(nodes))
+  }
+
+  fn repetition(&mut self, atom: Node) -> Result<Node, String> {
+    let (min, max) = if self.eat('*') {
+      (0, None)
+    } else if self.eat('+') {
+      (1, None)
+    } else if self.eat('?') {
+      (0, Some(1))
+    } else {
+      match self.bounds() {
+        Some(bounds) => bounds,
+        None => return Ok(atom),
+      }
+    };
+    if let Node::Start | Node::End | Node::WordBoundary = atom {
+      return Err("nothing to repeat".to_owned());
+    }
+    let greedy = !self.eat('?');
+    Ok(Node::Repeat {
+      node: Box::new(atom),
+      min,
+      max,
+      greedy,
+    })
+  }
+
+  // Parses a "{n}", "{n,}", or "{n,m}" quantifier, consuming it only if it's
+  // well formed. Otherwise, the "{" is treated as a literal character.
+  fn bounds(&mut self) -> Option<(usize, Option<usize>)> {
+    if self.peek() != Some('{') {
+      return None;
+    }
+    let rest: String = self.chars[self.pos..].iter().collect();
+    let end = rest.find('}')?;
+    let inner = &rest[1..end];
+    let bounds = match inner.split_once(',') {
+      None => {
+        let n = inner.parse().ok()?;
+        (n, Some(n))
+      }
+      Some((min, "")) => (min.parse().ok()?, None),
+      Some((min, max)) => (min.parse().ok()?, Some(max.parse().ok()?)),
+    };
+    self.pos += rest[..=end].chars().count();
+    Some(bounds)
+  }
+
+  fn atom(&mut self) -> Result<Node, String> {
+    match self.next() {
+      Some('.') => Ok(Node::Any),
+      Some('^') => Ok(Node::Start),
+      Some('$') => Ok(Node::End),
+      Some('(') => {
+        if self.eat('?') && !self.eat(':') {
+          return Err("unsupported group syntax".to_owned());
+        }
+        let node = self.alternation()?;
+        match self.eat(')') {
+          true => Ok(node),
+          false => Err("unmatched '('".to_owned()),
+        }
+      }
+      Some('[') => self.class(),
+      Some('\\') => match self.escape()? {
+        Escape::Char(c) => Ok(self.literal(c)),
+        Escape::Class(item) => Ok(Node::Class(Class {
+          negated: false,
+          items: vec![item],
+        })),
+        Escape::WordBoundary => Ok(Node::WordBoundary),
+      },
+      Some(c @ ('*' | '+' | '?')) => Err(format!("nothing to repeat before '{}'", c)),
+      Some(c) => Ok(self.literal(c)),
+      None => Err("unexpected end of pattern".to_owned()),
+    }
+  }
+
+  fn literal(&self, c: char) -> Node {
+    match self.case_insensitive {
+      true => Node::Char(c.to_lowercase().next().unwrap_or(c)),
+      false => Node::Char(c),
+    }
+  }
+
+  fn escape(&mut self) -> Result<Escape, String> {
+    match self.next() {
+      Some('d') => Ok(Escape::Class(ClassItem::Digit(true))),
+      Some('D') => Ok(Escape::Class(ClassItem::Digit(false))),
+      Some('w') => Ok(Escape::Class(ClassItem::Word(true))),
+      Some('W') => Ok(Escape::Class(ClassItem::Word(false))),
+      Some('s') => Ok(Escape::Class(ClassItem::Space(true))),
+      Some('S') => Ok(Escape::Class(ClassItem::Space(false))),
+      Some('b') => Ok(Escape::WordBoundary),
+      Some('n') => Ok(Escape::Char('\n')),
+      Some('r') => Ok(Escape::Char('\r')),
+      Some('t') => Ok(Escape::Char('\t')),
+      Some(c) if c.is_alphanumeric() => Err(format!("unsupported escape '\\{}'", c)),
+      Some(c) => Ok(Escape::Char(c)),
+      None => Err("trailing backslash".to_owned()),
+    }
+  }
+
+  fn class(&mut self) -> Result<Node, String> {
+    let negated = self.eat('^');
+    let mut items = Vec::new();
+    let mut first = true;
+    loop {
+      let c = match self.next() {
+        None => return Err("unmatched '['".to_owned()),
+        Some(']') if !first => break,
+        Some(c) => c,
+      };
+      first = false;
+      let lo = match c {
+        '\\' => match self.escape()? {
+          Escape::Char(c) => c,
+          Escape::Class(item) => {
+            items.push(item);
+            continue;
+          }
+          Escape::WordBoundary => '\u{8}',
+        },
+        c => c,
+      };
+      let is_range =
+        self.peek() == Some('-') && self.chars.get(self.pos + 1).is_some_and(|&c| c != ']');
+      if !is_range {
+        items.push(ClassItem::Range(lo, lo));
+        continue;
+      }
+      self.pos += 1;
+      let hi = match self.next() {
+        Some('\\') => match self.escape()? {
+          Escape::Char(c) => c,
+          _ => return Err("invalid class range".to_owned()),
+        },
+        Some(c) => c,
+        None => return Err("unmatched '['".to_owned()),
+      };
+      if hi < lo {
+        return Err("invalid class range".to_owned());
+      }
+      items.push(ClassItem::Range(lo, hi));
+    }
+    if self.case_insensitive {
+      let mut folded = Vec::new();
+      for item in &items {
+        if let ClassItem::Range(lo, hi) = *item {
+          if lo.is_ascii_uppercase() && hi.is_ascii_uppercase() {
+            folded.push(ClassItem::Range(
+              lo.to_ascii_lowercase(),
+              hi.to_ascii_lowercase(),
+            ));
+          }
+        }
+      }
+      items.extend(folded);
+    }
+    Ok(Node::Class(Class { negated, items }))
+  }
+}
+
+enum Escape {
+  Char(char),
+  Class(ClassItem),
+  WordBoundary,
+}
+
+// Appends the instructions for a node to a program.
+fn compile(node: &Node, program: &mut Vec<Inst>) -> Result<(), String> {
+  if program.len() > MAX_PROGRAM {
+    return Err("pattern is too large".to_owned());
+  }
+  match node {
+    Node::Char(c) => program.push(Inst::Char(*c)),
+    Node::Any => program.push(Inst::Any),
+    Node::Class(class) => program.push(Inst::Class(class.clone())),
+    Node::Start => program.push(Inst::Start),
+    Node::End => program.push(Inst::End),
+    Node::WordBoundary => program.push(Inst::WordBoundary),
+    Node::Concat(nodes) => {
+      for node in nodes {
+        compile(node, program)?;
+      }
+    }
+    Node::Alt(options) => {
+      // Each option but the last is tried by a split to it, and jumps to the end
+      // of them all once it matches.
+      let mut jumps = Vec::new();
+      for (i, option) in options.iter().enumerate() {
+        if i + 1 == options.len() {
+          compile(option, program)?;
+          break;
+        }
+        let split = program.len();
+        program.push(Inst::Split(split + 1, 0));
+        compile(option, program)?;
+        jumps.push(program.len());
+        program.push(Inst::Jump(0));
+        program[split] = Inst::Split(split + 1, program.len());
+      }
+      for jump in jumps {
+        program[jump] = Inst::Jump(program.len());
+      }
+    }
+    Node::Repeat {
+      node,
+      min,
+      max,
+      greedy,
+    } => {
+      if max.is_some_and(|max| max < *min) {
+        return Err("repetition has a maximum below its minimum".to_owned());
+      }
+      if max.unwrap_or(*min) > MAX_PROGRAM {
+        return Err("repetition count is too large".to_owned());
+      }
+      for _ in 0..*min {
+        compile(node, program)?;
+      }
+      // A greedy repetition prefers matching once more to stopping, and a lazy
+      // one the opposite, which only matters for where a match ends.
+      let split = |split: usize, end: usize| match greedy {
+        true => Inst::Split(split + 1, end),
+        false => Inst::Split(end, split + 1),
+      };
+      match max {
+        None => {
+          let start = program.len();
+          program.push(Inst::Jump(0));
+          compile(node, program)?;
+          program.push(Inst::Jump(start));
+          program[start] = split(start, program.len());
+        }
+        Some(max) => {
+          let mut splits = Vec::new();
+          for _ in *min..*max {
+            splits.push(program.len());
+            program.push(Inst::Jump(0));
+            compile(node, program)?;
+          }
+          for start in splits {
+            program[start] = split(start, program.len());
+          }
+        }
+      }
+    }
+  }
+  Ok(())
+}
+
+// Threads is the set of instructions that the threads of the VM are at, in
+// the order they were added, with each instruction in it at most once.
+struct Threads {
+  pcs: Vec<usize>,
+  added: Vec<bool>,
+}
+
+impl Threads {
+  fn new(len: usize) -> Self {
+    Threads {
+      pcs: Vec::with_capacity(len),
+      added: vec![false; len],
+    }
+  }
+
+  fn insert(&mut self, pc: usize) -> bool {
+    if self.added[pc] {
+      return false;
+    }
+    self.added[pc] = true;
+    self.pcs.push(pc);
+    true
+  }
+
+  fn clear(&mut self) {
+    for &pc in &self.pcs {
+      self.added[pc] = false;
+    }
+    self.pcs.clear();
+  }
+}
+
+struct Vm<'p> {
+  program: &'p [Inst],
+  chars: &'p [char],
+  stack: Vec<usize>,
+}
+
+impl<'p> Vm<'p> {
+  // Adds a thread at an instruction for a position, following every split,
+  // jump, and assertion from it, and reports whether any of them reaches the
+  // end of the program. A thread only needs adding once per position, which
+  // also keeps patterns like "(a*)*" from looping forever.
+  fn add(&mut self, threads: &mut Threads, pc: usize, pos: usize) -> bool {
+    self.stack.push(pc);
+    while let Some(pc) = self.stack.pop() {
+      if !threads.insert(pc) {
+        continue;
+      }
+      let follow = match self.program[pc] {
+        Inst::Match => {
+          self.stack.clear();
+          return true;
+        }
+        Inst::Jump(to) => {
+          self.stack.push(to);
+          continue;
+        }
+        Inst::Split(first, second) => {
+          self.stack.push(second);
+          self.stack.push(first);
+          continue;
+        }
+        Inst::Start => pos == 0,
+        Inst::End => pos == self.chars.len(),
+        Inst::WordBoundary => {
+          let before = pos > 0 && is_word(self.chars[pos - 1]);
+          let after = self.chars.get(pos).is_some_and(|&c| is_word(c));
+          before != after
+        }
+        // Instructions that match a character wait for the next one.
+        Inst::Char(_) | Inst::Any | Inst::Class(_) => false,
+      };
+      if follow {
+        self.stack.push(pc + 1);
+      }
+    }
+    false
+  }
+}
+
+impl Class {
+  fn matches(&self, c: char) -> bool {
+    let found = self.items.iter().any(|item| match *item {
+      ClassItem::Range(lo, hi) => lo <= c && c <= hi,
+      ClassItem::Digit(want) => c.is_ascii_digit() == want,
+      ClassItem::Word(want) => is_word(c) == want,
+      ClassItem::Space(want) => c.is_whitespace() == want,
+    });
+    found != self.negated
+  }
+}
+
+fn is_word(c: char) -> bool {
+  c.is_alphanumeric() || c == '_'
+}
+
+#[cfg(test)]
+mod tests {
+  use super::Regex;
+
+  fn is_match(regex: &str, text: &str) -> bool {
+    regex.parse::<Regex>().unwrap().is_match(text)
+  }
+
+  #[test]
+  fn edge_cases() {
+    let cases = [
+      ("", "", true),
+      ("", "abc", true),
+      ("a|", "b", true),
+      ("^$", "\n", false),
+      ("a{3}", "aa", false),
+      ("^a{0}$", "", true),
+      ("x{,2}", "x{,2}", true),
+      ("a{", "a{", true),
+      (r"\bé", "café", false),
+      (r"\b", "", false),
+      (r"[\d-]+$", "12-34", true),
+      ("[a-]", "-", true),
+      ("[]a]", "]", true),
+      ("[^]]", "]", false),
+      (r"\.\*", ".*", true),
+      ("(?i)straße", "STRASSE", false),
+      ("(?i)[k]", "K", true),
+      ("((a)|b)+c", "babac", true),
+      ("(a*)+$", "aaa", true),
+      ("(|a)+b", "b", true),
+      ("ü.", "üx", true),
+    ];
+    for (regex, text, want) in cases {
+      assert_eq!(is_match(regex, text), want, "{:?} in {:?}", regex, text);
+    }
+  }
+
+  #[test]
+  fn invalid() {
+    let cases = [
+      ("(a", "unmatched '('"),
+      ("a)", "unmatched ')'"),
+      ("[a", "unmatched '['"),
+      ("*a", "nothing to repeat before '*'"),
+      ("^*", "nothing to repeat"),
+      (r"a\", "trailing backslash"),
+      (r"\q", "unsupported escape '\\q'"),
+      (r"\B", "unsupported escape '\\B'"),
+      ("(?=a)", "unsupported group syntax"),
+      ("[z-a]", "invalid class range"),
+      ("a{2,1}", "repetition has a maximum below its minimum"),
+      ("a{100000000}", "repetition count is too large"),
+    ];
+    for (regex, want) in cases {
+      let err = regex.parse::<Regex>().err().unwrap();
+      assert_eq!(
+        err,
+        format!("'{}' is not a valid regex ({})", regex, want),
+        "{:?}",
+        regex
+      );
+    }
+  }
+}
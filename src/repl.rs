@@ -0,0 +1,187 @@
+use std::env;
+use std::error::Error;
+use std::fs;
+use std::io::{self, IsTerminal, Write};
+use std::path::PathBuf;
+
+use structopt::StructOpt;
+
+use crate::color;
+use crate::highlight::Highlight;
+use crate::path::Path;
+use crate::transform::{Assignment, Transform};
+use crate::value::Value;
+use crate::{build_pipeline, editor, read_documents, write_output, Format, Opt};
+
+// ReplOpt holds the options for 'jyt repl', which loads the documents of a file
+// and shows the values at the paths typed in, for exploring data that's too big
+// or unfamiliar to read through.
+#[derive(StructOpt)]
+#[structopt(name = "jyt repl", verbatim_doc_comment)]
+/// Explore a file interactively
+///
+/// The file is read in the format of its extension, or the one detected from
+/// its contents, and then each line typed in is run against its documents:
+///
+///   .path or /path    show the values at a path, like .items[].name
+///   :to FORMAT        show values as json, yaml, toml, csv, tsv, table, or tree
+///   :set PATH=VALUE   set the value at a path, as --set does
+///   :delete PATH      delete the values at a path, as --delete does
+///   :reload           read the file again, undoing :set and :delete
+///   :history          list the lines typed so far
+///   :help             list these commands
+///   :quit             leave, as ctrl-D does
+///
+/// On a terminal, the up and down arrows go through the lines typed before,
+/// which are kept between sessions in $XDG_STATE_HOME/jyt/history (or
+/// ~/.local/state/jyt/history). Nothing is ever written back to the file.
+pub struct ReplOpt {
+  #[structopt(
+    short = "t",
+    long = "to",
+    value_name = "format",
+    help = "Format to show values in, until :to changes it",
+    default_value = "json"
+  )]
+  to: Format,
+
+  #[structopt(name = "file", help = "File to explore", parse(from_os_str))]
+  file: PathBuf,
+}
+
+// The most lines of history that are kept between sessions.
+const HISTORY_LEN: usize = 1000;
+
+const HELP: &str = "\
+.path or /path    show the values at a path, like .items[].name
+:to FORMAT        show values as json, yaml, toml, csv, tsv, table, or tree
+:set PATH=VALUE   set the value at a path, as --set does
+:delete PATH      delete the values at a path, as --delete does
+:reload           read the file again, undoing :set and :delete
+:history          list the lines typed so far
+:help             list these commands
+:quit             leave, as ctrl-D does";
+
+pub fn repl(opt: ReplOpt) -> Result<(), Box<dyn Error>> {
+  let mut docs = read_documents(&opt.file)?;
+  let mut to = opt.to;
+  let history_path = history_path();
+  let mut history: Vec<String> = match history_path.as_ref().map(fs::read_to_string) {
+    Some(Ok(saved)) => saved.lines().map(str::to_owned).collect(),
+    _ => Vec::new(),
+  };
+  let prompt = format!("{}> ", opt.file.display());
+  while let Some(line) = editor::read_line(&prompt, &history)? {
+    let line = line.trim();
+    if line.is_empty() {
+      continue;
+    }
+    if history.last().map(String::as_str) != Some(line) {
+      history.push(line.to_owned());
+    }
+    let (command, arg) = line.split_once(' ').unwrap_or((line, ""));
+    let arg = arg.trim();
+    let result = match command {
+      ":quit" | ":q" => break,
+      ":help" => {
+        println!("{}", HELP);
+        Ok(())
+      }
+      ":history" => {
+        for line in &history {
+          println!("{}", line);
+        }
+        Ok(())
+      }
+      ":to" => match arg.parse() {
+        Ok(Format::Template) => {
+          Err("template output needs --template, so :to can't give it".into())
+        }
+        Ok(format) => {
+          to = format;
+          Ok(())
+        }
+        Err(err) => Err(err.into()),
+      },
+      ":set" => arg
+        .parse::<Assignment>()
+        .map_err(|err| err.into())
+        .and_then(|assignment| apply(&mut docs, Transform::Set(assignment))),
+      ":delete" => arg
+        .parse::<Path>()
+        .map_err(|err| err.into())
+        .and_then(|path| apply(&mut docs, Transform::Delete(path))),
+      ":reload" => read_documents(&opt.file).map(|loaded| docs = loaded),
+      _ if line.starts_with(':') => Err(format!("unknown command {} (see :help)", command).into()),
+      _ => line
+        .parse::<Path>()
+        .map_err(|err| err.into())
+        .and_then(|path| {
+          let values: Vec<&Value> = docs.iter().flat_map(|doc| path.select(doc)).collect();
+          show(&values, to)
+        }),
+    };
+    if let Err(err) = result {
+      println!("error: {}", err);
+    }
+  }
+
+  // History is a convenience, and not worth an error if it can't be saved.
+  // Lines from a script, rather than a person, aren't worth recalling.
+  if let (Some(path), true) = (history_path, io::stdin().is_terminal()) {
+    let start = history.len().saturating_sub(HISTORY_LEN);
+    let mut saved = history[start..].join("\n");
+    saved.push('\n');
+    let _ = path
+      .parent()
+      .map_or(Ok(()), fs::create_dir_all)
+      .and_then(|_| fs::write(&path, saved));
+  }
+  Ok(())
+}
+
+fn apply(docs: &mut [Value], transform: Transform) -> Result<(), Box<dyn Error>> {
+  for doc in docs {
+    transform.apply(doc)?;
+  }
+  Ok(())
+}
+
+// Writes values to stdout in a format, as jyt would write documents with -t.
+fn show(values: &[&Value], to: Format) -> Result<(), Box<dyn Error>> {
+  if values.is_empty() {
+    println!("no values at this path");
+    return Ok(());
+  }
+  let mut json = Vec::new();
+  for value in values {
+    serde_json::to_writer(&mut json, value)?;
+    json.push(b'\n');
+  }
+  let matches = Opt::clap().get_matches_from_safe(["jyt", "-t", to.name()])?;
+  let opt = Opt::from_clap(&matches);
+  let pipeline = build_pipeline(&opt, &matches, "<repl>".to_owned())?;
+  let mut output = Vec::new();
+  write_output(&json, Format::Json, &opt, &pipeline, &mut output)?;
+  match (to, color::stdout()) {
+    (Format::Json, true) => Highlight::new(opt.theme.clone(), io::stdout()).write_all(&output)?,
+    _ => io::stdout().write_all(&output)?,
+  }
+  Ok(())
+}
+
+// Returns where the history is kept, under the XDG state directory.
+fn history_path() -> Option<PathBuf> {
+  match env::var_os("XDG_STATE_HOME") {
+    Some(dir) if !dir.is_empty() => Some(PathBuf::from(dir).join("jyt").join("history")),
+    _ => env::var_os("HOME")
+      .filter(|home| !home.is_empty())
+      .map(|home| {
+        PathBuf::from(home)
+          .join(".local")
+          .join("state")
+          .join("jyt")
+          .join("history")
+      }),
+  }
+}
@@ -0,0 +1,776 @@
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+
+use crate::regex::Regex;
+use crate::value::{Map, Value};
+
+// A string field is only reported as an enum when it has few distinct values,
+// each of which appears at least a couple of times on average. Otherwise we'd
+// turn every sample of a free-form field into an enum of its values.
+const ENUM_MAX_VALUES: usize = 8;
+const ENUM_MIN_REPEATS: usize = 2;
+
+// Inference accumulates the shape of every value seen at one location across
+// all input documents, from which it later produces a JSON Schema.
+#[derive(Default)]
+pub struct Inference {
+  count: usize,
+  types: [bool; 7],
+  // Properties in the order they were first seen, with the number of objects
+  // that contained each one.
+  properties: Vec<(String, usize, Inference)>,
+  objects: usize,
+  items: Option<Box<Inference>>,
+  strings: Vec<String>,
+  strings_overflowed: bool,
+}
+
+const TYPE_NAMES: [&str; 7] = [
+  "null", "boolean", "integer", "number", "string", "array", "object",
+];
+
+impl Inference {
+  pub fn add(&mut self, v: &Value) {
+    self.count += 1;
+    match v {
+      Value::Null => self.types[0] = true,
+      Value::Bool(_) => self.types[1] = true,
+      Value::I64(_) | Value::U64(_) | Value::I128(_) | Value::U128(_) => self.types[2] = true,
+      Value::F64(_) => self.types[3] = true,
+      Value::String(s) => {
+        self.types[4] = true;
+        if !self.strings_overflowed && !self.strings.contains(s) {
+          if self.strings.len() == ENUM_MAX_VALUES {
+            self.strings_overflowed = true;
+            self.strings.clear();
+          } else {
+            self.strings.push(s.clone());
+          }
+        }
+      }
+      Value::Seq(items) => {
+        self.types[5] = true;
+        let inference = self.items.get_or_insert_with(Default::default);
+        for item in items {
+          inference.add(item);
+        }
+      }
+      Value::Map(map) => {
+        self.types[6] = true;
+        self.objects += 1;
+        for (k, v) in map.iter() {
+          let key = k.key_str().unwrap_or_default();
+          match self.properties.iter_mut().find(|(name, _, _)| *name == key) {
+            Some((_, seen, inference)) => {
+              *seen += 1;
+              inference.add(v);
+            }
+            None => {
+              let mut inference = Inference::default();
+              inference.add(v);
+              self.properties.push((key, 1, inference));
+            }
+          }
+        }
+      }
+    }
+  }
+
+  // Returns a JSON Schema describing every value seen so far, with a "$schema"
+  // keyword identifying the draft.
+  pub fn to_schema(&self) -> Value {
+    let mut schema = Map::new();
+    schema.push(
+      Value::from("$schema"),
+      Value::from("https://json-schema.org/draft/2020-12/schema"),
+    );
+    if let Value::Map(rest) = self.to_subschema() {
+      for (k, v) in rest {
+        schema.push(k, v);
+      }
+    }
+    Value::Map(schema)
+  }
+
+  fn to_subschema(&self) -> Value {
+    let mut schema = Map::new();
+
+    // "integer" is a subset of "number", so there's no point listing both.
+    let mut types: Vec<Value> = TYPE_NAMES
+      .iter()
+      .zip(self.types.iter())
+      .filter(|(name, &seen)| seen && !(**name == "integer" && self.types[3]))
+      .map(|(name, _)| Value::from(*name))
+      .collect();
+    match types.len() {
+      0 => {}
+      1 => schema.push(Value::from("type"), types.remove(0)),
+      _ => schema.push(Value::from("type"), Value::Seq(types)),
+    }
+
+    let only_strings = self
+      .types
+      .iter()
+      .enumerate()
+      .all(|(i, &seen)| seen == (i == 4));
+    if only_strings
+      && !self.strings_overflowed
+      && self.count >= self.strings.len() * ENUM_MIN_REPEATS
+    {
+      let values = self.strings.iter().map(|s| Value::from(s.as_str()));
+      schema.push(Value::from("enum"), Value::Seq(values.collect()));
+    }
+
+    if self.types[6] {
+      let mut properties = Map::new();
+      let mut required = Vec::new();
+      for (name, seen, inference) in &self.properties {
+        properties.push(Value::from(name.as_str()), inference.to_subschema());
+        if *seen == self.objects {
+          required.push(Value::from(name.as_str()));
+        }
+      }
+      schema.push(Value::from("properties"), Value::Map(properties));
+      if !required.is_empty() {
+        schema.push(Value::from("required"), Value::Seq(required));
+      }
+    }
+
+    if let Some(items) = &self.items {
+      if items.count > 0 {
+        schema.push(Value::from("items"), items.to_subschema());
+      }
+    }
+
+    Value::Map(schema)
+  }
+}
+
+// Validator checks documents against a JSON Schema. It supports the commonly
+// used assertion and applicator keywords of recent drafts, with patterns in
+// jyt's own regex syntax. It refuses schemas that use the keywords it doesn't
+// support, like "format" or "if", rather than quietly accepting anything they
+// would reject. References can only point within the schema document itself.
+pub struct Validator {
+  root: Value,
+  // Every "pattern" and "patternProperties" regex in the schema, compiled.
+  patterns: HashMap<String, Regex>,
+}
+
+// The keywords that assert something about a document, which Validator would
+// have to understand to give the right answer.
+const UNSUPPORTED: &[&str] = &[
+  "format",
+  "contains",
+  "minContains",
+  "maxContains",
+  "propertyNames",
+  "dependencies",
+  "dependentRequired",
+  "dependentSchemas",
+  "if",
+  "then",
+  "else",
+  "unevaluatedItems",
+  "unevaluatedProperties",
+  "$dynamicRef",
+  "$recursiveRef",
+];
+
+// Violation is a single way in which a document fails to match a schema.
+pub struct Violation {
+  pub path: String,
+  pub message: String,
+  // The line and column where the value at the path starts in the input, when
+  // jyt knows it.
+  pub position: Option<(usize, usize)>,
+}
+
+// Positions maps the index of a document and the path of a value within it to
+// the line and column where that value starts in the input, for reporting
+// violations. The path is in the same form as that of a Violation. A path with
+// duplicate keys in it has more than one value, in the order of the input.
+pub type Positions = HashMap<(usize, String), Vec<(usize, usize)>>;
+
+// ValidationError reports every violation found across all of the documents
+// in an input.
+#[derive(Default)]
+pub struct ValidationError(Vec<(usize, Violation)>);
+
+impl ValidationError {
+  pub fn add(&mut self, document: usize, violations: Vec<Violation>) {
+    self.0.extend(violations.into_iter().map(|v| (document, v)));
+  }
+
+  pub fn is_empty(&self) -> bool {
+    self.0.is_empty()
+  }
+
+  pub fn violations(&self) -> &[(usize, Violation)] {
+    &self.0
+  }
+
+  // Adds the position of each violation in the input, where there is one.
+  pub fn locate(&mut self, positions: &Positions) {
+    for (document, violation) in &mut self.0 {
+      let key = (*document, std::mem::take(&mut violation.path));
+      violation.position = positions.get(&key).and_then(|p| p.first()).copied();
+      violation.path = key.1;
+    }
+  }
+}
+
+impl fmt::Display for ValidationError {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    write!(f, "input does not match schema")?;
+    for (document, violation) in &self.0 {
+      write!(f, "\n  document {}, {}", document, violation.path)?;
+      if let Some((line, column)) = violation.position {
+        write!(f, " (line {}, column {})", line, column)?;
+      }
+      write!(f, ": {}", violation.message)?;
+    }
+    Ok(())
+  }
+}
+
+impl fmt::Debug for ValidationError {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    fmt::Display::fmt(self, f)
+  }
+}
+
+impl Error for ValidationError {}
+
+// References are followed at most this many times along any one path through
+// the schema, to catch cycles that don't consume any of the document.
+const MAX_REF_DEPTH: usize = 64;
+
+impl Validator {
+  pub fn new(root: Value) -> Result<Self, String> {
+    if !matches!(root, Value::Map(_) | Value::Bool(_)) {
+      return Err("schema must be an object or boolean".to_owned());
+    }
+    let mut patterns = HashMap::new();
+    inspect(&root, &mut String::from("#"), &mut patterns)?;
+    Ok(Validator { root, patterns })
+  }
+
+  pub fn validate(&self, doc: &Value) -> Vec<Violation> {
+    let mut violations = Vec::new();
+    let mut path = String::new();
+    self.check(&self.root, doc, &mut path, 0, &mut violations);
+    violations
+  }
+
+  fn check(
+    &self,
+    schema: &Value,
+    v: &Value,
+    path: &mut String,
+    refs: usize,
+    out: &mut Vec<Violation>,
+  ) {
+    // Violations of this schema's own keywords are collected separately, and
+    // reported after any from nested schemas.
+    let mut failures: Vec<String> = Vec::new();
+    let mut fail = |message: String| failures.push(message);
+
+    let schema = match schema {
+      Value::Bool(true) => return,
+      Value::Bool(false) => {
+        return push_violations(out, path, vec!["no value is allowed here".to_owned()])
+      }
+      Value::Map(schema) => schema,
+      _ => return,
+    };
+
+    if let Some(Value::String(reference)) = schema.get("$ref") {
+      match self.resolve(reference) {
+        Some(_) if refs >= MAX_REF_DEPTH => {
+          fail(format!("too many nested references at {}", reference))
+        }
+        Some(target) => self.check(target, v, path, refs + 1, out),
+        None => fail(format!("cannot resolve reference {}", reference)),
+      }
+    }
+
+    if let Some(types) = schema.get("type") {
+      let allowed: Vec<&str> = match types {
+        Value::String(t) => vec![t.as_str()],
+        Value::Seq(ts) => ts.iter().filter_map(as_str).collect(),
+        _ => vec![],
+      };
+      if !allowed.iter().any(|t| has_type(v, t)) {
+        fail(format!(
+          "expected {}, found {}",
+          allowed.join(" or "),
+          type_of(v)
+        ));
+      }
+    }
+    if let Some(Value::Seq(options)) = schema.get("enum") {
+      if !options.iter().any(|o| json_eq(o, v)) {
+        fail("value is not one of the allowed values".to_owned());
+      }
+    }
+    if let Some(expected) = schema.get("const") {
+      if !json_eq(expected, v) {
+        fail("value is not the expected constant".to_owned());
+      }
+    }
+
+    match v {
+      Value::I64(_) | Value::U64(_) | Value::I128(_) | Value::U128(_) | Value::F64(_) => {
+        let n = as_f64(v).unwrap_or_default();
+        let limit = |key| schema.get(key).and_then(as_f64);
+        if let Some(min) = limit("minimum").filter(|&min| n < min) {
+          fail(format!("{} is less than the minimum of {}", n, min));
+        }
+        if let Some(max) = limit("maximum").filter(|&max| n > max) {
+          fail(format!("{} is greater than the maximum of {}", n, max));
+        }
+        if let Some(min) = limit("exclusiveMinimum").filter(|&min| n <= min) {
+          fail(format!("{} is not greater than {}", n, min));
+        }
+        if let Some(max) = limit("exclusiveMaximum").filter(|&max| n >= max) {
+          fail(format!("{} is not less than {}", n, max));
+        }
+        if let Some(m) = limit("multipleOf").filter(|&m| m > 0.0 && !is_multiple(n, m)) {
+          fail(format!("{} is not a multiple of {}", n, m));
+        }
+      }
+      Value::String(s) => {
+        if let Some(Value::String(pattern)) = schema.get("pattern") {
+          if self.patterns.get(pattern).is_some_and(|re| !re.is_match(s)) {
+            fail(format!("string does not match the pattern {}", pattern));
+          }
+        }
+        let len = s.chars().count();
+        if let Some(min) = schema
+          .get("minLength")
+          .and_then(as_usize)
+          .filter(|&min| len < min)
+        {
+          fail(format!("string is shorter than {} characters", min));
+        }
+        if let Some(max) = schema
+          .get("maxLength")
+          .and_then(as_usize)
+          .filter(|&max| len > max)
+        {
+          fail(format!("string is longer than {} characters", max));
+        }
+      }
+      Value::Seq(items) => {
+        if let Some(min) = schema
+          .get("minItems")
+          .and_then(as_usize)
+          .filter(|&min| items.len() < min)
+        {
+          fail(format!("array has fewer than {} items", min));
+        }
+        if let Some(max) = schema
+          .get("maxItems")
+          .and_then(as_usize)
+          .filter(|&max| items.len() > max)
+        {
+          fail(format!("array has more than {} items", max));
+        }
+        if let Some(Value::Bool(true)) = schema.get("uniqueItems") {
+          let duplicate =
+            (0..items.len()).any(|i| items[..i].iter().any(|x| json_eq(x, &items[i])));
+          if duplicate {
+            fail("array items are not unique".to_owned());
+          }
+        }
+      }
+      Value::Map(map) => {
+        if let Some(min) = schema
+          .get("minProperties")
+          .and_then(as_usize)
+          .filter(|&min| map.len() < min)
+        {
+          fail(format!("object has fewer than {} properties", min));
+        }
+        if let Some(max) = schema
+          .get("maxProperties")
+          .and_then(as_usize)
+          .filter(|&max| map.len() > max)
+        {
+          fail(format!("object has more than {} properties", max));
+        }
+        if let Some(Value::Seq(required)) = schema.get("required") {
+          for name in required.iter().filter_map(as_str) {
+            if map.get(name).is_none() {
+              fail(format!("missing required property \"{}\"", name));
+            }
+          }
+        }
+      }
+      _ => {}
+    }
+
+    // The remaining keywords apply subschemas, which report their own
+    // violations at their own paths.
+    match v {
+      Value::Seq(items) => {
+        // "prefixItems" is the 2020-12 spelling of the array form of "items".
+        let (prefix, rest) = match (schema.get("prefixItems"), schema.get("items")) {
+          (Some(Value::Seq(prefix)), rest) => (prefix.as_slice(), rest),
+          (None, Some(Value::Seq(prefix))) => (prefix.as_slice(), schema.get("additionalItems")),
+          (_, rest) => (&[][..], rest),
+        };
+        for (i, item) in items.iter().enumerate() {
+          let subschema = match prefix.get(i) {
+            Some(subschema) => subschema,
+            None => match rest {
+              Some(subschema) => subschema,
+              None => break,
+            },
+          };
+          let len = path.len();
+          crate::path::push_index(path, i);
+          self.check(subschema, item, path, refs, out);
+          path.truncate(len);
+        }
+      }
+      Value::Map(map) => {
+        for (k, item) in map.iter() {
+          let len = path.len();
+          crate::path::push_key(path, k);
+          for subschema in self.property_schemas(schema, k) {
+            self.check(subschema, item, path, refs, out);
+          }
+          path.truncate(len);
+        }
+      }
+      _ => {}
+    }
+
+    if let Some(Value::Seq(all)) = schema.get("allOf") {
+      for subschema in all {
+        self.check(subschema, v, path, refs, out);
+      }
+    }
+    let matches = |subschema: &Value, path: &mut String| {
+      let mut scratch = Vec::new();
+      self.check(subschema, v, path, refs, &mut scratch);
+      scratch.is_empty()
+    };
+    if let Some(Value::Seq(any)) = schema.get("anyOf") {
+      if !any.iter().any(|subschema| matches(subschema, path)) {
+        failures.push("value does not match any of the allowed schemas".to_owned());
+      }
+    }
+    if let Some(Value::Seq(one)) = schema.get("oneOf") {
+      if one
+        .iter()
+        .filter(|subschema| matches(subschema, path))
+        .count()
+        != 1
+      {
+        failures.push("value does not match exactly one of the allowed schemas".to_owned());
+      }
+    }
+    if let Some(not) = schema.get("not") {
+      if matches(not, path) {
+        failures.push("value matches a disallowed schema".to_owned());
+      }
+    }
+    push_violations(out, path, failures);
+  }
+
+  // Converts values in a document to the types that the schema declares for
+  // them where the conversion is unambiguous, like the string "8080" to the
+  // integer 8080 or "yes" to true. Values that can't be converted are left
+  // alone, for validation to report if it's also requested.
+  pub fn cast(&self, doc: &mut Value) {
+    self.coerce(&self.root, doc, 0);
+  }
+
+  fn coerce(&self, schema: &Value, v: &mut Value, refs: usize) {
+    let schema = match schema {
+      Value::Map(schema) => schema,
+      _ => return,
+    };
+    if let Some(Value::String(reference)) = schema.get("$ref") {
+      if let Some(target) = self.resolve(reference).filter(|_| refs < MAX_REF_DEPTH) {
+        self.coerce(target, v, refs + 1);
+      }
+    }
+    if let Some(Value::Seq(all)) = schema.get("allOf") {
+      for subschema in all {
+        self.coerce(subschema, v, refs);
+      }
+    }
+
+    let allowed: Vec<&str> = match schema.get("type") {
+      Some(Value::String(t)) => vec![t.as_str()],
+      Some(Value::Seq(ts)) => ts.iter().filter_map(as_str).collect(),
+      _ => vec![],
+    };
+    if !allowed.is_empty() && !allowed.iter().any(|t| has_type(v, t)) {
+      if let Some(cast) = allowed.iter().find_map(|t| cast_to(v, t)) {
+        *v = cast;
+      }
+    }
+
+    match v {
+      Value::Seq(items) => {
+        let (prefix, rest) = match (schema.get("prefixItems"), schema.get("items")) {
+          (Some(Value::Seq(prefix)), rest) => (prefix.as_slice(), rest),
+          (None, Some(Value::Seq(prefix))) => (prefix.as_slice(), schema.get("additionalItems")),
+          (_, rest) => (&[][..], rest),
+        };
+        for (i, item) in items.iter_mut().enumerate() {
+          match prefix.get(i).or(rest) {
+            Some(subschema) => self.coerce(subschema, item, refs),
+            None => break,
+          }
+        }
+      }
+      Value::Map(map) => {
+        for (k, item) in map.iter_mut() {
+          for subschema in self.property_schemas(schema, k) {
+            self.coerce(subschema, item, refs);
+          }
+        }
+      }
+      _ => {}
+    }
+  }
+
+  // Returns the subschemas that apply to the value of a property: the one from
+  // "properties" and any from "patternProperties" whose patterns match the key,
+  // or else "additionalProperties".
+  fn property_schemas<'a>(&'a self, schema: &'a Map, k: &Value) -> Vec<&'a Value> {
+    let key = k.key_str().unwrap_or_default();
+    let mut subschemas = Vec::new();
+    if let Some(Value::Map(properties)) = schema.get("properties") {
+      subschemas.extend(properties.get(&key));
+    }
+    if let Some(Value::Map(properties)) = schema.get("patternProperties") {
+      for (pattern, subschema) in properties.iter() {
+        let pattern = pattern.key_str().unwrap_or_default();
+        if self
+          .patterns
+          .get(&pattern)
+          .is_some_and(|re| re.is_match(&key))
+        {
+          subschemas.push(subschema);
+        }
+      }
+    }
+    if subschemas.is_empty() {
+      subschemas.extend(schema.get("additionalProperties"));
+    }
+    subschemas
+  }
+
+  // Resolves a reference to a location within the schema document, like
+  // "#/$defs/item". The fragment is a JSON Pointer, which our own path syntax
+  // happens to accept.
+  fn resolve(&self, reference: &str) -> Option<&Value> {
+    match reference.strip_prefix('#')? {
+      "" => Some(&self.root),
+      pointer => {
+        let path: crate::path::Path = pointer.parse().ok()?;
+        path.select(&self.root).into_iter().next()
+      }
+    }
+  }
+}
+
+// Walks a schema and the subschemas within it, compiling its patterns and
+// refusing any keywords that Validator doesn't support. The location is a
+// reference to the schema, for error messages.
+fn inspect(
+  schema: &Value,
+  at: &mut String,
+  patterns: &mut HashMap<String, Regex>,
+) -> Result<(), String> {
+  let schema = match schema {
+    Value::Map(schema) => schema,
+    _ => return Ok(()),
+  };
+  if let Some(keyword) = UNSUPPORTED.iter().find(|k| schema.get(k).is_some()) {
+    return Err(format!(
+      "schema uses \"{}\" at {}, which jyt does not support",
+      keyword, at
+    ));
+  }
+
+  let mut compile = |pattern: String, at: &str| match pattern.parse::<Regex>() {
+    Ok(re) => {
+      patterns.insert(pattern, re);
+      Ok(())
+    }
+    Err(err) => Err(format!("schema has an invalid pattern at {}: {}", at, err)),
+  };
+  if let Some(Value::String(pattern)) = schema.get("pattern") {
+    compile(pattern.clone(), &format!("{}/pattern", at))?;
+  }
+  if let Some(Value::Map(properties)) = schema.get("patternProperties") {
+    for (pattern, _) in properties.iter() {
+      compile(
+        pattern.key_str().unwrap_or_default(),
+        &format!("{}/patternProperties", at),
+      )?;
+    }
+  }
+
+  for (k, v) in schema.iter() {
+    let keyword = k.key_str().unwrap_or_default();
+    let len = at.len();
+    at.push('/');
+    at.push_str(&escape_pointer(&keyword));
+    match (keyword.as_str(), v) {
+      ("properties" | "patternProperties" | "$defs" | "definitions", Value::Map(subschemas)) => {
+        for (name, subschema) in subschemas.iter() {
+          let len = at.len();
+          at.push('/');
+          at.push_str(&escape_pointer(&name.key_str().unwrap_or_default()));
+          inspect(subschema, at, patterns)?;
+          at.truncate(len);
+        }
+      }
+      ("items" | "prefixItems" | "allOf" | "anyOf" | "oneOf", Value::Seq(subschemas)) => {
+        for (i, subschema) in subschemas.iter().enumerate() {
+          let len = at.len();
+          at.push_str(&format!("/{}", i));
+          inspect(subschema, at, patterns)?;
+          at.truncate(len);
+        }
+      }
+      ("items" | "additionalItems" | "additionalProperties" | "not", subschema) => {
+        inspect(subschema, at, patterns)?;
+      }
+      _ => {}
+    }
+    at.truncate(len);
+  }
+  Ok(())
+}
+
+fn escape_pointer(token: &str) -> String {
+  token.replace('~', "~0").replace('/', "~1")
+}
+
+fn push_violations(out: &mut Vec<Violation>, path: &str, messages: Vec<String>) {
+  let path = if path.is_empty() { "." } else { path };
+  out.extend(messages.into_iter().map(|message| Violation {
+    path: path.to_owned(),
+    message,
+    position: None,
+  }));
+}
+
+fn has_type(v: &Value, t: &str) -> bool {
+  match (t, v) {
+    ("null", Value::Null) => true,
+    ("boolean", Value::Bool(_)) => true,
+    ("integer", Value::I64(_) | Value::U64(_) | Value::I128(_) | Value::U128(_)) => true,
+    ("integer", Value::F64(n)) => n.fract() == 0.0,
+    ("number", Value::I64(_) | Value::U64(_) | Value::I128(_) | Value::U128(_) | Value::F64(_)) => {
+      true
+    }
+    ("string", Value::String(_)) => true,
+    ("array", Value::Seq(_)) => true,
+    ("object", Value::Map(_)) => true,
+    _ => false,
+  }
+}
+
+// Converts a value to a JSON Schema type, if there's a clear way to do it.
+fn cast_to(v: &Value, t: &str) -> Option<Value> {
+  match (t, v) {
+    ("integer", Value::String(s)) => match s.trim().parse::<i64>() {
+      Ok(n) => Some(Value::I64(n)),
+      Err(_) => s.trim().parse::<u64>().ok().map(Value::U64),
+    },
+    ("integer", Value::F64(n)) if n.fract() == 0.0 => Some(Value::I64(*n as i64)),
+    ("number", Value::String(s)) => match serde_json::from_str(s.trim()) {
+      Ok(n @ (Value::I64(_) | Value::U64(_) | Value::I128(_) | Value::U128(_) | Value::F64(_))) => {
+        Some(n)
+      }
+      _ => None,
+    },
+    ("boolean", Value::String(s)) => match s.trim().to_ascii_lowercase().as_str() {
+      "true" | "yes" | "y" | "on" | "1" => Some(Value::Bool(true)),
+      "false" | "no" | "n" | "off" | "0" => Some(Value::Bool(false)),
+      _ => None,
+    },
+    ("boolean", Value::I64(0) | Value::U64(0)) => Some(Value::Bool(false)),
+    ("boolean", Value::I64(1) | Value::U64(1)) => Some(Value::Bool(true)),
+    ("null", Value::String(s)) if s.is_empty() || s == "null" => Some(Value::Null),
+    (
+      "string",
+      Value::Bool(_)
+      | Value::I64(_)
+      | Value::U64(_)
+      | Value::I128(_)
+      | Value::U128(_)
+      | Value::F64(_),
+    ) => v.key_str().map(Value::String),
+    _ => None,
+  }
+}
+
+fn type_of(v: &Value) -> &'static str {
+  match v {
+    Value::I64(_) | Value::U64(_) | Value::I128(_) | Value::U128(_) => "integer",
+    v => v.type_name(),
+  }
+}
+
+fn as_str(v: &Value) -> Option<&str> {
+  match v {
+    Value::String(s) => Some(s),
+    _ => None,
+  }
+}
+
+fn as_f64(v: &Value) -> Option<f64> {
+  match *v {
+    Value::I64(n) => Some(n as f64),
+    Value::U64(n) => Some(n as f64),
+    Value::I128(n) => Some(n as f64),
+    Value::U128(n) => Some(n as f64),
+    Value::F64(n) => Some(n),
+    _ => None,
+  }
+}
+
+// Reports whether n is a multiple of m, allowing for the rounding error in
+// decimal fractions like 0.1 that floating point can't represent exactly.
+fn is_multiple(n: f64, m: f64) -> bool {
+  let quotient = n / m;
+  (quotient - quotient.round()).abs() <= quotient.abs().max(1.0) * 8.0 * f64::EPSILON
+}
+
+fn as_usize(v: &Value) -> Option<usize> {
+  as_f64(v).filter(|n| *n >= 0.0).map(|n| n as usize)
+}
+
+// Compares values the way JSON Schema does, where numbers are equal if they're
+// mathematically equal and the order of object properties doesn't matter.
+pub fn json_eq(a: &Value, b: &Value) -> bool {
+  match (a, b) {
+    (Value::Seq(a), Value::Seq(b)) => {
+      a.len() == b.len() && a.iter().zip(b.iter()).all(|(a, b)| json_eq(a, b))
+    }
+    (Value::Map(a), Value::Map(b)) => {
+      a.len() == b.len()
+        && a.iter().all(|(k, v)| match k.key_str() {
+          Some(k) => b.get(&k).is_some_and(|bv| json_eq(v, bv)),
+          None => false,
+        })
+    }
+    _ => match (as_f64(a), as_f64(b)) {
+      (Some(x), Some(y)) => x == y,
+      _ => a == b,
+    },
+  }
+}
@@ -0,0 +1,814 @@
+// The stages of jyt's pipeline, each an Output that does one thing to the
+// documents passing through it on their way to the next. Most of them pass
+// documents straight through when their option isn't given, so that the
+// pipeline can be built the same way every time.
+
+use std::collections::VecDeque;
+use std::error::Error;
+use std::fmt;
+use std::str;
+
+use serde::Deserialize;
+
+use crate::digest;
+use crate::grep::Grep;
+use crate::json;
+use crate::output::Output;
+use crate::path;
+use crate::progress;
+use crate::schema::{self, Inference, ValidationError, Validator};
+use crate::stats::{Histogram, Stats};
+use crate::timing;
+use crate::transform::{self, Aggregate, Transform};
+use crate::value::{ControlChars, DuplicateKeys, Value};
+use crate::yaml;
+use crate::{Classified, Format};
+
+// ValidateOutput checks each input document against a schema before passing it
+// on. Invalid documents are still output, so that all violations across the
+// whole input can be reported together at the end.
+pub struct ValidateOutput<'v, O> {
+  validator: Option<&'v Validator>,
+  // The input and its format, for finding where each violation is, when the
+  // documents that reach validation are the documents of the input.
+  source: Option<(&'v [u8], Format)>,
+  output: O,
+  documents: usize,
+  error: ValidationError,
+}
+
+impl<'v, O> ValidateOutput<'v, O> {
+  pub fn new(
+    validator: Option<&'v Validator>,
+    source: Option<(&'v [u8], Format)>,
+    output: O,
+  ) -> Self {
+    ValidateOutput {
+      validator,
+      source,
+      output,
+      documents: 0,
+      error: ValidationError::default(),
+    }
+  }
+
+  fn locate(&mut self) {
+    if let Some(positions) = self.source.and_then(positions) {
+      self.error.locate(&positions);
+    }
+  }
+}
+
+// Returns where each value of an input starts, for the formats where jyt can
+// tell.
+fn positions((input, from): (&[u8], Format)) -> Option<schema::Positions> {
+  match from {
+    Format::Json => json::positions(input),
+    Format::Yaml => str::from_utf8(input)
+      .ok()
+      .and_then(|input| yaml::positions(input).ok()),
+    _ => None,
+  }
+}
+
+impl<'v, O> Output for ValidateOutput<'v, O>
+where
+  O: Output,
+{
+  fn transcode_from<'de, D, E>(&mut self, de: D) -> Result<(), Box<dyn Error>>
+  where
+    D: serde::de::Deserializer<'de, Error = E>,
+    E: serde::de::Error + 'static,
+  {
+    let validator = match self.validator {
+      Some(validator) => validator,
+      None => return self.output.transcode_from(de),
+    };
+    let doc = Value::deserialize(de)?;
+    let violations = validator.validate(&doc);
+    self.error.add(self.documents, violations);
+    self.documents += 1;
+    self.output.transcode_from(doc)
+  }
+
+  fn finish(&mut self) -> Result<(), Box<dyn Error>> {
+    self.output.finish()?;
+    if self.error.is_empty() {
+      return Ok(());
+    }
+    self.locate();
+    Err(std::mem::take(&mut self.error))?
+  }
+
+  fn is_done(&self) -> bool {
+    self.output.is_done()
+  }
+}
+
+// DuplicateKeysOutput resolves duplicate keys in each input document according
+// to a policy. Without one, duplicates pass through to the output as the input
+// parser produced them.
+pub struct DuplicateKeysOutput<'i, O> {
+  policy: Option<DuplicateKeys>,
+  // The input and its format, as for ValidateOutput, for finding where a
+  // duplicate key is.
+  source: Option<(&'i [u8], Format)>,
+  documents: usize,
+  output: O,
+}
+
+impl<'i, O> DuplicateKeysOutput<'i, O> {
+  pub fn new(policy: Option<DuplicateKeys>, source: Option<(&'i [u8], Format)>, output: O) -> Self {
+    DuplicateKeysOutput {
+      policy,
+      source,
+      documents: 0,
+      output,
+    }
+  }
+}
+
+impl<'i, O> Output for DuplicateKeysOutput<'i, O>
+where
+  O: Output,
+{
+  fn transcode_from<'de, D, E>(&mut self, de: D) -> Result<(), Box<dyn Error>>
+  where
+    D: serde::de::Deserializer<'de, Error = E>,
+    E: serde::de::Error + 'static,
+  {
+    let policy = match self.policy {
+      Some(policy) => policy,
+      None => return self.output.transcode_from(de),
+    };
+    let mut doc = Value::deserialize(de)?;
+    if let Err(path) = doc.dedup_keys(policy) {
+      // The error is about the first duplicate in the input, which is always
+      // the second value at its path.
+      let position = self.source.and_then(positions).and_then(|positions| {
+        positions
+          .get(&(self.documents, path.clone()))?
+          .get(1)
+          .copied()
+      });
+      Err(DuplicateKey {
+        document: self.documents,
+        path,
+        position,
+      })?;
+    }
+    self.documents += 1;
+    self.output.transcode_from(doc)
+  }
+
+  fn finish(&mut self) -> Result<(), Box<dyn Error>> {
+    self.output.finish()
+  }
+
+  fn is_done(&self) -> bool {
+    self.output.is_done()
+  }
+}
+
+// DuplicateKey is the error for a duplicate key with '--duplicate-keys error',
+// which gives its position the way that a violation of a schema does.
+#[derive(Debug)]
+pub struct DuplicateKey {
+  pub document: usize,
+  pub path: String,
+  pub position: Option<(usize, usize)>,
+}
+
+impl fmt::Display for DuplicateKey {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    write!(f, "document {}, {}", self.document, self.path)?;
+    if let Some((line, column)) = self.position {
+      write!(f, " (line {}, column {})", line, column)?;
+    }
+    write!(f, ": duplicate key")
+  }
+}
+
+impl Error for DuplicateKey {}
+
+// ControlCharsOutput escapes or rejects the control characters in the strings of
+// each output document.
+pub struct ControlCharsOutput<O> {
+  pub policy: Option<ControlChars>,
+  pub output: O,
+}
+
+impl<O> Output for ControlCharsOutput<O>
+where
+  O: Output,
+{
+  fn transcode_from<'de, D, E>(&mut self, de: D) -> Result<(), Box<dyn Error>>
+  where
+    D: serde::de::Deserializer<'de, Error = E>,
+    E: serde::de::Error + 'static,
+  {
+    let policy = match self.policy {
+      Some(policy) => policy,
+      None => return self.output.transcode_from(de),
+    };
+    let mut doc = Value::deserialize(de)?;
+    doc.control_chars(policy)?;
+    self.output.transcode_from(doc)
+  }
+
+  fn finish(&mut self) -> Result<(), Box<dyn Error>> {
+    self.output.finish()
+  }
+
+  fn is_done(&self) -> bool {
+    self.output.is_done()
+  }
+}
+
+// MaxDepthOutput truncates each output document to a maximum depth. Truncation
+// is a property of the output rather than a transform in its own right, so it
+// comes after aggregation and sees the final form of each document.
+pub struct MaxDepthOutput<O> {
+  pub depth: Option<usize>,
+  pub output: O,
+}
+
+impl<O> Output for MaxDepthOutput<O>
+where
+  O: Output,
+{
+  fn transcode_from<'de, D, E>(&mut self, de: D) -> Result<(), Box<dyn Error>>
+  where
+    D: serde::de::Deserializer<'de, Error = E>,
+    E: serde::de::Error + 'static,
+  {
+    let depth = match self.depth {
+      Some(depth) => depth,
+      None => return self.output.transcode_from(de),
+    };
+    let mut doc = Value::deserialize(de)?;
+    transform::truncate(&mut doc, depth);
+    self.output.transcode_from(doc)
+  }
+
+  fn finish(&mut self) -> Result<(), Box<dyn Error>> {
+    self.output.finish()
+  }
+
+  fn is_done(&self) -> bool {
+    self.output.is_done()
+  }
+}
+
+// CastOutput converts the values in each input document to the types that a
+// schema declares for them, ahead of validation so that a schema can describe
+// what the input should look like once it's been cast.
+pub struct CastOutput<'v, O> {
+  pub caster: Option<&'v Validator>,
+  pub output: O,
+}
+
+impl<'v, O> Output for CastOutput<'v, O>
+where
+  O: Output,
+{
+  fn transcode_from<'de, D, E>(&mut self, de: D) -> Result<(), Box<dyn Error>>
+  where
+    D: serde::de::Deserializer<'de, Error = E>,
+    E: serde::de::Error + 'static,
+  {
+    let caster = match self.caster {
+      Some(caster) => caster,
+      None => return self.output.transcode_from(de),
+    };
+    let mut doc = Value::deserialize(de)?;
+    caster.cast(&mut doc);
+    self.output.transcode_from(doc)
+  }
+
+  fn finish(&mut self) -> Result<(), Box<dyn Error>> {
+    self.output.finish()
+  }
+
+  fn is_done(&self) -> bool {
+    self.output.is_done()
+  }
+}
+
+pub struct TransformOutput<'t, O> {
+  pub transforms: &'t [Transform],
+  pub output: O,
+}
+
+impl<'t, O> Output for TransformOutput<'t, O>
+where
+  O: Output,
+{
+  fn transcode_from<'de, D, E>(&mut self, de: D) -> Result<(), Box<dyn Error>>
+  where
+    D: serde::de::Deserializer<'de, Error = E>,
+    E: serde::de::Error + 'static,
+  {
+    if self.transforms.is_empty() {
+      return self.output.transcode_from(de);
+    }
+    let mut doc = Value::deserialize(de)?;
+    for transform in self.transforms {
+      transform.apply(&mut doc)?;
+    }
+    self.output.transcode_from(doc)
+  }
+
+  fn finish(&mut self) -> Result<(), Box<dyn Error>> {
+    self.output.finish()
+  }
+
+  fn is_done(&self) -> bool {
+    self.output.is_done()
+  }
+}
+
+// AggregateOutput collects all of the documents of the input, and outputs the
+// result of applying each aggregate to them in turn.
+pub struct AggregateOutput<'a, O> {
+  aggregates: &'a [Aggregate],
+  docs: Vec<Value>,
+  output: O,
+}
+
+impl<'a, O> AggregateOutput<'a, O> {
+  pub fn new(aggregates: &'a [Aggregate], output: O) -> Self {
+    AggregateOutput {
+      aggregates,
+      docs: Vec::new(),
+      output,
+    }
+  }
+}
+
+impl<'a, O> Output for AggregateOutput<'a, O>
+where
+  O: Output,
+{
+  fn transcode_from<'de, D, E>(&mut self, de: D) -> Result<(), Box<dyn Error>>
+  where
+    D: serde::de::Deserializer<'de, Error = E>,
+    E: serde::de::Error + 'static,
+  {
+    if self.aggregates.is_empty() {
+      return self.output.transcode_from(de);
+    }
+    self.docs.push(Value::deserialize(de)?);
+    Ok(())
+  }
+
+  fn finish(&mut self) -> Result<(), Box<dyn Error>> {
+    if !self.aggregates.is_empty() {
+      let mut docs = std::mem::take(&mut self.docs);
+      for aggregate in self.aggregates {
+        docs = aggregate.apply(docs)?;
+      }
+      for doc in docs {
+        self.output.transcode_from(doc)?;
+      }
+    }
+    self.output.finish()
+  }
+
+  fn is_done(&self) -> bool {
+    self.output.is_done()
+  }
+}
+
+// Summary is something computed over all of the input documents, like a set
+// of statistics or an inferred schema.
+pub trait Summary {
+  fn add(&mut self, doc: &Value);
+  fn to_value(&self) -> Value;
+}
+
+impl Summary for Stats {
+  fn add(&mut self, doc: &Value) {
+    Stats::add(self, doc)
+  }
+
+  fn to_value(&self) -> Value {
+    Stats::to_value(self)
+  }
+}
+
+impl Summary for Histogram {
+  fn add(&mut self, doc: &Value) {
+    Histogram::add(self, doc)
+  }
+
+  fn to_value(&self) -> Value {
+    Histogram::to_value(self)
+  }
+}
+
+impl Summary for Inference {
+  fn add(&mut self, doc: &Value) {
+    Inference::add(self, doc)
+  }
+
+  fn to_value(&self) -> Value {
+    self.to_schema()
+  }
+}
+
+// SummaryOutput computes a summary of the input documents, and outputs it in
+// place of the documents themselves.
+pub struct SummaryOutput<S, O> {
+  pub summary: S,
+  pub output: O,
+}
+
+impl<S, O> Output for SummaryOutput<S, O>
+where
+  S: Summary,
+  O: Output,
+{
+  fn transcode_from<'de, D, E>(&mut self, de: D) -> Result<(), Box<dyn Error>>
+  where
+    D: serde::de::Deserializer<'de, Error = E>,
+    E: serde::de::Error + 'static,
+  {
+    let doc = Value::deserialize(de)?;
+    self.summary.add(&doc);
+    Ok(())
+  }
+
+  fn finish(&mut self) -> Result<(), Box<dyn Error>> {
+    self.output.transcode_from(self.summary.to_value())?;
+    self.output.finish()
+  }
+}
+
+// GrepOutput searches the input documents, and outputs a report of each match
+// in place of the documents themselves.
+pub struct GrepOutput<O> {
+  pub grep: Grep,
+  pub output: O,
+}
+
+impl<O> Output for GrepOutput<O>
+where
+  O: Output,
+{
+  fn transcode_from<'de, D, E>(&mut self, de: D) -> Result<(), Box<dyn Error>>
+  where
+    D: serde::de::Deserializer<'de, Error = E>,
+    E: serde::de::Error + 'static,
+  {
+    let doc = Value::deserialize(de)?;
+    for report in self.grep.search(&doc) {
+      self.output.transcode_from(report)?;
+    }
+    Ok(())
+  }
+
+  fn finish(&mut self) -> Result<(), Box<dyn Error>> {
+    self.output.finish()
+  }
+
+  fn is_done(&self) -> bool {
+    self.output.is_done()
+  }
+}
+
+// HashOutput outputs a digest of the canonical JSON form of each input document
+// in place of the document itself, so that equivalent documents can be compared
+// regardless of their format or formatting.
+pub struct HashOutput<O> {
+  pub algorithm: digest::Algorithm,
+  pub output: O,
+}
+
+impl<O> Output for HashOutput<O>
+where
+  O: Output,
+{
+  fn transcode_from<'de, D, E>(&mut self, de: D) -> Result<(), Box<dyn Error>>
+  where
+    D: serde::de::Deserializer<'de, Error = E>,
+    E: serde::de::Error + 'static,
+  {
+    let doc = Value::deserialize(de)?;
+    let canonical = serde_json::to_vec(&doc.canonical())?;
+    let digest = self.algorithm.hex_digest(&canonical);
+    self.output.transcode_from(Value::String(digest))
+  }
+
+  fn finish(&mut self) -> Result<(), Box<dyn Error>> {
+    self.output.finish()
+  }
+
+  fn is_done(&self) -> bool {
+    self.output.is_done()
+  }
+}
+
+#[derive(Copy, Clone)]
+pub enum Limit {
+  Head(usize),
+  Tail(usize),
+}
+
+// LimitOutput passes through only the first or last N documents of the input.
+// When the input is a single array, it limits the elements of that array
+// instead, to match how people tend to think of a JSON array of records. Since
+// we can't tell which case applies until we see a second document (or the end
+// of the input), the first document is always held back.
+pub struct LimitOutput<O> {
+  limit: Option<Limit>,
+  output: O,
+  first: Option<Value>,
+  seen: usize,
+  emitted: usize,
+  tail: VecDeque<Value>,
+}
+
+impl<O> LimitOutput<O>
+where
+  O: Output,
+{
+  pub fn new(limit: Option<Limit>, output: O) -> Self {
+    LimitOutput {
+      limit,
+      output,
+      first: None,
+      seen: 0,
+      emitted: 0,
+      tail: VecDeque::new(),
+    }
+  }
+
+  fn accept(&mut self, doc: Value) -> Result<(), Box<dyn Error>> {
+    match self.limit {
+      Some(Limit::Head(n)) if self.emitted < n => {
+        self.emitted += 1;
+        self.output.transcode_from(doc)?;
+      }
+      Some(Limit::Head(_)) => {}
+      Some(Limit::Tail(n)) => {
+        if self.tail.len() == n {
+          self.tail.pop_front();
+        }
+        if n > 0 {
+          self.tail.push_back(doc);
+        }
+      }
+      None => self.output.transcode_from(doc)?,
+    }
+    Ok(())
+  }
+}
+
+impl<O> Output for LimitOutput<O>
+where
+  O: Output,
+{
+  fn transcode_from<'de, D, E>(&mut self, de: D) -> Result<(), Box<dyn Error>>
+  where
+    D: serde::de::Deserializer<'de, Error = E>,
+    E: serde::de::Error + 'static,
+  {
+    if self.limit.is_none() {
+      return self.output.transcode_from(de);
+    }
+    let doc = Value::deserialize(de)?;
+    self.seen += 1;
+    if self.seen == 1 {
+      self.first = Some(doc);
+      return Ok(());
+    }
+    if let Some(first) = self.first.take() {
+      self.accept(first)?;
+    }
+    self.accept(doc)
+  }
+
+  fn finish(&mut self) -> Result<(), Box<dyn Error>> {
+    match self.first.take() {
+      Some(Value::Seq(mut items)) => {
+        match self.limit {
+          Some(Limit::Head(n)) => items.truncate(n),
+          Some(Limit::Tail(n)) => drop(items.drain(..items.len().saturating_sub(n))),
+          None => {}
+        }
+        self.output.transcode_from(Value::Seq(items))?;
+      }
+      Some(doc) => self.accept(doc)?,
+      None => {}
+    }
+    for doc in self.tail.drain(..) {
+      self.output.transcode_from(doc)?;
+    }
+    self.output.finish()
+  }
+
+  fn is_done(&self) -> bool {
+    match self.limit {
+      // Even with one document, we have to see whether there is a second one
+      // to know whether we're limiting documents or array elements.
+      Some(Limit::Head(n)) => (self.seen > 1 && self.emitted >= n) || self.output.is_done(),
+      _ => self.output.is_done(),
+    }
+  }
+}
+
+// TimingOutput times the parsing of each document apart from the rest of its
+// conversion, for --timing. Parsing usually happens as the output pulls values
+// from the parser, so documents are read in full before they go any further.
+pub struct TimingOutput<O> {
+  pub enabled: bool,
+  pub output: O,
+}
+
+impl<O> Output for TimingOutput<O>
+where
+  O: Output,
+{
+  fn transcode_from<'de, D, E>(&mut self, de: D) -> Result<(), Box<dyn Error>>
+  where
+    D: serde::de::Deserializer<'de, Error = E>,
+    E: serde::de::Error + 'static,
+  {
+    if !self.enabled {
+      return self.output.transcode_from(de);
+    }
+    let doc = timing::time(|timing| &mut timing.parse, || Value::deserialize(de))?;
+    timing::record(|timing| timing.documents += 1);
+    timing::time(
+      |timing| &mut timing.convert,
+      || self.output.transcode_from(doc),
+    )
+  }
+
+  fn finish(&mut self) -> Result<(), Box<dyn Error>> {
+    timing::time(|timing| &mut timing.convert, || self.output.finish())
+  }
+
+  fn is_done(&self) -> bool {
+    self.output.is_done()
+  }
+}
+
+// ProgressOutput counts the documents for --progress, and passes them on with
+// the positions of their strings noted.
+pub struct ProgressOutput<O> {
+  pub enabled: bool,
+  pub output: O,
+}
+
+impl<O> Output for ProgressOutput<O>
+where
+  O: Output,
+{
+  fn transcode_from<'de, D, E>(&mut self, de: D) -> Result<(), Box<dyn Error>>
+  where
+    D: serde::de::Deserializer<'de, Error = E>,
+    E: serde::de::Error + 'static,
+  {
+    if !self.enabled {
+      return self.output.transcode_from(de);
+    }
+    self.output.transcode_from(progress::Tracked(de))?;
+    progress::document();
+    Ok(())
+  }
+
+  fn finish(&mut self) -> Result<(), Box<dyn Error>> {
+    self.output.finish()
+  }
+
+  fn is_done(&self) -> bool {
+    self.output.is_done()
+  }
+}
+
+// NthOutput passes through only the document at a given index, skipping past
+// the documents before it without buffering them.
+pub struct NthOutput<O> {
+  nth: Option<usize>,
+  seen: usize,
+  output: O,
+}
+
+impl<O> NthOutput<O> {
+  pub fn new(nth: Option<usize>, output: O) -> Self {
+    NthOutput {
+      nth,
+      seen: 0,
+      output,
+    }
+  }
+}
+
+impl<O> Output for NthOutput<O>
+where
+  O: Output,
+{
+  fn transcode_from<'de, D, E>(&mut self, de: D) -> Result<(), Box<dyn Error>>
+  where
+    D: serde::de::Deserializer<'de, Error = E>,
+    E: serde::de::Error + 'static,
+  {
+    let idx = self.seen;
+    self.seen += 1;
+    match self.nth {
+      Some(n) if idx != n => {
+        serde::de::IgnoredAny::deserialize(de)?;
+        Ok(())
+      }
+      _ => self.output.transcode_from(de),
+    }
+  }
+
+  fn finish(&mut self) -> Result<(), Box<dyn Error>> {
+    match self.nth {
+      Some(n) if self.seen <= n => Err(Classified::input(format!(
+        "cannot select document {}, input has {} documents",
+        n, self.seen
+      )))?,
+      _ => self.output.finish(),
+    }
+  }
+
+  fn is_done(&self) -> bool {
+    matches!(self.nth, Some(n) if self.seen > n) || self.output.is_done()
+  }
+}
+
+// PointerOutput passes through only the values at a path in the first document,
+// for --pointer. It reads the value at a single path straight from the parser,
+// and stops the parse as soon as it has it, so none of the input past the
+// value is read. A path with wildcards or slices needs the whole document.
+pub struct PointerOutput<'p, O> {
+  pointer: Option<&'p path::Path>,
+  done: bool,
+  output: O,
+}
+
+impl<'p, O> PointerOutput<'p, O> {
+  pub fn new(pointer: Option<&'p path::Path>, output: O) -> Self {
+    PointerOutput {
+      pointer,
+      done: false,
+      output,
+    }
+  }
+}
+
+impl<'p, O> Output for PointerOutput<'p, O>
+where
+  O: Output,
+{
+  fn transcode_from<'de, D, E>(&mut self, de: D) -> Result<(), Box<dyn Error>>
+  where
+    D: serde::de::Deserializer<'de, Error = E>,
+    E: serde::de::Error + 'static,
+  {
+    let pointer = match self.pointer {
+      Some(pointer) => pointer,
+      None => return self.output.transcode_from(de),
+    };
+    self.done = true;
+    let values: Vec<Value> = if pointer.is_single() {
+      pointer.extract(de)?.into_iter().collect()
+    } else {
+      let doc = Value::deserialize(de)?;
+      pointer.select(&doc).into_iter().cloned().collect()
+    };
+    // A path with wildcards or slices can match nothing, the way an empty
+    // slice does; only a single path has to be there.
+    if values.is_empty() && pointer.is_single() {
+      Err(Classified::input(format!(
+        "input has no value at {}",
+        pointer
+      )))?;
+    }
+    for value in values {
+      if self.output.is_done() {
+        break;
+      }
+      self.output.transcode_from(value)?;
+    }
+    Ok(())
+  }
+
+  fn finish(&mut self) -> Result<(), Box<dyn Error>> {
+    match self.pointer {
+      Some(pointer) if !self.done => Err(Classified::input(format!(
+        "input has no value at {}",
+        pointer
+      )))?,
+      _ => self.output.finish(),
+    }
+  }
+
+  fn is_done(&self) -> bool {
+    self.done || self.output.is_done()
+  }
+}
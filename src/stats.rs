@@ -0,0 +1,187 @@
+use std::collections::HashMap;
+
+use crate::path::{self, Path};
+use crate::value::{Map, Value};
+
+// The number of entries kept for each "largest" list in the report.
+const TOP_N: usize = 3;
+
+// Stats accumulates a summary of the documents in an input, which is reported
+// as a document of its own so that it can be output in any format.
+#[derive(Default)]
+pub struct Stats {
+  documents: usize,
+  keys: usize,
+  types: [usize; 7],
+  max_depth: usize,
+  longest_strings: Vec<Largest>,
+  largest_arrays: Vec<Largest>,
+}
+
+const TYPE_NAMES: [&str; 7] = [
+  "null", "boolean", "integer", "float", "string", "array", "object",
+];
+
+struct Largest {
+  document: usize,
+  path: String,
+  len: usize,
+}
+
+impl Stats {
+  pub fn add(&mut self, doc: &Value) {
+    self.documents += 1;
+    let mut path = String::new();
+    self.visit(doc, &mut path, 0);
+  }
+
+  fn visit(&mut self, v: &Value, path: &mut String, depth: usize) {
+    self.max_depth = self.max_depth.max(depth);
+    let type_idx = match v {
+      Value::Null => 0,
+      Value::Bool(_) => 1,
+      Value::I64(_) | Value::U64(_) | Value::I128(_) | Value::U128(_) => 2,
+      Value::F64(_) => 3,
+      Value::String(_) => 4,
+      Value::Seq(_) => 5,
+      Value::Map(_) => 6,
+    };
+    self.types[type_idx] += 1;
+
+    let doc = self.documents - 1;
+    match v {
+      Value::String(s) => record(&mut self.longest_strings, doc, path, s.chars().count()),
+      Value::Seq(items) => {
+        record(&mut self.largest_arrays, doc, path, items.len());
+        for (i, item) in items.iter().enumerate() {
+          let len = path.len();
+          path::push_index(path, i);
+          self.visit(item, path, depth + 1);
+          path.truncate(len);
+        }
+      }
+      Value::Map(map) => {
+        self.keys += map.len();
+        for (k, v) in map.iter() {
+          let len = path.len();
+          path::push_key(path, k);
+          self.visit(v, path, depth + 1);
+          path.truncate(len);
+        }
+      }
+      _ => {}
+    }
+  }
+
+  pub fn to_value(&self) -> Value {
+    let mut types = Map::new();
+    for (name, &count) in TYPE_NAMES.iter().zip(self.types.iter()) {
+      if count > 0 {
+        types.push(Value::from(*name), Value::U64(count as u64));
+      }
+    }
+
+    let mut report = Map::new();
+    report.push(Value::from("documents"), Value::U64(self.documents as u64));
+    report.push(Value::from("keys"), Value::U64(self.keys as u64));
+    report.push(Value::from("types"), Value::Map(types));
+    report.push(Value::from("max_depth"), Value::U64(self.max_depth as u64));
+    report.push(
+      Value::from("longest_strings"),
+      largest_value(&self.longest_strings),
+    );
+    report.push(
+      Value::from("largest_arrays"),
+      largest_value(&self.largest_arrays),
+    );
+    Value::Map(report)
+  }
+}
+
+// Adds an entry to a "largest" list if it's large enough to make the cut,
+// keeping the list sorted from largest to smallest.
+fn record(list: &mut Vec<Largest>, document: usize, path: &str, len: usize) {
+  if list.len() == TOP_N && list.last().is_some_and(|last| last.len >= len) {
+    return;
+  }
+  let idx = list.iter().position(|l| l.len < len).unwrap_or(list.len());
+  list.insert(
+    idx,
+    Largest {
+      document,
+      path: if path.is_empty() {
+        ".".to_owned()
+      } else {
+        path.to_owned()
+      },
+      len,
+    },
+  );
+  list.truncate(TOP_N);
+}
+
+fn largest_value(list: &[Largest]) -> Value {
+  Value::Seq(
+    list
+      .iter()
+      .map(|l| {
+        let mut entry = Map::new();
+        entry.push(Value::from("document"), Value::U64(l.document as u64));
+        entry.push(Value::from("path"), Value::from(l.path.as_str()));
+        entry.push(Value::from("length"), Value::U64(l.len as u64));
+        Value::Map(entry)
+      })
+      .collect(),
+  )
+}
+
+// Histogram counts how often each distinct value appears at a path across all
+// of the documents in an input. Values are compared by their canonical JSON
+// form, so that 1 and 1.0 count as the same value.
+pub struct Histogram {
+  path: Path,
+  index: HashMap<String, usize>,
+  counts: Vec<(Value, usize)>,
+}
+
+impl Histogram {
+  pub fn new(path: Path) -> Self {
+    Histogram {
+      path,
+      index: HashMap::new(),
+      counts: Vec::new(),
+    }
+  }
+
+  pub fn add(&mut self, doc: &Value) {
+    for v in self.path.select(doc) {
+      let v = v.canonical();
+      let key = serde_json::to_string(&v).unwrap_or_default();
+      match self.index.get(&key) {
+        Some(&idx) => self.counts[idx].1 += 1,
+        None => {
+          self.index.insert(key, self.counts.len());
+          self.counts.push((v, 1));
+        }
+      }
+    }
+  }
+
+  // Reports the values from most to least frequent, with values of equal
+  // frequency in the order they first appeared.
+  pub fn to_value(&self) -> Value {
+    let mut counts: Vec<&(Value, usize)> = self.counts.iter().collect();
+    counts.sort_by_key(|(_, count)| std::cmp::Reverse(*count));
+    Value::Seq(
+      counts
+        .into_iter()
+        .map(|(v, count)| {
+          let mut entry = Map::new();
+          entry.push(Value::from("value"), v.clone());
+          entry.push(Value::from("count"), Value::U64(*count as u64));
+          Value::Map(entry)
+        })
+        .collect(),
+    )
+  }
+}
@@ -0,0 +1,117 @@
+use std::io::{self, Write};
+
+use unicode_width::UnicodeWidthStr;
+
+use crate::csv;
+use crate::value::{Map, Value};
+
+// Writes rows as an aligned table with box-drawing borders, for reading on a
+// terminal. Cells wider than the maximum width are truncated with an ellipsis,
+// and numbers are aligned to the right.
+pub fn write<W>(w: &mut W, columns: &[String], rows: &[Map], max_width: usize) -> io::Result<()>
+where
+  W: Write,
+{
+  if columns.is_empty() {
+    return Ok(());
+  }
+  let header: Vec<Cell> = columns
+    .iter()
+    .map(|c| Cell::new(c, false, max_width))
+    .collect();
+  let body: Vec<Vec<Cell>> = rows
+    .iter()
+    .map(|row| {
+      columns
+        .iter()
+        .map(|c| match row.get(c) {
+          Some(v) => Cell::new(&csv::field(v), is_number(v), max_width),
+          None => Cell::new("", false, max_width),
+        })
+        .collect()
+    })
+    .collect();
+
+  let mut widths: Vec<usize> = header.iter().map(|c| c.width).collect();
+  for row in &body {
+    for (width, cell) in widths.iter_mut().zip(row) {
+      *width = (*width).max(cell.width);
+    }
+  }
+
+  rule(w, &widths, ['┌', '┬', '┐'])?;
+  line(w, &widths, &header)?;
+  rule(w, &widths, ['├', '┼', '┤'])?;
+  for row in &body {
+    line(w, &widths, row)?;
+  }
+  rule(w, &widths, ['└', '┴', '┘'])
+}
+
+struct Cell {
+  text: String,
+  width: usize,
+  right: bool,
+}
+
+impl Cell {
+  fn new(text: &str, right: bool, max_width: usize) -> Self {
+    // Line breaks and tabs would throw off the alignment of the whole table.
+    let mut text = text
+      .replace('\n', "\\n")
+      .replace('\r', "\\r")
+      .replace('\t', "\\t");
+    if text.width() > max_width {
+      let mut truncated = String::new();
+      for c in text.chars() {
+        if truncated.width() + c.to_string().width() + 1 > max_width {
+          break;
+        }
+        truncated.push(c);
+      }
+      truncated.push('…');
+      text = truncated;
+    }
+    Cell {
+      width: text.width(),
+      text,
+      right,
+    }
+  }
+}
+
+fn rule<W>(w: &mut W, widths: &[usize], [left, mid, right]: [char; 3]) -> io::Result<()>
+where
+  W: Write,
+{
+  write!(w, "{}", left)?;
+  for (i, width) in widths.iter().enumerate() {
+    if i > 0 {
+      write!(w, "{}", mid)?;
+    }
+    write!(w, "{}", "─".repeat(width + 2))?;
+  }
+  writeln!(w, "{}", right)
+}
+
+fn line<W>(w: &mut W, widths: &[usize], cells: &[Cell]) -> io::Result<()>
+where
+  W: Write,
+{
+  write!(w, "│")?;
+  for (width, cell) in widths.iter().zip(cells) {
+    let pad = " ".repeat(width - cell.width);
+    match cell.right {
+      true => write!(w, " {}{} │", pad, cell.text)?,
+      false => write!(w, " {}{} │", cell.text, pad)?,
+    }
+  }
+  writeln!(w)
+}
+
+fn is_number(v: &Value) -> bool {
+  matches!(
+    v,
+    Value::I64(_) | Value::U64(_) | Value::I128(_) | Value::U128(_) | Value::F64(_)
+  )
+}
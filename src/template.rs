@@ -0,0 +1,323 @@
+use std::error::Error;
+use std::io::Write;
+
+use crate::path::Path;
+use crate::value::Value;
+use crate::vars;
+
+// Template is a small Handlebars-like template language for rendering
+// documents as arbitrary text. It supports the following tags:
+//
+//   {{ .path }}                     Value at a path relative to the current
+//                                   context (strings are inserted as they
+//                                   are, and other values as compact JSON),
+//                                   with &<>"'`= escaped for HTML
+//   {{{ .path }}}                   The same value without escaping, which
+//                                   works for {{{@key}}} too
+//   {{#each .path}} ... {{/each}}   Renders the block once for each element of
+//                                   an array or value of an object
+//   {{#if .path}} ... {{else}} ... {{/if}}
+//                                   Renders the first block if the value is
+//                                   present and not false, null, or empty
+//   {{@key}} {{@index}}             Key or index of the current element within
+//                                   an {{#each}} block
+//   {{ $name }} {{ $name.path }}    Value of a variable from --arg or
+//                                   --argjson, or a value within it, which
+//                                   also works in place of a path in
+//                                   {{#each}} and {{#if}}
+//   {{! comment }}                  Renders nothing
+//
+// A "~" just inside a tag, as in "{{~" or "~}}", removes all whitespace between
+// the tag and the adjacent text, which helps with line-oriented output.
+pub struct Template(Vec<Node>);
+
+// The bool of an Insert or Key is whether to escape the text for HTML.
+enum Node {
+  Text(String),
+  Insert(Ref, bool),
+  Key(bool),
+  Index,
+  Each(Ref, Vec<Node>),
+  If(Ref, Vec<Node>, Vec<Node>),
+}
+
+// Ref is what a tag refers to: a path from the current context, or the value
+// of a variable, which is looked up once, when the template is parsed.
+enum Ref {
+  Path(Path),
+  Value(Value),
+}
+
+impl Ref {
+  fn select<'v>(&'v self, ctx: &'v Value) -> Vec<&'v Value> {
+    match self {
+      Ref::Path(path) => path.select(ctx),
+      Ref::Value(v) => vec![v],
+    }
+  }
+}
+
+impl Template {
+  pub fn parse(src: &str) -> Result<Self, String> {
+    let mut parser = Parser { src, pos: 0 };
+    let (nodes, end) = parser.parse_nodes()?;
+    match end {
+      None => Ok(Template(nodes)),
+      Some(tag) => Err(parser.error(&format!("unexpected {{{{{}}}}}", tag))),
+    }
+  }
+
+  pub fn render<W>(&self, w: &mut W, doc: &Value) -> Result<(), Box<dyn Error>>
+  where
+    W: Write,
+  {
+    let ctx = Context {
+      value: doc,
+      key: None,
+      index: None,
+    };
+    render_nodes(w, &self.0, &ctx)
+  }
+}
+
+struct Parser<'s> {
+  src: &'s str,
+  pos: usize,
+}
+
+impl<'s> Parser<'s> {
+  // Parses nodes up to the end of the input or a closing tag ("else", "/each",
+  // or "/if"), which is returned for the caller to validate.
+  fn parse_nodes(&mut self) -> Result<(Vec<Node>, Option<String>), String> {
+    let mut nodes = Vec::new();
+    loop {
+      let rest = &self.src[self.pos..];
+      let start = match rest.find("{{") {
+        None => {
+          push_text(&mut nodes, rest);
+          self.pos = self.src.len();
+          return Ok((nodes, None));
+        }
+        Some(start) => start,
+      };
+      // A tag in three braces inserts its value without escaping it.
+      let raw = rest[start..].starts_with("{{{");
+      let (open, close) = match raw {
+        true => ("{{{", "}}}"),
+        false => ("{{", "}}"),
+      };
+      let end = match rest[start..].find(close) {
+        None => return Err(self.error("unterminated tag")),
+        Some(end) => start + end,
+      };
+
+      let mut text = &rest[..start];
+      let mut tag = &rest[start + open.len()..end];
+      if let Some(trimmed) = tag.strip_prefix('~') {
+        tag = trimmed;
+        text = text.trim_end();
+      }
+      push_text(&mut nodes, text);
+      let trim_after = tag.ends_with('~');
+      if trim_after {
+        tag = &tag[..tag.len() - 1];
+      }
+      self.pos += end + close.len();
+      if trim_after {
+        let rest = &self.src[self.pos..];
+        self.pos += rest.len() - rest.trim_start().len();
+      }
+
+      let tag = tag.trim();
+      if raw {
+        match tag {
+          "@key" => nodes.push(Node::Key(false)),
+          path => nodes.push(Node::Insert(self.parse_ref(path)?, false)),
+        }
+        continue;
+      }
+      if tag.starts_with('!') {
+        continue;
+      }
+      match tag.split_once(char::is_whitespace) {
+        Some(("#each", path)) => {
+          let path = self.parse_ref(path)?;
+          let (body, end) = self.parse_nodes()?;
+          self.expect_end(end, "/each")?;
+          nodes.push(Node::Each(path, body));
+        }
+        Some(("#if", path)) => {
+          let path = self.parse_ref(path)?;
+          let (then, end) = self.parse_nodes()?;
+          let otherwise = match end.as_deref() {
+            Some("else") => {
+              let (otherwise, end) = self.parse_nodes()?;
+              self.expect_end(end, "/if")?;
+              otherwise
+            }
+            _ => {
+              self.expect_end(end, "/if")?;
+              vec![]
+            }
+          };
+          nodes.push(Node::If(path, then, otherwise));
+        }
+        _ => match tag {
+          "else" | "/each" | "/if" => return Ok((nodes, Some(tag.to_owned()))),
+          "@key" => nodes.push(Node::Key(true)),
+          "@index" => nodes.push(Node::Index),
+          path => nodes.push(Node::Insert(self.parse_ref(path)?, true)),
+        },
+      }
+    }
+  }
+
+  fn parse_ref(&self, path: &str) -> Result<Ref, String> {
+    match vars::lookup(path.trim()) {
+      Some(value) => value.map(Ref::Value).map_err(|err| self.error(&err)),
+      None => path
+        .trim()
+        .parse()
+        .map(Ref::Path)
+        .map_err(|err: String| self.error(&err)),
+    }
+  }
+
+  fn expect_end(&self, end: Option<String>, want: &str) -> Result<(), String> {
+    match end {
+      Some(end) if end == want => Ok(()),
+      _ => Err(self.error(&format!("missing {{{{{}}}}}", want))),
+    }
+  }
+
+  fn error(&self, msg: &str) -> String {
+    let line = self.src[..self.pos].matches('\n').count() + 1;
+    format!("template line {}: {}", line, msg)
+  }
+}
+
+fn push_text(nodes: &mut Vec<Node>, text: &str) {
+  if !text.is_empty() {
+    nodes.push(Node::Text(text.to_owned()));
+  }
+}
+
+struct Context<'v> {
+  value: &'v Value,
+  key: Option<&'v Value>,
+  index: Option<usize>,
+}
+
+fn render_nodes<W>(w: &mut W, nodes: &[Node], ctx: &Context) -> Result<(), Box<dyn Error>>
+where
+  W: Write,
+{
+  for node in nodes {
+    match node {
+      Node::Text(text) => w.write_all(text.as_bytes())?,
+      Node::Insert(path, escape) => {
+        for v in path.select(ctx.value) {
+          write_value(w, v, *escape)?;
+        }
+      }
+      Node::Key(escape) => {
+        if let Some(key) = ctx.key {
+          write_value(w, key, *escape)?;
+        }
+      }
+      Node::Index => {
+        if let Some(index) = ctx.index {
+          write!(w, "{}", index)?;
+        }
+      }
+      Node::Each(path, body) => {
+        for v in path.select(ctx.value) {
+          match v {
+            Value::Seq(items) => {
+              for (i, item) in items.iter().enumerate() {
+                let ctx = Context {
+                  value: item,
+                  key: None,
+                  index: Some(i),
+                };
+                render_nodes(w, body, &ctx)?;
+              }
+            }
+            Value::Map(map) => {
+              for (i, (k, v)) in map.iter().enumerate() {
+                let ctx = Context {
+                  value: v,
+                  key: Some(k),
+                  index: Some(i),
+                };
+                render_nodes(w, body, &ctx)?;
+              }
+            }
+            Value::Null => {}
+            v => {
+              let ctx = Context {
+                value: v,
+                key: None,
+                index: None,
+              };
+              render_nodes(w, body, &ctx)?;
+            }
+          }
+        }
+      }
+      Node::If(path, then, otherwise) => {
+        let truthy = path.select(ctx.value).into_iter().any(is_truthy);
+        render_nodes(w, if truthy { then } else { otherwise }, ctx)?;
+      }
+    }
+  }
+  Ok(())
+}
+
+fn is_truthy(v: &Value) -> bool {
+  match v {
+    Value::Null | Value::Bool(false) => false,
+    Value::String(s) => !s.is_empty(),
+    Value::Seq(items) => !items.is_empty(),
+    Value::Map(map) => !map.is_empty(),
+    _ => true,
+  }
+}
+
+fn write_value<W>(w: &mut W, v: &Value, escape: bool) -> Result<(), Box<dyn Error>>
+where
+  W: Write,
+{
+  let json;
+  let text = match v {
+    Value::Null => return Ok(()),
+    Value::String(s) => s,
+    v => {
+      json = serde_json::to_string(v)?;
+      &json
+    }
+  };
+  if !escape {
+    w.write_all(text.as_bytes())?;
+    return Ok(());
+  }
+  // These are the characters that Handlebars escapes.
+  let mut last = 0;
+  for (i, c) in text.char_indices() {
+    let entity = match c {
+      '&' => "&amp;",
+      '<' => "&lt;",
+      '>' => "&gt;",
+      '"' => "&quot;",
+      '\'' => "&#x27;",
+      '`' => "&#x60;",
+      '=' => "&#x3D;",
+      _ => continue,
+    };
+    w.write_all(&text.as_bytes()[last..i])?;
+    w.write_all(entity.as_bytes())?;
+    last = i + 1;
+  }
+  w.write_all(&text.as_bytes()[last..])?;
+  Ok(())
+}
@@ -0,0 +1,76 @@
+use std::error::Error;
+use std::fs;
+use std::io::{self, Write};
+use std::path::PathBuf;
+
+use serde::Deserialize;
+use structopt::StructOpt;
+
+use crate::output::Output;
+use crate::value::Value;
+use crate::{detect_format, format_from_extension, transcode_all_input, warn, ReadOptions};
+
+// TextconvOpt holds the options for 'jyt git-textconv', which shows a file to
+// git diff as pretty JSON in a stable form, so that a diff between two
+// versions of it shows what changed in the data rather than in its layout.
+#[derive(StructOpt)]
+#[structopt(name = "jyt git-textconv", verbatim_doc_comment)]
+/// Show a file as stable, pretty JSON for git diff
+///
+/// The file is read in the format of its extension, or the one detected from
+/// its contents, and each of its documents is written to standard output as
+/// indented JSON in the same canonical form as --hash, with object keys in
+/// sorted order and floats with integer values as integers. Reordering keys or
+/// reformatting the file doesn't change its output, so only changes to the
+/// data show up in a diff. A file that can't be read is written out as it is.
+///
+/// To have git diff TOML and YAML files this way:
+///
+///   git config diff.jyt.textconv 'jyt git-textconv'
+///   echo '*.toml diff=jyt' >> .gitattributes
+///   echo '*.yaml diff=jyt' >> .gitattributes
+pub struct TextconvOpt {
+  #[structopt(name = "file", help = "File to show", parse(from_os_str))]
+  file: PathBuf,
+}
+
+pub fn textconv(opt: TextconvOpt) -> Result<(), Box<dyn Error>> {
+  let input = fs::read(&opt.file)?;
+  let from = format_from_extension(&opt.file).or_else(|| detect_format(&input));
+  let mut output = Vec::new();
+  let result = match from {
+    Some(from) => transcode_all_input(
+      &input,
+      from,
+      &ReadOptions::default(),
+      TextconvOutput { w: &mut output },
+    ),
+    None => Err("cannot parse it as any known format".into()),
+  };
+  // A diff that can't show one version of a file at all is worse than one that
+  // shows its raw text.
+  if let Err(err) = result {
+    warn::warn(&format!("{} is shown as is: {}", opt.file.display(), err));
+    output = input;
+  }
+  io::stdout().write_all(&output)?;
+  Ok(())
+}
+
+// TextconvOutput writes each document as pretty JSON in canonical form.
+struct TextconvOutput<'w> {
+  w: &'w mut Vec<u8>,
+}
+
+impl Output for TextconvOutput<'_> {
+  fn transcode_from<'de, D, E>(&mut self, de: D) -> Result<(), Box<dyn Error>>
+  where
+    D: serde::de::Deserializer<'de, Error = E>,
+    E: serde::de::Error + 'static,
+  {
+    let doc = Value::deserialize(de)?;
+    serde_json::to_writer_pretty(&mut *self.w, &doc.canonical())?;
+    self.w.push(b'\n');
+    Ok(())
+  }
+}
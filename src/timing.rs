@@ -0,0 +1,96 @@
+use std::io::{self, Write};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+// Timing is how long each part of a conversion took, along with how much it
+// handled, for --timing. Like warnings, it's global state, so that each part
+// can add to it without every layer of jyt passing it around.
+#[derive(Default)]
+pub struct Timing {
+  pub read: Duration,
+  pub parse: Duration,
+  pub convert: Duration,
+  pub bytes_in: Option<usize>,
+  pub documents: usize,
+}
+
+static TIMING: Mutex<Option<Timing>> = Mutex::new(None);
+
+pub fn enable() {
+  *TIMING.lock().unwrap_or_else(|err| err.into_inner()) = Some(Timing::default());
+}
+
+pub fn is_enabled() -> bool {
+  TIMING
+    .lock()
+    .unwrap_or_else(|err| err.into_inner())
+    .is_some()
+}
+
+// Updates the timing, if --timing turned it on.
+pub fn record<F>(f: F)
+where
+  F: FnOnce(&mut Timing),
+{
+  if let Some(timing) = &mut *TIMING.lock().unwrap_or_else(|err| err.into_inner()) {
+    f(timing);
+  }
+}
+
+// Returns the result of f, adding the time it took to the part of the timing
+// that part picks out.
+pub fn time<T, F, P>(part: P, f: F) -> T
+where
+  F: FnOnce() -> T,
+  P: FnOnce(&mut Timing) -> &mut Duration,
+{
+  let start = Instant::now();
+  let result = f();
+  record(|timing| *part(timing) += start.elapsed());
+  result
+}
+
+// Writes the timing to stderr, if --timing turned it on.
+pub fn report(total: Duration, bytes_out: usize) {
+  let timing = TIMING.lock().unwrap_or_else(|err| err.into_inner());
+  let timing = match &*timing {
+    Some(timing) => timing,
+    None => return,
+  };
+  eprintln!(
+    "jyt timing: read {:.1?}, parse {:.1?}, convert and write {:.1?}, total {:.1?}",
+    timing.read, timing.parse, timing.convert, total
+  );
+  let bytes_in = match timing.bytes_in {
+    Some(bytes) => format!("{} bytes in, ", bytes),
+    None => String::new(),
+  };
+  eprintln!(
+    "jyt timing: {}{} documents, {} bytes out",
+    bytes_in, timing.documents, bytes_out
+  );
+}
+
+// Counted counts the bytes written through it, for the timing report.
+pub struct Counted<W> {
+  w: W,
+  pub count: usize,
+}
+
+impl<W> Counted<W> {
+  pub fn new(w: W) -> Self {
+    Counted { w, count: 0 }
+  }
+}
+
+impl<W: Write> Write for Counted<W> {
+  fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+    let n = self.w.write(buf)?;
+    self.count += n;
+    Ok(n)
+  }
+
+  fn flush(&mut self) -> io::Result<()> {
+    self.w.flush()
+  }
+}
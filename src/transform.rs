@@ -0,0 +1,718 @@
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::error::Error;
+use std::str::FromStr;
+
+use crate::base64;
+use crate::k8s;
+use crate::path::{self, Path};
+use crate::value::{Map, Value};
+use crate::vars;
+
+// Transform is a modification applied to each document between the input and
+// the output. Transforms run in the order that they appear in the list built by
+// Opt::transforms.
+pub enum Transform {
+  Base64Decode(Path),
+  Base64Encode(Path),
+  Set(Assignment),
+  Delete(Path),
+  Rename(Vec<(Path, Path)>),
+  Transpose(Path),
+  Defaults(Value),
+  StrategicMerge(Vec<Value>),
+  Only(Vec<Path>),
+  Except(Vec<Path>),
+  Interpolate,
+}
+
+impl Transform {
+  pub fn apply(&self, doc: &mut Value) -> Result<(), Box<dyn Error>> {
+    match self {
+      // Decoded values become strings, so they have to be UTF-8 text.
+      Transform::Base64Decode(path) => path.visit_paths_mut(doc, &mut |at, v| match v {
+        Value::String(s) => {
+          let decoded = base64::decode(s).map_err(|err| format!("{}: {}", at, err))?;
+          *s = String::from_utf8(decoded)
+            .map_err(|_| format!("{}: decoded value is not UTF-8 text", at))?;
+          Ok(())
+        }
+        _ => Err(format!("{}: cannot base64 decode {}", at, v.type_name()))?,
+      }),
+      Transform::Base64Encode(path) => path.visit_paths_mut(doc, &mut |at, v| match v {
+        Value::String(s) => {
+          *s = base64::encode(s.as_bytes());
+          Ok(())
+        }
+        _ => Err(format!("{}: cannot base64 encode {}", at, v.type_name()))?,
+      }),
+      Transform::Set(Assignment { path, value }) => Ok(path.set(doc, value)?),
+      Transform::Delete(path) => Ok(path.delete(doc)?),
+      Transform::Rename(renames) => {
+        for (from, to) in renames {
+          from.rename(to, doc)?;
+        }
+        Ok(())
+      }
+      Transform::Transpose(path) => path.visit_mut(doc, &mut |v| {
+        *v = transpose(std::mem::replace(v, Value::Null))
+          .map_err(|t| format!("{}: cannot transpose {}", path, t))?;
+        Ok(())
+      }),
+      Transform::Only(paths) => {
+        *doc = path::keep_only(paths, doc);
+        Ok(())
+      }
+      Transform::Except(paths) => {
+        for path in paths {
+          path.delete(doc)?;
+        }
+        Ok(())
+      }
+      Transform::StrategicMerge(patches) => {
+        for patch in patches {
+          k8s::patch(doc, patch);
+        }
+        Ok(())
+      }
+      Transform::Defaults(defaults) => {
+        fill_defaults(doc, defaults);
+        Ok(())
+      }
+      Transform::Interpolate => {
+        *doc = Interpolation::new(doc).value(doc, 0)?;
+        Ok(())
+      }
+    }
+  }
+}
+
+// Reads the renames in a rename map, which is an object whose keys are the
+// paths to rename from and whose values are the paths to rename to.
+pub fn parse_rename_map(map: Value) -> Result<Vec<(Path, Path)>, String> {
+  let map = match map {
+    Value::Map(map) => map,
+    v => {
+      return Err(format!(
+        "rename map must be an object, not {}",
+        v.type_name()
+      ))
+    }
+  };
+  let mut renames = Vec::with_capacity(map.len());
+  for (from, to) in map {
+    match (from, to) {
+      (Value::String(from), Value::String(to)) => renames.push((from.parse()?, to.parse()?)),
+      _ => return Err("rename map keys and values must be paths".to_owned()),
+    }
+  }
+  Ok(renames)
+}
+
+// Assignment is a PATH=VALUE argument, where the value is parsed as JSON if
+// possible so that numbers, booleans, and so on come out with the right type,
+// and is used as a plain string otherwise.
+#[derive(Clone)]
+pub struct Assignment {
+  path: Path,
+  value: Value,
+}
+
+impl FromStr for Assignment {
+  type Err = String;
+
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    // The path may contain a quoted key with its own "=" character.
+    let mut quoted = false;
+    let mut escaped = false;
+    let split = s.char_indices().find(|&(_, c)| {
+      match c {
+        _ if escaped => escaped = false,
+        '\\' if quoted => escaped = true,
+        '"' => quoted = !quoted,
+        '=' if !quoted => return true,
+        _ => {}
+      }
+      false
+    });
+    let (path, value) = match split {
+      Some((i, _)) => (&s[..i], &s[i + 1..]),
+      None => return Err(format!("'{}' is not of the form PATH=VALUE", s)),
+    };
+    let value = match serde_json::from_str(value) {
+      Ok(value) => value,
+      Err(_) => Value::from(value),
+    };
+    Ok(Assignment {
+      path: path.parse()?,
+      value,
+    })
+  }
+}
+
+impl Assignment {
+  // Replaces a value that refers to a variable, like $name, with the value of
+  // the variable. A value that names a variable that isn't bound stays a plain
+  // string.
+  pub fn bind(&mut self) -> Result<(), String> {
+    if let Value::String(s) = &self.value {
+      match vars::reference(s) {
+        Some((name, _)) if vars::is_bound(name) => {
+          if let Some(value) = vars::lookup(s) {
+            self.value = value?;
+          }
+        }
+        _ => {}
+      }
+    }
+    Ok(())
+  }
+}
+
+// The deepest chain of references that interpolation follows before assuming
+// that the references form a cycle.
+const MAX_INTERPOLATION_DEPTH: usize = 32;
+
+// The most that references can insert into a document, counting each value
+// and each byte of a string. References to references can otherwise double
+// the size of a document with each step of a chain.
+const MAX_INTERPOLATED_SIZE: usize = 1 << 22;
+
+// Interpolation replaces each "${path}" reference within the strings of a value
+// with the value at that path in the root document. A string consisting of a
+// single reference becomes a copy of the referenced value, with its type
+// intact. Otherwise, the referenced value is inserted as it would be in a
+// template, with strings inserted as-is and other values as compact JSON. A
+// "$${" stands for a literal "${". A reference can also be to a variable, as
+// in "${$name}".
+struct Interpolation<'r> {
+  root: &'r Value,
+  // Each path is resolved once, however many references there are to it.
+  resolved: HashMap<String, Value>,
+  inserted: usize,
+}
+
+impl<'r> Interpolation<'r> {
+  fn new(root: &'r Value) -> Self {
+    Interpolation {
+      root,
+      resolved: HashMap::new(),
+      inserted: 0,
+    }
+  }
+
+  // Returns a copy of a value with its references replaced.
+  fn value(&mut self, v: &Value, depth: usize) -> Result<Value, String> {
+    if depth > MAX_INTERPOLATION_DEPTH {
+      return Err("cannot interpolate: references form a cycle".to_owned());
+    }
+    match v {
+      Value::String(s) if s.contains("${") => self.string(s, depth),
+      Value::Seq(items) => Ok(Value::Seq(
+        items
+          .iter()
+          .map(|v| self.value(v, depth))
+          .collect::<Result<_, _>>()?,
+      )),
+      Value::Map(map) => {
+        let mut out = Map::new();
+        for (k, v) in map.iter() {
+          out.push(k.clone(), self.value(v, depth)?);
+        }
+        Ok(Value::Map(out))
+      }
+      v => Ok(v.clone()),
+    }
+  }
+
+  fn string(&mut self, s: &str, depth: usize) -> Result<Value, String> {
+    if let Some(path) = s.strip_prefix("${").and_then(|s| s.strip_suffix('}')) {
+      if !path.contains('}') {
+        return self.resolve(path, depth);
+      }
+    }
+
+    let mut out = String::new();
+    let mut rest = s;
+    while let Some(start) = rest.find('$') {
+      out.push_str(&rest[..start]);
+      rest = &rest[start..];
+      if let Some(after) = rest.strip_prefix("$${") {
+        out.push_str("${");
+        rest = after;
+      } else if let Some(after) = rest.strip_prefix("${") {
+        let end = after
+          .find('}')
+          .ok_or_else(|| format!("cannot interpolate '{}': unterminated reference", s))?;
+        match self.resolve(&after[..end], depth)? {
+          Value::Null => {}
+          Value::String(v) => out.push_str(&v),
+          v => out.push_str(&serde_json::to_string(&v).unwrap_or_default()),
+        }
+        rest = &after[end + 1..];
+      } else {
+        out.push('$');
+        rest = &rest[1..];
+      }
+    }
+    out.push_str(rest);
+    Ok(Value::String(out))
+  }
+
+  fn resolve(&mut self, path: &str, depth: usize) -> Result<Value, String> {
+    let path = path.trim();
+    // The value of a variable is data, which is inserted as it is.
+    let value = match (vars::lookup(path), self.resolved.get(path)) {
+      (Some(value), _) => value?,
+      (None, Some(value)) => value.clone(),
+      (None, None) => {
+        let parsed: Path = path.parse()?;
+        let root = self.root;
+        let value = match parsed.select(root).first() {
+          Some(v) => self.value(v, depth + 1)?,
+          None => {
+            return Err(format!(
+              "cannot interpolate {}: no value at that path",
+              parsed
+            ))
+          }
+        };
+        self.resolved.insert(path.to_owned(), value.clone());
+        value
+      }
+    };
+    self.inserted += size_of(&value);
+    if self.inserted > MAX_INTERPOLATED_SIZE {
+      return Err(format!(
+        "cannot interpolate: references insert more than {} values and bytes",
+        MAX_INTERPOLATED_SIZE
+      ));
+    }
+    Ok(value)
+  }
+}
+
+// Returns the size of a value as interpolation counts it, with each value and
+// each byte of a string counting once.
+fn size_of(v: &Value) -> usize {
+  match v {
+    Value::String(s) => 1 + s.len(),
+    Value::Seq(items) => 1 + items.iter().map(size_of).sum::<usize>(),
+    Value::Map(map) => {
+      1 + map
+        .iter()
+        .map(|(k, v)| size_of(k) + size_of(v))
+        .sum::<usize>()
+    }
+    _ => 1,
+  }
+}
+
+// Adds every key of the defaults that's missing from the document, recursing
+// into objects that both of them have. Values already in the document always
+// win, including arrays, which are not merged element by element.
+fn fill_defaults(doc: &mut Value, defaults: &Value) {
+  if let (Value::Map(doc), Value::Map(defaults)) = (doc, defaults) {
+    for (k, default) in defaults.iter() {
+      let existing = doc.iter_mut().find(|(dk, _)| *dk == k);
+      match existing {
+        Some((_, v)) => fill_defaults(v, default),
+        None => doc.push(k.clone(), default.clone()),
+      }
+    }
+  }
+}
+
+// Converts between an array of objects (rows) and an object of parallel arrays
+// (columns), in whichever direction applies. A row without some column, or a
+// column shorter than the others, gets null in the missing positions. On
+// failure, returns the type of value that couldn't be transposed.
+fn transpose(v: Value) -> Result<Value, &'static str> {
+  match v {
+    Value::Seq(rows) => {
+      let mut columns: Vec<(Value, Vec<Value>)> = Vec::new();
+      for (i, row) in rows.into_iter().enumerate() {
+        let row = match row {
+          Value::Map(row) => row,
+          v => return Err(v.type_name()),
+        };
+        for (k, v) in row {
+          let idx = match columns.iter().position(|(col, _)| *col == k) {
+            Some(idx) => idx,
+            None => {
+              columns.push((k, Vec::new()));
+              columns.len() - 1
+            }
+          };
+          let column = &mut columns[idx].1;
+          column.resize(i, Value::Null);
+          column.push(v);
+        }
+        for (_, column) in &mut columns {
+          column.resize(i + 1, Value::Null);
+        }
+      }
+      let mut map = Map::new();
+      for (k, column) in columns {
+        map.push(k, Value::Seq(column));
+      }
+      Ok(Value::Map(map))
+    }
+    Value::Map(columns) => {
+      let mut parsed = Vec::with_capacity(columns.len());
+      for (k, column) in columns {
+        match column {
+          Value::Seq(column) => parsed.push((k, column.into_iter())),
+          v => return Err(v.type_name()),
+        }
+      }
+      let len = parsed.iter().map(|(_, c)| c.len()).max().unwrap_or(0);
+      let rows = (0..len)
+        .map(|_| {
+          let mut row = Map::new();
+          for (k, column) in &mut parsed {
+            row.push(k.clone(), column.next().unwrap_or(Value::Null));
+          }
+          Value::Map(row)
+        })
+        .collect();
+      Ok(Value::Seq(rows))
+    }
+    v => Err(v.type_name()),
+  }
+}
+
+// Replaces non-empty arrays and objects nested deeper than the limit with a
+// short string placeholder, so that the overall shape of a large document fits
+// on a screen.
+pub fn truncate(v: &mut Value, depth: usize) {
+  match v {
+    Value::Seq(items) if depth == 0 && !items.is_empty() => {
+      *v = Value::String(format!("[{} items]", items.len()));
+    }
+    Value::Map(map) if depth == 0 && !map.is_empty() => {
+      *v = Value::String("{...}".to_owned());
+    }
+    Value::Seq(items) => items.iter_mut().for_each(|v| truncate(v, depth - 1)),
+    Value::Map(map) => map.values_mut().for_each(|v| truncate(v, depth - 1)),
+    _ => {}
+  }
+}
+
+// Aggregate is an operation on all of the documents of an input at once,
+// producing a new set of documents.
+pub enum Aggregate {
+  Join {
+    right: Vec<Value>,
+    on: Path,
+    join_type: JoinType,
+  },
+  GroupBy(Path),
+  SortDocsBy(Path),
+  K8sSelect(Vec<k8s::Selector>),
+  K8sSort,
+  Reduce(Vec<Reduction>),
+}
+
+#[derive(Copy, Clone)]
+pub enum JoinType {
+  Inner,
+  Left,
+}
+
+impl FromStr for JoinType {
+  type Err = String;
+
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    match s {
+      "inner" => Ok(JoinType::Inner),
+      "left" => Ok(JoinType::Left),
+      _ => Err(format!("'{}' is not a valid join type", s)),
+    }
+  }
+}
+
+impl Aggregate {
+  pub fn apply(&self, docs: Vec<Value>) -> Result<Vec<Value>, Box<dyn Error>> {
+    match self {
+      Aggregate::Join {
+        right,
+        on,
+        join_type,
+      } => {
+        let right = records(right.clone());
+        Ok(vec![join(on, *join_type, records(docs), &right)?])
+      }
+      Aggregate::GroupBy(path) => Ok(vec![group_by(path, records(docs))?]),
+      Aggregate::SortDocsBy(path) => {
+        let mut docs = docs;
+        docs.sort_by(|a, b| compare(first(path, a), first(path, b)));
+        Ok(docs)
+      }
+      Aggregate::K8sSelect(selectors) => Ok(
+        docs
+          .into_iter()
+          .filter(|doc| selectors.iter().any(|s| s.matches(doc)))
+          .collect(),
+      ),
+      Aggregate::K8sSort => {
+        let mut docs = docs;
+        k8s::sort(&mut docs);
+        Ok(docs)
+      }
+      Aggregate::Reduce(reductions) => reductions.iter().map(|r| r.apply(&docs)).collect(),
+    }
+  }
+}
+
+// Reduction is a numeric summary of the values at a path across all documents,
+// given on the command line like 'sum(.items[].price)'.
+#[derive(Clone)]
+pub struct Reduction {
+  source: String,
+  op: ReduceOp,
+  path: Path,
+}
+
+#[derive(Copy, Clone)]
+enum ReduceOp {
+  Sum,
+  Min,
+  Max,
+  Avg,
+  Count,
+}
+
+impl FromStr for Reduction {
+  type Err = String;
+
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    let invalid = || format!("'{}' is not of the form FUNCTION(PATH)", s);
+    let (name, path) = s.trim().split_once('(').ok_or_else(invalid)?;
+    let path = path.strip_suffix(')').ok_or_else(invalid)?;
+    let op = match name.trim() {
+      "sum" => ReduceOp::Sum,
+      "min" => ReduceOp::Min,
+      "max" => ReduceOp::Max,
+      "avg" => ReduceOp::Avg,
+      "count" => ReduceOp::Count,
+      name => {
+        return Err(format!(
+          "'{}' is not a supported function (must be sum, min, max, avg, or count)",
+          name
+        ))
+      }
+    };
+    Ok(Reduction {
+      source: s.to_owned(),
+      op,
+      path: path.trim().parse()?,
+    })
+  }
+}
+
+impl Reduction {
+  fn apply(&self, docs: &[Value]) -> Result<Value, Box<dyn Error>> {
+    let values: Vec<&Value> = docs.iter().flat_map(|doc| self.path.select(doc)).collect();
+    if let ReduceOp::Count = self.op {
+      return Ok(Value::U64(values.len() as u64));
+    }
+
+    // Nulls stand in for missing values often enough that skipping them is more
+    // useful than failing, but anything else that isn't a number is an error.
+    let mut numbers = Vec::with_capacity(values.len());
+    for v in values {
+      match v {
+        Value::Null => {}
+        Value::I64(_) | Value::U64(_) | Value::I128(_) | Value::U128(_) | Value::F64(_) => {
+          numbers.push(v)
+        }
+        v => Err(format!("{}: cannot reduce {}", self.source, v.type_name()))?,
+      }
+    }
+    let cmp = |a: &&&Value, b: &&&Value| compare(Some(a), Some(b));
+    Ok(match self.op {
+      ReduceOp::Sum => sum(&numbers),
+      ReduceOp::Min => numbers
+        .iter()
+        .min_by(cmp)
+        .map_or(Value::Null, |v| (**v).clone()),
+      ReduceOp::Max => numbers
+        .iter()
+        .max_by(cmp)
+        .map_or(Value::Null, |v| (**v).clone()),
+      ReduceOp::Avg if numbers.is_empty() => Value::Null,
+      ReduceOp::Avg => {
+        let total: f64 = numbers.iter().map(|v| number_of(v)).sum();
+        Value::F64(total / numbers.len() as f64)
+      }
+      ReduceOp::Count => unreachable!(),
+    })
+  }
+}
+
+// Adds numbers, keeping an integer result when every number is an integer and
+// the total fits.
+fn sum(numbers: &[&Value]) -> Value {
+  let mut total = Some(0i128);
+  for v in numbers {
+    total = match (total, v) {
+      (Some(t), Value::I64(n)) => t.checked_add(*n as i128),
+      (Some(t), Value::U64(n)) => t.checked_add(*n as i128),
+      (Some(t), Value::I128(n)) => t.checked_add(*n),
+      (Some(t), Value::U128(n)) if *n <= i128::MAX as u128 => t.checked_add(*n as i128),
+      _ => None,
+    };
+  }
+  match total {
+    Some(t) if t >= 0 && t <= u64::MAX as i128 => Value::U64(t as u64),
+    Some(t) if t >= i64::MIN as i128 && t <= i64::MAX as i128 => Value::I64(t as i64),
+    Some(t) => Value::I128(t),
+    None => Value::F64(numbers.iter().map(|v| number_of(v)).sum()),
+  }
+}
+
+// Returns the records of an input, which are the elements of the input when it
+// consists of a single array, and the documents of the input otherwise.
+fn records(mut docs: Vec<Value>) -> Vec<Value> {
+  match docs.as_mut_slice() {
+    [Value::Seq(items)] => std::mem::take(items),
+    _ => docs,
+  }
+}
+
+// Returns a string that identifies the value at a path within a record, for use
+// as a join or grouping key. The string is the value's JSON encoding, so that values of
+// different types like 1 and "1" have different keys.
+fn key_of(path: &Path, record: &Value) -> Option<String> {
+  first(path, record).map(|v| serde_json::to_string(v).unwrap_or_default())
+}
+
+fn join(
+  on: &Path,
+  join_type: JoinType,
+  left: Vec<Value>,
+  right: &[Value],
+) -> Result<Value, Box<dyn Error>> {
+  let mut index: HashMap<String, Vec<&Map>> = HashMap::new();
+  for record in right {
+    match (key_of(on, record), record) {
+      (Some(key), Value::Map(map)) => index.entry(key).or_default().push(map),
+      (None, Value::Map(_)) => {}
+      _ => Err(format!(
+        "cannot join with {}, expected an object",
+        record.type_name()
+      ))?,
+    }
+  }
+
+  let mut joined = Vec::new();
+  for record in left {
+    let matches = key_of(on, &record).and_then(|key| index.get(&key));
+    // Merged records keep the input record's own key.
+    let key = first(on, &record).cloned();
+    match (matches, record) {
+      (Some(matches), Value::Map(map)) => {
+        for right in matches {
+          let mut merged = map.clone();
+          for (k, v) in right.iter() {
+            merged.insert(k.clone(), v.clone());
+          }
+          let mut merged = Value::Map(merged);
+          if let Some(key) = &key {
+            on.set(&mut merged, key)?;
+          }
+          joined.push(merged);
+        }
+      }
+      (None, record @ Value::Map(_)) => {
+        if let JoinType::Left = join_type {
+          joined.push(record);
+        }
+      }
+      (_, record) => Err(format!(
+        "cannot join {}, expected an object",
+        record.type_name()
+      ))?,
+    }
+  }
+  Ok(Value::Seq(joined))
+}
+
+// Groups records by the value at a path within each of them, producing an
+// object whose keys are the distinct grouping values (in the order first seen)
+// and whose values are arrays of the members of each group. Values of different
+// types like 1 and "1" make different groups, as they make different join keys,
+// and a record without a value at the path is an error.
+fn group_by(path: &Path, records: Vec<Value>) -> Result<Value, String> {
+  let mut groups: Vec<(Value, Vec<Value>)> = Vec::new();
+  let mut index: HashMap<String, usize> = HashMap::new();
+  for (i, record) in records.into_iter().enumerate() {
+    let (id, key) = match (key_of(path, &record), first(path, &record)) {
+      (Some(id), Some(key)) => (id, key.clone()),
+      _ => return Err(format!("record {} has no value at {} to group by", i, path)),
+    };
+    match index.get(&id) {
+      Some(&group) => groups[group].1.push(record),
+      None => {
+        index.insert(id, groups.len());
+        groups.push((key, vec![record]));
+      }
+    }
+  }
+  let mut map = Map::new();
+  for (key, members) in groups {
+    map.push(key, Value::Seq(members));
+  }
+  Ok(Value::Map(map))
+}
+
+// Orders values for sorting, with missing values first, then values of each
+// type in the same order as jq (null, booleans, numbers, strings, arrays, and
+// objects). NaN sorts after every other number. Arrays and objects are compared
+// by their JSON encodings, which is arbitrary but at least deterministic.
+fn compare(a: Option<&Value>, b: Option<&Value>) -> Ordering {
+  let rank = |v: Option<&Value>| match v {
+    None => 0,
+    Some(Value::Null) => 1,
+    Some(Value::Bool(_)) => 2,
+    Some(Value::I64(_) | Value::U64(_) | Value::I128(_) | Value::U128(_) | Value::F64(_)) => 3,
+    Some(Value::String(_)) => 4,
+    Some(Value::Seq(_)) => 5,
+    Some(Value::Map(_)) => 6,
+  };
+  match (a, b) {
+    (Some(Value::Bool(a)), Some(Value::Bool(b))) => a.cmp(b),
+    (Some(Value::String(a)), Some(Value::String(b))) => a.cmp(b),
+    (Some(a), Some(b)) if rank(Some(a)) == rank(Some(b)) => match rank(Some(a)) {
+      3 => {
+        let (a, b) = (number_of(a), number_of(b));
+        match (a.is_nan(), b.is_nan()) {
+          (false, false) => a.total_cmp(&b),
+          (a, b) => a.cmp(&b),
+        }
+      }
+      _ => {
+        let a = serde_json::to_string(a).unwrap_or_default();
+        let b = serde_json::to_string(b).unwrap_or_default();
+        a.cmp(&b)
+      }
+    },
+    _ => rank(a).cmp(&rank(b)),
+  }
+}
+
+fn first<'v>(path: &Path, v: &'v Value) -> Option<&'v Value> {
+  path.select(v).first().copied()
+}
+
+fn number_of(v: &Value) -> f64 {
+  match *v {
+    Value::I64(n) => n as f64,
+    Value::U64(n) => n as f64,
+    Value::I128(n) => n as f64,
+    Value::U128(n) => n as f64,
+    Value::F64(n) => n,
+    _ => 0.0,
+  }
+}
@@ -0,0 +1,62 @@
+use std::io::{self, Write};
+
+use crate::value::Value;
+
+// Writes a document in the style of tree(1), with a branch for each key or
+// index and scalar values shown alongside their keys, for getting a sense of the
+// shape of an unfamiliar document.
+pub fn write<W>(w: &mut W, doc: &Value) -> io::Result<()>
+where
+  W: Write,
+{
+  match doc {
+    Value::Seq(items) if !items.is_empty() => writeln!(w, ".")?,
+    Value::Map(map) if !map.is_empty() => writeln!(w, ".")?,
+    v => return writeln!(w, "{}", scalar(v)),
+  }
+  children(w, doc, &mut String::new())
+}
+
+fn children<W>(w: &mut W, v: &Value, prefix: &mut String) -> io::Result<()>
+where
+  W: Write,
+{
+  let entries: Vec<(String, &Value)> = match v {
+    Value::Seq(items) => items
+      .iter()
+      .enumerate()
+      .map(|(i, v)| (format!("[{}]", i), v))
+      .collect(),
+    Value::Map(map) => map
+      .iter()
+      .map(|(k, v)| (k.key_str().unwrap_or_else(|| scalar(k)), v))
+      .collect(),
+    _ => return Ok(()),
+  };
+  let count = entries.len();
+  for (i, (label, v)) in entries.into_iter().enumerate() {
+    let last = i + 1 == count;
+    let branch = if last { "└── " } else { "├── " };
+    match v {
+      Value::Seq(items) if !items.is_empty() => writeln!(w, "{}{}{}", prefix, branch, label)?,
+      Value::Map(map) if !map.is_empty() => writeln!(w, "{}{}{}", prefix, branch, label)?,
+      v => writeln!(w, "{}{}{}: {}", prefix, branch, label, scalar(v))?,
+    }
+    let len = prefix.len();
+    prefix.push_str(if last { "    " } else { "│   " });
+    children(w, v, prefix)?;
+    prefix.truncate(len);
+  }
+  Ok(())
+}
+
+// Returns the text shown for a leaf value. Strings appear as-is unless quoting
+// is needed to see where they begin and end.
+fn scalar(v: &Value) -> String {
+  match v {
+    Value::String(s) if !s.is_empty() && !s.contains(char::is_control) && s.trim() == s => {
+      s.clone()
+    }
+    v => serde_json::to_string(v).unwrap_or_default(),
+  }
+}
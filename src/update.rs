@@ -0,0 +1,192 @@
+use std::env;
+use std::error::Error;
+use std::fs;
+use std::path::Path;
+use std::process::Command;
+
+use structopt::StructOpt;
+
+use crate::digest::Algorithm;
+
+// UpdateOpt holds the options for 'jyt self-update', which replaces a
+// standalone jyt binary with the one from the latest GitHub release, for
+// installs that cargo doesn't manage. It's only built with the self-update
+// feature, since a jyt from cargo or a package manager should be updated the
+// same way it was installed.
+#[derive(StructOpt)]
+#[structopt(name = "jyt self-update", verbatim_doc_comment)]
+/// Replace this jyt with the binary from the latest GitHub release
+///
+/// The latest release of ahamlinman/jyt is found through the GitHub API, and if
+/// it's newer than this jyt, its binary for this system replaces the one that's
+/// running. A release's binaries are named for the systems they run on, like
+/// jyt-x86_64-unknown-linux-gnu or jyt-aarch64-apple-darwin (with .exe for
+/// Windows). The download has to match the SHA-256 digest that GitHub lists for
+/// it, and then run and report the new version, before it takes the place of
+/// the old one. jyt won't install a binary that has no digest to check.
+///
+/// Downloads go through curl, which has to be on the PATH, and which follows
+/// the usual proxy variables like HTTPS_PROXY.
+pub struct UpdateOpt {
+  #[structopt(
+    long = "check",
+    help = "Only report whether a newer release is out, without installing it"
+  )]
+  check: bool,
+}
+
+const LATEST_RELEASE: &str = "https://api.github.com/repos/ahamlinman/jyt/releases/latest";
+
+pub fn self_update(opt: UpdateOpt) -> Result<(), Box<dyn Error>> {
+  let release: serde_json::Value = serde_json::from_slice(&fetch(LATEST_RELEASE, None)?)
+    .map_err(|err| format!("can't read the latest release from GitHub: {}", err))?;
+  let tag = match release["tag_name"].as_str() {
+    Some(tag) => tag,
+    None => Err("the latest release on GitHub has no tag")?,
+  };
+  let latest = tag.trim_start_matches('v');
+  let current = env!("CARGO_PKG_VERSION");
+  if !is_newer(latest, current) {
+    println!("jyt {} is up to date", current);
+    return Ok(());
+  }
+  if opt.check {
+    println!("jyt {} is out, and this is jyt {}", latest, current);
+    return Ok(());
+  }
+
+  let assets = release["assets"]
+    .as_array()
+    .map(Vec::as_slice)
+    .unwrap_or_default();
+  let asset = match assets
+    .iter()
+    .find(|asset| asset["name"].as_str().is_some_and(is_for_this_system))
+  {
+    Some(asset) => asset,
+    None => Err(format!(
+      "release {} has no binary for {} on {}",
+      tag,
+      env::consts::ARCH,
+      env::consts::OS
+    ))?,
+  };
+  let url = match asset["browser_download_url"].as_str() {
+    Some(url) => url,
+    None => Err(format!("release {} has no link to its binary", tag))?,
+  };
+  // Nothing is downloaded, let alone run, unless it can be checked first.
+  let digest = match asset["digest"]
+    .as_str()
+    .and_then(|d| d.strip_prefix("sha256:"))
+  {
+    Some(digest) => digest,
+    None => Err(format!(
+      "release {} lists no SHA-256 digest for its binary, so jyt can't verify it",
+      tag
+    ))?,
+  };
+
+  // The download goes beside the binary it replaces, so that renaming it over
+  // that binary stays within one file system.
+  let exe = env::current_exe()?.canonicalize()?;
+  let download = exe.with_file_name(".jyt-update");
+  let result = install(&download, &exe, url, latest, digest);
+  let _ = fs::remove_file(&download);
+  result?;
+  println!("updated jyt from {} to {}", current, latest);
+  Ok(())
+}
+
+fn install(
+  download: &Path,
+  exe: &Path,
+  url: &str,
+  version: &str,
+  digest: &str,
+) -> Result<(), Box<dyn Error>> {
+  fetch(url, Some(download))?;
+  let got = Algorithm::Sha256.hex_digest(&fs::read(download)?);
+  if !got.eq_ignore_ascii_case(digest) {
+    Err(format!(
+      "the download's SHA-256 digest is {}, not {} as the release says",
+      got, digest
+    ))?;
+  }
+  #[cfg(unix)]
+  {
+    use std::os::unix::fs::PermissionsExt;
+    fs::set_permissions(download, fs::Permissions::from_mode(0o755))?;
+  }
+  let output = Command::new(download).arg("--version").output()?;
+  if !String::from_utf8_lossy(&output.stdout).contains(version) {
+    Err(format!("the download doesn't run as jyt {}", version))?;
+  }
+  // Windows won't replace a program that's running, but it will rename it.
+  let old = exe.with_extension("old.exe");
+  if cfg!(windows) {
+    let _ = fs::remove_file(&old);
+    fs::rename(exe, &old)?;
+  }
+  if let Err(err) = fs::rename(download, exe) {
+    if cfg!(windows) {
+      let _ = fs::rename(&old, exe);
+    }
+    Err(format!("can't replace {}: {}", exe.display(), err))?;
+  }
+  Ok(())
+}
+
+// Downloads a URL with curl, writing it to a file if there is one, or else
+// returning it.
+fn fetch(url: &str, file: Option<&Path>) -> Result<Vec<u8>, Box<dyn Error>> {
+  let mut curl = Command::new("curl");
+  curl
+    .args(["--silent", "--show-error", "--fail", "--location"])
+    .args(["--proto", "=https"])
+    .args(["--header", "Accept: application/vnd.github+json"]);
+  if let Some(file) = file {
+    curl.arg("--output").arg(file);
+  }
+  let output = curl
+    .arg(url)
+    .output()
+    .map_err(|err| format!("can't run curl to reach GitHub: {}", err))?;
+  if !output.status.success() {
+    let message = String::from_utf8_lossy(&output.stderr);
+    Err(message.trim().to_owned())?;
+  }
+  Ok(output.stdout)
+}
+
+// Reports whether a version like 1.10.0 comes after another. Pre-release
+// suffixes like -rc.1 aren't compared.
+fn is_newer(version: &str, than: &str) -> bool {
+  let parts = |version: &str| -> Vec<u64> {
+    let release = version.split(['-', '+']).next().unwrap_or_default();
+    release
+      .split('.')
+      .map(|part| part.parse().unwrap_or(0))
+      .collect()
+  };
+  parts(version) > parts(than)
+}
+
+// Reports whether a release binary's name, like jyt-x86_64-unknown-linux-gnu,
+// is for the system that this jyt was built for.
+fn is_for_this_system(name: &str) -> bool {
+  let os = match env::consts::OS {
+    "macos" => "apple-darwin",
+    os => os,
+  };
+  let name = match (cfg!(windows), name.strip_suffix(".exe")) {
+    (true, Some(name)) => name,
+    (false, None) => name,
+    _ => return false,
+  };
+  // Archives and checksums go beside the binaries, with extensions that the
+  // binaries don't have.
+  !name.contains('.')
+    && name.starts_with(&format!("jyt-{}-", env::consts::ARCH))
+    && name.contains(os)
+}
@@ -0,0 +1,95 @@
+use std::str::{self, FromStr};
+
+// InvalidUtf8 is what to do with input that isn't valid UTF-8, which every
+// input format requires. "replace" puts U+FFFD in place of each bad sequence,
+// as String::from_utf8_lossy does. "escape" puts the text \xNN in place of
+// each bad byte, so that the original bytes can be told apart afterward.
+#[derive(Copy, Clone, Default)]
+pub enum InvalidUtf8 {
+  #[default]
+  Error,
+  Replace,
+  Escape,
+}
+
+impl FromStr for InvalidUtf8 {
+  type Err = String;
+
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    match s {
+      "error" => Ok(InvalidUtf8::Error),
+      "replace" => Ok(InvalidUtf8::Replace),
+      "escape" => Ok(InvalidUtf8::Escape),
+      _ => Err(format!("'{}' is not a valid invalid UTF-8 policy", s)),
+    }
+  }
+}
+
+// Returns a copy of the input with its invalid UTF-8 fixed according to the
+// policy, or None if it's all valid. With the error policy, the error points
+// to the first bad byte.
+pub fn fix(input: &[u8], policy: InvalidUtf8) -> Result<Option<Vec<u8>>, String> {
+  let err = match str::from_utf8(input) {
+    Ok(_) => return Ok(None),
+    Err(err) => err,
+  };
+  match policy {
+    InvalidUtf8::Error => {
+      let valid = &input[..err.valid_up_to()];
+      // The part before the error is valid, so this can't fail.
+      let before = str::from_utf8(valid).unwrap_or_default();
+      let line = before.matches('\n').count() + 1;
+      let column = before
+        .rsplit('\n')
+        .next()
+        .map_or(0, |text| text.chars().count())
+        + 1;
+      Err(format!(
+        "input is not valid UTF-8 (found byte 0x{:02x}) at line {} column {}",
+        input[err.valid_up_to()],
+        line,
+        column
+      ))
+    }
+    InvalidUtf8::Replace => Ok(Some(
+      String::from_utf8_lossy(input).into_owned().into_bytes(),
+    )),
+    InvalidUtf8::Escape => Ok(Some(escape(input))),
+  }
+}
+
+// Replaces each invalid byte with the text \xNN. Within double quotes, where
+// JSON, YAML, and TOML all treat a backslash as the start of an escape, the
+// backslash itself is escaped. Only quotes opened on the same line count,
+// which covers every JSON string and nearly every YAML and TOML one.
+fn escape(mut input: &[u8]) -> Vec<u8> {
+  let mut out = Vec::with_capacity(input.len());
+  let mut quoted = false;
+  loop {
+    let (valid, rest) = match str::from_utf8(input) {
+      Ok(_) => (input, &[][..]),
+      Err(err) => input.split_at(err.valid_up_to()),
+    };
+    let mut escaped = false;
+    for &b in valid {
+      match b {
+        b'\n' => quoted = false,
+        b'"' if !escaped => quoted = !quoted,
+        _ => {}
+      }
+      escaped = b == b'\\' && !escaped;
+    }
+    out.extend_from_slice(valid);
+    let len = match str::from_utf8(rest) {
+      Ok(_) => return out,
+      Err(err) => err.error_len().unwrap_or(rest.len()),
+    };
+    for b in &rest[..len] {
+      if quoted {
+        out.push(b'\\');
+      }
+      out.extend_from_slice(format!("\\x{:02x}", b).as_bytes());
+    }
+    input = &rest[len..];
+  }
+}
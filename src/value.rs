@@ -0,0 +1,677 @@
+use std::collections::HashMap;
+use std::fmt;
+
+use serde::de::value::{Error as DeError, MapDeserializer, SeqDeserializer};
+use serde::de::{
+  Deserialize, Deserializer, Error as _, IntoDeserializer, MapAccess, SeqAccess, Visitor,
+};
+use serde::ser::{Serialize, SerializeMap, SerializeSeq, Serializer};
+
+use crate::json;
+
+// Value is jyt's own in-memory representation of a document, used whenever a
+// transform needs to look at or modify a document instead of streaming it
+// straight from the input to the output. Unlike serde_json::Value, it can hold
+// non-string map keys from YAML, and maps keep entries in input order.
+//
+// Value implements Deserializer, so a transformed document can be fed to an
+// Output exactly as if it had come from one of the input parsers.
+//
+// I128 and U128 hold only integers outside the 64-bit range, so that everything
+// else can keep treating I64 and U64 as the typical integer representations.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Value {
+  Null,
+  Bool(bool),
+  I64(i64),
+  U64(u64),
+  I128(i128),
+  U128(u128),
+  F64(f64),
+  String(String),
+  Seq(Vec<Value>),
+  Map(Map),
+}
+
+impl Value {
+  pub fn type_name(&self) -> &'static str {
+    match self {
+      Value::Null => "null",
+      Value::Bool(_) => "boolean",
+      Value::I64(_) | Value::U64(_) | Value::I128(_) | Value::U128(_) | Value::F64(_) => "number",
+      Value::String(_) => "string",
+      Value::Seq(_) => "array",
+      Value::Map(_) => "object",
+    }
+  }
+
+  // Returns the string form of a scalar value for the purpose of matching it
+  // against a path segment, so that keys like YAML's `1: one` can still be
+  // addressed.
+  pub fn key_str(&self) -> Option<String> {
+    match self {
+      Value::String(s) => Some(s.clone()),
+      Value::Bool(b) => Some(b.to_string()),
+      Value::I64(n) => Some(n.to_string()),
+      Value::U64(n) => Some(n.to_string()),
+      Value::I128(n) => Some(n.to_string()),
+      Value::U128(n) => Some(n.to_string()),
+      Value::F64(n) => Some(n.to_string()),
+      _ => None,
+    }
+  }
+}
+
+impl Value {
+  // Returns the canonical form of a value, where everything that should not
+  // affect its meaning has been made uniform: object keys are strings in sorted
+  // order, and floats with integer values are integers. Serializing this as
+  // compact JSON gives the same bytes for equivalent documents from any format.
+  pub fn canonical(&self) -> Value {
+    match self {
+      Value::F64(n) if n.fract() == 0.0 && n.abs() < 9007199254740992.0 => Value::I64(*n as i64),
+      Value::Seq(items) => Value::Seq(items.iter().map(Value::canonical).collect()),
+      Value::Map(map) => {
+        let mut entries: Vec<(String, Value)> = map
+          .iter()
+          .map(|(k, v)| {
+            let k = k
+              .key_str()
+              .unwrap_or_else(|| serde_json::to_string(k).unwrap_or_default());
+            (k, v.canonical())
+          })
+          .collect();
+        entries.sort_by(|(a, _), (b, _)| a.cmp(b));
+        let mut canonical = Map::new();
+        for (k, v) in entries {
+          canonical.push(Value::String(k), v);
+        }
+        Value::Map(canonical)
+      }
+      v => v.clone(),
+    }
+  }
+}
+
+// DuplicateKeys is what to do about an object with more than one entry for the
+// same key, which JSON and YAML both allow syntactically.
+#[derive(Copy, Clone)]
+pub enum DuplicateKeys {
+  Error,
+  First,
+  Last,
+}
+
+impl std::str::FromStr for DuplicateKeys {
+  type Err = String;
+
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    match s {
+      "error" => Ok(DuplicateKeys::Error),
+      "first" => Ok(DuplicateKeys::First),
+      "last" => Ok(DuplicateKeys::Last),
+      _ => Err(format!("'{}' is not a valid duplicate key policy", s)),
+    }
+  }
+}
+
+impl Value {
+  // Resolves duplicate keys in every object of a value according to a policy.
+  // With "last," the last value for a key takes the position of the first, as
+  // it would with an assignment to the key in most languages. With "error,"
+  // the error is the path of the first duplicate key in the order of the input.
+  pub fn dedup_keys(&mut self, policy: DuplicateKeys) -> Result<(), String> {
+    let mut path = String::new();
+    self.dedup_keys_at(policy, &mut path)
+  }
+
+  fn dedup_keys_at(&mut self, policy: DuplicateKeys, path: &mut String) -> Result<(), String> {
+    let error = matches!(policy, DuplicateKeys::Error);
+    match self {
+      Value::Seq(items) => {
+        for (i, item) in items.iter_mut().enumerate() {
+          let len = path.len();
+          crate::path::push_index(path, i);
+          item.dedup_keys_at(policy, path)?;
+          path.truncate(len);
+        }
+      }
+      Value::Map(map) => {
+        let mut deduped = Map::new();
+        // String keys are looked up by their text, so that a big object takes
+        // linear time. Other keys, which only YAML has, are rare enough to
+        // compare one by one.
+        let mut strings: HashMap<String, usize> = HashMap::new();
+        for (k, mut v) in std::mem::take(map) {
+          let seen = match &k {
+            Value::String(s) => strings.get(s.as_str()).copied(),
+            k => deduped.0.iter().position(|(seen, _)| seen == k),
+          };
+          match seen {
+            None => {
+              // An error has to be about the first duplicate in the input, which
+              // could be within this value rather than later in this object.
+              if error {
+                let len = path.len();
+                crate::path::push_key(path, &k);
+                v.dedup_keys_at(policy, path)?;
+                path.truncate(len);
+              }
+              if let Value::String(s) = &k {
+                strings.insert(s.clone(), deduped.len());
+              }
+              deduped.push(k, v);
+            }
+            Some(_) if matches!(policy, DuplicateKeys::First) => {}
+            Some(i) if matches!(policy, DuplicateKeys::Last) => deduped.0[i].1 = v,
+            Some(_) => {
+              crate::path::push_key(path, &k);
+              return Err(path.clone());
+            }
+          }
+        }
+        if !error {
+          for (k, v) in deduped.0.iter_mut() {
+            let len = path.len();
+            crate::path::push_key(path, k);
+            v.dedup_keys_at(policy, path)?;
+            path.truncate(len);
+          }
+        }
+        *map = deduped;
+      }
+      _ => {}
+    }
+    Ok(())
+  }
+}
+
+// KeyStyle is what to do about object keys that aren't strings, like the
+// integer and boolean keys that YAML allows, when writing a format that only
+// has string keys. "stringify" writes scalars as their text and arrays and
+// objects as compact JSON. "tagged" does the same, but with the key's YAML type
+// in front, so that 1 and "1" stay different keys: "!!int 1".
+#[derive(Copy, Clone)]
+pub enum KeyStyle {
+  Stringify,
+  Error,
+  Tagged,
+}
+
+impl std::str::FromStr for KeyStyle {
+  type Err = String;
+
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    match s {
+      "stringify" => Ok(KeyStyle::Stringify),
+      "error" => Ok(KeyStyle::Error),
+      "tagged" => Ok(KeyStyle::Tagged),
+      _ => Err(format!("'{}' is not a valid key style", s)),
+    }
+  }
+}
+
+impl Value {
+  // Replaces every object key that isn't a string according to a style.
+  pub fn string_keys(&mut self, style: KeyStyle) -> Result<(), String> {
+    let mut path = String::new();
+    self.string_keys_at(style, &mut path)
+  }
+
+  fn string_keys_at(&mut self, style: KeyStyle, path: &mut String) -> Result<(), String> {
+    match self {
+      Value::Seq(items) => {
+        for (i, item) in items.iter_mut().enumerate() {
+          let len = path.len();
+          crate::path::push_index(path, i);
+          item.string_keys_at(style, path)?;
+          path.truncate(len);
+        }
+      }
+      Value::Map(map) => {
+        for (k, v) in map.0.iter_mut() {
+          let len = path.len();
+          crate::path::push_key(path, k);
+          if !matches!(k, Value::String(_)) {
+            *k = Value::String(k.key_string(style, path)?);
+          }
+          v.string_keys_at(style, path)?;
+          path.truncate(len);
+        }
+      }
+      _ => {}
+    }
+    Ok(())
+  }
+
+  fn key_string(&mut self, style: KeyStyle, path: &mut String) -> Result<String, String> {
+    let text = match self {
+      _ if matches!(style, KeyStyle::Error) => {
+        return Err(format!(
+          "{}: key of type {} is not a string (see --key-style)",
+          path,
+          self.type_name()
+        ))
+      }
+      Value::Null => "null".to_owned(),
+      Value::Seq(_) | Value::Map(_) => {
+        self.string_keys_at(style, path)?;
+        serde_json::to_string(self).map_err(|err| err.to_string())?
+      }
+      _ => self.key_str().unwrap_or_default(),
+    };
+    Ok(match style {
+      KeyStyle::Tagged => match self {
+        Value::Null => "!!null".to_owned(),
+        Value::Bool(_) => format!("!!bool {}", text),
+        Value::I64(_) | Value::U64(_) | Value::I128(_) | Value::U128(_) => {
+          format!("!!int {}", text)
+        }
+        Value::F64(_) => format!("!!float {}", text),
+        Value::Seq(_) => format!("!!seq {}", text),
+        _ => format!("!!map {}", text),
+      },
+      _ => text,
+    })
+  }
+}
+
+// ControlChars is what to do about C0 control characters (other than tab, line
+// feed, and carriage return) in strings. JSON, YAML, and TOML output always
+// escape them, since the formats require it, but other outputs write them as
+// they are unless "escape" asks for \u0007-style escapes there too.
+#[derive(Copy, Clone, PartialEq)]
+pub enum ControlChars {
+  Raw,
+  Escape,
+  Error,
+}
+
+impl std::str::FromStr for ControlChars {
+  type Err = String;
+
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    match s {
+      "raw" => Ok(ControlChars::Raw),
+      "escape" => Ok(ControlChars::Escape),
+      "error" => Ok(ControlChars::Error),
+      _ => Err(format!("'{}' is not a valid control character policy", s)),
+    }
+  }
+}
+
+impl Value {
+  // Escapes the control characters in every string and object key, or returns
+  // an error for the first one, according to a policy.
+  pub fn control_chars(&mut self, policy: ControlChars) -> Result<(), String> {
+    let mut path = String::new();
+    self.control_chars_at(policy, &mut path)
+  }
+
+  fn control_chars_at(&mut self, policy: ControlChars, path: &mut String) -> Result<(), String> {
+    match self {
+      Value::String(s) => match s.chars().find(|&c| is_control(c)) {
+        None => {}
+        Some(c) if policy == ControlChars::Error => {
+          return Err(format!(
+            "{}: string has control character U+{:04X} (see --control-chars)",
+            path, c as u32
+          ))
+        }
+        Some(_) => {
+          *s = s
+            .chars()
+            .map(|c| match is_control(c) {
+              true => format!("\\u{:04x}", c as u32),
+              false => c.to_string(),
+            })
+            .collect()
+        }
+      },
+      Value::Seq(items) => {
+        for (i, item) in items.iter_mut().enumerate() {
+          let len = path.len();
+          crate::path::push_index(path, i);
+          item.control_chars_at(policy, path)?;
+          path.truncate(len);
+        }
+      }
+      Value::Map(map) => {
+        for (k, v) in map.0.iter_mut() {
+          let len = path.len();
+          crate::path::push_key(path, k);
+          k.control_chars_at(policy, path)?;
+          v.control_chars_at(policy, path)?;
+          path.truncate(len);
+        }
+      }
+      _ => {}
+    }
+    Ok(())
+  }
+}
+
+fn is_control(c: char) -> bool {
+  c < ' ' && !matches!(c, '\t' | '\n' | '\r')
+}
+
+impl From<&str> for Value {
+  fn from(s: &str) -> Self {
+    Value::String(s.to_owned())
+  }
+}
+
+// Map is an insertion-ordered list of entries. Lookups are linear, which is
+// fine for the size of objects that people typically write by hand, and it
+// means that we never have to think about hashing floats.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Map(Vec<(Value, Value)>);
+
+impl Map {
+  pub fn new() -> Self {
+    Map(Vec::new())
+  }
+
+  pub fn len(&self) -> usize {
+    self.0.len()
+  }
+
+  pub fn is_empty(&self) -> bool {
+    self.0.is_empty()
+  }
+
+  pub fn iter(&self) -> impl Iterator<Item = (&Value, &Value)> {
+    self.0.iter().map(|(k, v)| (k, v))
+  }
+
+  pub fn iter_mut(&mut self) -> impl Iterator<Item = (&Value, &mut Value)> {
+    self.0.iter_mut().map(|(k, v)| (&*k, v))
+  }
+
+  pub fn values_mut(&mut self) -> impl Iterator<Item = &mut Value> {
+    self.0.iter_mut().map(|(_, v)| v)
+  }
+
+  pub fn get(&self, key: &str) -> Option<&Value> {
+    self
+      .0
+      .iter()
+      .find(|(k, _)| k.key_str().as_deref() == Some(key))
+      .map(|(_, v)| v)
+  }
+
+  pub fn get_mut(&mut self, key: &str) -> Option<&mut Value> {
+    self
+      .0
+      .iter_mut()
+      .find(|(k, _)| k.key_str().as_deref() == Some(key))
+      .map(|(_, v)| v)
+  }
+
+  // Returns the value for a key, first adding a null value with that key if the
+  // map doesn't already have one.
+  pub fn entry(&mut self, key: &str) -> &mut Value {
+    let idx = match self
+      .0
+      .iter()
+      .position(|(k, _)| k.key_str().as_deref() == Some(key))
+    {
+      Some(idx) => idx,
+      None => {
+        self.0.push((Value::from(key), Value::Null));
+        self.0.len() - 1
+      }
+    };
+    &mut self.0[idx].1
+  }
+
+  // Inserts a value, replacing the value of any existing entry with the same
+  // key in place so that the original ordering is preserved.
+  pub fn insert(&mut self, key: Value, value: Value) {
+    match self.0.iter_mut().find(|(k, _)| *k == key) {
+      Some((_, v)) => *v = value,
+      None => self.0.push((key, value)),
+    }
+  }
+
+  // Changes the key of an existing entry, and reports whether there was one.
+  pub fn rename(&mut self, from: &str, to: &str) -> bool {
+    match self
+      .0
+      .iter_mut()
+      .find(|(k, _)| k.key_str().as_deref() == Some(from))
+    {
+      Some((k, _)) => {
+        *k = Value::from(to);
+        true
+      }
+      None => false,
+    }
+  }
+
+  pub fn remove(&mut self, key: &str) -> Option<Value> {
+    let idx = self
+      .0
+      .iter()
+      .position(|(k, _)| k.key_str().as_deref() == Some(key))?;
+    Some(self.0.remove(idx).1)
+  }
+
+  // Appends an entry without checking for an existing one with the same key.
+  pub fn push(&mut self, key: Value, value: Value) {
+    self.0.push((key, value));
+  }
+}
+
+impl IntoIterator for Map {
+  type Item = (Value, Value);
+  type IntoIter = std::vec::IntoIter<(Value, Value)>;
+
+  fn into_iter(self) -> Self::IntoIter {
+    self.0.into_iter()
+  }
+}
+
+impl<'de> Deserialize<'de> for Value {
+  fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+  where
+    D: Deserializer<'de>,
+  {
+    deserializer.deserialize_any(ValueVisitor)
+  }
+}
+
+struct ValueVisitor;
+
+impl<'de> Visitor<'de> for ValueVisitor {
+  type Value = Value;
+
+  fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    f.write_str("any value")
+  }
+
+  fn visit_bool<E>(self, v: bool) -> Result<Value, E> {
+    Ok(Value::Bool(v))
+  }
+
+  fn visit_i64<E>(self, v: i64) -> Result<Value, E> {
+    Ok(Value::I64(v))
+  }
+
+  fn visit_u64<E>(self, v: u64) -> Result<Value, E> {
+    Ok(Value::U64(v))
+  }
+
+  // 128-bit integers are narrowed to 64 bits wherever they fit.
+  fn visit_i128<E>(self, v: i128) -> Result<Value, E> {
+    Ok(match v {
+      v if v >= 0 && v <= u64::MAX as i128 => Value::U64(v as u64),
+      v if v >= i64::MIN as i128 && v < 0 => Value::I64(v as i64),
+      v => Value::I128(v),
+    })
+  }
+
+  fn visit_u128<E>(self, v: u128) -> Result<Value, E> {
+    Ok(match v {
+      v if v <= u64::MAX as u128 => Value::U64(v as u64),
+      v => Value::U128(v),
+    })
+  }
+
+  fn visit_f64<E>(self, v: f64) -> Result<Value, E> {
+    Ok(Value::F64(v))
+  }
+
+  fn visit_str<E>(self, v: &str) -> Result<Value, E> {
+    Ok(Value::String(v.to_owned()))
+  }
+
+  fn visit_string<E>(self, v: String) -> Result<Value, E> {
+    Ok(Value::String(v))
+  }
+
+  fn visit_unit<E>(self) -> Result<Value, E> {
+    Ok(Value::Null)
+  }
+
+  fn visit_none<E>(self) -> Result<Value, E> {
+    Ok(Value::Null)
+  }
+
+  fn visit_some<D>(self, deserializer: D) -> Result<Value, D::Error>
+  where
+    D: Deserializer<'de>,
+  {
+    Value::deserialize(deserializer)
+  }
+
+  fn visit_newtype_struct<D>(self, deserializer: D) -> Result<Value, D::Error>
+  where
+    D: Deserializer<'de>,
+  {
+    Value::deserialize(deserializer)
+  }
+
+  fn visit_seq<A>(self, mut seq: A) -> Result<Value, A::Error>
+  where
+    A: SeqAccess<'de>,
+  {
+    let mut items = Vec::with_capacity(seq.size_hint().unwrap_or(0));
+    while let Some(item) = seq.next_element()? {
+      items.push(item);
+    }
+    Ok(Value::Seq(items))
+  }
+
+  fn visit_map<A>(self, mut map: A) -> Result<Value, A::Error>
+  where
+    A: MapAccess<'de>,
+  {
+    // serde_json gives numbers as maps with a special key, which can't appear
+    // in real input (see the json module).
+    let mut entries = Map::new();
+    let first: Option<Value> = map.next_key()?;
+    if let Some(Value::String(k)) = &first {
+      if k == json::NUMBER_TOKEN {
+        let text: String = map.next_value()?;
+        let n = json::Number::parse(&text).map_err(A::Error::custom)?;
+        return n.visit(self);
+      }
+    }
+    if let Some(k) = first {
+      entries.push(k, map.next_value()?);
+    }
+    while let Some((k, v)) = map.next_entry()? {
+      entries.push(k, v);
+    }
+    Ok(Value::Map(entries))
+  }
+}
+
+impl Serialize for Value {
+  fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+  where
+    S: Serializer,
+  {
+    match self {
+      Value::Null => serializer.serialize_unit(),
+      Value::Bool(b) => serializer.serialize_bool(*b),
+      Value::I64(n) => serializer.serialize_i64(*n),
+      Value::U64(n) => serializer.serialize_u64(*n),
+      Value::I128(n) => serializer.serialize_i128(*n),
+      Value::U128(n) => serializer.serialize_u128(*n),
+      Value::F64(n) => serializer.serialize_f64(*n),
+      Value::String(s) => serializer.serialize_str(s),
+      Value::Seq(items) => {
+        let mut seq = serializer.serialize_seq(Some(items.len()))?;
+        for item in items {
+          seq.serialize_element(item)?;
+        }
+        seq.end()
+      }
+      Value::Map(entries) => {
+        let mut map = serializer.serialize_map(Some(entries.len()))?;
+        for (k, v) in entries.iter() {
+          map.serialize_entry(k, v)?;
+        }
+        map.end()
+      }
+    }
+  }
+}
+
+impl<'de> Deserializer<'de> for Value {
+  type Error = DeError;
+
+  fn deserialize_any<V>(self, visitor: V) -> Result<V::Value, DeError>
+  where
+    V: Visitor<'de>,
+  {
+    match self {
+      Value::Null => visitor.visit_unit(),
+      Value::Bool(b) => visitor.visit_bool(b),
+      Value::I64(n) => visitor.visit_i64(n),
+      Value::U64(n) => visitor.visit_u64(n),
+      Value::I128(n) => visitor.visit_i128(n),
+      Value::U128(n) => visitor.visit_u128(n),
+      Value::F64(n) => visitor.visit_f64(n),
+      Value::String(s) => visitor.visit_string(s),
+      Value::Seq(items) => {
+        let mut de = SeqDeserializer::new(items.into_iter());
+        let result = visitor.visit_seq(&mut de)?;
+        de.end()?;
+        Ok(result)
+      }
+      Value::Map(entries) => {
+        let mut de = MapDeserializer::new(entries.into_iter());
+        let result = visitor.visit_map(&mut de)?;
+        de.end()?;
+        Ok(result)
+      }
+    }
+  }
+
+  fn deserialize_option<V>(self, visitor: V) -> Result<V::Value, DeError>
+  where
+    V: Visitor<'de>,
+  {
+    match self {
+      Value::Null => visitor.visit_none(),
+      _ => visitor.visit_some(self),
+    }
+  }
+
+  serde::forward_to_deserialize_any! {
+    bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+    bytes byte_buf unit unit_struct newtype_struct seq tuple tuple_struct map
+    struct enum identifier ignored_any
+  }
+}
+
+impl<'de> IntoDeserializer<'de, DeError> for Value {
+  type Deserializer = Self;
+
+  fn into_deserializer(self) -> Self {
+    self
+  }
+}
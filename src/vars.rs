@@ -0,0 +1,109 @@
+use std::sync::Mutex;
+
+use crate::path::Path;
+use crate::value::Value;
+
+// Variables are the values that --arg and --argjson bind to names, for --set,
+// --interpolate, and templates to refer to as $name, or as $name.path for a
+// value within one. Like the formats of the config file's [extensions], they're
+// global state, so that a reference can be looked up wherever it turns up.
+static VARIABLES: Mutex<Vec<Variable>> = Mutex::new(Vec::new());
+
+#[derive(Clone)]
+pub struct Variable {
+  name: String,
+  value: Value,
+}
+
+// Parses a NAME=VALUE argument to --arg, where the value is a string.
+pub fn parse_arg(s: &str) -> Result<Variable, String> {
+  let (name, value) = split(s)?;
+  Ok(Variable {
+    name: name.to_owned(),
+    value: Value::from(value),
+  })
+}
+
+// Parses a NAME=JSON argument to --argjson.
+pub fn parse_argjson(s: &str) -> Result<Variable, String> {
+  let (name, value) = split(s)?;
+  match serde_json::from_str(value) {
+    Ok(value) => Ok(Variable {
+      name: name.to_owned(),
+      value,
+    }),
+    Err(err) => Err(format!("the value of ${} is not valid JSON: {}", name, err)),
+  }
+}
+
+fn split(s: &str) -> Result<(&str, &str), String> {
+  match s.split_once('=') {
+    Some((name, value)) if is_name(name) => Ok((name, value)),
+    Some((name, _)) => Err(format!("'{}' is not a valid variable name", name)),
+    None => Err(format!("'{}' is not of the form NAME=VALUE", s)),
+  }
+}
+
+fn is_name(s: &str) -> bool {
+  let mut chars = s.chars();
+  matches!(chars.next(), Some(c) if c.is_ascii_alphabetic() || c == '_')
+    && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+pub fn bind(variables: Vec<Variable>) -> Result<(), String> {
+  for (i, variable) in variables.iter().enumerate() {
+    if variables[..i]
+      .iter()
+      .any(|other| other.name == variable.name)
+    {
+      return Err(format!("${} is bound more than once", variable.name));
+    }
+  }
+  *VARIABLES.lock().unwrap_or_else(|err| err.into_inner()) = variables;
+  Ok(())
+}
+
+// Reports whether a variable by some name is bound.
+pub fn is_bound(name: &str) -> bool {
+  let variables = VARIABLES.lock().unwrap_or_else(|err| err.into_inner());
+  variables.iter().any(|variable| variable.name == name)
+}
+
+// Returns the value that a reference like $name or $name.items[0] refers to,
+// or None if s isn't a reference at all.
+pub fn lookup(s: &str) -> Option<Result<Value, String>> {
+  let (name, path) = reference(s)?;
+  Some(resolve(name, &path))
+}
+
+// Splits a reference into the name of its variable and the path within it.
+pub fn reference(s: &str) -> Option<(&str, String)> {
+  let s = s.strip_prefix('$')?;
+  let end = s
+    .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))
+    .unwrap_or(s.len());
+  let (name, rest) = s.split_at(end);
+  if !is_name(name) {
+    return None;
+  }
+  let path = match rest.chars().next() {
+    None => ".".to_owned(),
+    Some('.' | '/') => rest.to_owned(),
+    Some('[') => format!(".{}", rest),
+    Some(_) => return None,
+  };
+  Some((name, path))
+}
+
+fn resolve(name: &str, path: &str) -> Result<Value, String> {
+  let path: Path = path.parse()?;
+  let variables = VARIABLES.lock().unwrap_or_else(|err| err.into_inner());
+  let value = match variables.iter().find(|variable| variable.name == name) {
+    Some(variable) => &variable.value,
+    None => return Err(format!("${} is not bound (see --arg)", name)),
+  };
+  match path.select(value).first() {
+    Some(v) => Ok((*v).clone()),
+    None => Err(format!("${}: no value at {}", name, path)),
+  }
+}
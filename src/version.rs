@@ -0,0 +1,93 @@
+use std::error::Error;
+use std::io::{self, Write};
+
+use structopt::StructOpt;
+
+use crate::Format;
+
+// VersionOpt holds the options for 'jyt version', which reports what this build
+// of jyt can do, for bug reports and for scripts that depend on a format or
+// feature.
+#[derive(StructOpt)]
+#[structopt(name = "jyt version", verbatim_doc_comment)]
+/// Print the version of jyt and what it was built with
+///
+/// Along with the version, this lists the formats that jyt reads and writes,
+/// and the features that depend on the system it was built for: mmap-advice
+/// for --mmap-advice, and preallocate for --preallocate. Where a feature isn't
+/// built in, its option is accepted and does nothing. self-update is for 'jyt
+/// self-update', which only builds with 'cargo build --features self-update'.
+/// --json writes the same report as a JSON object, e.g.
+///
+///   {"features":{"mmap-advice":true,...},"input_formats":["json",...],
+///    "name":"jyt","output_formats":["json",...],"version":"0.5.2"}
+pub struct VersionOpt {
+  #[structopt(long = "json", help = "Write the report as JSON")]
+  json: bool,
+}
+
+const INPUT_FORMATS: &[Format] = &[
+  Format::Json,
+  Format::Yaml,
+  Format::Toml,
+  Format::Csv,
+  Format::Tsv,
+];
+
+const OUTPUT_FORMATS: &[Format] = &[
+  Format::Json,
+  Format::Yaml,
+  Format::Toml,
+  Format::Csv,
+  Format::Tsv,
+  Format::Table,
+  Format::Tree,
+  Format::Template,
+];
+
+const FEATURES: &[(&str, bool)] = &[
+  ("mmap-advice", cfg!(unix)),
+  ("preallocate", cfg!(target_os = "linux")),
+  ("self-update", cfg!(feature = "self-update")),
+];
+
+pub fn version(opt: VersionOpt) -> Result<(), Box<dyn Error>> {
+  let names = |formats: &[Format]| {
+    formats
+      .iter()
+      .map(|format| format.name())
+      .collect::<Vec<_>>()
+  };
+  let mut stdout = io::stdout();
+  if opt.json {
+    let features: serde_json::Map<_, _> = FEATURES
+      .iter()
+      .map(|&(name, enabled)| (name.to_owned(), enabled.into()))
+      .collect();
+    let report = serde_json::json!({
+      "name": "jyt",
+      "version": env!("CARGO_PKG_VERSION"),
+      "input_formats": names(INPUT_FORMATS),
+      "output_formats": names(OUTPUT_FORMATS),
+      "features": features,
+    });
+    writeln!(stdout, "{}", report)?;
+    return Ok(());
+  }
+  writeln!(stdout, "jyt {}", env!("CARGO_PKG_VERSION"))?;
+  writeln!(stdout, "input formats: {}", names(INPUT_FORMATS).join(" "))?;
+  writeln!(
+    stdout,
+    "output formats: {}",
+    names(OUTPUT_FORMATS).join(" ")
+  )?;
+  let features: Vec<_> = FEATURES
+    .iter()
+    .map(|&(name, enabled)| match enabled {
+      true => format!("+{}", name),
+      false => format!("-{}", name),
+    })
+    .collect();
+  writeln!(stdout, "features: {}", features.join(" "))?;
+  Ok(())
+}
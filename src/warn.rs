@@ -0,0 +1,40 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+
+use crate::color;
+
+// Warnings about conversions that lose information, like YAML tags that the
+// output has no way to write. They go to stderr as soon as they're found, each
+// at most once, unless -q turned them off. With -v, notes about what jyt is
+// doing, like how it chose the input format, go to stderr along with them. This
+// is global state so that warnings can come from anywhere without every layer
+// of jyt passing around a place to put them.
+
+static QUIET: AtomicBool = AtomicBool::new(false);
+static VERBOSE: AtomicBool = AtomicBool::new(false);
+static WARNED: Mutex<Vec<String>> = Mutex::new(Vec::new());
+
+pub fn set_quiet(quiet: bool) {
+  QUIET.store(quiet, Ordering::Relaxed);
+}
+
+pub fn set_verbose(verbose: bool) {
+  VERBOSE.store(verbose, Ordering::Relaxed);
+}
+
+pub fn info(message: &str) {
+  if VERBOSE.load(Ordering::Relaxed) {
+    eprintln!("{} {}", color::label("info"), message);
+  }
+}
+
+pub fn warn(message: &str) {
+  if QUIET.load(Ordering::Relaxed) {
+    return;
+  }
+  let mut warned = WARNED.lock().unwrap_or_else(|err| err.into_inner());
+  if !warned.iter().any(|seen| seen == message) {
+    eprintln!("{} {}", color::label("warning"), message);
+    warned.push(message.to_owned());
+  }
+}
@@ -0,0 +1,477 @@
+// The outputs that write documents in each of jyt's formats, at the end of the
+// pipeline.
+
+use std::error::Error;
+use std::io::Write;
+
+use serde::{Deserialize, Serialize};
+
+use crate::csv;
+use crate::datetime::{self, TomlDatetime};
+use crate::json::{self, NonFinite};
+use crate::output::Output;
+use crate::path;
+use crate::table;
+use crate::template::Template;
+use crate::tree;
+use crate::value::{self, KeyStyle, Value};
+use crate::yaml;
+
+pub struct JsonOutput<W> {
+  w: W,
+  non_finite: NonFinite,
+  key_style: KeyStyle,
+  // Whether every key in the input is a string, so that key_style can't apply.
+  string_keys: bool,
+  documents: usize,
+}
+
+impl<W> JsonOutput<W> {
+  pub fn new(w: W, non_finite: NonFinite, key_style: KeyStyle, string_keys: bool) -> Self {
+    JsonOutput {
+      w,
+      non_finite,
+      key_style,
+      string_keys,
+      documents: 0,
+    }
+  }
+}
+
+impl<W> Output for JsonOutput<W>
+where
+  W: Write,
+{
+  fn transcode_from<'de, D, E>(&mut self, de: D) -> Result<(), Box<dyn Error>>
+  where
+    D: serde::de::Deserializer<'de, Error = E>,
+    E: serde::de::Error + 'static,
+  {
+    let idx = self.documents;
+    self.documents += 1;
+    let mut ser = serde_json::Serializer::new(&mut self.w);
+    match (self.non_finite, self.string_keys) {
+      // serde_json already writes non-finite floats as null, so there's no need
+      // to buffer the document.
+      (NonFinite::Null, true) => serde_transcode::transcode(de, &mut ser)?,
+      (policy, _) => {
+        let mut doc = Value::deserialize(de)?;
+        doc
+          .string_keys(self.key_style)
+          .map_err(|err| format!("document {}, {}", idx, err))?;
+        if let (NonFinite::Error, Some((path, n))) = (policy, json::find_non_finite(&doc)) {
+          Err(format!(
+            "document {}, {}: {} cannot be represented in JSON (see --non-finite)",
+            idx,
+            if path.is_empty() { "." } else { &path },
+            json::non_finite_name(n)
+          ))?;
+        }
+        json::WithNonFinite(&doc, policy).serialize(&mut ser)?;
+      }
+    }
+    writeln!(&mut self.w)?;
+    Ok(())
+  }
+}
+
+pub struct YamlOutput<W> {
+  w: W,
+  tags: yaml::Tags,
+  version: Option<yaml::Version>,
+  ints: yaml::IntLiterals,
+  anchors: bool,
+  // Each document is emitted here before it's written, reusing the space from
+  // the last one.
+  buf: String,
+}
+
+impl<W> YamlOutput<W> {
+  pub fn new(
+    w: W,
+    tags: yaml::Tags,
+    version: Option<yaml::Version>,
+    ints: yaml::IntLiterals,
+    anchors: bool,
+  ) -> Self {
+    YamlOutput {
+      w,
+      tags,
+      version,
+      ints,
+      anchors,
+      buf: String::new(),
+    }
+  }
+}
+
+impl<W> Output for YamlOutput<W>
+where
+  W: Write,
+{
+  fn transcode_from<'de, D, E>(&mut self, de: D) -> Result<(), Box<dyn Error>>
+  where
+    D: serde::de::Deserializer<'de, Error = E>,
+    E: serde::de::Error + 'static,
+  {
+    // Anchors and wrapped tags depend on the whole document, so those need it
+    // built as a value first. Otherwise, each value is emitted as it's read.
+    self.buf.clear();
+    if self.anchors || self.tags == yaml::Tags::Wrap {
+      let doc = Value::deserialize(de)?;
+      yaml::Emitter::new(&mut self.buf, self.tags, self.version, self.ints)
+        .dump(&doc, self.anchors);
+    } else {
+      yaml::emit_from(&mut self.buf, de, self.version, self.ints)?;
+    }
+    self.buf.push('\n');
+    self.w.write_all(self.buf.as_bytes())?;
+    Ok(())
+  }
+}
+
+pub struct TomlOutput<W> {
+  w: W,
+  used: bool,
+  key_style: KeyStyle,
+  datetime: TomlDatetime,
+}
+
+impl<W> TomlOutput<W> {
+  pub fn new(w: W, key_style: KeyStyle, datetime: TomlDatetime) -> Self {
+    TomlOutput {
+      w,
+      used: false,
+      key_style,
+      datetime,
+    }
+  }
+}
+
+impl<W> Output for TomlOutput<W>
+where
+  W: Write,
+{
+  fn transcode_from<'de, D, E>(&mut self, de: D) -> Result<(), Box<dyn Error>>
+  where
+    D: serde::de::Deserializer<'de, Error = E>,
+    E: serde::de::Error + 'static,
+  {
+    self.used = match self.used {
+      false => true,
+      true => Err("TOML does not support multi-document output")?,
+    };
+
+    // TOML requires that all non-table values appear before any tables at a
+    // given "level." Since we can't enforce this for all input types, we buffer
+    // the inputs into a toml::Value, which will serialize them back out in the
+    // necessary order: plain values first, then arrays of tables, then tables,
+    // each in their original order. This holds at every level, including within
+    // the tables of an array.
+    let mut doc = Value::deserialize(de)?;
+    doc.string_keys(self.key_style)?;
+    let mut value = toml::Value::deserialize(doc)?;
+
+    // From the spec: "TOML is designed to map unambiguously to a hash table."
+    // Without this check, the other input types could produce something like a
+    // boolean or array that we would attempt to dump the TOML representation of
+    // without a second thought. The toml crate can even produce invalid TOML
+    // for some of these representations, such as dumping each element of an
+    // array of tables with an empty name, i.e. with a "[[]]" header.
+    if !value.is_table() {
+      Err("root of TOML output must be a table")?;
+    }
+
+    // Reordering can't help with tables in an array alongside other values, or
+    // in a nested array. The toml crate would write the first as broken TOML
+    // and fail on the second with a confusing error, since it doesn't write
+    // arrays of tables inline.
+    check_toml_arrays(&value, &mut String::new())?;
+
+    // The toml crate writes negative zero as "--0.0", which isn't valid TOML, so
+    // each one is swapped for a string that appears nowhere else in the
+    // document, and written by hand where that string ends up.
+    let mut marker = String::from("jyt-negative-zero");
+    while contains_text(&value, &marker) {
+      marker.push('-');
+    }
+    let has_negative_zero = mark_negative_zeros(&mut value, &marker);
+    if self.datetime == TomlDatetime::String {
+      datetime::to_toml(&mut value);
+    }
+
+    // As of this writing, the toml crate can't output directly to a writer.
+    let mut output_buf = toml::to_string_pretty(&value)?;
+    if has_negative_zero {
+      for quote in ['\'', '"'] {
+        output_buf = output_buf.replace(&format!("{0}{1}{0}", quote, marker), "-0.0");
+      }
+    }
+    self.w.write_all(output_buf.as_bytes())?;
+    Ok(())
+  }
+}
+
+// Returns an error for the arrays in a TOML output document that the toml crate
+// can't write, with the path to the offending element.
+fn check_toml_arrays(v: &toml::Value, path: &mut String) -> Result<(), String> {
+  let items = match v {
+    toml::Value::Array(items) => items,
+    toml::Value::Table(table) => {
+      for (k, v) in table {
+        let len = path.len();
+        path::push_key(path, &Value::from(k.as_str()));
+        check_toml_arrays(v, path)?;
+        path.truncate(len);
+      }
+      return Ok(());
+    }
+    _ => return Ok(()),
+  };
+  let of_tables = items.iter().any(toml::Value::is_table);
+  for (i, item) in items.iter().enumerate() {
+    let len = path.len();
+    path::push_index(path, i);
+    match item {
+      toml::Value::Table(_) => {}
+      _ if of_tables => Err(format!(
+        "{}: TOML output cannot mix tables with other values in an array",
+        path
+      ))?,
+      toml::Value::Array(inner) if inner.iter().any(toml::Value::is_table) => Err(format!(
+        "{}: TOML output cannot have tables within a nested array",
+        path
+      ))?,
+      _ => {}
+    }
+    check_toml_arrays(item, path)?;
+    path.truncate(len);
+  }
+  Ok(())
+}
+
+// Reports whether any key or string in a TOML value contains some text.
+fn contains_text(v: &toml::Value, text: &str) -> bool {
+  match v {
+    toml::Value::String(s) => s.contains(text),
+    toml::Value::Array(items) => items.iter().any(|v| contains_text(v, text)),
+    toml::Value::Table(table) => table
+      .iter()
+      .any(|(k, v)| k.contains(text) || contains_text(v, text)),
+    _ => false,
+  }
+}
+
+// Replaces each negative zero in a TOML value with a marker string, and reports
+// whether there were any.
+fn mark_negative_zeros(v: &mut toml::Value, marker: &str) -> bool {
+  let mut found = false;
+  match v {
+    toml::Value::Float(n) if *n == 0.0 && n.is_sign_negative() => {
+      *v = toml::Value::String(marker.to_owned());
+      found = true;
+    }
+    toml::Value::Array(items) => {
+      for v in items {
+        found |= mark_negative_zeros(v, marker);
+      }
+    }
+    toml::Value::Table(table) => {
+      for (_, v) in table.iter_mut() {
+        found |= mark_negative_zeros(v, marker);
+      }
+    }
+    _ => {}
+  }
+  found
+}
+
+pub struct TreeOutput<W>(pub W);
+
+impl<W> Output for TreeOutput<W>
+where
+  W: Write,
+{
+  fn transcode_from<'de, D, E>(&mut self, de: D) -> Result<(), Box<dyn Error>>
+  where
+    D: serde::de::Deserializer<'de, Error = E>,
+    E: serde::de::Error + 'static,
+  {
+    let doc = Value::deserialize(de)?;
+    tree::write(&mut self.0, &doc)?;
+    Ok(())
+  }
+}
+
+pub struct TemplateOutput<'t, W> {
+  pub w: W,
+  pub template: &'t Template,
+}
+
+impl<'t, W> Output for TemplateOutput<'t, W>
+where
+  W: Write,
+{
+  fn transcode_from<'de, D, E>(&mut self, de: D) -> Result<(), Box<dyn Error>>
+  where
+    D: serde::de::Deserializer<'de, Error = E>,
+    E: serde::de::Error + 'static,
+  {
+    let doc = Value::deserialize(de)?;
+    self.template.render(&mut self.w, &doc)
+  }
+}
+
+// CsvOutput writes each object document, or each element of an array document,
+// as a row. Unless the columns are given up front, the header is the union of
+// the keys of every row in the order first seen, so rows are held back until
+// the end of the input.
+pub struct CsvOutput<'c, W> {
+  w: W,
+  delimiter: char,
+  columns: &'c [String],
+  missing: csv::Missing,
+  rows: Vec<value::Map>,
+  written: usize,
+}
+
+impl<'c, W> CsvOutput<'c, W>
+where
+  W: Write,
+{
+  pub fn new(w: W, delimiter: char, columns: &'c [String], missing: csv::Missing) -> Self {
+    CsvOutput {
+      w,
+      delimiter,
+      columns,
+      missing,
+      rows: Vec::new(),
+      written: 0,
+    }
+  }
+
+  fn write_row(&mut self, columns: &[String], row: &value::Map) -> Result<(), Box<dyn Error>> {
+    if self.written == 0 {
+      csv::write_record(&mut self.w, columns, self.delimiter)?;
+    }
+    let mut fields = Vec::with_capacity(columns.len());
+    for column in columns {
+      fields.push(match (row.get(column), self.missing) {
+        (Some(v), _) => csv::field(v),
+        (None, csv::Missing::Blank) => String::new(),
+        (None, csv::Missing::Error) => Err(format!(
+          "row {} has no value for column '{}'",
+          self.written + 1,
+          column
+        ))?,
+      });
+    }
+    csv::write_record(&mut self.w, &fields, self.delimiter)?;
+    self.written += 1;
+    Ok(())
+  }
+}
+
+impl<'c, W> Output for CsvOutput<'c, W>
+where
+  W: Write,
+{
+  fn transcode_from<'de, D, E>(&mut self, de: D) -> Result<(), Box<dyn Error>>
+  where
+    D: serde::de::Deserializer<'de, Error = E>,
+    E: serde::de::Error + 'static,
+  {
+    for row in rows_of(Value::deserialize(de)?)? {
+      if self.columns.is_empty() {
+        self.rows.push(row);
+      } else {
+        self.write_row(self.columns, &row)?;
+      }
+    }
+    Ok(())
+  }
+
+  fn finish(&mut self) -> Result<(), Box<dyn Error>> {
+    let columns = columns_of(self.columns, &self.rows);
+    for row in std::mem::take(&mut self.rows) {
+      self.write_row(&columns, &row)?;
+    }
+    if self.written == 0 && !columns.is_empty() {
+      csv::write_record(&mut self.w, &columns, self.delimiter)?;
+    }
+    Ok(())
+  }
+}
+
+// Returns the rows of a document for tabular output, which are the elements of
+// an array or else the document itself.
+fn rows_of(doc: Value) -> Result<Vec<value::Map>, Box<dyn Error>> {
+  let rows = match doc {
+    Value::Seq(items) => items,
+    doc => vec![doc],
+  };
+  let mut maps = Vec::with_capacity(rows.len());
+  for row in rows {
+    match row {
+      Value::Map(row) => maps.push(row),
+      v => Err(format!("cannot output {} as a row", v.type_name()))?,
+    }
+  }
+  Ok(maps)
+}
+
+// Returns the requested columns for tabular output, or else the union of the
+// keys of all rows in the order first seen.
+fn columns_of(requested: &[String], rows: &[value::Map]) -> Vec<String> {
+  let mut columns = requested.to_vec();
+  if columns.is_empty() {
+    for row in rows {
+      for (k, _) in row.iter() {
+        let k = csv::field(k);
+        if !columns.contains(&k) {
+          columns.push(k);
+        }
+      }
+    }
+  }
+  columns
+}
+
+// TableOutput writes all of the rows of the input as an aligned table.
+pub struct TableOutput<'c, W> {
+  w: W,
+  columns: &'c [String],
+  max_width: usize,
+  rows: Vec<value::Map>,
+}
+
+impl<'c, W> TableOutput<'c, W> {
+  pub fn new(w: W, columns: &'c [String], max_width: usize) -> Self {
+    TableOutput {
+      w,
+      columns,
+      max_width,
+      rows: Vec::new(),
+    }
+  }
+}
+
+impl<'c, W> Output for TableOutput<'c, W>
+where
+  W: Write,
+{
+  fn transcode_from<'de, D, E>(&mut self, de: D) -> Result<(), Box<dyn Error>>
+  where
+    D: serde::de::Deserializer<'de, Error = E>,
+    E: serde::de::Error + 'static,
+  {
+    self.rows.extend(rows_of(Value::deserialize(de)?)?);
+    Ok(())
+  }
+
+  fn finish(&mut self) -> Result<(), Box<dyn Error>> {
+    let columns = columns_of(self.columns, &self.rows);
+    table::write(&mut self.w, &columns, &self.rows, self.max_width)?;
+    Ok(())
+  }
+}
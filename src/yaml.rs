@@ -0,0 +1,1932 @@
+use std::collections::{HashMap, HashSet};
+use std::convert::TryFrom;
+use std::fmt::{self, Write as _};
+use std::io::{self, BufRead};
+use std::str::FromStr;
+
+use serde::de::{self, DeserializeSeed, Deserializer, MapAccess, SeqAccess, Visitor};
+use serde::Deserialize;
+use yaml_rust::parser::{Event, MarkedEventReceiver, Parser};
+use yaml_rust::scanner::{Marker, Scanner, TScalarStyle, TokenType};
+
+use crate::base64;
+use crate::comments::{find_comment, Line};
+use crate::datetime::{self, YamlTimestamps};
+use crate::path;
+use crate::schema::Positions;
+use crate::value::{Map, Value};
+
+// serde_yaml handles everything about YAML input that can be expressed through
+// serde, which leaves out details of the source text like anchors and aliases.
+// For the options that care about those, we look at the YAML token stream
+// directly.
+
+// Returns an error describing the first alias in the input, if it has any.
+pub fn forbid_aliases(input: &str) -> Result<(), String> {
+  let mut scanner = Scanner::new(input.chars());
+  loop {
+    match scanner.next_token() {
+      Ok(Some(token)) => {
+        if let TokenType::Alias(name) = token.1 {
+          return Err(format!(
+            "input uses alias *{} at line {} column {}, and --no-expand-aliases was given",
+            name,
+            token.0.line(),
+            token.0.col() + 1
+          ));
+        }
+      }
+      // Syntax errors are better reported by the real parser.
+      Ok(None) | Err(_) => return Ok(()),
+    }
+  }
+}
+
+// Splits a YAML input into the text of each document, along with the line that
+// it starts on, for reading the documents one at a time. A document starts at a
+// "---" line, unless everything before it is directives, comments, and blank
+// lines, which belong to the same document.
+pub fn split_documents(input: &str) -> Vec<(usize, &str)> {
+  let mut docs = Vec::new();
+  let (mut start, mut start_line, mut content) = (0, 0, false);
+  let mut pos = 0;
+  for (i, line) in input.split_inclusive('\n').enumerate() {
+    let marker =
+      line.starts_with("---") && line[3..].chars().next().is_none_or(char::is_whitespace);
+    if marker && content {
+      docs.push((start_line, &input[start..pos]));
+      start = pos;
+      start_line = i;
+      content = false;
+    }
+    let trimmed = line.trim();
+    content |= marker || !(trimmed.is_empty() || trimmed.starts_with(['#', '%']));
+    pos += line.len();
+  }
+  if content {
+    docs.push((start_line, &input[start..]));
+  }
+  docs
+}
+
+// Documents reads the documents of a YAML input one at a time, split the same
+// way as split_documents, for input that isn't all in memory at once.
+pub struct Documents<R> {
+  reader: R,
+  line: usize,
+  // The "---" line that starts the next document, once it's been read.
+  next: Option<String>,
+}
+
+impl<R> Documents<R> {
+  pub fn new(reader: R) -> Self {
+    Documents {
+      reader,
+      line: 0,
+      next: None,
+    }
+  }
+}
+
+impl<R: BufRead> Iterator for Documents<R> {
+  type Item = io::Result<(usize, String)>;
+
+  fn next(&mut self) -> Option<Self::Item> {
+    let (start, mut text, mut content) = match self.next.take() {
+      Some(marker) => (self.line - 1, marker, true),
+      None => (self.line, String::new(), false),
+    };
+    loop {
+      let mut line = String::new();
+      match self.reader.read_line(&mut line) {
+        Ok(0) => break,
+        Ok(_) => {}
+        Err(err) => return Some(Err(err)),
+      }
+      self.line += 1;
+      let marker =
+        line.starts_with("---") && line[3..].chars().next().is_none_or(char::is_whitespace);
+      if marker && content {
+        self.next = Some(line);
+        return Some(Ok((start, text)));
+      }
+      let trimmed = line.trim();
+      content |= marker || !(trimmed.is_empty() || trimmed.starts_with(['#', '%']));
+      text.push_str(&line);
+    }
+    match content {
+      true => Some(Ok((start, text))),
+      false => None,
+    }
+  }
+}
+
+// Features are the parts of a YAML input that some conversions can't keep:
+// aliases, custom tags, and "!!binary" tags.
+#[derive(Default)]
+pub struct Features {
+  pub aliases: bool,
+  pub tags: bool,
+  pub binary: bool,
+}
+
+// Reports which of the features that some conversions can't keep a YAML input
+// uses.
+pub fn find_features(input: &str) -> Features {
+  let mut features = Features::default();
+  let mut scanner = Scanner::new(input.chars());
+  while let Ok(Some(token)) = scanner.next_token() {
+    match token.1 {
+      TokenType::Alias(_) => features.aliases = true,
+      TokenType::Tag(handle, suffix) if handle == "!!" && suffix == "binary" => {
+        features.binary = true
+      }
+      TokenType::Tag(handle, suffix) => features.tags |= custom_tag(&handle, &suffix).is_some(),
+      _ => {}
+    }
+  }
+  features
+}
+
+// Returns an error describing the first line of the input that is indented
+// with a tab, which YAML forbids but yaml-rust accepts, often by reading the tab
+// as part of a key or value.
+pub fn forbid_tab_indentation(input: &str) -> Result<(), String> {
+  let layout = Layout::new(input)?;
+  for (i, text) in layout.texts.iter().enumerate() {
+    if layout.block_lines.contains(&i) || layout.in_quotes(i, 0) {
+      continue;
+    }
+    let indent = &text[..text.len() - text.trim_start_matches([' ', '\t']).len()];
+    if let Some(col) = indent.find('\t') {
+      return Err(format!(
+        "input uses a tab for indentation at line {} column {}, and --strict was given",
+        i + 1,
+        col + 1
+      ));
+    }
+  }
+  Ok(())
+}
+
+// Tags is what to do with custom tags like "!Ref" in YAML input and output.
+// Dropping them keeps only the tagged values, as serde_yaml does. Wrapping them
+// represents each tagged value as an object with the tag as its only key, like
+// {"!Ref": "MyBucket"}, which YAML output turns back into a tag.
+#[derive(Copy, Clone, Default, PartialEq)]
+pub enum Tags {
+  #[default]
+  Drop,
+  Wrap,
+}
+
+impl FromStr for Tags {
+  type Err = String;
+
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    match s {
+      "drop" => Ok(Tags::Drop),
+      "wrap" => Ok(Tags::Wrap),
+      _ => Err(format!("'{}' is not a valid YAML tag mode", s)),
+    }
+  }
+}
+
+// Version is the version of YAML whose rules decide the types of plain scalars.
+// YAML 1.1 reads yes, no, on, and off as booleans, numbers with leading zeros
+// as octal, and numbers like 1:30 in base 60. YAML 1.2, following its core
+// schema, reads all of those as strings.
+#[derive(Copy, Clone, PartialEq)]
+pub enum Version {
+  V1_1,
+  V1_2,
+}
+
+impl FromStr for Version {
+  type Err = String;
+
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    match s {
+      "1.1" => Ok(Version::V1_1),
+      "1.2" => Ok(Version::V1_2),
+      _ => Err(format!("'{}' is not a valid YAML version", s)),
+    }
+  }
+}
+
+// IntLiterals is how plain YAML scalars written as integers in another base,
+// like 0o755, 0x1F, and the legacy octal 0755, are read and written. They're
+// numbers by default, except for legacy octal, which YAML 1.2 doesn't have.
+// "octal" reads legacy octal as octal too, as YAML 1.1 does. "keep" keeps all
+// of them as strings of their original text, so that file modes and the like
+// come through a conversion as they were written, and YAML output writes such
+// strings back without quotes.
+#[derive(Copy, Clone, Default, PartialEq)]
+pub enum IntLiterals {
+  #[default]
+  Convert,
+  Octal,
+  Keep,
+}
+
+impl FromStr for IntLiterals {
+  type Err = String;
+
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    match s {
+      "convert" => Ok(IntLiterals::Convert),
+      "octal" => Ok(IntLiterals::Octal),
+      "keep" => Ok(IntLiterals::Keep),
+      _ => Err(format!("'{}' is not a valid integer literal mode", s)),
+    }
+  }
+}
+
+// Returns the radix of a plain scalar that's an integer in another base, along
+// with its sign and digits.
+fn radix_literal(v: &str) -> Option<(u32, &str, &str)> {
+  let digits = v.strip_prefix(['-', '+']).unwrap_or(v);
+  let sign = &v[..v.len() - digits.len()];
+  let (radix, rest) = match digits.get(..2) {
+    Some("0o") => (8, &digits[2..]),
+    Some("0x") => (16, &digits[2..]),
+    _ if digits.len() > 1 && digits.starts_with('0') => (8, &digits[1..]),
+    _ => return None,
+  };
+  match !rest.is_empty() && rest.chars().all(|c| c.is_digit(radix)) {
+    true => Some((radix, sign, rest)),
+    false => None,
+  }
+}
+
+// MergeKeys is what to do with the "<<" merge keys of YAML 1.1, which pull the
+// entries of other objects (usually aliases) into the object they're in. They
+// can be merged in, kept as ordinary "<<" keys, or refused.
+#[derive(Copy, Clone, Default, PartialEq)]
+pub enum MergeKeys {
+  #[default]
+  Merge,
+  Keep,
+  Error,
+}
+
+impl FromStr for MergeKeys {
+  type Err = String;
+
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    match s {
+      "merge" => Ok(MergeKeys::Merge),
+      "keep" => Ok(MergeKeys::Keep),
+      "error" => Ok(MergeKeys::Error),
+      _ => Err(format!("'{}' is not a valid merge key mode", s)),
+    }
+  }
+}
+
+// Applies a merge key mode to a document. A merge key's value is an object or
+// an array of objects, whose entries take the place of the key except where
+// the object already has them, with earlier objects in an array winning over
+// later ones.
+pub fn merge_keys(v: &mut Value, mode: MergeKeys) -> Result<(), String> {
+  let mut path = String::new();
+  match mode {
+    MergeKeys::Keep => Ok(()),
+    mode => merge_keys_at(v, mode, &mut path),
+  }
+}
+
+fn merge_keys_at(v: &mut Value, mode: MergeKeys, path: &mut String) -> Result<(), String> {
+  let map = match v {
+    Value::Seq(items) => {
+      for (i, item) in items.iter_mut().enumerate() {
+        let len = path.len();
+        path::push_index(path, i);
+        merge_keys_at(item, mode, path)?;
+        path.truncate(len);
+      }
+      return Ok(());
+    }
+    Value::Map(map) => map,
+    _ => return Ok(()),
+  };
+  for (k, v) in map.iter_mut() {
+    let len = path.len();
+    path::push_key(path, k);
+    merge_keys_at(v, mode, path)?;
+    path.truncate(len);
+  }
+  if map.get("<<").is_none() {
+    return Ok(());
+  }
+  if mode == MergeKeys::Error {
+    return Err(format!(
+      "{}: input has a YAML merge key (see --yaml-merge)",
+      path
+    ));
+  }
+
+  let entries = std::mem::take(map);
+  let explicit: Vec<Value> = entries
+    .iter()
+    .filter(|(k, _)| !is_merge_key(k))
+    .map(|(k, _)| k.clone())
+    .collect();
+  for (k, v) in entries {
+    if !is_merge_key(&k) {
+      map.push(k, v);
+      continue;
+    }
+    let sources = match v {
+      Value::Map(source) => vec![source],
+      Value::Seq(items) if items.iter().all(|item| matches!(item, Value::Map(_))) => items
+        .into_iter()
+        .filter_map(|item| match item {
+          Value::Map(source) => Some(source),
+          _ => None,
+        })
+        .collect(),
+      _ => Err(format!(
+        "{}: the value of a YAML merge key must be an object or an array of objects",
+        path
+      ))?,
+    };
+    for (k, v) in sources.into_iter().flatten() {
+      let taken = explicit.contains(&k) || map.iter().any(|(seen, _)| *seen == k);
+      if !taken {
+        map.push(k, v);
+      }
+    }
+  }
+  Ok(())
+}
+
+fn is_merge_key(k: &Value) -> bool {
+  matches!(k, Value::String(s) if s == "<<")
+}
+
+// Returns the tag of a wrapper object made by --yaml-tags wrap, along with the
+// tagged value.
+fn wrapped_tag(v: &Value) -> Option<(&str, &Value)> {
+  let map = match v {
+    Value::Map(map) if map.len() == 1 => map,
+    _ => return None,
+  };
+  match map.iter().next() {
+    Some((Value::String(tag), v)) if tag.len() > 1 && tag.starts_with('!') => Some((tag, v)),
+    _ => None,
+  }
+}
+
+// Binary is how "!!binary" values in YAML input are represented. By default,
+// they keep their base64 text exactly as written, which serde_yaml does. They
+// can also be decoded and then written as normalized base64 (without line
+// breaks), or as arrays of byte values.
+#[derive(Copy, Clone, Default, PartialEq)]
+pub enum Binary {
+  #[default]
+  Text,
+  Base64,
+  Array,
+}
+
+impl FromStr for Binary {
+  type Err = String;
+
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    match s {
+      "text" => Ok(Binary::Text),
+      "base64" => Ok(Binary::Base64),
+      "array" => Ok(Binary::Array),
+      _ => Err(format!("'{}' is not a valid binary mode", s)),
+    }
+  }
+}
+
+impl Binary {
+  fn decode(self, text: String) -> Result<Value, String> {
+    let bytes = match self {
+      Binary::Text => return Ok(Value::String(text)),
+      _ => base64::decode(&text)?,
+    };
+    Ok(match self {
+      Binary::Array => Value::Seq(bytes.into_iter().map(|b| Value::U64(b.into())).collect()),
+      _ => Value::String(base64::encode(&bytes)),
+    })
+  }
+}
+
+// Loads the documents of a YAML input with custom tags wrapped (if requested),
+// binary values decoded, and plain scalars resolved by the rules of a specific
+// version of YAML, with timestamps converted, none of which serde_yaml can do. serde_yaml
+// can't see tags on collections at all, and yaml-rust's parser drops them too,
+// so the tags of collections come from a separate pass over the token stream,
+// keyed by the position of the token that starts each tagged collection.
+pub fn load(
+  input: &str,
+  tags: Tags,
+  binary: Binary,
+  version: Option<Version>,
+  ints: IntLiterals,
+  timestamps: YamlTimestamps,
+) -> Result<Vec<Value>, String> {
+  let mut collection_tags = HashMap::new();
+  let mut pending = None;
+  let mut scanner = Scanner::new(input.chars());
+  while let Ok(Some(token)) = scanner.next_token() {
+    match token.1 {
+      TokenType::Tag(handle, suffix) if tags == Tags::Wrap => {
+        pending = custom_tag(&handle, &suffix)
+      }
+      TokenType::Anchor(_) => {}
+      _ => {
+        if let Some(tag) = pending.take() {
+          collection_tags.insert(token.0.index(), tag);
+        }
+      }
+    }
+  }
+
+  let mut loader = Loader {
+    tags,
+    binary,
+    version,
+    ints,
+    timestamps,
+    collection_tags,
+    docs: Vec::new(),
+    stack: Vec::new(),
+    anchors: HashMap::new(),
+    error: None,
+  };
+  let mut parser = Parser::new(input.chars());
+  parser
+    .load(&mut loader, true)
+    .map_err(|err| err.to_string())?;
+  match loader.error {
+    Some(err) => Err(err),
+    None => Ok(loader.docs),
+  }
+}
+
+// Returns the full text of a custom tag, or None for the standard "!!" tags and
+// the non-specific "!" tag.
+fn custom_tag(handle: &str, suffix: &str) -> Option<String> {
+  match (handle, suffix) {
+    ("!!", _) | ("!", "") => None,
+    ("", suffix) => Some(format!("!<{}>", suffix)),
+    (handle, suffix) => Some(format!("{}{}", handle, suffix)),
+  }
+}
+
+struct Loader {
+  tags: Tags,
+  binary: Binary,
+  version: Option<Version>,
+  ints: IntLiterals,
+  timestamps: YamlTimestamps,
+  collection_tags: HashMap<usize, String>,
+  docs: Vec<Value>,
+  stack: Vec<Frame>,
+  anchors: HashMap<usize, Value>,
+  error: Option<String>,
+}
+
+struct Frame {
+  value: Value,
+  key: Option<Value>,
+  anchor: usize,
+  tag: Option<String>,
+}
+
+impl Loader {
+  fn add(&mut self, v: Value, anchor: usize, tag: Option<String>) {
+    let v = match tag {
+      Some(tag) => {
+        let mut wrapper = Map::new();
+        wrapper.push(Value::String(tag), v);
+        Value::Map(wrapper)
+      }
+      None => v,
+    };
+    if anchor > 0 {
+      self.anchors.insert(anchor, v.clone());
+    }
+    let frame = match self.stack.last_mut() {
+      Some(frame) => frame,
+      None => return self.docs.push(v),
+    };
+    match &mut frame.value {
+      Value::Seq(items) => items.push(v),
+      Value::Map(map) => match frame.key.take() {
+        Some(k) => map.push(k, v),
+        None => frame.key = Some(v),
+      },
+      _ => unreachable!(),
+    }
+  }
+
+  fn start(&mut self, value: Value, anchor: usize, mark: Marker) {
+    self.stack.push(Frame {
+      value,
+      key: None,
+      anchor,
+      tag: self.collection_tags.remove(&mark.index()),
+    });
+  }
+
+  fn end(&mut self) {
+    if let Some(frame) = self.stack.pop() {
+      self.add(frame.value, frame.anchor, frame.tag);
+    }
+  }
+
+  fn resolve(&self, v: String, style: TScalarStyle) -> Value {
+    if let (TScalarStyle::Plain, Some(timestamp)) =
+      (style, datetime::from_yaml(&v, self.timestamps))
+    {
+      return timestamp;
+    }
+    let literal = match style {
+      TScalarStyle::Plain => radix_literal(&v),
+      _ => None,
+    };
+    match (self.ints, literal) {
+      (IntLiterals::Keep, Some(_)) => return Value::String(v),
+      (IntLiterals::Octal, Some((radix, sign, digits))) => {
+        if let Ok(n) = i128::from_str_radix(&format!("{}{}", sign, digits), radix) {
+          return int_value(n);
+        }
+      }
+      _ => {}
+    }
+    match (self.version, style) {
+      (Some(version), TScalarStyle::Plain) => resolve_plain(v, version),
+      _ => resolve_scalar(v, style),
+    }
+  }
+}
+
+impl MarkedEventReceiver for Loader {
+  fn on_event(&mut self, event: Event, mark: Marker) {
+    match event {
+      Event::Scalar(v, style, anchor, tag) => {
+        let (v, tag) = match tag {
+          Some(TokenType::Tag(handle, suffix)) => match custom_tag(&handle, &suffix) {
+            Some(tag) if self.tags == Tags::Wrap => (Value::String(v), Some(tag)),
+            Some(_) => (Value::String(v), None),
+            None if handle == "!!" && suffix == "binary" => match self.binary.decode(v) {
+              Ok(v) => (v, None),
+              Err(err) => {
+                self.error.get_or_insert(format!(
+                  "invalid !!binary value at line {} column {}: {}",
+                  mark.line(),
+                  mark.col() + 1,
+                  err
+                ));
+                (Value::Null, None)
+              }
+            },
+            None if handle == "!" || suffix == "str" => (Value::String(v), None),
+            None => (self.resolve(v, style), None),
+          },
+          _ => (self.resolve(v, style), None),
+        };
+        self.add(v, anchor, tag);
+      }
+      Event::SequenceStart(anchor) => self.start(Value::Seq(Vec::new()), anchor, mark),
+      Event::MappingStart(anchor) => self.start(Value::Map(Map::new()), anchor, mark),
+      Event::SequenceEnd | Event::MappingEnd => self.end(),
+      Event::Alias(id) => {
+        let v = self.anchors.get(&id).cloned().unwrap_or(Value::Null);
+        self.add(v, 0, None);
+      }
+      _ => {}
+    }
+  }
+}
+
+// Layout describes where the values of a YAML input are, for the options that
+// care about the source text. The parser reports where each value starts, but
+// not where it ends, so we find the text of quoted and block scalars ourselves
+// to tell comments and indentation apart from text within them.
+struct Layout<'i> {
+  texts: Vec<&'i str>,
+  // The character index of the start of each line.
+  line_starts: Vec<usize>,
+  starts: Vec<(usize, usize, usize, Vec<String>)>,
+  // The character indexes of the opening and closing quotes of each quoted
+  // scalar.
+  quoted: Vec<(usize, usize)>,
+  block_lines: HashSet<usize>,
+}
+
+impl<'i> Layout<'i> {
+  fn new(input: &'i str) -> Result<Self, String> {
+    let finder = StartFinder::find(input)?;
+
+    let chars: Vec<char> = input.chars().collect();
+    let texts: Vec<&str> = input.lines().collect();
+    let mut line_starts = Vec::with_capacity(texts.len());
+    let mut next = 0;
+    for text in &texts {
+      line_starts.push(next);
+      next += text.chars().count() + 1;
+    }
+    let mut quoted = Vec::new();
+    let mut block_lines = HashSet::new();
+    for &(index, line, style) in &finder.scalars {
+      match style {
+        TScalarStyle::SingleQuoted | TScalarStyle::DoubleQuoted => {
+          quoted.push((index, quoted_end(&chars, index)));
+        }
+        TScalarStyle::Literal | TScalarStyle::Foled => {
+          block_lines.extend(block_scalar_lines(&texts, line));
+        }
+        _ => {}
+      }
+    }
+    Ok(Layout {
+      texts,
+      line_starts,
+      starts: finder.starts,
+      quoted,
+      block_lines,
+    })
+  }
+
+  // Reports whether a character of a line is within a quoted scalar.
+  fn in_quotes(&self, line: usize, col: usize) -> bool {
+    let index = self.line_starts[line] + col;
+    self
+      .quoted
+      .iter()
+      .any(|&(from, to)| (from..=to).contains(&index))
+  }
+}
+
+// Describes the lines of a YAML input for --keep-comments.
+pub fn comment_lines(input: &str) -> Result<Vec<Line>, String> {
+  let layout = Layout::new(input)?;
+  let texts = &layout.texts;
+  let mut lines: Vec<Line> = texts.iter().map(|_| Line::default()).collect();
+  for (i, text) in texts.iter().enumerate() {
+    let line = &mut lines[i];
+    if layout.block_lines.contains(&i) {
+      line.code = true;
+      continue;
+    }
+    let pos = find_comment(text, |col| layout.in_quotes(i, col));
+    line.comment = pos.map(|pos| text[pos..].trim_end().to_owned());
+    let code = &text[..pos.unwrap_or(text.len())];
+    line.code = !code.trim().is_empty() || layout.in_quotes(i, 0);
+  }
+  for (line, _, doc, path) in layout.starts {
+    if let Some(l) = lines.get_mut(line) {
+      l.starts.push((doc, path));
+    }
+  }
+  Ok(lines)
+}
+
+// Returns the index of the closing quote of a quoted scalar.
+fn quoted_end(chars: &[char], start: usize) -> usize {
+  let quote = chars[start];
+  let mut i = start + 1;
+  while i < chars.len() {
+    match chars[i] {
+      '\\' if quote == '"' => i += 1,
+      '\'' if quote == '\'' && chars.get(i + 1) == Some(&'\'') => i += 1,
+      c if c == quote => return i,
+      _ => {}
+    }
+    i += 1;
+  }
+  chars.len()
+}
+
+// Returns the lines within a block scalar whose content starts on the given
+// line. Its content is the lines indented at least as far as its first line,
+// along with any blank lines before or between them.
+fn block_scalar_lines(texts: &[&str], first: usize) -> std::ops::Range<usize> {
+  let indent = |text: &str| text.len() - text.trim_start_matches(' ').len();
+  let content_indent = indent(texts[first]);
+  let mut start = first;
+  while start > 0 && texts[start - 1].trim().is_empty() {
+    start -= 1;
+  }
+  let mut end = first + 1;
+  for (i, text) in texts.iter().enumerate().skip(end) {
+    if text.trim().is_empty() {
+      continue;
+    }
+    if indent(text) < content_indent {
+      break;
+    }
+    end = i + 1;
+  }
+  start..end
+}
+
+// StartFinder follows a stream of parser events to find where each value
+// starts, as a line and column counting from 0 along with the document and
+// path of the value, and the start of each quoted or block scalar.
+struct StartFinder {
+  docs: usize,
+  stack: Vec<StartFrame>,
+  path: Vec<String>,
+  starts: Vec<(usize, usize, usize, Vec<String>)>,
+  // The start of the collection that was opened last, until it has a value in
+  // it.
+  opened: Option<usize>,
+  scalars: Vec<(usize, usize, TScalarStyle)>,
+}
+
+enum StartFrame {
+  Seq(usize),
+  Map(Option<String>),
+}
+
+// Returns where each value of a YAML input starts, for reporting violations of
+// a schema. A map entry starts at its key.
+pub fn positions(input: &str) -> Result<Positions, String> {
+  let finder = StartFinder::find(input)?;
+  let mut positions = Positions::new();
+  for (line, column, doc, path) in finder.starts {
+    let path = match path.concat() {
+      path if path.is_empty() => ".".to_owned(),
+      path => path,
+    };
+    positions
+      .entry((doc, path))
+      .or_default()
+      .push((line + 1, column + 1));
+  }
+  Ok(positions)
+}
+
+impl StartFinder {
+  fn find(input: &str) -> Result<Self, String> {
+    let mut finder = StartFinder {
+      docs: 0,
+      stack: Vec::new(),
+      path: Vec::new(),
+      starts: Vec::new(),
+      opened: None,
+      scalars: Vec::new(),
+    };
+    let mut parser = Parser::new(input.chars());
+    parser
+      .load(&mut finder, true)
+      .map_err(|err| err.to_string())?;
+    Ok(finder)
+  }
+
+  // Records the start of a value, and returns the path segment that it adds
+  // to the paths of any values within it.
+  fn start(&mut self, mark: Marker, key: Option<Value>) -> Option<String> {
+    let (line, column) = (mark.line() - 1, mark.col());
+    // The mark of a block collection is past the start of its first value on
+    // the same line, as with the key of a map in a sequence ("- key: value").
+    if let Some(i) = self.opened.take() {
+      let start = &mut self.starts[i];
+      if start.0 == line && start.1 > column {
+        start.1 = column;
+      }
+    }
+    let segment = match self.stack.last_mut() {
+      None => {
+        self.starts.push((line, column, self.docs - 1, Vec::new()));
+        return None;
+      }
+      Some(StartFrame::Seq(i)) => {
+        let mut segment = String::new();
+        path::push_index(&mut segment, *i);
+        *i += 1;
+        segment
+      }
+      Some(StartFrame::Map(pending)) => match pending.take() {
+        Some(segment) => return Some(segment),
+        None => {
+          let mut segment = String::new();
+          match key {
+            Some(key) => path::push_key(&mut segment, &key),
+            None => segment.push('?'),
+          }
+          *pending = Some(segment.clone());
+          segment
+        }
+      },
+    };
+    let mut path = self.path.clone();
+    path.push(segment.clone());
+    self.starts.push((line, column, self.docs - 1, path));
+    Some(segment)
+  }
+
+  // Records the start of a collection, as start does.
+  fn open_start(&mut self, mark: Marker) -> Option<String> {
+    let recorded = self.starts.len();
+    let segment = self.start(mark, None);
+    if self.starts.len() > recorded {
+      self.opened = Some(recorded);
+    }
+    segment
+  }
+
+  fn open(&mut self, segment: Option<String>, frame: StartFrame) {
+    self.path.extend(segment);
+    self.stack.push(frame);
+  }
+}
+
+impl MarkedEventReceiver for StartFinder {
+  fn on_event(&mut self, event: Event, mark: Marker) {
+    let in_key = matches!(self.stack.last(), Some(StartFrame::Map(None)));
+    match event {
+      Event::DocumentStart => self.docs += 1,
+      Event::Scalar(v, style, _, _) => {
+        // The mark of a block scalar is at the start of its content, and an
+        // empty one has no content to mark.
+        if style != TScalarStyle::Plain && !v.is_empty() {
+          self.scalars.push((mark.index(), mark.line() - 1, style));
+        }
+        let key = match in_key {
+          true => Some(resolve_scalar(v, style)),
+          false => None,
+        };
+        self.start(mark, key);
+      }
+      Event::Alias(_) => {
+        self.start(mark, None);
+      }
+      Event::SequenceStart(_) => {
+        let segment = self.open_start(mark);
+        self.open(segment, StartFrame::Seq(0));
+      }
+      Event::MappingStart(_) => {
+        let segment = self.open_start(mark);
+        self.open(segment, StartFrame::Map(None));
+      }
+      Event::SequenceEnd | Event::MappingEnd => {
+        self.opened = None;
+        self.stack.pop();
+        if !self.stack.is_empty() {
+          self.path.pop();
+        }
+      }
+      _ => {}
+    }
+  }
+}
+
+// Resolves the type of a scalar the way that serde_yaml does, by deserializing
+// it as a document of its own when it's plain.
+fn resolve_scalar(v: String, style: TScalarStyle) -> Value {
+  if style != TScalarStyle::Plain || v.is_empty() {
+    return match (style, v.is_empty()) {
+      (TScalarStyle::Plain, true) => Value::Null,
+      _ => Value::String(v),
+    };
+  }
+  // A plain scalar can't hold any of the syntax that would make it parse as
+  // something else, except for document markers like "---", which parse as an
+  // empty document.
+  match serde_yaml::from_str(&v) {
+    Ok(Value::Null) if v != "~" && v != "null" => Value::String(v),
+    Ok(Value::Seq(_) | Value::Map(_)) | Err(_) => Value::String(v),
+    Ok(resolved) => resolved,
+  }
+}
+
+// Resolves the type of a plain scalar by the rules of a version of YAML: the
+// core schema for 1.2, and the types at yaml.org/type for 1.1.
+fn resolve_plain(v: String, version: Version) -> Value {
+  let (sign, digits) = match v.strip_prefix(['-', '+']) {
+    Some(digits) => (if v.starts_with('-') { -1 } else { 1 }, digits),
+    None => (1, v.as_str()),
+  };
+  let v11 = version == Version::V1_1;
+  let is_digits = |s: &str, radix: u32| {
+    !s.is_empty()
+      && s.chars().all(|c| c.is_digit(radix) || (v11 && c == '_'))
+      && !s.starts_with('_')
+  };
+  let int = |s: &str, radix| {
+    let n = i128::from_str_radix(&s.replace('_', ""), radix).ok()?;
+    Some(int_value(sign * n))
+  };
+
+  let resolved = match v.as_str() {
+    "" | "~" | "null" | "Null" | "NULL" => Some(Value::Null),
+    "true" | "True" | "TRUE" => Some(Value::Bool(true)),
+    "false" | "False" | "FALSE" => Some(Value::Bool(false)),
+    "y" | "Y" | "yes" | "Yes" | "YES" | "on" | "On" | "ON" if v11 => Some(Value::Bool(true)),
+    "n" | "N" | "no" | "No" | "NO" | "off" | "Off" | "OFF" if v11 => Some(Value::Bool(false)),
+    ".nan" | ".NaN" | ".NAN" => Some(Value::F64(f64::NAN)),
+    _ => match digits {
+      ".inf" | ".Inf" | ".INF" => Some(Value::F64(sign as f64 * f64::INFINITY)),
+      _ if (v11 || digits == v) && digits.starts_with("0x") && is_digits(&digits[2..], 16) => {
+        int(&digits[2..], 16)
+      }
+      _ if v11 && digits.starts_with("0b") && is_digits(&digits[2..], 2) => int(&digits[2..], 2),
+      _ if !v11 && v.starts_with("0o") && is_digits(&v[2..], 8) => int(&v[2..], 8),
+      _ if v11 && digits.starts_with('0') && digits.len() > 1 && is_digits(digits, 8) => {
+        int(&digits[1..], 8)
+      }
+      _ if is_digits(digits, 10) && !(v11 && digits.starts_with('0') && digits.len() > 1) => {
+        int(digits, 10)
+      }
+      _ if v11 && digits.contains(':') => sexagesimal(digits).map(|n| match n {
+        n if n.fract() == 0.0 && !digits.contains('.') => int_value(sign * n as i128),
+        n => Value::F64(sign as f64 * n),
+      }),
+      _ => float(digits, v11).map(|n| Value::F64(sign as f64 * n)),
+    },
+  };
+  resolved.unwrap_or(Value::String(v))
+}
+
+fn int_value(n: i128) -> Value {
+  match (i64::try_from(n), u64::try_from(n)) {
+    (_, Ok(n)) => Value::U64(n),
+    (Ok(n), _) => Value::I64(n),
+    _ => Value::I128(n),
+  }
+}
+
+// Parses an unsigned float: digits with an optional fraction and exponent. YAML
+// 1.1 also allows underscores, and a fraction without any digits before it;
+// YAML 1.2 allows no digits on either side of the point, but not both.
+fn float(s: &str, v11: bool) -> Option<f64> {
+  let (mantissa, exponent) = match s.find(['e', 'E']) {
+    Some(i) => (&s[..i], Some(&s[i + 1..])),
+    None => (s, None),
+  };
+  let (whole, fraction) = match mantissa.split_once('.') {
+    Some((whole, fraction)) => (whole, Some(fraction)),
+    None => (mantissa, None),
+  };
+  let digits = |s: &str| s.chars().all(|c| c.is_ascii_digit() || (v11 && c == '_'));
+  let valid = digits(whole)
+    && fraction.is_none_or(digits)
+    && !(whole.is_empty() && fraction.is_none_or(str::is_empty))
+    && !whole.starts_with('_')
+    && (fraction.is_some() || !v11)
+    && exponent.is_none_or(|e| {
+      let e = e.strip_prefix(['-', '+']).unwrap_or(e);
+      !e.is_empty() && e.chars().all(|c| c.is_ascii_digit())
+    });
+  match valid {
+    true => s.replace('_', "").parse().ok(),
+    false => None,
+  }
+}
+
+// Parses a YAML 1.1 base 60 number like 1:30 (90) or 1:30.5 (90.5).
+fn sexagesimal(s: &str) -> Option<f64> {
+  let mut parts = s.split(':');
+  let first = parts.next()?.replace('_', "");
+  if first.is_empty() || first.starts_with('0') || !first.chars().all(|c| c.is_ascii_digit()) {
+    return None;
+  }
+  let mut n: f64 = first.parse().ok()?;
+  let mut rest = parts.peekable();
+  while let Some(part) = rest.next() {
+    let last = rest.peek().is_none();
+    let (digits, fraction) = match (last, part.split_once('.')) {
+      (true, Some((digits, fraction))) => (digits, Some(fraction)),
+      _ => (part, None),
+    };
+    if digits.is_empty() || digits.len() > 2 || !digits.chars().all(|c| c.is_ascii_digit()) {
+      return None;
+    }
+    let digits: f64 = digits.parse().ok()?;
+    if digits >= 60.0 {
+      return None;
+    }
+    n = n * 60.0 + digits;
+    if let Some(fraction) = fraction {
+      let fraction = fraction.replace('_', "");
+      if !fraction.chars().all(|c| c.is_ascii_digit()) {
+        return None;
+      }
+      n += format!("0.{}", fraction).parse::<f64>().unwrap_or(0.0);
+    }
+  }
+  Some(n)
+}
+
+// Writes a document as YAML, in the same style that serde_yaml produces through
+// yaml-rust's emitter: block style with 2-space indentation, and "compact"
+// nesting of collections within sequences. Having our own emitter lets us write
+// floats in their shortest form that parses back to the same value (yaml-rust
+// can add digits, e.g. writing 0.30000000000000004 as 0.30000000000000007), and
+// write the tags and anchors that yaml-rust can't.
+pub struct Emitter<'w> {
+  w: &'w mut String,
+  level: isize,
+  tags: Tags,
+  version: Option<Version>,
+  ints: IntLiterals,
+  anchors: Option<Anchors>,
+}
+
+// Anchors tracks the subtrees of a document that repeat, so that each can be
+// written in full once and as an alias everywhere else. Equal subtrees are in
+// the same class, and each value of the document is found by its address, since
+// the document can't change while it's written.
+struct Anchors {
+  classes: HashMap<*const Value, usize>,
+  aliased: HashSet<usize>,
+  names: HashMap<usize, String>,
+}
+
+impl<'w> Emitter<'w> {
+  // With a version, strings that would read back as something else under that
+  // version's rules are always quoted. With IntLiterals::Keep, strings that are
+  // integer literals in another base are never quoted.
+  pub fn new(w: &'w mut String, tags: Tags, version: Option<Version>, ints: IntLiterals) -> Self {
+    Emitter {
+      w,
+      level: -1,
+      tags,
+      version,
+      ints,
+      anchors: None,
+    }
+  }
+
+  // Writes a document, starting with a "---" marker. With anchors enabled, each
+  // object or array that appears more than once is anchored where it first
+  // appears, and written as an alias after that.
+  pub fn dump(&mut self, doc: &Value, anchors: bool) {
+    self.level = -1;
+    self.anchors = match anchors {
+      true => Some(find_repeats(doc)),
+      false => None,
+    };
+    self.w.push_str("---");
+    match self.properties(doc) {
+      Node::Alias(_) => unreachable!(),
+      Node::Value(props, v) if is_block(v) => {
+        if !props.is_empty() {
+          self.w.push(' ');
+          self.w.push_str(&props);
+        }
+        self.w.push('\n');
+        self.emit_node(v);
+      }
+      Node::Value(props, v) => {
+        self.w.push('\n');
+        if !props.is_empty() {
+          self.w.push_str(&props);
+          self.w.push(' ');
+        }
+        self.emit_node(v);
+      }
+    }
+  }
+
+  fn write_indent(&mut self) {
+    for _ in 0..self.level.max(0) {
+      self.w.push_str("  ");
+    }
+  }
+
+  // Returns the tag and anchor to write before a value, along with the value
+  // to write after them, or the alias to write instead of the value.
+  fn properties<'v>(&mut self, v: &'v Value) -> Node<'v> {
+    let mut props = String::new();
+    if let Some(anchors) = &mut self.anchors {
+      if let Some(&class) = anchors.classes.get(&(v as *const Value)) {
+        if let Some(name) = anchors.names.get(&class) {
+          return Node::Alias(name.clone());
+        }
+        if anchors.aliased.contains(&class) {
+          let name = format!("id{:03}", anchors.names.len() + 1);
+          props.push('&');
+          props.push_str(&name);
+          anchors.names.insert(class, name);
+        }
+      }
+    }
+    let mut v = v;
+    if self.tags == Tags::Wrap {
+      if let Some((tag, tagged)) = wrapped_tag(v) {
+        if !props.is_empty() {
+          props.push(' ');
+        }
+        props.push_str(tag);
+        v = tagged;
+      }
+    }
+    Node::Value(props, v)
+  }
+
+  fn emit_node(&mut self, v: &Value) {
+    match v {
+      Value::Seq(items) => self.emit_seq(items),
+      Value::Map(map) => self.emit_map(map),
+      v => write_scalar(self.w, v, self.version, self.ints),
+    }
+  }
+
+  fn emit_seq(&mut self, items: &[Value]) {
+    if items.is_empty() {
+      self.w.push_str("[]");
+      return;
+    }
+    self.level += 1;
+    for (i, item) in items.iter().enumerate() {
+      if i > 0 {
+        self.w.push('\n');
+        self.write_indent();
+      }
+      self.w.push('-');
+      self.emit_val(true, item);
+    }
+    self.level -= 1;
+  }
+
+  fn emit_map(&mut self, map: &Map) {
+    if map.is_empty() {
+      self.w.push_str("{}");
+      return;
+    }
+    self.level += 1;
+    let repeats = has_repeated_keys(map);
+    let mut first = true;
+    for (i, (k, v)) in map.iter().enumerate() {
+      // Like a hash table, a repeated key keeps its first position but takes
+      // its last value.
+      let mut v = v;
+      if repeats {
+        if map.iter().take(i).any(|(seen, _)| seen == k) {
+          continue;
+        }
+        for (other, last) in map.iter().skip(i + 1) {
+          if other == k {
+            v = last;
+          }
+        }
+      }
+      if !first {
+        self.w.push('\n');
+        self.write_indent();
+      }
+      first = false;
+      if is_block(k) {
+        self.w.push('?');
+        self.emit_val(true, k);
+        self.w.push('\n');
+        self.write_indent();
+        self.w.push(':');
+        self.emit_val(true, v);
+      } else {
+        self.emit_node(k);
+        self.w.push(':');
+        self.emit_val(false, v);
+      }
+    }
+    self.level -= 1;
+  }
+
+  // Writes a value that follows a ":" or "-". Compact nesting applies only when
+  // inline is set, meaning the value follows a "-".
+  fn emit_val(&mut self, inline: bool, v: &Value) {
+    let (props, v) = match self.properties(v) {
+      Node::Alias(name) => {
+        self.w.push_str(" *");
+        self.w.push_str(&name);
+        return;
+      }
+      Node::Value(props, v) => (props, v),
+    };
+    if !props.is_empty() {
+      self.w.push(' ');
+      self.w.push_str(&props);
+    }
+    let empty = match v {
+      Value::Seq(items) => items.is_empty(),
+      Value::Map(map) => map.is_empty(),
+      _ => true,
+    };
+    if (inline && props.is_empty()) || empty {
+      self.w.push(' ');
+    } else {
+      self.w.push('\n');
+      self.level += 1;
+      self.write_indent();
+      self.level -= 1;
+    }
+    self.emit_node(v);
+  }
+}
+
+// Reports whether any key of a map appears more than once. Comparing every pair
+// of keys is quick enough for small maps, and the string keys of larger ones
+// are checked with a set instead.
+fn has_repeated_keys(map: &Map) -> bool {
+  let keys = map.iter().map(|(k, _)| k);
+  if map.len() <= 16 {
+    return keys
+      .enumerate()
+      .any(|(i, k)| map.iter().take(i).any(|(seen, _)| seen == k));
+  }
+  let mut strings = HashSet::with_capacity(map.len());
+  let mut others: Vec<&Value> = Vec::new();
+  for k in keys {
+    match k {
+      Value::String(s) if !strings.insert(s.as_str()) => return true,
+      Value::String(_) => {}
+      k if others.contains(&k) => return true,
+      k => others.push(k),
+    }
+  }
+  false
+}
+
+enum Node<'v> {
+  Value(String, &'v Value),
+  Alias(String),
+}
+
+// Emits a document straight from a deserializer, just as Emitter::dump would
+// emit the Value that it deserializes to, but without building the Value.
+// Anchors and wrapped tags need a look at the whole document first, so this is
+// only for output without them.
+pub fn emit_from<'de, D>(
+  w: &mut String,
+  de: D,
+  version: Option<Version>,
+  ints: IntLiterals,
+) -> Result<(), D::Error>
+where
+  D: Deserializer<'de>,
+{
+  w.push_str("---\n");
+  let mut stream = Stream {
+    w,
+    level: -1,
+    version,
+    ints,
+    spare: Vec::new(),
+  };
+  let node = StreamNode {
+    stream: &mut stream,
+    after: After::Document,
+  };
+  node.deserialize(de)
+}
+
+// Stream is the state of emit_from, which mirrors an Emitter's.
+struct Stream<'w> {
+  w: &'w mut String,
+  level: isize,
+  version: Option<Version>,
+  ints: IntLiterals,
+  // Lists of map entries to reuse, so that each map doesn't need a new one.
+  spare: Vec<Vec<(usize, usize)>>,
+}
+
+// After is what a value follows, which decides what goes between the two.
+#[derive(Copy, Clone, PartialEq)]
+enum After {
+  Document,
+  Dash,
+  Colon,
+}
+
+impl<'w> Stream<'w> {
+  fn write_indent(&mut self) {
+    for _ in 0..self.level.max(0) {
+      self.w.push_str("  ");
+    }
+  }
+
+  // Writes what goes before a scalar or an empty array or object.
+  fn open_scalar(&mut self, after: After) {
+    if after != After::Document {
+      self.w.push(' ');
+    }
+  }
+
+  // Writes what goes before the first entry of an array or object, at the
+  // level of its entries.
+  fn open_block(&mut self, after: After) {
+    match after {
+      After::Document => {}
+      After::Dash => self.w.push(' '),
+      After::Colon => {
+        self.w.push('\n');
+        self.write_indent();
+      }
+    }
+  }
+
+  // Writes the entries of an object over again if any of them share a key.
+  // Like a hash table, a repeated key keeps its first position but takes its
+  // last value. Each entry is the start of its text and the end of its key.
+  fn dedup_entries(&mut self, entries: &[(usize, usize)]) {
+    let key = |(start, end): (usize, usize)| &self.w[start..end];
+    let repeats = match entries.len() {
+      n if n <= 16 => (0..n).any(|i| entries[..i].iter().any(|e| key(*e) == key(entries[i]))),
+      n => {
+        let mut keys = HashSet::with_capacity(n);
+        !entries.iter().all(|e| keys.insert(key(*e)))
+      }
+    };
+    if !repeats {
+      return;
+    }
+    let sep = 1 + 2 * self.level.max(0) as usize;
+    let text = |i: usize| match entries.get(i + 1) {
+      Some((next, _)) => &self.w[entries[i].0..next - sep],
+      None => &self.w[entries[i].0..],
+    };
+    let mut rewritten = String::new();
+    for i in 0..entries.len() {
+      if entries[..i].iter().any(|e| key(*e) == key(entries[i])) {
+        continue;
+      }
+      let last = (i..entries.len())
+        .rev()
+        .find(|j| key(entries[*j]) == key(entries[i]))
+        .unwrap_or(i);
+      if !rewritten.is_empty() {
+        rewritten.push_str(&self.w[entries[1].0 - sep..entries[1].0]);
+      }
+      rewritten.push_str(text(last));
+    }
+    self.w.truncate(entries[0].0);
+    self.w.push_str(&rewritten);
+  }
+}
+
+// StreamNode emits a value as it's read, for emit_from.
+struct StreamNode<'s, 'w> {
+  stream: &'s mut Stream<'w>,
+  after: After,
+}
+
+impl<'s, 'w> StreamNode<'s, 'w> {
+  fn scalar<E, F>(self, f: F) -> Result<(), E>
+  where
+    F: FnOnce(&mut Stream),
+  {
+    self.stream.open_scalar(self.after);
+    f(self.stream);
+    Ok(())
+  }
+}
+
+impl<'de, 's, 'w> DeserializeSeed<'de> for StreamNode<'s, 'w> {
+  type Value = ();
+
+  fn deserialize<D>(self, de: D) -> Result<(), D::Error>
+  where
+    D: Deserializer<'de>,
+  {
+    de.deserialize_any(self)
+  }
+}
+
+impl<'de, 's, 'w> Visitor<'de> for StreamNode<'s, 'w> {
+  type Value = ();
+
+  fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    f.write_str("any value")
+  }
+
+  fn visit_bool<E>(self, v: bool) -> Result<(), E>
+  where
+    E: de::Error,
+  {
+    self.scalar(|s| s.w.push_str(if v { "true" } else { "false" }))
+  }
+
+  fn visit_i64<E>(self, v: i64) -> Result<(), E>
+  where
+    E: de::Error,
+  {
+    self.scalar(|s| write_display(s.w, v))
+  }
+
+  fn visit_u64<E>(self, v: u64) -> Result<(), E>
+  where
+    E: de::Error,
+  {
+    self.scalar(|s| write_display(s.w, v))
+  }
+
+  fn visit_i128<E>(self, v: i128) -> Result<(), E>
+  where
+    E: de::Error,
+  {
+    self.scalar(|s| write_display(s.w, v))
+  }
+
+  fn visit_u128<E>(self, v: u128) -> Result<(), E>
+  where
+    E: de::Error,
+  {
+    self.scalar(|s| write_display(s.w, v))
+  }
+
+  fn visit_f64<E>(self, v: f64) -> Result<(), E>
+  where
+    E: de::Error,
+  {
+    self.scalar(|s| write_float(s.w, v))
+  }
+
+  fn visit_str<E>(self, v: &str) -> Result<(), E>
+  where
+    E: de::Error,
+  {
+    self.scalar(|s| write_string(s.w, v, s.version, s.ints))
+  }
+
+  fn visit_unit<E>(self) -> Result<(), E>
+  where
+    E: de::Error,
+  {
+    self.scalar(|s| s.w.push('~'))
+  }
+
+  fn visit_none<E>(self) -> Result<(), E>
+  where
+    E: de::Error,
+  {
+    self.visit_unit()
+  }
+
+  fn visit_some<D>(self, de: D) -> Result<(), D::Error>
+  where
+    D: Deserializer<'de>,
+  {
+    self.deserialize(de)
+  }
+
+  fn visit_newtype_struct<D>(self, de: D) -> Result<(), D::Error>
+  where
+    D: Deserializer<'de>,
+  {
+    self.deserialize(de)
+  }
+
+  // Whether an array or object is empty isn't known until it's read, so the
+  // text for its first entry is taken back if it turns out not to have one.
+  fn visit_seq<A>(self, mut seq: A) -> Result<(), A::Error>
+  where
+    A: SeqAccess<'de>,
+  {
+    let s = self.stream;
+    s.level += 1;
+    let mut empty = true;
+    loop {
+      let mark = s.w.len();
+      if empty {
+        s.open_block(self.after);
+      } else {
+        s.w.push('\n');
+        s.write_indent();
+      }
+      s.w.push('-');
+      let item = StreamNode {
+        stream: &mut *s,
+        after: After::Dash,
+      };
+      if seq.next_element_seed(item)?.is_none() {
+        s.w.truncate(mark);
+        break;
+      }
+      empty = false;
+    }
+    s.level -= 1;
+    if empty {
+      s.open_scalar(self.after);
+      s.w.push_str("[]");
+    }
+    Ok(())
+  }
+
+  fn visit_map<A>(self, mut map: A) -> Result<(), A::Error>
+  where
+    A: MapAccess<'de>,
+  {
+    let s = self.stream;
+    s.level += 1;
+    let mut entries = s.spare.pop().unwrap_or_default();
+    loop {
+      let mark = s.w.len();
+      if entries.is_empty() {
+        s.open_block(self.after);
+      } else {
+        s.w.push('\n');
+        s.write_indent();
+      }
+      let start = s.w.len();
+      let key = StreamKey { stream: &mut *s };
+      match map.next_key_seed(key)? {
+        None => {
+          s.w.truncate(mark);
+          break;
+        }
+        Some(Key::Scalar) => {
+          entries.push((start, s.w.len()));
+          s.w.push(':');
+          let value = StreamNode {
+            stream: &mut *s,
+            after: After::Colon,
+          };
+          map.next_value_seed(value)?;
+        }
+        Some(Key::Block(k)) => {
+          s.w.push('?');
+          let mut emitter = Emitter::new(&mut *s.w, Tags::Drop, s.version, s.ints);
+          emitter.level = s.level;
+          emitter.emit_val(true, &k);
+          entries.push((start, s.w.len()));
+          s.w.push('\n');
+          s.write_indent();
+          s.w.push(':');
+          let value = StreamNode {
+            stream: &mut *s,
+            after: After::Dash,
+          };
+          map.next_value_seed(value)?;
+        }
+      }
+    }
+    if entries.is_empty() {
+      s.level -= 1;
+      s.open_scalar(self.after);
+      s.w.push_str("{}");
+    } else {
+      s.dedup_entries(&entries);
+      s.level -= 1;
+    }
+    entries.clear();
+    s.spare.push(entries);
+    Ok(())
+  }
+}
+
+// Key is what StreamKey did with a key: wrote it as a scalar, or read it as an
+// array or object for the "?" form.
+enum Key {
+  Scalar,
+  Block(Value),
+}
+
+// StreamKey emits a scalar key as it's read, for emit_from.
+struct StreamKey<'s, 'w> {
+  stream: &'s mut Stream<'w>,
+}
+
+impl<'de, 's, 'w> DeserializeSeed<'de> for StreamKey<'s, 'w> {
+  type Value = Key;
+
+  fn deserialize<D>(self, de: D) -> Result<Key, D::Error>
+  where
+    D: Deserializer<'de>,
+  {
+    de.deserialize_any(self)
+  }
+}
+
+impl<'de, 's, 'w> Visitor<'de> for StreamKey<'s, 'w> {
+  type Value = Key;
+
+  fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    f.write_str("any value")
+  }
+
+  fn visit_bool<E>(self, v: bool) -> Result<Key, E>
+  where
+    E: de::Error,
+  {
+    self.stream.w.push_str(if v { "true" } else { "false" });
+    Ok(Key::Scalar)
+  }
+
+  fn visit_i64<E>(self, v: i64) -> Result<Key, E>
+  where
+    E: de::Error,
+  {
+    write_display(self.stream.w, v);
+    Ok(Key::Scalar)
+  }
+
+  fn visit_u64<E>(self, v: u64) -> Result<Key, E>
+  where
+    E: de::Error,
+  {
+    write_display(self.stream.w, v);
+    Ok(Key::Scalar)
+  }
+
+  fn visit_i128<E>(self, v: i128) -> Result<Key, E>
+  where
+    E: de::Error,
+  {
+    write_display(self.stream.w, v);
+    Ok(Key::Scalar)
+  }
+
+  fn visit_u128<E>(self, v: u128) -> Result<Key, E>
+  where
+    E: de::Error,
+  {
+    write_display(self.stream.w, v);
+    Ok(Key::Scalar)
+  }
+
+  fn visit_f64<E>(self, v: f64) -> Result<Key, E>
+  where
+    E: de::Error,
+  {
+    write_float(self.stream.w, v);
+    Ok(Key::Scalar)
+  }
+
+  fn visit_str<E>(self, v: &str) -> Result<Key, E>
+  where
+    E: de::Error,
+  {
+    let s = self.stream;
+    write_string(s.w, v, s.version, s.ints);
+    Ok(Key::Scalar)
+  }
+
+  fn visit_unit<E>(self) -> Result<Key, E>
+  where
+    E: de::Error,
+  {
+    self.stream.w.push('~');
+    Ok(Key::Scalar)
+  }
+
+  fn visit_none<E>(self) -> Result<Key, E>
+  where
+    E: de::Error,
+  {
+    self.visit_unit()
+  }
+
+  fn visit_some<D>(self, de: D) -> Result<Key, D::Error>
+  where
+    D: Deserializer<'de>,
+  {
+    self.deserialize(de)
+  }
+
+  fn visit_newtype_struct<D>(self, de: D) -> Result<Key, D::Error>
+  where
+    D: Deserializer<'de>,
+  {
+    self.deserialize(de)
+  }
+
+  fn visit_seq<A>(self, seq: A) -> Result<Key, A::Error>
+  where
+    A: SeqAccess<'de>,
+  {
+    let k = Value::deserialize(de::value::SeqAccessDeserializer::new(seq))?;
+    self.block(k)
+  }
+
+  fn visit_map<A>(self, map: A) -> Result<Key, A::Error>
+  where
+    A: MapAccess<'de>,
+  {
+    let k = Value::deserialize(de::value::MapAccessDeserializer::new(map))?;
+    self.block(k)
+  }
+}
+
+impl<'s, 'w> StreamKey<'s, 'w> {
+  // An empty array or object as a key is written like a scalar.
+  fn block<E>(self, k: Value) -> Result<Key, E> {
+    match &k {
+      Value::Seq(items) if items.is_empty() => self.stream.w.push_str("[]"),
+      Value::Map(map) if map.is_empty() => self.stream.w.push_str("{}"),
+      _ => return Ok(Key::Block(k)),
+    }
+    Ok(Key::Scalar)
+  }
+}
+
+fn is_block(v: &Value) -> bool {
+  match v {
+    Value::Seq(items) => !items.is_empty(),
+    Value::Map(map) => !map.is_empty(),
+    _ => false,
+  }
+}
+
+// Finds the non-empty objects and arrays in a document that an emitter will
+// write as aliases, walking the document in the same order as the emitter so
+// that subtrees within a repeat aren't counted again. Subtrees are put into
+// classes from the bottom up, so that finding them takes time in proportion to
+// the size of the document, however deeply the repeats nest.
+fn find_repeats(doc: &Value) -> Anchors {
+  // Shape is what makes subtrees equal: the classes of their children, or the
+  // value itself for scalars.
+  #[derive(PartialEq, Eq, Hash)]
+  enum Shape {
+    Scalar(String),
+    Seq(Vec<usize>),
+    Map(Vec<(usize, usize)>),
+  }
+
+  struct Classifier {
+    shapes: HashMap<Shape, usize>,
+    classes: HashMap<*const Value, usize>,
+    counts: Vec<usize>,
+  }
+
+  impl Classifier {
+    fn classify(&mut self, v: &Value) -> usize {
+      let shape = match v {
+        Value::Seq(items) => Shape::Seq(items.iter().map(|item| self.classify(item)).collect()),
+        Value::Map(map) => Shape::Map(
+          map
+            .iter()
+            .map(|(k, v)| (self.classify(k), self.classify(v)))
+            .collect(),
+        ),
+        v => Shape::Scalar(serde_json::to_string(v).unwrap_or_default()),
+      };
+      let next = self.shapes.len();
+      let class = *self.shapes.entry(shape).or_insert(next);
+      if is_block(v) {
+        self.classes.insert(v, class);
+        if self.counts.len() <= class {
+          self.counts.resize(class + 1, 0);
+        }
+        self.counts[class] += 1;
+      }
+      class
+    }
+  }
+
+  fn walk(
+    v: &Value,
+    classifier: &Classifier,
+    seen: &mut HashSet<usize>,
+    aliased: &mut HashSet<usize>,
+  ) {
+    let class = match classifier.classes.get(&(v as *const Value)) {
+      Some(&class) => class,
+      None => return,
+    };
+    if classifier.counts[class] > 1 {
+      if seen.contains(&class) {
+        aliased.insert(class);
+        return;
+      }
+      seen.insert(class);
+    }
+    visit_children(v, |child| walk(child, classifier, seen, aliased));
+  }
+
+  let mut classifier = Classifier {
+    shapes: HashMap::new(),
+    classes: HashMap::new(),
+    counts: Vec::new(),
+  };
+  classifier.classify(doc);
+  let mut aliased = HashSet::new();
+  walk(doc, &classifier, &mut HashSet::new(), &mut aliased);
+  Anchors {
+    classes: classifier.classes,
+    aliased,
+    names: HashMap::new(),
+  }
+}
+
+fn visit_children<F>(v: &Value, mut f: F)
+where
+  F: FnMut(&Value),
+{
+  match v {
+    Value::Seq(items) => items.iter().for_each(f),
+    Value::Map(map) => map.iter().for_each(|(k, v)| {
+      f(k);
+      f(v);
+    }),
+    _ => {}
+  }
+}
+
+fn write_scalar(w: &mut String, v: &Value, version: Option<Version>, ints: IntLiterals) {
+  match v {
+    Value::Null => w.push('~'),
+    Value::Bool(b) => w.push_str(if *b { "true" } else { "false" }),
+    Value::I64(n) => write_display(w, n),
+    Value::U64(n) => write_display(w, n),
+    Value::I128(n) => write_display(w, n),
+    Value::U128(n) => write_display(w, n),
+    Value::F64(n) => write_float(w, *n),
+    Value::String(s) => write_string(w, s, version, ints),
+    Value::Seq(_) | Value::Map(_) => unreachable!(),
+  }
+}
+
+fn write_string(w: &mut String, s: &str, version: Option<Version>, ints: IntLiterals) {
+  let resolves =
+    |s: &str, version| !matches!(resolve_plain(s.to_owned(), version), Value::String(_));
+  match s {
+    s if ints == IntLiterals::Keep && radix_literal(s).is_some() => w.push_str(s),
+    s if need_quotes(s) || version.is_some_and(|v| resolves(s, v)) => escape_str(w, s),
+    s => w.push_str(s),
+  }
+}
+
+// Reports whether a string must be quoted to be read back as the same string,
+// following the same rules as yaml-rust.
+fn need_quotes(s: &str) -> bool {
+  // Only strings that start like a number or a keyword can read back as one,
+  // which saves trying every other string as each of them.
+  let first = match s.chars().next() {
+    Some(first) => first,
+    None => return true,
+  };
+  let number = matches!(first, '0'..='9' | '+' | 'i' | 'I' | 'n' | 'N');
+  let keyword = s.len() <= 5
+    && matches!(
+      first,
+      'y' | 'Y' | 'n' | 'N' | 't' | 'T' | 'f' | 'F' | 'o' | 'O' | '~'
+    );
+  s.starts_with(' ')
+    || s.ends_with(' ')
+    || s.starts_with(['&', '*', '?', '|', '-', '<', '>', '=', '!', '%', '@'])
+    || s.contains(|c: char| {
+      matches!(
+        c,
+        ':' | '{'
+          | '}'
+          | '['
+          | ']'
+          | ','
+          | '#'
+          | '`'
+          | '"'
+          | '\''
+          | '\\'
+          | '\0'..='\x06'
+          | '\t'
+          | '\n'
+          | '\r'
+          | '\x0e'..='\x1a'
+          | '\x1c'..='\x1f'
+      )
+    })
+    || (keyword
+      && [
+        "yes", "Yes", "YES", "no", "No", "NO", "True", "TRUE", "true", "False", "FALSE", "false",
+        "on", "On", "ON", "off", "Off", "OFF", "null", "Null", "NULL", "~",
+      ]
+      .contains(&s))
+    || s.starts_with('.')
+    || (number && (s.starts_with("0x") || s.parse::<i64>().is_ok() || s.parse::<f64>().is_ok()))
+}
+
+// Writes a double-quoted string, with the same escapes as yaml-rust.
+fn escape_str(w: &mut String, s: &str) {
+  w.push('"');
+  for c in s.chars() {
+    match c {
+      '"' => w.push_str("\\\""),
+      '\\' => w.push_str("\\\\"),
+      '\x08' => w.push_str("\\b"),
+      '\t' => w.push_str("\\t"),
+      '\n' => w.push_str("\\n"),
+      '\x0c' => w.push_str("\\f"),
+      '\r' => w.push_str("\\r"),
+      '\0'..='\x1f' | '\x7f' => w.push_str(&format!("\\u{:04x}", c as u32)),
+      c => w.push(c),
+    }
+  }
+  w.push('"');
+}
+
+// Writes a value straight into the output, rather than formatting it into a
+// String of its own first. Writing to a String can't fail.
+fn write_display<T: fmt::Display>(w: &mut String, v: T) {
+  let _ = write!(w, "{}", v);
+}
+
+fn write_float(w: &mut String, n: f64) {
+  if n.is_nan() {
+    w.push_str(".nan");
+  } else if n.is_infinite() {
+    w.push_str(if n > 0.0 { ".inf" } else { "-.inf" });
+  } else {
+    // Debug formatting is the shortest that round-trips, and switches to
+    // exponents for very large and small magnitudes.
+    let _ = write!(w, "{:?}", n);
+  }
+}
@@ -0,0 +1,53 @@
+mod common;
+
+use common::{run, stdout, write_file};
+
+#[test]
+fn join_matches_keys_of_the_same_type() {
+  let right = write_file(
+    "join_right.json",
+    r#"[{"id":"1","name":"a"},{"id":2,"name":"b"}]"#,
+  );
+  let right = right.to_str().unwrap();
+  let output = stdout(
+    &["-f", "json", "-t", "json", "--join", right, "--on", ".id"],
+    r#"[{"id":1,"x":1},{"id":2,"x":2}]"#,
+  );
+  assert_eq!(output.trim(), r#"[{"id":2,"x":2,"name":"b"}]"#);
+}
+
+#[test]
+fn sorting_puts_nan_after_other_numbers() {
+  let output = stdout(
+    &["-f", "yaml", "-t", "yaml", "--sort-docs-by", "."],
+    "2\n---\n.nan\n---\n1\n---\n.nan\n---\n-.inf\n",
+  );
+  assert_eq!(output, "---\n-.inf\n---\n1\n---\n2\n---\n.nan\n---\n.nan\n");
+}
+
+#[test]
+fn group_by_keeps_the_types_of_keys() {
+  let output = stdout(
+    &["-f", "json", "-t", "yaml", "--group-by", ".k"],
+    r#"[{"k":1},{"k":"1"},{"k":null},{"k":"null"},{"k":1}]"#,
+  );
+  assert_eq!(
+    output,
+    "---\n1:\n  - k: 1\n  - k: 1\n\"1\":\n  - k: \"1\"\n~:\n  - k: ~\n\"null\":\n  - k: \"null\"\n"
+  );
+}
+
+#[test]
+fn group_by_needs_a_key_in_every_record() {
+  let output = run(&["-f", "json", "--group-by", ".k"], r#"[{"k":1},{}]"#);
+  assert!(!output.status.success());
+  assert!(String::from_utf8_lossy(&output.stderr).contains("record 1 has no value at .k"));
+}
+
+#[test]
+fn limits_apply_after_sorting() {
+  let input = "{\"v\":3}\n{\"v\":1}\n{\"v\":2}\n";
+  let sorted = |limit: &str| stdout(&["-f", "json", "--sort-docs-by", ".v", limit, "2"], input);
+  assert_eq!(sorted("--head"), "{\"v\":1}\n{\"v\":2}\n");
+  assert_eq!(sorted("--tail"), "{\"v\":2}\n{\"v\":3}\n");
+}
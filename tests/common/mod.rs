@@ -0,0 +1,41 @@
+// Helpers for running jyt in the integration tests. Each test file uses only
+// some of them.
+#![allow(dead_code)]
+
+use std::fs;
+use std::io::Write;
+use std::path::PathBuf;
+use std::process::{Command, Output, Stdio};
+
+// Runs jyt with the input on stdin.
+pub fn run(args: &[&str], input: &str) -> Output {
+  let mut child = Command::new(env!("CARGO_BIN_EXE_jyt"))
+    .args(args)
+    .stdin(Stdio::piped())
+    .stdout(Stdio::piped())
+    .stderr(Stdio::piped())
+    .spawn()
+    .unwrap();
+  // jyt can exit before it reads its input, as it does for usage errors.
+  let _ = child.stdin.take().unwrap().write_all(input.as_bytes());
+  child.wait_with_output().unwrap()
+}
+
+// Runs jyt with the input on stdin, and returns its output once it succeeds.
+pub fn stdout(args: &[&str], input: &str) -> String {
+  let output = run(args, input);
+  assert!(
+    output.status.success(),
+    "jyt {:?} failed: {}",
+    args,
+    String::from_utf8_lossy(&output.stderr)
+  );
+  String::from_utf8(output.stdout).unwrap()
+}
+
+// Writes a file for a test to give to jyt, named for the test.
+pub fn write_file(name: &str, contents: &str) -> PathBuf {
+  let path = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join(name);
+  fs::write(&path, contents).unwrap();
+  path
+}
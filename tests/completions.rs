@@ -0,0 +1,19 @@
+mod common;
+
+use structopt::clap::Shell;
+
+use common::run;
+
+#[test]
+fn every_shell_gets_a_script() {
+  for shell in Shell::variants() {
+    let output = run(&["completions", shell], "");
+    assert!(
+      output.status.success(),
+      "jyt completions {}: {}",
+      shell,
+      String::from_utf8_lossy(&output.stderr)
+    );
+    assert!(!output.stdout.is_empty(), "jyt completions {}", shell);
+  }
+}
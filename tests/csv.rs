@@ -0,0 +1,29 @@
+mod common;
+
+use common::run;
+
+#[test]
+fn byte_order_mark() {
+  let output = run(&["-f", "csv", "-t", "json"], "\u{feff}id,name\n1,a\n");
+  assert!(output.status.success());
+  assert_eq!(
+    String::from_utf8_lossy(&output.stdout),
+    "{\"id\":\"1\",\"name\":\"a\"}\n"
+  );
+}
+
+#[test]
+fn bad_input_is_an_input_error() {
+  let cases = [
+    "a,a\n1,2\n",
+    "a,b\n1,2,3\n",
+    "a,b\n\"1,2\n",
+    "a,b\n\"1\"x,2\n",
+  ];
+  for input in cases {
+    let output = run(&["-f", "csv", "-t", "json"], input);
+    assert_eq!(output.status.code(), Some(2), "{:?}", input);
+  }
+  let output = run(&["-f", "csv", "--column-types", "a:int"], "a\nx\n");
+  assert_eq!(output.status.code(), Some(2));
+}
@@ -0,0 +1,131 @@
+mod common;
+
+use std::path::PathBuf;
+
+use common::{run, write_file};
+
+fn assert_status(args: &[&str], input: &str, status: i32) {
+  let output = run(args, input);
+  assert_eq!(
+    output.status.code(),
+    Some(status),
+    "jyt {:?}: {}",
+    args,
+    String::from_utf8_lossy(&output.stderr)
+  );
+}
+
+#[test]
+fn success() {
+  assert_status(&["-f", "json", "-t", "yaml"], r#"{"a":1}"#, 0);
+}
+
+#[test]
+fn usage_error() {
+  assert_status(&["--no-such-option"], "{}", 1);
+  assert_status(&["-t", "no-such-format"], "{}", 1);
+}
+
+#[test]
+fn unparsable_input() {
+  assert_status(&["-f", "json"], "{", 2);
+  assert_status(&["-f", "yaml"], "a: [", 2);
+}
+
+#[test]
+fn output_error() {
+  assert_status(&["-f", "json", "-t", "toml"], "[1]", 3);
+}
+
+#[test]
+fn io_error() {
+  let missing = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join("no-such-file.json");
+  assert_status(&[missing.to_str().unwrap()], "", 4);
+}
+
+#[test]
+fn invalid_document() {
+  let schema = write_file(
+    "exit_status_schema.json",
+    r#"{"type":"object","required":["name"]}"#,
+  );
+  let schema = schema.to_str().unwrap();
+  assert_status(
+    &["-f", "json", "--validate-schema", schema],
+    r#"{"name":"a"}"#,
+    0,
+  );
+  assert_status(&["-f", "json", "--validate-schema", schema], "{}", 5);
+}
+
+#[test]
+fn skipped_documents() {
+  let output = run(
+    &["-f", "json", "--keep-going"],
+    "{\"a\":1}\n{bad\n{\"b\":2}\n",
+  );
+  assert_eq!(output.status.code(), Some(6));
+  assert_eq!(
+    String::from_utf8_lossy(&output.stdout),
+    "{\"a\":1}\n{\"b\":2}\n"
+  );
+}
+
+#[test]
+fn input_policies() {
+  assert_status(
+    &["-f", "json", "--duplicate-keys", "error"],
+    r#"{"a":1,"a":2}"#,
+    2,
+  );
+  assert_status(
+    &["-f", "yaml", "--yaml-merge", "error"],
+    "a: &x {b: 1}\nc: {<<: *x}\n",
+    2,
+  );
+  assert_status(&["-f", "csv"], "a,b\n\"x\n", 2);
+  assert_status(&["-f", "json", "--nth", "2"], "{}\n{}\n", 2);
+}
+
+#[test]
+fn commands() {
+  let missing = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join("no-such-file.json");
+  let missing = missing.to_str().unwrap();
+  assert_status(&["repl", missing], "", 4);
+  assert_status(&["git-textconv", missing], "", 4);
+  assert_status(&["convert", "-t", "yaml", missing], "", 4);
+  let bad = write_file("exit_status_convert.json", "{");
+  assert_status(&["convert", "-t", "yaml", bad.to_str().unwrap()], "", 2);
+  assert_status(&["convert", "-t", "table", missing], "", 1);
+}
+
+#[test]
+fn first_duplicate_key_in_the_input() {
+  let output = run(
+    &["-f", "json", "--duplicate-keys", "error"],
+    "{\"a\":1,\n\"b\":{\"c\":1,\"c\":2},\n\"a\":3}",
+  );
+  assert_eq!(output.status.code(), Some(2));
+  assert!(String::from_utf8_lossy(&output.stderr)
+    .contains("document 0, .b.c (line 2, column 12): duplicate key"));
+}
+
+#[test]
+fn yaml_merge_keys_are_input_errors() {
+  let merge = "a: &x {b: 1}\nc: {<<: *x}\n";
+  assert_status(&["-f", "yaml", "--yaml-merge", "error"], merge, 2);
+  assert_status(&["-f", "yaml"], "a: {<<: 1}\n", 2);
+  let output = run(
+    &[
+      "-f",
+      "yaml",
+      "--yaml-merge",
+      "error",
+      "--error-format",
+      "json",
+      "-q",
+    ],
+    merge,
+  );
+  assert!(String::from_utf8_lossy(&output.stderr).starts_with(r#"{"code":"input","#));
+}
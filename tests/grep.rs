@@ -0,0 +1,54 @@
+mod common;
+
+use common::stdout;
+
+// Reports whether jyt --grep finds a regex in a string.
+fn is_match(regex: &str, text: &str) -> bool {
+  let doc = serde_json::json!({ "v": text }).to_string();
+  !stdout(
+    &["-f", "json", "--grep-in", "values", "--grep", regex],
+    &doc,
+  )
+  .is_empty()
+}
+
+#[test]
+fn syntax() {
+  let cases = [
+    ("o w", "hello world", true),
+    ("^world", "hello world", false),
+    ("world$", "hello world", true),
+    ("h.llo", "hello", true),
+    ("a.b", "a\nb", false),
+    ("[0-9]+x", "abc123x", true),
+    ("[^a-z]", "abc", false),
+    (r"\d{3}", "ab12c", false),
+    (r"\d{2,}", "ab12c", true),
+    (r"^\w+\s\w+$", "two words", true),
+    (r"\bcat\b", "concatenate", false),
+    (r"\bcat\b", "a cat sat", true),
+    ("^(ab|cd)+$", "abcdab", true),
+    ("^(ab|cd)+$", "abcda", false),
+    ("^(?:a|b)?c$", "c", true),
+    ("^a{2,3}$", "aaaa", false),
+    ("^a{2,3}?$", "aaa", true),
+    ("^a*?b", "aaab", true),
+    (r"\.json$", "config.json", true),
+    (r"\.json$", "configjson", false),
+    ("(?i)^HELLO", "hello there", true),
+    ("(?i)[A-Z]+!", "hi!", true),
+    ("^$", "", true),
+  ];
+  for (regex, text, want) in cases {
+    assert_eq!(is_match(regex, text), want, "{:?} in {:?}", regex, text);
+  }
+}
+
+#[test]
+fn pathological_patterns() {
+  // A backtracking matcher takes exponential time on the first, and recursion
+  // as deep as the text on the second.
+  assert!(!is_match("(a*)*b", &"a".repeat(30)));
+  assert!(is_match(".*y", &format!("{}y", "x".repeat(100_000))));
+  assert!(is_match("^(a|a)*$", &"a".repeat(1000)));
+}
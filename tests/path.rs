@@ -0,0 +1,45 @@
+mod common;
+
+use std::process::Output;
+
+use common::run;
+
+fn pointer(path: &str) -> Output {
+  run(&["-f", "json", "--pointer", path], r#"{"a":[1,2,3]}"#)
+}
+
+#[test]
+fn slices() {
+  let cases = [
+    (".a[1:]", "2\n3\n"),
+    (".a[:-1]", "1\n2\n"),
+    (".a[-2:]", "2\n3\n"),
+    (".a[5:1]", ""),
+    (".a[2:1]", ""),
+    (".b[*]", ""),
+  ];
+  for (path, want) in cases {
+    let output = pointer(path);
+    assert!(output.status.success(), "{}", path);
+    assert_eq!(String::from_utf8_lossy(&output.stdout), want, "{}", path);
+  }
+}
+
+#[test]
+fn slices_with_a_step() {
+  for path in [".a[::-1]", ".a[1:-1:2]"] {
+    let output = pointer(path);
+    assert_eq!(output.status.code(), Some(1), "{}", path);
+    assert!(
+      String::from_utf8_lossy(&output.stderr).contains("slices can't have a step"),
+      "{}",
+      path
+    );
+  }
+}
+
+#[test]
+fn missing_single_path() {
+  assert_eq!(pointer(".b").status.code(), Some(2));
+  assert_eq!(pointer(".a[3]").status.code(), Some(2));
+}
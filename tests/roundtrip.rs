@@ -0,0 +1,65 @@
+mod common;
+
+use common::{run, stdout};
+
+const FORMATS: [&str; 3] = ["json", "yaml", "toml"];
+
+const FLOATS: &str =
+  r#"{"a":[0.1,-0.0,0.0,1e300,5e-324,-1.5,100.0,2.5e-8,1.7976931348623157e308]}"#;
+
+fn convert(from: &str, to: &str, input: &str) -> String {
+  stdout(&["-f", from, "-t", to], input)
+}
+
+#[test]
+fn floats_round_trip_between_every_pair_of_formats() {
+  for from in FORMATS {
+    for to in FORMATS {
+      let start = convert("json", from, FLOATS);
+      let there = convert(from, to, &start);
+      let back = convert(to, from, &there);
+      assert_eq!(back, start, "{} to {} and back", from, to);
+      assert_eq!(
+        convert(from, "json", &back).trim(),
+        FLOATS,
+        "{} to {} and back",
+        from,
+        to
+      );
+    }
+  }
+}
+
+#[test]
+fn toml_output_keeps_the_sign_of_zero() {
+  assert_eq!(convert("json", "toml", r#"{"z":-0.0}"#), "z = -0.0\n");
+  assert_eq!(
+    convert("json", "toml", r#"{"s":"jyt-negative-zero","z":-0.0}"#),
+    "s = 'jyt-negative-zero'\nz = -0.0\n"
+  );
+}
+
+#[test]
+fn yaml_anchors_for_nested_aliases() {
+  let mut input = String::from("l0: &l0 {k: v}\n");
+  for i in 1..16 {
+    input.push_str(&format!("l{}: &l{} [*l{}, *l{}]\n", i, i, i - 1, i - 1));
+  }
+  let anchored = stdout(
+    &["-f", "yaml", "-t", "yaml", "--yaml-anchors", "-q"],
+    &input,
+  );
+  assert!(anchored.len() < 1000, "{}", anchored);
+  assert!(anchored.ends_with("l15:\n  - *id015\n  - *id015\n"));
+  assert_eq!(
+    stdout(&["-f", "yaml", "-t", "json", "-q"], &anchored),
+    stdout(&["-f", "yaml", "-t", "json", "-q"], &input)
+  );
+}
+
+#[test]
+fn yaml_binary_tags_are_dropped_with_a_warning() {
+  let output = run(&["-f", "yaml", "-t", "yaml"], "a: !!binary aGk=\n");
+  assert_eq!(String::from_utf8_lossy(&output.stdout), "---\na: aGk=\n");
+  assert!(String::from_utf8_lossy(&output.stderr).contains("!!binary tags were dropped"));
+}
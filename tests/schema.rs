@@ -0,0 +1,79 @@
+mod common;
+
+use common::{run, write_file};
+
+// Runs jyt --validate-schema with a schema on a JSON document, and returns its
+// exit status.
+fn validate(name: &str, schema: &str, doc: &str) -> Option<i32> {
+  let path = write_file(name, schema);
+  run(
+    &["-f", "json", "--validate-schema", path.to_str().unwrap()],
+    doc,
+  )
+  .status
+  .code()
+}
+
+#[test]
+fn multiple_of_decimal_fractions() {
+  let schema = r#"{"multipleOf":0.1}"#;
+  for n in ["0.3", "0.7", "1.1", "12.3", "3", "0"] {
+    assert_eq!(validate("multiple_of.json", schema, n), Some(0), "{}", n);
+  }
+  assert_eq!(validate("multiple_of.json", schema, "0.35"), Some(5));
+}
+
+#[test]
+fn patterns() {
+  let schema = r#"{"properties":{"id":{"pattern":"^[a-z]+-\\d+$"}}}"#;
+  assert_eq!(
+    validate("pattern.json", schema, r#"{"id":"abc-12"}"#),
+    Some(0)
+  );
+  assert_eq!(validate("pattern.json", schema, r#"{"id":"abc"}"#), Some(5));
+  // Patterns aren't anchored unless they say so.
+  let schema = r#"{"pattern":"b"}"#;
+  assert_eq!(validate("pattern_search.json", schema, r#""abc""#), Some(0));
+}
+
+#[test]
+fn pattern_properties() {
+  let schema = r#"{
+    "properties": {"name": {"type": "string"}},
+    "patternProperties": {"^x-": {"type": "integer"}},
+    "additionalProperties": false
+  }"#;
+  let valid = r#"{"name":"a","x-one":1,"x-two":2}"#;
+  assert_eq!(validate("pattern_properties.json", schema, valid), Some(0));
+  let wrong_type = r#"{"name":"a","x-one":"1"}"#;
+  assert_eq!(
+    validate("pattern_properties.json", schema, wrong_type),
+    Some(5)
+  );
+  let additional = r#"{"name":"a","y":1}"#;
+  assert_eq!(
+    validate("pattern_properties.json", schema, additional),
+    Some(5)
+  );
+}
+
+#[test]
+fn unsupported_keywords_are_usage_errors() {
+  let schemas = [
+    r#"{"format":"email"}"#,
+    r#"{"properties":{"a":{"items":{"contains":{}}}}}"#,
+    r#"{"$defs":{"a":{"if":{"type":"string"}}}}"#,
+    r#"{"pattern":"("}"#,
+  ];
+  for schema in schemas {
+    assert_eq!(
+      validate("unsupported.json", schema, "{}"),
+      Some(1),
+      "{}",
+      schema
+    );
+  }
+  // Annotations don't change whether a document is valid.
+  let schema = r#"{"title":"A","description":"B","examples":[{"format":1}]}"#;
+  assert_eq!(validate("annotations.json", schema, "{}"), Some(0));
+}
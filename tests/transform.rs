@@ -0,0 +1,54 @@
+mod common;
+
+use common::run;
+
+#[test]
+fn base64_errors_name_the_value() {
+  let cases = [
+    (
+      ".data.*",
+      r#"{"data":{"a":"aGk=","b":"//79"}}"#,
+      ".data.b: decoded value is not UTF-8 text",
+    ),
+    (
+      ".data[].a",
+      r#"{"data":[{"a":"aGk="},{"a":"%"}]}"#,
+      ".data[1].a: invalid base64",
+    ),
+    (
+      ".data.*",
+      r#"{"data":{"a":1}}"#,
+      ".data.a: cannot base64 decode",
+    ),
+  ];
+  for (path, input, message) in cases {
+    let output = run(&["-f", "json", "--b64-decode", path], input);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains(message), "{}: {}", path, stderr);
+  }
+}
+
+// Returns a document where each value refers twice to the one before it, all
+// the way back to v0.
+fn chain(len: usize) -> String {
+  let mut doc = serde_json::Map::new();
+  doc.insert("v0".into(), "x".into());
+  for i in 1..len {
+    let reference = format!("${{.v{}}}", i - 1);
+    doc.insert(format!("v{}", i), reference.repeat(2).into());
+  }
+  serde_json::Value::Object(doc).to_string()
+}
+
+#[test]
+fn interpolation_of_repeated_references() {
+  // Each step of the chain doubles the length of the string, so the first few
+  // are fine, but a long chain would take forever.
+  let output = run(&["-f", "json", "--interpolate"], &chain(11));
+  assert!(output.status.success());
+  let doc: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+  assert_eq!(doc["v10"].as_str().unwrap(), "x".repeat(1024));
+  let output = run(&["-f", "json", "--interpolate"], &chain(40));
+  assert!(!output.status.success());
+  assert!(String::from_utf8_lossy(&output.stderr).contains("references insert more than"));
+}